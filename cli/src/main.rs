@@ -0,0 +1,513 @@
+// Admin CLI for SolBox deployments. Instructions are built with the
+// program's client module, then either printed for review (--dry-run, and
+// simulated when --url names a cluster), printed as an unsigned message for
+// an external signer (--sign-only), or signed with a keypair file or a
+// Ledger and printed as a base64 transaction ready for the sendTransaction
+// RPC call
+
+use std::collections::HashMap;
+use std::fs;
+use std::process::exit;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::Value;
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::message::Message;
+use solana_sdk::pubkey::Pubkey;
+use solana_clap_utils::keypair::signer_from_path;
+use solana_rpc_client::rpc_client::RpcClient;
+use solana_rpc_client_api::config::RpcSimulateTransactionConfig;
+use solana_sdk::signature::{read_keypair_file, Signer};
+use solana_sdk::transaction::Transaction;
+use solbox_contract_devnet::statements::{merkle_proof, merkle_root, MigratedPosition};
+use solbox_contract_devnet::{client, Capacity, ContractConfig, DEFAULT_CAPACITY, SUPPORT_TIERS};
+
+const USAGE: &str = "\
+usage: solbox-cli <command> [args] [options]
+
+commands:
+  initialize --founder <pubkey> --config <file> --solbox-keypair <file> [--sandbox]
+             [--max-amounts <n>] [--max-levels <n>] [--max-relationships <n>]
+             [--max-blacklisted <n>]
+                                  capacity defaults to 8 amounts, 15 commission
+                                  levels, 64 relationships and 32 blacklisted
+                                  wallets
+  update-config --config <file>
+  pause --user <wallet>           toggle the pause flag (any registered wallet)
+  pause-guardians <threshold> [<guardian>...]
+                                  guardians whose quorum unpausing then needs
+  blacklist add|remove <wallet>
+  revoke-handle <wallet> <handle> take an abusive display handle away
+  brand-code <code>               tag every event with a tenant code of up to
+                                  8 ASCII bytes
+  migration-root --csv <file> [--proofs <file>]
+                                  commit legacy positions, one `wallet,package,
+                                  sponsor,lifetime_volume` line each; --proofs
+                                  writes each wallet's claim proof as JSON
+  package create|update <lamports> <category> <uri>
+                                  card category and artwork/terms URI of a package
+  package card-mint <lamports>    create the package's Token-2022 card mint
+  tier-mint <tier>                create the non-transferable position token of
+                                  a support tier
+  withdraw <lamports> [--recipient <pubkey>]
+  emergency-withdraw initiate|execute <recipient>
+  emergency-withdraw cancel       announce, run or call off draining the whole
+                                  vault; execute waits out a 14-day timelock
+  roll-epoch                      distribute realized yield for the epoch
+  warp <seconds> [<epochs>]       move a sandbox instance's clock forward
+
+options:
+  --solbox <pubkey>     SolBox account (not needed for initialize)
+  --keypair <path>      admin signer: a keypair file, or usb://ledger[?key=<n>]
+                        with the `ledger` feature [default: ~/.config/solana/id.json]
+  --admin <pubkey>      admin public key when signing elsewhere
+  --url <rpc>           cluster to simulate --dry-run against and to take the
+                        blockhash from
+  --blockhash <hash>    recent blockhash to sign against [default: from --url]
+  --dry-run             print the instruction without signing, and simulate
+                        it when --url is given
+  --sign-only           print the unsigned message for an external signer
+  --sandbox             mark a new SolBox as non-production
+";
+
+// Parsed command line: positional arguments, `--name value` options and
+// bare switches
+struct Options {
+    positional: Vec<String>,
+    values: HashMap<String, String>,
+    switches: Vec<String>,
+}
+
+const SWITCHES: [&str; 3] = ["dry-run", "sign-only", "sandbox"];
+
+impl Options {
+    fn parse(args: impl Iterator<Item = String>) -> Result<Self> {
+        let mut options = Options {
+            positional: Vec::new(),
+            values: HashMap::new(),
+            switches: Vec::new(),
+        };
+        let mut args = args.peekable();
+        while let Some(arg) = args.next() {
+            match arg.strip_prefix("--") {
+                Some(name) if SWITCHES.contains(&name) => options.switches.push(name.to_string()),
+                Some(name) => {
+                    let value = args.next().ok_or_else(|| anyhow!("--{name} needs a value"))?;
+                    options.values.insert(name.to_string(), value);
+                }
+                None => options.positional.push(arg),
+            }
+        }
+        Ok(options)
+    }
+
+    fn value(&self, name: &str) -> Result<&str> {
+        self.values
+            .get(name)
+            .map(String::as_str)
+            .ok_or_else(|| anyhow!("missing --{name}"))
+    }
+
+    fn pubkey(&self, name: &str) -> Result<Pubkey> {
+        let value = self.value(name)?;
+        Pubkey::from_str(value).with_context(|| format!("invalid --{name} `{value}`"))
+    }
+
+    fn number_or<T: FromStr>(&self, name: &str, default: T) -> Result<T> {
+        match self.values.get(name) {
+            Some(value) => value.parse().map_err(|_| anyhow!("invalid --{name} `{value}`")),
+            None => Ok(default),
+        }
+    }
+
+    fn switch(&self, name: &str) -> bool {
+        self.switches.iter().any(|switch| switch == name)
+    }
+
+    fn positional(&self, index: usize, name: &str) -> Result<&str> {
+        self.positional
+            .get(index)
+            .map(String::as_str)
+            .ok_or_else(|| anyhow!("missing <{name}>"))
+    }
+}
+
+fn main() {
+    if let Err(error) = run() {
+        eprintln!("error: {error:#}");
+        exit(1);
+    }
+}
+
+fn run() -> Result<()> {
+    let options = Options::parse(std::env::args().skip(1))?;
+    let command = match options.positional.first() {
+        Some(command) => command.as_str(),
+        None => {
+            print!("{USAGE}");
+            return Ok(());
+        }
+    };
+
+    // The admin key comes from the signer unless signing happens elsewhere
+    let signing = !options.switch("dry-run") && !options.switch("sign-only");
+    let keypair = if signing || !options.values.contains_key("admin") {
+        let path = match options.values.get("keypair") {
+            Some(path) => path.clone(),
+            None => default_keypair_path()?,
+        };
+        ensure!(
+            cfg!(feature = "ledger") || !path.starts_with("usb://"),
+            "Ledger signing needs solbox-cli built with `--features ledger`"
+        );
+        let mut wallet_manager = None;
+        let signer = signer_from_path(&Default::default(), &path, "keypair", &mut wallet_manager)
+            .map_err(|error| anyhow!("reading keypair {path}: {error}"))?;
+        Some(signer)
+    } else {
+        None
+    };
+    let admin = match &keypair {
+        Some(keypair) => keypair.pubkey(),
+        None => options.pubkey("admin")?,
+    };
+
+    // A new SolBox account signs its own creation
+    let mut extra_signers = Vec::new();
+    let instruction = match command {
+        "initialize" => {
+            let path = options.value("solbox-keypair")?;
+            let solbox = read_keypair_file(path)
+                .map_err(|error| anyhow!("reading keypair {path}: {error}"))?;
+            let ix = client::initialize(
+                solbox.pubkey(),
+                admin,
+                options.pubkey("founder")?,
+                read_config(options.value("config")?)?,
+                options.switch("sandbox"),
+                Capacity {
+                    max_amounts: options.number_or("max-amounts", DEFAULT_CAPACITY.max_amounts)?,
+                    max_levels: options.number_or("max-levels", DEFAULT_CAPACITY.max_levels)?,
+                    max_relationships: options.number_or("max-relationships", DEFAULT_CAPACITY.max_relationships)?,
+                    max_blacklisted: options.number_or("max-blacklisted", DEFAULT_CAPACITY.max_blacklisted)?,
+                },
+            );
+            extra_signers.push(solbox);
+            ix
+        }
+        "update-config" => client::update_config(
+            options.pubkey("solbox")?,
+            admin,
+            read_config(options.value("config")?)?,
+        ),
+        "pause" => client::toggle_pause(options.pubkey("solbox")?, admin, options.pubkey("user")?),
+        "pause-guardians" => {
+            let threshold = options.positional(1, "threshold")?.parse().context("invalid <threshold>")?;
+            let guardians = options.positional[2..]
+                .iter()
+                .map(|guardian| Pubkey::from_str(guardian).with_context(|| format!("invalid guardian `{guardian}`")))
+                .collect::<Result<Vec<_>>>()?;
+            client::set_pause_guardians(options.pubkey("solbox")?, admin, guardians, threshold)
+        }
+        "blacklist" => {
+            let solbox = options.pubkey("solbox")?;
+            let wallet = Pubkey::from_str(options.positional(2, "wallet")?).context("invalid <wallet>")?;
+            match options.positional(1, "add|remove")? {
+                "add" => client::add_to_blacklist(solbox, admin, wallet),
+                "remove" => client::remove_from_blacklist(solbox, admin, wallet),
+                other => bail!("unknown blacklist action `{other}`"),
+            }
+        }
+        "package" => {
+            let solbox = options.pubkey("solbox")?;
+            let amount = options.positional(2, "lamports")?.parse().context("invalid <lamports>")?;
+            match options.positional(1, "create|update|card-mint")? {
+                "card-mint" => client::create_card_mint(solbox, admin, amount),
+                action => {
+                    let category = options.positional(3, "category")?.parse().context("invalid <category>")?;
+                    let uri = options.positional(4, "uri")?.to_string();
+                    match action {
+                        "create" => client::create_package(solbox, admin, amount, category, uri),
+                        "update" => client::update_package(solbox, admin, amount, category, uri),
+                        other => bail!("unknown package action `{other}`"),
+                    }
+                }
+            }
+        }
+        "tier-mint" => {
+            let tier = options.positional(1, "tier")?.parse().context("invalid <tier>")?;
+            client::create_tier_mint(options.pubkey("solbox")?, admin, tier)
+        }
+        "revoke-handle" => {
+            let wallet = Pubkey::from_str(options.positional(1, "wallet")?).context("invalid <wallet>")?;
+            client::revoke_handle(options.pubkey("solbox")?, admin, wallet, options.positional(2, "handle")?)
+        }
+        "brand-code" => {
+            let code = options.positional(1, "code")?;
+            ensure!(code.is_ascii() && code.len() <= 8, "<code> must be at most 8 ASCII bytes");
+            let mut brand_code = [0u8; 8];
+            brand_code[..code.len()].copy_from_slice(code.as_bytes());
+            client::set_brand_code(options.pubkey("solbox")?, admin, brand_code)
+        }
+        "migration-root" => {
+            let positions = read_positions(options.value("csv")?)?;
+            let leaves: Vec<[u8; 32]> = positions.iter().map(MigratedPosition::leaf).collect();
+            if let Some(path) = options.values.get("proofs") {
+                write_proofs(path, &positions, &leaves)?;
+            }
+            let count = positions.len().try_into().context("too many positions")?;
+            client::commit_migration_root(options.pubkey("solbox")?, admin, merkle_root(&leaves), count)
+        }
+        "withdraw" => {
+            let amount = options.positional(1, "lamports")?.parse().context("invalid <lamports>")?;
+            let recipient = match options.values.contains_key("recipient") {
+                true => options.pubkey("recipient")?,
+                false => admin,
+            };
+            client::withdraw_treasury(options.pubkey("solbox")?, admin, recipient, amount)
+        }
+        "emergency-withdraw" => {
+            let solbox = options.pubkey("solbox")?;
+            let recipient = || -> Result<Pubkey> {
+                Pubkey::from_str(options.positional(2, "recipient")?).context("invalid <recipient>")
+            };
+            match options.positional(1, "initiate|cancel|execute")? {
+                "initiate" => client::initiate_emergency_withdraw(solbox, admin, recipient()?),
+                "cancel" => client::cancel_emergency_withdraw(solbox, admin),
+                "execute" => client::execute_emergency_withdraw(solbox, admin, recipient()?),
+                other => bail!("unknown emergency-withdraw action `{other}`"),
+            }
+        }
+        "roll-epoch" => client::distribute_yield(options.pubkey("solbox")?, admin),
+        "warp" => {
+            let seconds = options.positional(1, "seconds")?.parse().context("invalid <seconds>")?;
+            let epochs = match options.positional.get(2) {
+                Some(epochs) => epochs.parse().context("invalid <epochs>")?,
+                None => 0,
+            };
+            client::warp_time_offset(options.pubkey("solbox")?, admin, seconds, epochs)
+        }
+        other => bail!("unknown command `{other}`\n\n{USAGE}"),
+    };
+
+    let rpc = options.values.get("url").map(|url| RpcClient::new(url.clone()));
+    // The version check makes the transaction fail against an upgraded program
+    let instructions = [client::assert_version(), instruction];
+
+    if options.switch("dry-run") {
+        print_instruction(command, &instructions[1]);
+        if let Some(rpc) = &rpc {
+            let message = Message::new(&instructions, Some(&admin));
+            simulate(rpc, Transaction::new_unsigned(message))?;
+        }
+        return Ok(());
+    }
+
+    let blockhash = match (options.values.get("blockhash"), &rpc) {
+        (Some(blockhash), _) => Hash::from_str(blockhash).context("invalid --blockhash")?,
+        (None, Some(rpc)) => rpc.get_latest_blockhash().context("fetching the latest blockhash")?,
+        (None, None) => bail!("missing --blockhash (or --url to fetch one)"),
+    };
+    let message = Message::new_with_blockhash(&instructions, Some(&admin), &blockhash);
+    if options.switch("sign-only") {
+        println!("{}", STANDARD.encode(message.serialize()));
+        return Ok(());
+    }
+
+    let keypair = keypair.expect("signing reads the keypair");
+    let mut signers: Vec<&dyn Signer> = vec![keypair.as_ref()];
+    signers.extend(extra_signers.iter().map(|signer| signer as &dyn Signer));
+    let mut transaction = Transaction::new_unsigned(message);
+    transaction.try_sign(&signers, blockhash).context("signing the transaction")?;
+    println!("{}", STANDARD.encode(bincode::serialize(&transaction)?));
+    Ok(())
+}
+
+// Run an unsigned transaction against the cluster and print its logs; the
+// cluster supplies the blockhash and skips signature checks
+fn simulate(rpc: &RpcClient, transaction: Transaction) -> Result<()> {
+    let config = RpcSimulateTransactionConfig {
+        sig_verify: false,
+        replace_recent_blockhash: true,
+        ..RpcSimulateTransactionConfig::default()
+    };
+    let result = rpc
+        .simulate_transaction_with_config(&transaction, config)
+        .context("simulating the transaction")?
+        .value;
+    for line in result.logs.unwrap_or_default() {
+        println!("  log {line}");
+    }
+    if let Some(units) = result.units_consumed {
+        println!("  compute units {units}");
+    }
+    match result.err {
+        Some(error) => bail!("simulation failed: {error}"),
+        None => {
+            println!("  simulation succeeded");
+            Ok(())
+        }
+    }
+}
+
+fn default_keypair_path() -> Result<String> {
+    let home = std::env::var("HOME").context("HOME is not set; pass --keypair")?;
+    Ok(format!("{home}/.config/solana/id.json"))
+}
+
+// Print the accounts and data an instruction would submit
+fn print_instruction(command: &str, instruction: &Instruction) {
+    println!("{command} -> program {}", instruction.program_id);
+    for meta in &instruction.accounts {
+        let signer = if meta.is_signer { "signer" } else { "" };
+        let writable = if meta.is_writable { "writable" } else { "" };
+        println!("  {} {signer:<6} {writable}", meta.pubkey);
+    }
+    println!("  data {}", STANDARD.encode(&instruction.data));
+}
+
+// Read a ContractConfig from JSON using the on-chain field names
+fn read_config(path: &str) -> Result<ContractConfig> {
+    let text = fs::read_to_string(path).with_context(|| format!("reading config {path}"))?;
+    let value: Value = serde_json::from_str(&text).with_context(|| format!("parsing config {path}"))?;
+
+    let thresholds = uints(&value, "support_tier_thresholds")?;
+    let support_tier_thresholds: [u64; SUPPORT_TIERS] = thresholds
+        .try_into()
+        .map_err(|_| anyhow!("support_tier_thresholds needs {SUPPORT_TIERS} entries"))?;
+
+    Ok(ContractConfig {
+        matrix_width: uint(&value, "matrix_width")?.try_into()?,
+        matrix_depth: uint(&value, "matrix_depth")?.try_into()?,
+        commission_percentage: uint(&value, "commission_percentage")?,
+        commission_levels: uint(&value, "commission_levels")?.try_into()?,
+        bonus_percentage: uint(&value, "bonus_percentage")?,
+        valid_amounts: uints(&value, "valid_amounts")?,
+        refund_window: int(&value, "refund_window")?,
+        recycling: boolean(&value, "recycling")?,
+        cycle_bonus: uint(&value, "cycle_bonus")?,
+        holding_tank_period: int(&value, "holding_tank_period")?,
+        stake_pool: Pubkey::from_str(string(&value, "stake_pool")?).context("invalid stake_pool")?,
+        max_stake_bps: uint(&value, "max_stake_bps")?.try_into()?,
+        yield_sharing: boolean(&value, "yield_sharing")?,
+        support_tier_thresholds,
+        tier_period: int(&value, "tier_period")?,
+        tier_grace_periods: uint(&value, "tier_grace_periods")?.try_into()?,
+        residual_bps: uint(&value, "residual_bps")?.try_into()?,
+        membership_period: int(&value, "membership_period")?,
+        reactivation_window: int(&value, "reactivation_window")?,
+        reactivation_bps: uint(&value, "reactivation_bps")?.try_into()?,
+        min_claim: uint(&value, "min_claim")?,
+        payout_weekday: uint(&value, "payout_weekday")?.try_into()?,
+        claims_during_pause: boolean(&value, "claims_during_pause")?,
+        humanity_verifier: Pubkey::from_str(string(&value, "humanity_verifier")?)
+            .context("invalid humanity_verifier")?,
+        humanity_threshold: uint(&value, "humanity_threshold")?,
+        drip_threshold: uint(&value, "drip_threshold")?,
+        drip_days: uint(&value, "drip_days")?.try_into()?,
+        require_current_terms: boolean(&value, "require_current_terms")?,
+        tier_window_days: uint(&value, "tier_window_days")?.try_into()?,
+        voucher_chain: uint(&value, "voucher_chain")?.try_into()?,
+        storefront_chain: uint(&value, "storefront_chain")?.try_into()?,
+        storefront_emitter: bytes32(&value, "storefront_emitter")?,
+        hot_daily_limit: uint(&value, "hot_daily_limit")?,
+        hot_sweep_threshold: uint(&value, "hot_sweep_threshold")?,
+        flag_funder_threshold: uint(&value, "flag_funder_threshold")?.try_into()?,
+        flag_burst_threshold: uint(&value, "flag_burst_threshold")?.try_into()?,
+        block_flagged: boolean(&value, "block_flagged")?,
+        card_bonus_bps: uint(&value, "card_bonus_bps")?.try_into()?,
+        redemption_fee_bps: uint(&value, "redemption_fee_bps")?.try_into()?,
+    })
+}
+
+// Read legacy positions from CSV: `wallet,package,sponsor,lifetime_volume`
+// per line, with an empty sponsor for users without one. A header line
+// starting with `wallet` is skipped
+fn read_positions(path: &str) -> Result<Vec<MigratedPosition>> {
+    let text = fs::read_to_string(path).with_context(|| format!("reading positions {path}"))?;
+    let mut positions = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("wallet") {
+            continue;
+        }
+        let context = || format!("{path}:{}", number + 1);
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let [wallet, package, sponsor, volume] = fields[..] else {
+            bail!("{}: expected 4 fields", context());
+        };
+        positions.push(MigratedPosition {
+            wallet: Pubkey::from_str(wallet).with_context(context)?,
+            package: package.parse().with_context(context)?,
+            sponsor: match sponsor {
+                "" => Pubkey::default(),
+                sponsor => Pubkey::from_str(sponsor).with_context(context)?,
+            },
+            lifetime_volume: volume.parse().with_context(context)?,
+        });
+    }
+    Ok(positions)
+}
+
+// Write every position with its proof, hex-encoded, for users to claim with
+fn write_proofs(path: &str, positions: &[MigratedPosition], leaves: &[[u8; 32]]) -> Result<()> {
+    let hex = |bytes: &[u8; 32]| bytes.iter().map(|byte| format!("{byte:02x}")).collect::<String>();
+    let entries: Vec<Value> = positions
+        .iter()
+        .enumerate()
+        .map(|(index, position)| {
+            serde_json::json!({
+                "wallet": position.wallet.to_string(),
+                "package": position.package,
+                "sponsor": position.sponsor.to_string(),
+                "lifetime_volume": position.lifetime_volume,
+                "proof": merkle_proof(leaves, index).iter().map(hex).collect::<Vec<_>>(),
+            })
+        })
+        .collect();
+    fs::write(path, serde_json::to_string_pretty(&entries)?).with_context(|| format!("writing proofs {path}"))
+}
+
+fn field<'a>(value: &'a Value, name: &str) -> Result<&'a Value> {
+    value.get(name).ok_or_else(|| anyhow!("config is missing `{name}`"))
+}
+
+fn uint(value: &Value, name: &str) -> Result<u64> {
+    field(value, name)?.as_u64().ok_or_else(|| anyhow!("`{name}` must be an unsigned integer"))
+}
+
+fn int(value: &Value, name: &str) -> Result<i64> {
+    field(value, name)?.as_i64().ok_or_else(|| anyhow!("`{name}` must be an integer"))
+}
+
+fn boolean(value: &Value, name: &str) -> Result<bool> {
+    field(value, name)?.as_bool().ok_or_else(|| anyhow!("`{name}` must be a boolean"))
+}
+
+fn string<'a>(value: &'a Value, name: &str) -> Result<&'a str> {
+    field(value, name)?.as_str().ok_or_else(|| anyhow!("`{name}` must be a string"))
+}
+
+// 32 bytes written as 64 hex digits, like a Wormhole emitter address
+fn bytes32(value: &Value, name: &str) -> Result<[u8; 32]> {
+    let text = string(value, name)?;
+    let digits = |index: usize| text.get(index..index + 2).and_then(|pair| u8::from_str_radix(pair, 16).ok());
+    let bytes: Option<Vec<u8>> = (0..64).step_by(2).map(digits).collect();
+    match bytes {
+        Some(bytes) if text.len() == 64 => Ok(bytes.try_into().expect("32 bytes")),
+        _ => Err(anyhow!("`{name}` must be 64 hex digits")),
+    }
+}
+
+fn uints(value: &Value, name: &str) -> Result<Vec<u64>> {
+    field(value, name)?
+        .as_array()
+        .ok_or_else(|| anyhow!("`{name}` must be an array"))?
+        .iter()
+        .map(|item| item.as_u64().ok_or_else(|| anyhow!("`{name}` entries must be unsigned integers")))
+        .collect()
+}
@@ -0,0 +1,1229 @@
+// Event layouts emitted by the SolBox program, shared by off-chain
+// consumers. Types mirror the program's `#[event]` structs field for field;
+// the program crate tests that both encodings and every discriminator agree.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+// Prefix of the `Program data:` log lines `emit!` writes
+pub const LOG_PREFIX: &str = "Program data: ";
+
+// Tag Anchor puts in front of events emitted through `emit_cpi!`
+pub const EVENT_IX_TAG_LE: [u8; 8] = [0xe4, 0x45, 0xa5, 0x2e, 0x51, 0xcb, 0x9a, 0x1d];
+
+// A 32-byte account address, serialized as base58 in serde formats
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Address(pub [u8; 32]);
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&bs58::encode(self.0).into_string())
+    }
+}
+
+impl fmt::Debug for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl Serialize for Address {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Address {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = <String as Deserialize>::deserialize(deserializer)?;
+        let mut bytes = [0u8; 32];
+        let len = bs58::decode(&text)
+            .onto(&mut bytes)
+            .map_err(serde::de::Error::custom)?;
+        if len != 32 {
+            return Err(serde::de::Error::custom("address must be 32 bytes"));
+        }
+        Ok(Address(bytes))
+    }
+}
+
+// Mirror of the program's ContractConfig, carried by ConfigUpdateEvent
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ContractConfig {
+    pub matrix_width: u8,
+    pub matrix_depth: u8,
+    pub commission_percentage: u64,
+    pub commission_levels: u8,
+    pub bonus_percentage: u64,
+    pub valid_amounts: Vec<u64>,
+    pub refund_window: i64,
+    pub recycling: bool,
+    pub cycle_bonus: u64,
+    pub holding_tank_period: i64,
+    pub stake_pool: Address,
+    pub max_stake_bps: u16,
+    pub yield_sharing: bool,
+    pub support_tier_thresholds: [u64; 3],
+    pub tier_period: i64,
+    pub tier_grace_periods: u8,
+    pub residual_bps: u16,
+    pub membership_period: i64,
+    pub reactivation_window: i64,
+    pub reactivation_bps: u16,
+    pub min_claim: u64,
+    pub payout_weekday: u8,
+    pub claims_during_pause: bool,
+    pub humanity_verifier: Address,
+    pub humanity_threshold: u64,
+    pub drip_threshold: u64,
+    pub drip_days: u16,
+    pub require_current_terms: bool,
+    pub tier_window_days: u16,
+    pub voucher_chain: u16,
+    pub storefront_chain: u16,
+    pub storefront_emitter: [u8; 32],
+    pub hot_daily_limit: u64,
+    pub hot_sweep_threshold: u64,
+    pub flag_funder_threshold: u16,
+    pub flag_burst_threshold: u16,
+    pub block_flagged: bool,
+    pub card_bonus_bps: u16,
+    pub redemption_fee_bps: u16,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum BlacklistAction {
+    Add,
+    Remove,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum EarningSource {
+    Direct,
+    Spillover,
+    Pools,
+}
+
+// Mirror of the program's InstanceSnapshot, carried by ConfigExportedEvent
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InstanceSnapshot {
+    pub source: Address,
+    pub config: ContractConfig,
+    pub total_sold: u64,
+    pub total_commission_distributed: u64,
+    pub referral_count: u64,
+    pub purchase_count: u64,
+    pub tree_root: [u8; 32],
+    pub positions: u32,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum CardAction {
+    MintCreated,
+    Issued,
+    Redeemed,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum PositionTokenAction {
+    MintCreated,
+    Wrapped,
+    Unwrapped,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum HandleAction {
+    Registered,
+    Released,
+    Revoked,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum MembershipAction {
+    Renewed,
+    Reactivated,
+    Lapsed,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum ColdWithdrawalAction {
+    Proposed,
+    Approved,
+    Executed,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum CardBalanceAction {
+    Converted,
+    Spent,
+    Refunded,
+    Redeemed,
+}
+
+// Mirror of the program's ContestMetric, carried by ContestCreatedEvent
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum ContestMetric {
+    Directs,
+    Volume,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InitializeEvent {
+    pub owner: Address,
+    pub founder_wallet: Address,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct UserRegisteredEvent {
+    pub user: Address,
+    pub user_account: Address,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ConfigUpdateEvent {
+    pub admin: Address,
+    pub new_config: ContractConfig,
+    pub version: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PauseEvent {
+    pub admin: Address,
+    pub paused: bool,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PackageUpgradeEvent {
+    pub user: Address,
+    pub old_package: u64,
+    pub new_package: u64,
+    pub difference: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct GiftCardPurchaseEvent {
+    pub purchase_id: u64,
+    pub user: Address,
+    pub referrer: Address,
+    pub amount: u64,
+    pub commission: u64,
+    pub founder_share: u64,
+    pub integrator: Option<Address>,
+    pub integrator_fee: u64,
+    pub attribution: Option<[u8; 32]>, // nonce hash of the consumed click
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TenantLinkedEvent {
+    pub admin: Address,
+    pub source: Address,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct UserProofExportedEvent {
+    pub user: Address,
+    pub package: u64,
+    pub lifetime_volume: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct UserImportedEvent {
+    pub user: Address,
+    pub source: Address,
+    pub package: u64,
+    pub lifetime_volume: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SessionKeyCreatedEvent {
+    pub owner: Address,
+    pub session_key: Address,
+    pub max_lamports: u64,
+    pub expires_at: i64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SessionKeyRevokedEvent {
+    pub owner: Address,
+    pub session_key: Address,
+    pub spent: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AttributionRecordedEvent {
+    pub nonce_hash: [u8; 32],
+    pub referrer: Address,
+    pub recorded_by: Address,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PurchaseSettledEvent {
+    pub purchase_id: u64,
+    pub buyer: Address,
+    pub referrer: Address,
+    pub commission: u64,
+    pub founder_share: u64,
+    pub integrator_fee: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct IntegratorUpdatedEvent {
+    pub admin: Address,
+    pub integrator: Address,
+    pub name_hash: [u8; 32],
+    pub wallet: Address,
+    pub fee_share_bps: u16,
+    pub allowed_instructions: u32,
+    pub cpi_authority: Address,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct IntegratorLimitsEvent {
+    pub admin: Address,
+    pub integrator: Address,
+    pub epoch_volume_limit: u64,
+    pub epoch_purchase_limit: u32,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InvitationEvent {
+    pub referrer: Address,
+    pub identifier_hash: [u8; 32],
+    pub invitee: Option<Address>,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PurchaseCancelledEvent {
+    pub purchase_id: u64,
+    pub buyer: Address,
+    pub refunded: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AdminRefundEvent {
+    pub admin: Address,
+    pub purchase_id: u64,
+    pub buyer: Address,
+    pub amount: u64,
+    pub total_refunded: u64,
+    pub reason: String,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TreasuryWithdrawnEvent {
+    pub admin: Address,
+    pub recipient: Address,
+    pub amount: u64,
+    pub remaining_treasury: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InvariantViolationEvent {
+    pub invariant: u8, // INVARIANT_* code
+    pub account: Address,
+    pub expected: u64,
+    pub actual: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct StateVerifiedEvent {
+    pub receipts_checked: u32,
+    pub pending_escrow: u64, // escrow of the pending receipts in this batch
+    pub pending_count: u64,
+    pub violations: u32,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AccountExpandedEvent {
+    pub admin: Address,
+    pub account: Address,
+    pub new_size: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PlacementEvent {
+    pub user: Address,
+    pub sponsor: Address,
+    pub placed_under: Address,
+    pub placed_under_cycle: u8,
+    pub placed_by: Address,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CycleEvent {
+    pub user: Address,
+    pub cycle: u8,
+    pub placed_under: Address,
+    pub placed_under_cycle: u8,
+    pub bonus: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct StakeDelegatedEvent {
+    pub admin: Address,
+    pub stake_pool: Address,
+    pub lamports: u64,
+    pub staked_lamports: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct StakeUndelegatedEvent {
+    pub admin: Address,
+    pub stake_pool: Address,
+    pub pool_tokens: u64,
+    pub lamports_received: u64,
+    pub yield_lamports: u64,
+    pub staked_lamports: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct YieldDistributionEvent {
+    pub epoch: u64,
+    pub amount: u64,
+    pub total_package_weight: u64,
+    pub yield_per_package: u128,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct YieldClaimedEvent {
+    pub user: Address,
+    pub amount: u64,
+    pub epoch: u64,
+    pub receipt: Address,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PackageGrantedEvent {
+    pub admin: Address,
+    pub user: Address,
+    pub package: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CommissionConfigEvent {
+    pub admin: Address,
+    pub new_percentage: u64,
+    pub new_levels: u8,
+    pub version: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FeatureFlagsProposedEvent {
+    pub admin: Address,
+    pub current_flags: u64,
+    pub pending_flags: u64,
+    pub eta: i64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FeatureFlagsAppliedEvent {
+    pub previous_flags: u64,
+    pub flags: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FounderWalletProposedEvent {
+    pub admin: Address,
+    pub current_wallet: Address,
+    pub pending_wallet: Address,
+    pub eta: i64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FounderWalletChangedEvent {
+    pub previous_wallet: Address,
+    pub new_wallet: Address,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BoostsConfiguredEvent {
+    pub admin: Address,
+    pub loyalty_mint: Address,
+    pub price: u64,
+    pub bonus_bps: u16,
+    pub duration: i64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BoostActivatedEvent {
+    pub wallet: Address,
+    pub tokens_burned: u64,
+    pub bonus_bps: u16,
+    pub expires_at: i64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ContestCreatedEvent {
+    pub contest: Address,
+    pub contest_id: u64,
+    pub start: i64,
+    pub end: i64,
+    pub metric: ContestMetric,
+    pub prize_pool: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ContestFinalizedEvent {
+    pub contest: Address,
+    pub winners: u32,
+    pub awarded: u64,
+    pub returned: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ContestPrizeClaimedEvent {
+    pub contest: Address,
+    pub wallet: Address,
+    pub prize: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TierDecayWarningEvent {
+    pub user: Address,
+    pub missed_periods: u8,
+    pub grace_periods_left: u8,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TierDecayedEvent {
+    pub user: Address,
+    pub support_tier: u8,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct NoticePostedEvent {
+    pub notice: Address,
+    pub notice_id: u64,
+    pub recipient: Address,
+    pub kind: u8,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RecoveryApprovedEvent {
+    pub wallet: Address,
+    pub guardian: Address,
+    pub new_wallet: Address,
+    pub approvals: u8,
+    pub recovery_eta: i64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct WalletRecoveredEvent {
+    pub old_wallet: Address,
+    pub new_wallet: Address,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BeneficiarySetEvent {
+    pub user: Address,
+    pub beneficiary: Address,
+    pub inactivity_period: i64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InheritanceClaimedEvent {
+    pub old_wallet: Address,
+    pub beneficiary: Address,
+    pub unclaimed_yield: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct StatementsAnchoredEvent {
+    pub epoch: u64,
+    pub root: [u8; 32],
+    pub users: u32,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PauseGuardiansSetEvent {
+    pub admin: Address,
+    pub guardians: Vec<Address>,
+    pub threshold: u8,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct UnpauseApprovedEvent {
+    pub guardian: Address,
+    pub pause_count: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ResidualClaimedEvent {
+    pub user: Address,
+    pub amount: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TimeWarpedEvent {
+    pub admin: Address,
+    pub time_offset: i64,
+    pub epoch_offset: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LookupTableEvent {
+    pub admin: Address,
+    pub table: Address,
+    pub added: u32,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PackageMetadataEvent {
+    pub admin: Address,
+    pub package: Address,
+    pub amount: u64,
+    pub category: u8,
+    pub metadata_uri: String,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FiatPurchaseAttestedEvent {
+    pub integrator: Address,
+    pub receipt: Address,
+    pub buyer: Address,
+    pub amount: u64,
+    pub external_ref: [u8; 32],
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct HandleEvent {
+    pub wallet: Address,
+    pub handle: String,
+    pub action: HandleAction,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MigrationRootCommittedEvent {
+    pub admin: Address,
+    pub root: [u8; 32],
+    pub positions: u32,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PositionMigratedEvent {
+    pub user: Address,
+    pub package: u64,
+    pub sponsor: Address,
+    pub lifetime_volume: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SponsorshipEvent {
+    pub sponsor: Address,
+    pub user: Address,
+    pub accepted: bool,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MembershipEvent {
+    pub user: Address,
+    pub action: MembershipAction,
+    pub package: u64,
+    pub price: u64,
+    pub paid_until: i64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct HouseCommissionEvent {
+    pub house: Address,
+    pub purchase_id: u64,
+    pub amount: u64,
+    pub pooled: bool,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct QueuedPayoutEvent {
+    pub user: Address,
+    pub amount: u64,
+    pub paid: bool,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CoverageEvent {
+    pub vault_balance: u64,
+    pub liabilities: u64,
+    pub coverage_bps: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EmergencyWithdrawInitiatedEvent {
+    pub admin: Address,
+    pub recipient: Address,
+    pub vault_balance: u64,
+    pub liabilities: u64,
+    pub eta: i64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EmergencyWithdrawCancelledEvent {
+    pub admin: Address,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EmergencyWithdrawExecutedEvent {
+    pub admin: Address,
+    pub recipient: Address,
+    pub amount: u64,
+    pub liabilities: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BrandCodeEvent {
+    pub admin: Address,
+    pub previous: [u8; 8],
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EarningsEvent {
+    pub user: Address,
+    pub source: EarningSource,
+    pub amount: u64,
+    pub reference: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DeferredFounderShareEvent {
+    pub founder: Address,
+    pub amount: u64,
+    pub paid: bool,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CardEvent {
+    pub card_mint: Address,
+    pub wallet: Address,
+    pub amount: u64,
+    pub action: CardAction,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CardClawbackEvent {
+    pub admin: Address,
+    pub card_mint: Address,
+    pub from: Address,
+    pub to: Address,
+    pub units: u64,
+    pub reason: String,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TierTokenEvent {
+    pub wallet: Address,
+    pub tier_mint: Address,
+    pub tier: u8,
+    pub held: bool,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PositionTokenEvent {
+    pub wallet: Address,
+    pub position_mint: Address,
+    pub holder: Address,
+    pub action: PositionTokenAction,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TermsPublishedEvent {
+    pub admin: Address,
+    pub terms_hash: [u8; 32],
+    pub version: u32,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TermsAcceptedEvent {
+    pub user: Address,
+    pub terms_hash: [u8; 32],
+    pub version: u32,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TrancheReleasedEvent {
+    pub wallet: Address,
+    pub purchase_id: u64,
+    pub amount: u64,
+    pub released: u64,
+    pub total: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ConfigExportedEvent {
+    pub admin: Address,
+    pub snapshot: InstanceSnapshot,
+    pub hash: [u8; 32],
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RestoreApprovedEvent {
+    pub source: Address,
+    pub target: Address,
+    pub guardian: Address,
+    pub approvals: u8,
+    pub restore_eta: i64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InstanceRestoredEvent {
+    pub admin: Address,
+    pub source: Address,
+    pub hash: [u8; 32],
+    pub config_version: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LowStockEvent {
+    pub package: Address,
+    pub amount: u64,
+    pub remaining: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ClaimVoucherEvent {
+    pub wallet: Address,
+    pub voucher_id: u64,
+    pub amount: u64,
+    pub recipient_chain: u16,
+    pub recipient: [u8; 32],
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct WormholePurchaseEvent {
+    pub sequence: u64,
+    pub user: Address,
+    pub referrer: Address,
+    pub amount: u64,
+    pub commission: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DeletionRequestedEvent {
+    pub user: Address,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct UserDeletedEvent {
+    pub tombstone: Address,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TreeCheckpointEvent {
+    pub epoch: u64,
+    pub root: [u8; 32],
+    pub relationships: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TreasurySweptEvent {
+    pub amount: u64,
+    pub hot_treasury: u64,
+    pub cold_treasury: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ColdWithdrawalEvent {
+    pub action: ColdWithdrawalAction,
+    pub actor: Address,
+    pub recipient: Address,
+    pub amount: u64,
+    pub approvals: u8,
+    pub withdraw_eta: i64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FraudFlagEvent {
+    pub user: Address,
+    pub funder: Option<Address>,
+    pub integrator: Option<Address>,
+    pub reasons: u8,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CardBalanceEvent {
+    pub user: Address,
+    pub amount: u64,
+    pub bonus: u64,
+    pub balance: u64,
+    pub action: CardBalanceAction,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MerchantRedemptionEvent {
+    pub merchant: Address,
+    pub user: Address,
+    pub amount: u64,
+    pub fee: u64,
+    pub month: u32,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BlacklistEvent {
+    pub admin: Address,
+    pub user: Address,
+    pub action: BlacklistAction,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    // Shorter than a discriminator, or not a `Program data:` line
+    Malformed,
+    // Discriminator of an event this crate does not know
+    UnknownEvent([u8; 8]),
+    // Known event whose body failed to deserialize
+    InvalidBody,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::Malformed => f.write_str("malformed event data"),
+            DecodeError::UnknownEvent(discriminator) => write!(f, "unknown event {discriminator:?}"),
+            DecodeError::InvalidBody => f.write_str("event body does not match its layout"),
+        }
+    }
+}
+
+// Discriminators follow Anchor's sha256("event:<Name>")[..8]
+macro_rules! events {
+    ($($name:ident = $discriminator:expr,)*) => {
+        // Every event the program emits, tagged by its struct name
+        #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+        #[serde(tag = "event", content = "data")]
+        pub enum Event {
+            $($name($name),)*
+        }
+
+        pub trait Discriminator {
+            const DISCRIMINATOR: [u8; 8];
+        }
+
+        $(impl Discriminator for $name {
+            const DISCRIMINATOR: [u8; 8] = $discriminator;
+        })*
+
+        impl Event {
+            // Decode discriminator-prefixed event bytes
+            pub fn decode(data: &[u8]) -> Result<Event, DecodeError> {
+                if data.len() < 8 {
+                    return Err(DecodeError::Malformed);
+                }
+                let (discriminator, mut body) = data.split_at(8);
+                $(if discriminator == $name::DISCRIMINATOR {
+                    let event = <$name as BorshDeserialize>::deserialize(&mut body).map_err(|_| DecodeError::InvalidBody)?;
+                    return Ok(Event::$name(event));
+                })*
+                let mut unknown = [0u8; 8];
+                unknown.copy_from_slice(discriminator);
+                Err(DecodeError::UnknownEvent(unknown))
+            }
+
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Event::$name(_) => stringify!($name),)*
+                }
+            }
+        }
+    };
+}
+
+events! {
+    InitializeEvent = [206, 175, 169, 208, 241, 210, 35, 221],
+    UserRegisteredEvent = [228, 209, 170, 9, 109, 101, 32, 131],
+    ConfigUpdateEvent = [158, 144, 170, 167, 15, 184, 45, 12],
+    PauseEvent = [32, 51, 61, 169, 156, 104, 130, 43],
+    PackageUpgradeEvent = [132, 150, 182, 175, 218, 99, 156, 250],
+    GiftCardPurchaseEvent = [131, 162, 18, 124, 112, 160, 101, 50],
+    TenantLinkedEvent = [109, 88, 104, 32, 43, 149, 158, 109],
+    UserProofExportedEvent = [54, 138, 11, 218, 253, 118, 6, 101],
+    UserImportedEvent = [185, 38, 86, 145, 95, 88, 214, 67],
+    SessionKeyCreatedEvent = [36, 132, 241, 232, 102, 37, 75, 151],
+    SessionKeyRevokedEvent = [76, 0, 41, 207, 129, 196, 64, 225],
+    AttributionRecordedEvent = [53, 140, 133, 174, 22, 224, 224, 72],
+    PurchaseSettledEvent = [217, 173, 63, 23, 67, 187, 19, 237],
+    IntegratorUpdatedEvent = [77, 114, 106, 9, 103, 204, 153, 117],
+    PurchaseCancelledEvent = [105, 201, 64, 171, 239, 81, 150, 111],
+    AdminRefundEvent = [218, 25, 92, 66, 29, 248, 137, 131],
+    TreasuryWithdrawnEvent = [29, 130, 89, 213, 225, 104, 229, 154],
+    InvariantViolationEvent = [41, 208, 169, 121, 36, 85, 34, 152],
+    StateVerifiedEvent = [180, 53, 80, 240, 202, 202, 113, 21],
+    AccountExpandedEvent = [184, 181, 211, 74, 211, 137, 252, 153],
+    PlacementEvent = [71, 68, 75, 39, 222, 79, 86, 240],
+    CycleEvent = [31, 40, 54, 170, 52, 25, 90, 134],
+    StakeDelegatedEvent = [123, 81, 237, 33, 234, 81, 98, 177],
+    StakeUndelegatedEvent = [110, 166, 89, 119, 78, 64, 232, 26],
+    YieldDistributionEvent = [248, 87, 57, 233, 95, 160, 132, 73],
+    YieldClaimedEvent = [185, 147, 18, 126, 197, 84, 224, 209],
+    PackageGrantedEvent = [122, 67, 62, 128, 65, 117, 59, 92],
+    CommissionConfigEvent = [40, 42, 34, 238, 63, 89, 252, 175],
+    FeatureFlagsProposedEvent = [22, 134, 154, 223, 67, 38, 169, 242],
+    FeatureFlagsAppliedEvent = [1, 68, 98, 171, 143, 157, 229, 211],
+    FounderWalletProposedEvent = [177, 152, 162, 122, 42, 10, 97, 252],
+    FounderWalletChangedEvent = [170, 195, 57, 89, 48, 128, 173, 30],
+    BoostsConfiguredEvent = [116, 47, 164, 82, 216, 254, 145, 116],
+    BoostActivatedEvent = [100, 102, 122, 148, 254, 173, 169, 32],
+    ContestCreatedEvent = [172, 156, 174, 236, 181, 203, 253, 1],
+    ContestFinalizedEvent = [141, 24, 138, 192, 173, 8, 138, 31],
+    ContestPrizeClaimedEvent = [146, 184, 65, 181, 222, 89, 145, 148],
+    TierDecayWarningEvent = [69, 16, 22, 42, 239, 209, 57, 184],
+    TierDecayedEvent = [184, 154, 134, 56, 23, 246, 28, 92],
+    NoticePostedEvent = [73, 21, 115, 253, 243, 202, 51, 194],
+    RecoveryApprovedEvent = [38, 101, 27, 197, 22, 102, 200, 221],
+    WalletRecoveredEvent = [0, 89, 60, 127, 96, 132, 152, 152],
+    BeneficiarySetEvent = [98, 247, 175, 189, 249, 109, 117, 67],
+    InheritanceClaimedEvent = [99, 108, 175, 235, 104, 142, 100, 225],
+    StatementsAnchoredEvent = [187, 131, 2, 211, 2, 56, 240, 201],
+    PauseGuardiansSetEvent = [23, 116, 255, 133, 116, 215, 1, 1],
+    UnpauseApprovedEvent = [198, 159, 61, 21, 62, 196, 63, 138],
+    ResidualClaimedEvent = [110, 146, 175, 143, 228, 93, 113, 255],
+    TimeWarpedEvent = [230, 200, 121, 236, 62, 220, 236, 209],
+    LookupTableEvent = [237, 89, 201, 188, 3, 99, 254, 179],
+    PackageMetadataEvent = [5, 236, 197, 138, 248, 64, 171, 41],
+    FiatPurchaseAttestedEvent = [120, 34, 226, 142, 10, 10, 98, 205],
+    HandleEvent = [185, 252, 194, 104, 142, 144, 7, 192],
+    MigrationRootCommittedEvent = [97, 234, 214, 151, 200, 105, 54, 252],
+    PositionMigratedEvent = [52, 72, 109, 53, 108, 189, 48, 169],
+    SponsorshipEvent = [68, 98, 0, 109, 144, 38, 21, 43],
+    MembershipEvent = [55, 172, 0, 110, 252, 160, 213, 118],
+    HouseCommissionEvent = [38, 27, 109, 202, 39, 130, 87, 55],
+    QueuedPayoutEvent = [78, 21, 32, 183, 221, 25, 75, 232],
+    CoverageEvent = [201, 211, 35, 219, 51, 134, 134, 25],
+    EmergencyWithdrawInitiatedEvent = [198, 134, 252, 154, 80, 83, 171, 184],
+    EmergencyWithdrawCancelledEvent = [174, 46, 128, 86, 190, 149, 4, 248],
+    EmergencyWithdrawExecutedEvent = [5, 0, 206, 189, 146, 225, 200, 168],
+    BrandCodeEvent = [237, 12, 75, 72, 217, 92, 87, 50],
+    EarningsEvent = [182, 187, 202, 23, 209, 127, 255, 132],
+    DeferredFounderShareEvent = [224, 126, 188, 94, 244, 16, 48, 156],
+    CardEvent = [230, 146, 160, 164, 79, 158, 118, 154],
+    CardClawbackEvent = [144, 91, 6, 169, 190, 170, 150, 143],
+    TierTokenEvent = [211, 219, 160, 131, 243, 190, 59, 134],
+    PositionTokenEvent = [32, 8, 76, 245, 182, 158, 101, 0],
+    TermsPublishedEvent = [61, 188, 124, 82, 189, 212, 81, 0],
+    TermsAcceptedEvent = [86, 81, 203, 159, 210, 2, 66, 228],
+    TrancheReleasedEvent = [10, 154, 80, 26, 97, 188, 40, 144],
+    ConfigExportedEvent = [15, 151, 91, 182, 42, 224, 103, 19],
+    RestoreApprovedEvent = [138, 194, 185, 128, 206, 114, 22, 254],
+    InstanceRestoredEvent = [119, 82, 143, 115, 219, 140, 59, 140],
+    LowStockEvent = [136, 22, 239, 93, 255, 184, 156, 207],
+    ClaimVoucherEvent = [167, 153, 176, 137, 51, 16, 188, 93],
+    WormholePurchaseEvent = [139, 80, 1, 250, 80, 38, 133, 118],
+    DeletionRequestedEvent = [98, 231, 167, 83, 151, 62, 17, 104],
+    UserDeletedEvent = [127, 188, 223, 255, 94, 95, 203, 120],
+    TreeCheckpointEvent = [60, 34, 230, 190, 91, 13, 137, 98],
+    TreasurySweptEvent = [43, 137, 93, 193, 125, 26, 88, 144],
+    ColdWithdrawalEvent = [90, 133, 227, 53, 44, 176, 46, 5],
+    FraudFlagEvent = [53, 32, 181, 3, 28, 167, 115, 17],
+    IntegratorLimitsEvent = [162, 11, 64, 128, 234, 90, 208, 205],
+    InvitationEvent = [156, 47, 194, 76, 132, 186, 147, 237],
+    CardBalanceEvent = [206, 31, 52, 166, 40, 15, 168, 0],
+    MerchantRedemptionEvent = [29, 178, 116, 249, 120, 213, 190, 74],
+    BlacklistEvent = [136, 139, 147, 12, 218, 203, 2, 173],
+}
+
+impl Event {
+    // Decode a `Program data:` log line; None for any other log line
+    pub fn from_log(line: &str) -> Option<Result<Event, DecodeError>> {
+        let encoded = line.strip_prefix(LOG_PREFIX)?;
+        Some(
+            STANDARD
+                .decode(encoded)
+                .map_err(|_| DecodeError::Malformed)
+                .and_then(|data| Event::decode(&data)),
+        )
+    }
+
+    // Decode the data of a self-CPI made by `emit_cpi!`
+    pub fn from_cpi_data(data: &[u8]) -> Result<Event, DecodeError> {
+        match data.strip_prefix(&EVENT_IX_TAG_LE[..]) {
+            Some(event) => Event::decode(event),
+            None => Err(DecodeError::Malformed),
+        }
+    }
+}
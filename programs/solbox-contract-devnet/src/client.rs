@@ -0,0 +1,1937 @@
+// Off-chain helpers for Rust services: PDA derivation, typed instruction
+// builders and account decoding, all tracking the program's own layouts
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::address_lookup_table::state::AddressLookupTable;
+use anchor_lang::solana_program::address_lookup_table::{self, AddressLookupTableAccount};
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::sysvar;
+use anchor_lang::{system_program, InstructionData};
+
+use crate::statements::{merkle_root, tree_leaf, MigratedPosition, Statement};
+use crate::{
+    accounts, handle_key, instruction, invitation_hash, tombstone_key, Capacity, ContestMetric, ContractConfig, Handle, InstanceSnapshot, Position,
+    PostedVaa, Receipt, SolBox, StorefrontPurchase, User, ID, INTERFACE_VERSION, WORMHOLE_CORE_BRIDGE_ID,
+};
+
+// Derive the config history PDA of a SolBox
+pub fn find_config_history_address(solbox: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"config_history", solbox.as_ref()], &ID)
+}
+
+// Derive the blacklist filter PDA of a SolBox
+pub fn find_blacklist_filter_address(solbox: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"blacklist_filter", solbox.as_ref()], &ID)
+}
+
+// Derive the User PDA of a wallet
+pub fn find_user_address(solbox: &Pubkey, wallet: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"user", solbox.as_ref(), wallet.as_ref()], &ID)
+}
+
+// Derive the Receipt PDA of a purchase
+pub fn find_receipt_address(solbox: &Pubkey, purchase_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"receipt", solbox.as_ref(), &purchase_id.to_le_bytes()], &ID)
+}
+
+// Derive the DailyStats PDA of a day index, the program clock's unix
+// timestamp / 86_400; a sandbox's clock runs ahead by its time_offset
+pub fn find_daily_stats_address(solbox: &Pubkey, day: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"daily_stats", solbox.as_ref(), &day.to_le_bytes()], &ID)
+}
+
+// Derive the Integrator PDA from the hash of its name
+pub fn find_integrator_address(solbox: &Pubkey, name_hash: &[u8; 32]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"integrator", solbox.as_ref(), name_hash.as_ref()], &ID)
+}
+
+// Derive the Attribution PDA from its nonce hash
+pub fn find_attribution_address(solbox: &Pubkey, nonce_hash: &[u8; 32]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"attribution", solbox.as_ref(), nonce_hash.as_ref()], &ID)
+}
+
+// Derive the Invitation PDA from its identifier hash, see `invitation_hash`
+pub fn find_invitation_address(solbox: &Pubkey, identifier_hash: &[u8; 32]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"invitation", solbox.as_ref(), identifier_hash.as_ref()], &ID)
+}
+
+// Derive the Merchant PDA of a merchant wallet
+pub fn find_merchant_address(solbox: &Pubkey, wallet: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"merchant", solbox.as_ref(), wallet.as_ref()], &ID)
+}
+
+// Derive a merchant's MerchantMonth PDA, see `month_of`
+pub fn find_merchant_month_address(merchant: &Pubkey, month: u32) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"merchant_month", merchant.as_ref(), &month.to_le_bytes()], &ID)
+}
+
+// Derive the SessionKey PDA an owner created for a session signer
+pub fn find_session_key_address(solbox: &Pubkey, owner: &Pubkey, session_key: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"session_key", solbox.as_ref(), owner.as_ref(), session_key.as_ref()],
+        &ID,
+    )
+}
+
+// Derive the stake authority PDA used for stake pool deposits
+pub fn find_stake_authority_address(solbox: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"stake_authority", solbox.as_ref()], &ID)
+}
+
+// Derive the TenantLink PDA trusting a source SolBox
+pub fn find_tenant_link_address(solbox: &Pubkey, source: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"tenant_link", solbox.as_ref(), source.as_ref()], &ID)
+}
+
+// Derive the UserProof PDA exported for a wallet
+pub fn find_user_proof_address(solbox: &Pubkey, wallet: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"user_proof", solbox.as_ref(), wallet.as_ref()], &ID)
+}
+
+// Derive the FeatureFlags PDA of a SolBox
+pub fn find_feature_flags_address(solbox: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"feature_flags", solbox.as_ref()], &ID)
+}
+
+// Derive the Boost PDA of a wallet
+pub fn find_boost_address(solbox: &Pubkey, wallet: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"boost", solbox.as_ref(), wallet.as_ref()], &ID)
+}
+
+// Derive the Contest PDA of an admin-chosen contest id
+pub fn find_contest_address(solbox: &Pubkey, contest_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"contest", solbox.as_ref(), &contest_id.to_le_bytes()], &ID)
+}
+
+// Derive the ContestEntry PDA of a participant
+pub fn find_contest_entry_address(contest: &Pubkey, wallet: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"contest_entry", contest.as_ref(), wallet.as_ref()], &ID)
+}
+
+// Derive the Notice PDA of an admin-chosen notice id
+pub fn find_notice_address(solbox: &Pubkey, notice_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"notice", solbox.as_ref(), &notice_id.to_le_bytes()], &ID)
+}
+
+// Derive the FiatAttestation PDA recorded for a purchase
+pub fn find_fiat_attestation_address(solbox: &Pubkey, purchase_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"fiat_attestation", solbox.as_ref(), &purchase_id.to_le_bytes()], &ID)
+}
+
+// Derive the Handle PDA a display handle registers at; handles differing
+// only in ASCII case share one
+pub fn find_handle_address(solbox: &Pubkey, handle: &str) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"handle", solbox.as_ref(), &handle_key(handle)], &ID)
+}
+
+// Derive the wallet address of the `index`th house position; it is also
+// the key of the house's User PDA
+pub fn find_house_address(solbox: &Pubkey, index: u8) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"house", solbox.as_ref(), &[index]], &ID)
+}
+
+// Derive the Package PDA describing a package amount
+pub fn find_package_address(solbox: &Pubkey, amount: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"package", solbox.as_ref(), &amount.to_le_bytes()], &ID)
+}
+
+// Derive a wallet's DropCommit PDA for drop `drop_id` of the package of
+// an amount
+pub fn find_drop_commit_address(solbox: &Pubkey, amount: u64, drop_id: u64, wallet: &Pubkey) -> (Pubkey, u8) {
+    let package = find_package_address(solbox, amount).0;
+    Pubkey::find_program_address(&[b"drop_commit", package.as_ref(), &drop_id.to_le_bytes(), wallet.as_ref()], &ID)
+}
+
+// Derive the Token-2022 card mint of a package amount
+pub fn find_card_mint_address(solbox: &Pubkey, amount: u64) -> (Pubkey, u8) {
+    let package = find_package_address(solbox, amount).0;
+    Pubkey::find_program_address(&[b"card_mint", package.as_ref()], &ID)
+}
+
+// Derive the non-transferable position token mint of a support tier
+pub fn find_tier_mint_address(solbox: &Pubkey, tier: u8) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"tier_mint", solbox.as_ref(), &[tier]], &ID)
+}
+
+// Derive the mint a wallet's matrix position is wrapped into
+pub fn find_position_mint_address(solbox: &Pubkey, wallet: &Pubkey) -> (Pubkey, u8) {
+    let user = find_user_address(solbox, wallet).0;
+    Pubkey::find_program_address(&[b"position_mint", user.as_ref()], &ID)
+}
+
+// Derive the Recovery PDA holding a wallet's guardians
+pub fn find_recovery_address(solbox: &Pubkey, wallet: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"recovery", solbox.as_ref(), wallet.as_ref()], &ID)
+}
+
+// Derive the ClaimReceipt PDA of a wallet's yield claim in an epoch
+pub fn find_claim_receipt_address(solbox: &Pubkey, wallet: &Pubkey, epoch: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"claim_receipt", solbox.as_ref(), wallet.as_ref(), &epoch.to_le_bytes()],
+        &ID,
+    )
+}
+
+// Derive a pause guardian's UnpauseApproval PDA
+pub fn find_unpause_approval_address(solbox: &Pubkey, guardian: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"unpause_approval", solbox.as_ref(), guardian.as_ref()], &ID)
+}
+
+// Derive the statement root PDA of an epoch
+pub fn find_statements_address(solbox: &Pubkey, epoch: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"statements", solbox.as_ref(), &epoch.to_le_bytes()], &ID)
+}
+
+// Derive the TreeCheckpoint PDA of a SolBox
+pub fn find_tree_checkpoint_address(solbox: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"tree_checkpoint", solbox.as_ref()], &ID)
+}
+
+// Derive the ColdTreasury PDA of a SolBox
+pub fn find_cold_treasury_address(solbox: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"cold_treasury", solbox.as_ref()], &ID)
+}
+
+// Derive the Migration PDA holding the legacy positions' root
+pub fn find_migration_address(solbox: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"migration", solbox.as_ref()], &ID)
+}
+
+// Derive the Wormhole message PDA of a claim voucher
+pub fn find_voucher_message_address(solbox: &Pubkey, voucher_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"voucher", solbox.as_ref(), &voucher_id.to_le_bytes()], &ID)
+}
+
+// Derive the PDA a SolBox posts Wormhole messages as
+pub fn find_emitter_address(solbox: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"emitter", solbox.as_ref()], &ID)
+}
+
+// Derive the PDA recording a redeemed storefront purchase, by the
+// storefront's chain and emitter and the message's sequence
+pub fn find_wormhole_purchase_address(
+    solbox: &Pubkey,
+    emitter_chain: u16,
+    emitter_address: &[u8; 32],
+    sequence: u64
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            b"wormhole_purchase",
+            solbox.as_ref(),
+            &emitter_chain.to_le_bytes(),
+            emitter_address,
+            &sequence.to_le_bytes(),
+        ],
+        &ID,
+    )
+}
+
+// Derive the Tranche PDA dripping the commission of a purchase
+pub fn find_tranche_address(solbox: &Pubkey, purchase_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"tranche", solbox.as_ref(), &purchase_id.to_le_bytes()], &ID)
+}
+
+// Derive the ConfigSnapshot PDA holding a SolBox's exported hash
+pub fn find_config_snapshot_address(solbox: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"config_snapshot", solbox.as_ref()], &ID)
+}
+
+// Derive the PDA that owns a SolBox's address lookup table
+pub fn find_lookup_authority_address(solbox: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"lookup_authority", solbox.as_ref()], &ID)
+}
+
+// Source of raw account data, implemented over whatever RPC client the
+// caller already uses
+pub trait AccountSource {
+    fn account_data(&self, address: &Pubkey) -> Option<Vec<u8>>;
+}
+
+// Decode an account, checking its discriminator
+pub fn decode<T: AccountDeserialize>(data: &[u8]) -> Result<T> {
+    let mut data = data;
+    T::try_deserialize(&mut data)
+}
+
+// Fetch and decode an account
+pub fn fetch<T: AccountDeserialize>(source: &impl AccountSource, address: &Pubkey) -> Result<T> {
+    let data = source
+        .account_data(address)
+        .ok_or(ErrorCode::AccountNotInitialized)?;
+    decode(&data)
+}
+
+// Name to show for a wallet in leaderboards and genealogy views: its
+// handle, or the shortened address when it has none
+pub fn display_name(source: &impl AccountSource, solbox: &Pubkey, wallet: &Pubkey) -> String {
+    let handle = fetch::<User>(source, &find_user_address(solbox, wallet).0)
+        .and_then(|user| fetch::<Handle>(source, &user.handle));
+    match handle {
+        Ok(handle) => handle.handle,
+        Err(_) => {
+            let address = wallet.to_string();
+            format!("{}…{}", &address[..4], &address[address.len() - 4..])
+        }
+    }
+}
+
+// Fetch a lookup table in the form v0 messages compile against
+pub fn fetch_lookup_table(source: &impl AccountSource, address: &Pubkey) -> Result<AddressLookupTableAccount> {
+    let data = source
+        .account_data(address)
+        .ok_or(ErrorCode::AccountNotInitialized)?;
+    let table = AddressLookupTable::deserialize(&data).map_err(|_| ErrorCode::AccountDidNotDeserialize)?;
+    Ok(AddressLookupTableAccount { key: *address, addresses: table.addresses.to_vec() })
+}
+
+fn build(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+// Prefix for transactions built by this client, rejected by a program whose
+// layouts have moved on
+pub fn assert_version() -> Instruction {
+    build(
+        accounts::AssertVersion { system_program: system_program::ID },
+        instruction::AssertVersion { expected_version: INTERFACE_VERSION },
+    )
+}
+
+// Create a SolBox; `solbox` is a fresh keypair that must also sign
+pub fn initialize(
+    solbox: Pubkey,
+    owner: Pubkey,
+    founder_wallet: Pubkey,
+    config: ContractConfig,
+    sandbox: bool,
+    capacity: Capacity,
+) -> Instruction {
+    build(
+        accounts::Initialize {
+            solbox,
+            config_history: find_config_history_address(&solbox).0,
+            blacklist_filter: find_blacklist_filter_address(&solbox).0,
+            owner,
+            system_program: system_program::ID,
+        },
+        instruction::Initialize { founder_wallet, config, sandbox, capacity },
+    )
+}
+
+pub fn propose_founder_wallet(solbox: Pubkey, admin: Pubkey, new_wallet: Pubkey) -> Instruction {
+    build(
+        accounts::ProposeFounderWallet { solbox, admin },
+        instruction::ProposeFounderWallet { new_wallet },
+    )
+}
+
+pub fn cancel_founder_wallet(solbox: Pubkey, admin: Pubkey) -> Instruction {
+    build(
+        accounts::ProposeFounderWallet { solbox, admin },
+        instruction::CancelFounderWallet {},
+    )
+}
+
+pub fn accept_founder_wallet(solbox: Pubkey, new_founder: Pubkey) -> Instruction {
+    build(
+        accounts::AcceptFounderWallet { solbox, new_founder },
+        instruction::AcceptFounderWallet {},
+    )
+}
+
+pub fn configure_boosts(
+    solbox: Pubkey,
+    admin: Pubkey,
+    loyalty_mint: Pubkey,
+    price: u64,
+    bonus_bps: u16,
+    duration: i64
+) -> Instruction {
+    build(
+        accounts::ConfigureBoosts { solbox, admin },
+        instruction::ConfigureBoosts { loyalty_mint, price, bonus_bps, duration },
+    )
+}
+
+pub fn set_brand_code(solbox: Pubkey, admin: Pubkey, brand_code: [u8; 8]) -> Instruction {
+    build(accounts::SetBrandCode { solbox, admin }, instruction::SetBrandCode { brand_code })
+}
+
+// Burn the boost price from `token_account`, a loyalty token account of the wallet
+// Fails unless the boost costs at most `max_tokens_in` and lasts at least
+// `min_duration` seconds
+pub fn activate_boost(
+    solbox: Pubkey,
+    wallet: Pubkey,
+    loyalty_mint: Pubkey,
+    token_account: Pubkey,
+    max_tokens_in: u64,
+    min_duration: i64,
+) -> Instruction {
+    build(
+        accounts::ActivateBoost {
+            solbox,
+            boost: find_boost_address(&solbox, &wallet).0,
+            loyalty_mint,
+            token_account,
+            wallet,
+            token_program: anchor_spl::token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::ActivateBoost { max_tokens_in, min_duration },
+    )
+}
+
+pub fn update_config(solbox: Pubkey, admin: Pubkey, new_config: ContractConfig) -> Instruction {
+    build(
+        accounts::UpdateConfig {
+            solbox,
+            config_history: find_config_history_address(&solbox).0,
+            admin,
+        },
+        instruction::UpdateConfig { new_config },
+    )
+}
+
+// `terms_hash` is SolBox.terms_hash, the terms the wallet accepts
+pub fn register_user(solbox: Pubkey, wallet: Pubkey, terms_hash: [u8; 32]) -> Instruction {
+    build(
+        accounts::RegisterUser {
+            solbox,
+            user_account: find_user_address(&solbox, &wallet).0,
+            wallet,
+            system_program: system_program::ID,
+        },
+        instruction::RegisterUser { terms_hash },
+    )
+}
+
+// Register `wallet` with the sponsor paying the rent
+pub fn sponsor_registration(solbox: Pubkey, sponsor: Pubkey, wallet: Pubkey) -> Instruction {
+    build(
+        accounts::SponsorRegistration {
+            solbox,
+            blacklist_filter: find_blacklist_filter_address(&solbox).0,
+            user_account: find_user_address(&solbox, &wallet).0,
+            sponsor_user_account: find_user_address(&solbox, &sponsor).0,
+            sponsor,
+            system_program: system_program::ID,
+        },
+        instruction::SponsorRegistration { wallet },
+    )
+}
+
+pub fn accept_sponsorship(solbox: Pubkey, wallet: Pubkey) -> Instruction {
+    build(
+        accounts::AcceptSponsorship {
+            solbox,
+            user_account: find_user_address(&solbox, &wallet).0,
+            wallet,
+        },
+        instruction::AcceptSponsorship {},
+    )
+}
+
+pub fn publish_terms(solbox: Pubkey, admin: Pubkey, terms_hash: [u8; 32]) -> Instruction {
+    build(accounts::PublishTerms { solbox, admin }, instruction::PublishTerms { terms_hash })
+}
+
+pub fn accept_terms(solbox: Pubkey, wallet: Pubkey, terms_hash: [u8; 32]) -> Instruction {
+    build(
+        accounts::AcceptTerms {
+            solbox,
+            user_account: find_user_address(&solbox, &wallet).0,
+            wallet,
+        },
+        instruction::AcceptTerms { terms_hash },
+    )
+}
+
+// Accounts of a purchase beyond the buyer and referrer
+pub struct Purchase {
+    pub solbox: Pubkey,
+    pub founder: Pubkey,
+    // SolBox.purchase_count at the time of the purchase
+    pub purchase_id: u64,
+    // Current day index, see `find_daily_stats_address`
+    pub day: u64,
+    pub amount: u64,
+    // Integrator and its fee wallet
+    pub integrator: Option<(Pubkey, Pubkey)>,
+    pub attribution: Option<Pubkey>,
+    // Session signer paying for the buyer, instead of the buyer signing
+    pub session_key: Option<Pubkey>,
+    // The referrer pays the first package of a wallet it sponsored, and
+    // signs instead of the buyer
+    pub sponsor_paid: bool,
+    // Partner PDA signing for the integrator when buying through CPI
+    pub cpi_authority: Option<Pubkey>,
+    // Load the referrer's Boost PDA, so an active boost pays out
+    pub referrer_boost: bool,
+    // Proof-of-humanity verifier co-signing a large first purchase
+    pub humanity_verifier: Option<Pubkey>,
+    // Open the Tranche PDA, for a commission above config.drip_threshold
+    pub tranche: bool,
+    // Load the Package PDA of the amount, while any package is limited
+    pub package: bool,
+    // Drop id of the buyer's allocated DropCommit to buy with, for a
+    // commit-reveal drop
+    pub drop_commit: Option<u64>,
+    // Wallets of the paid uplines above the placement, nearest first; see
+    // `purchase_uplines`. Only a buyer's first purchase places them
+    pub uplines: Vec<Pubkey>,
+}
+
+pub fn buy_gift_card(purchase: &Purchase, buyer: Pubkey, referrer: Pubkey) -> Instruction {
+    let solbox = purchase.solbox;
+    let (payer, session) = match purchase.session_key {
+        Some(session_key) => (session_key, Some(find_session_key_address(&solbox, &buyer, &session_key).0)),
+        None if purchase.sponsor_paid => (referrer, None),
+        None => (buyer, None),
+    };
+    let mut ix = build(
+        accounts::BuyGiftCard {
+            solbox,
+            blacklist_filter: find_blacklist_filter_address(&solbox).0,
+            user: buyer,
+            payer,
+            user_account: find_user_address(&solbox, &buyer).0,
+            referrer,
+            referrer_user_account: find_user_address(&solbox, &referrer).0,
+            founder: purchase.founder,
+            receipt: find_receipt_address(&solbox, purchase.purchase_id).0,
+            daily_stats: find_daily_stats_address(&solbox, purchase.day).0,
+            integrator: purchase.integrator.map(|(integrator, _)| integrator),
+            integrator_wallet: purchase.integrator.map(|(_, wallet)| wallet),
+            attribution: purchase.attribution,
+            session_key: session,
+            system_program: system_program::ID,
+            cpi_authority: purchase.cpi_authority,
+            referrer_boost: purchase.referrer_boost.then(|| find_boost_address(&solbox, &referrer).0),
+            humanity_verifier: purchase.humanity_verifier,
+            tranche: purchase.tranche.then(|| find_tranche_address(&solbox, purchase.purchase_id).0),
+            package: purchase.package.then(|| find_package_address(&solbox, purchase.amount).0),
+            drop_commit: purchase.drop_commit.map(|drop_id| find_drop_commit_address(&solbox, purchase.amount, drop_id, &buyer).0),
+        },
+        instruction::BuyGiftCard { amount: purchase.amount },
+    );
+
+    // The buyer signs unless a session or the sponsor pays
+    if purchase.session_key.is_none() && !purchase.sponsor_paid {
+        for meta in ix.accounts.iter_mut().filter(|meta| meta.pubkey == buyer) {
+            meta.is_signer = true;
+        }
+    }
+
+    ix.accounts.extend(upline_accounts(&solbox, &purchase.uplines));
+    ix
+}
+
+// Checks the accounts buy_gift_card would get for `purchase` without
+// buying; the program returns a PURCHASE_PROBLEM_* bitmask
+pub fn validate_purchase_accounts(purchase: &Purchase, buyer: Pubkey, referrer: Pubkey) -> Instruction {
+    let solbox = purchase.solbox;
+    let mut ix = build(
+        accounts::ValidatePurchaseAccounts {
+            solbox,
+            blacklist_filter: find_blacklist_filter_address(&solbox).0,
+            buyer,
+            user_account: find_user_address(&solbox, &buyer).0,
+            referrer,
+            referrer_user_account: find_user_address(&solbox, &referrer).0,
+            receipt: find_receipt_address(&solbox, purchase.purchase_id).0,
+            daily_stats: find_daily_stats_address(&solbox, purchase.day).0,
+        },
+        instruction::ValidatePurchaseAccounts { amount: purchase.amount },
+    );
+    ix.accounts.extend(upline_accounts(&solbox, &purchase.uplines));
+    ix
+}
+
+// Dry run of buy_gift_card for `purchase`; the program returns the
+// PurchaseFlows the buyer's wallet would pay
+pub fn simulate_purchase(purchase: &Purchase, buyer: Pubkey, referrer: Pubkey) -> Instruction {
+    let solbox = purchase.solbox;
+    build(
+        accounts::SimulatePurchase {
+            solbox,
+            blacklist_filter: find_blacklist_filter_address(&solbox).0,
+            buyer,
+            user_account: find_user_address(&solbox, &buyer).0,
+            referrer_user_account: find_user_address(&solbox, &referrer).0,
+            referrer_boost: purchase.referrer_boost.then(|| find_boost_address(&solbox, &referrer).0),
+            integrator: purchase.integrator.map(|(integrator, _)| integrator),
+        },
+        instruction::SimulatePurchase { amount: purchase.amount, referrer },
+    )
+}
+
+// Readable names of the problems set in a validate_purchase_accounts result
+pub fn purchase_problems(bits: u32) -> Vec<&'static str> {
+    [
+        (crate::PURCHASE_PROBLEM_PAUSED, "contract is paused"),
+        (crate::PURCHASE_PROBLEM_INVALID_AMOUNT, "amount is not a valid package"),
+        (crate::PURCHASE_PROBLEM_EMERGENCY_PENDING, "emergency withdrawal is pending"),
+        (crate::PURCHASE_PROBLEM_BLACKLISTED, "buyer is blacklisted"),
+        (crate::PURCHASE_PROBLEM_SELF_REFERRAL, "buyer refers themselves"),
+        (crate::PURCHASE_PROBLEM_INSUFFICIENT_FUNDS, "buyer can't pay the amount"),
+        (crate::PURCHASE_PROBLEM_BUYER_NOT_REGISTERED, "buyer is not registered"),
+        (crate::PURCHASE_PROBLEM_REFERRER_NOT_REGISTERED, "referrer is not registered"),
+        (crate::PURCHASE_PROBLEM_SPONSORSHIP_PENDING, "referrer hasn't accepted their sponsorship"),
+        (crate::PURCHASE_PROBLEM_STALE_RECEIPT, "receipt is not the next purchase id"),
+        (crate::PURCHASE_PROBLEM_STALE_DAY, "daily stats are not for today"),
+        (crate::PURCHASE_PROBLEM_NEEDS_HUMANITY_PROOF, "humanity verifier must co-sign"),
+        (crate::PURCHASE_PROBLEM_NO_PLACEMENT, "no open matrix position under the referrer"),
+        (crate::PURCHASE_PROBLEM_UPLINE_MISSING, "upline accounts are missing"),
+        (crate::PURCHASE_PROBLEM_UPLINE_MISMATCH, "upline accounts don't match the referral chain"),
+        (crate::PURCHASE_PROBLEM_TERMS_OUTDATED, "buyer hasn't accepted the current terms"),
+        (crate::PURCHASE_PROBLEM_INVITATION_MISMATCH, "buyer was invited under another referrer"),
+    ]
+    .into_iter()
+    .filter(|(bit, _)| bits & bit != 0)
+    .map(|(_, problem)| problem)
+    .collect()
+}
+
+// Wallets of the paid uplines of a position placed under `spot`, nearest
+// first, following the referral chain stored in the SolBox
+pub fn upline_wallets(state: &SolBox, spot: Position) -> Vec<Pubkey> {
+    let mut wallets = Vec::new();
+    let mut node = Some(spot);
+    while let Some(upline) = node {
+        if wallets.len() == state.config.commission_levels as usize {
+            break;
+        }
+        wallets.push(upline.user);
+        node = crate::parent_position(&state.referral_relationships, upline);
+    }
+    wallets
+}
+
+// Upline wallets for a first purchase under `referrer`, placed where the
+// program's spillover search will put it
+pub fn purchase_uplines(state: &SolBox, referrer: Pubkey) -> Result<Vec<Pubkey>> {
+    Ok(upline_wallets(state, crate::resolve_placement(state, referrer)?))
+}
+
+// Remaining accounts for a placement, in the order the program takes
+// them: each upline's User PDA followed by its wallet
+pub fn upline_accounts(solbox: &Pubkey, wallets: &[Pubkey]) -> Vec<AccountMeta> {
+    wallets
+        .iter()
+        .flat_map(|wallet| {
+            [
+                AccountMeta::new(find_user_address(solbox, wallet).0, false),
+                AccountMeta::new(*wallet, false),
+            ]
+        })
+        .collect()
+}
+
+// `posted_vaa` is the core bridge's PostedVAA account of `vaa`, the
+// storefront message carrying `sale`. The day, uplines, package, drop
+// commit and humanity verifier come from `purchase` as for `buy_gift_card`
+pub fn redeem_wormhole_purchase(
+    purchase: &Purchase,
+    relayer: Pubkey,
+    posted_vaa: Pubkey,
+    vaa: &PostedVaa,
+    sale: &StorefrontPurchase
+) -> Instruction {
+    let solbox = purchase.solbox;
+    let mut ix = build(
+        accounts::RedeemWormholePurchase {
+            solbox,
+            blacklist_filter: find_blacklist_filter_address(&solbox).0,
+            posted_vaa,
+            redemption: find_wormhole_purchase_address(&solbox, vaa.emitter_chain, &vaa.emitter_address, vaa.sequence).0,
+            buyer: sale.buyer,
+            user_account: find_user_address(&solbox, &sale.buyer).0,
+            referrer: sale.referrer,
+            referrer_user_account: find_user_address(&solbox, &sale.referrer).0,
+            relayer,
+            system_program: system_program::ID,
+            daily_stats: find_daily_stats_address(&solbox, purchase.day).0,
+            integrator: sale.integrator,
+            humanity_verifier: purchase.humanity_verifier,
+            package: purchase.package.then(|| find_package_address(&solbox, sale.amount).0),
+            drop_commit: purchase.drop_commit.map(|drop_id| find_drop_commit_address(&solbox, sale.amount, drop_id, &sale.buyer).0),
+        },
+        instruction::RedeemWormholePurchase { sequence: vaa.sequence },
+    );
+    ix.accounts.extend(upline_accounts(&solbox, &purchase.uplines));
+    ix
+}
+
+// `integrator_wallet` is the wallet of the receipt's integrator, if any
+pub fn settle_purchase(
+    solbox: Pubkey,
+    founder: Pubkey,
+    receipt: &Receipt,
+    integrator_wallet: Option<Pubkey>
+) -> Instruction {
+    build(
+        accounts::SettlePurchase {
+            solbox,
+            receipt: find_receipt_address(&solbox, receipt.purchase_id).0,
+            referrer: receipt.payee,
+            referrer_user_account: find_user_address(&solbox, &receipt.payee).0,
+            buyer_user_account: find_user_address(&solbox, &receipt.buyer).0,
+            founder,
+            integrator: (receipt.integrator != Pubkey::default()).then_some(receipt.integrator),
+            integrator_wallet,
+            tranche: receipt.dripped.then(|| find_tranche_address(&solbox, receipt.purchase_id).0),
+        },
+        instruction::SettlePurchase {},
+    )
+}
+
+pub fn release_tranche(solbox: Pubkey, purchase_id: u64, wallet: Pubkey) -> Instruction {
+    build(
+        accounts::ReleaseTranche {
+            solbox,
+            tranche: find_tranche_address(&solbox, purchase_id).0,
+            wallet,
+        },
+        instruction::ReleaseTranche {},
+    )
+}
+
+// Release the tranches of `wallet` from the given purchases in one call
+pub fn release_tranches(solbox: Pubkey, purchase_ids: &[u64], wallet: Pubkey) -> Instruction {
+    let mut ix = build(accounts::ReleaseTranches { solbox, wallet }, instruction::ReleaseTranches {});
+    ix.accounts.extend(
+        purchase_ids
+            .iter()
+            .map(|purchase_id| AccountMeta::new(find_tranche_address(&solbox, *purchase_id).0, false)),
+    );
+    ix
+}
+
+// Cancels a purchase inside its refund window. A cancelled first purchase
+// comes off the downline counters of the uplines it was counted on, which
+// are read from `state` as it stands
+pub fn cancel_purchase(solbox: Pubkey, state: &SolBox, receipt: &Receipt) -> Instruction {
+    let mut ix = build(
+        accounts::CancelPurchase {
+            solbox,
+            receipt: find_receipt_address(&solbox, receipt.purchase_id).0,
+            buyer: receipt.buyer,
+            buyer_user_account: find_user_address(&solbox, &receipt.buyer).0,
+            referrer_user_account: find_user_address(&solbox, &receipt.payee).0,
+            daily_stats: find_daily_stats_address(&solbox, receipt.purchased_at.div_euclid(crate::SECONDS_PER_DAY) as u64).0,
+            integrator: (receipt.integrator != Pubkey::default()).then_some(receipt.integrator),
+            package: (receipt.package != Pubkey::default()).then_some(receipt.package),
+        },
+        instruction::CancelPurchase {},
+    );
+    let placed = Position { user: receipt.buyer, cycle: 0 };
+    if let Some(parent) = receipt.registered
+        .then(|| crate::parent_position(&state.referral_relationships, placed))
+        .flatten()
+    {
+        ix.accounts.extend(upline_accounts(&solbox, &upline_wallets(state, parent)));
+    }
+    ix
+}
+
+// Refunds part of a settled purchase from the treasury, reversing the
+// payee's earnings stats with it
+pub fn admin_refund(solbox: Pubkey, admin: Pubkey, receipt: &Receipt, amount: u64, reason: &str) -> Instruction {
+    build(
+        accounts::AdminRefund {
+            solbox,
+            receipt: find_receipt_address(&solbox, receipt.purchase_id).0,
+            admin,
+            buyer: receipt.buyer,
+            buyer_user_account: find_user_address(&solbox, &receipt.buyer).0,
+            referrer_user_account: find_user_address(&solbox, &receipt.payee).0,
+            package: (receipt.package != Pubkey::default()).then_some(receipt.package),
+        },
+        instruction::AdminRefund {
+            _purchase_id: receipt.purchase_id,
+            amount,
+            reason: reason.to_string(),
+        },
+    )
+}
+
+pub fn toggle_pause(solbox: Pubkey, admin: Pubkey, user: Pubkey) -> Instruction {
+    build(admin_action(solbox, admin, user), instruction::TogglePause {})
+}
+
+pub fn clear_fraud_flags(solbox: Pubkey, admin: Pubkey, user: Pubkey) -> Instruction {
+    build(admin_action(solbox, admin, user), instruction::ClearFraudFlags {})
+}
+
+pub fn grant_package(solbox: Pubkey, admin: Pubkey, user: Pubkey, package: u64) -> Instruction {
+    build(admin_action(solbox, admin, user), instruction::GrantPackage { user, package })
+}
+
+// Sandbox instances only
+pub fn warp_time_offset(solbox: Pubkey, admin: Pubkey, seconds: i64, epochs: u64) -> Instruction {
+    build(
+        accounts::WarpTimeOffset { solbox, admin },
+        instruction::WarpTimeOffset { seconds, epochs },
+    )
+}
+
+fn admin_action(solbox: Pubkey, admin: Pubkey, user: Pubkey) -> accounts::AdminAction {
+    accounts::AdminAction {
+        solbox,
+        admin,
+        user: find_user_address(&solbox, &user).0,
+        system_program: system_program::ID,
+        integrator: None,
+        cpi_authority: None,
+    }
+}
+
+pub fn add_to_blacklist(solbox: Pubkey, admin: Pubkey, user: Pubkey) -> Instruction {
+    build(update_blacklist(solbox, admin), instruction::AddToBlacklist { user })
+}
+
+pub fn remove_from_blacklist(solbox: Pubkey, admin: Pubkey, user: Pubkey) -> Instruction {
+    build(update_blacklist(solbox, admin), instruction::RemoveFromBlacklist { user })
+}
+
+fn update_blacklist(solbox: Pubkey, admin: Pubkey) -> accounts::UpdateBlacklist {
+    accounts::UpdateBlacklist {
+        solbox,
+        blacklist_filter: find_blacklist_filter_address(&solbox).0,
+        admin,
+    }
+}
+
+pub fn withdraw_treasury(solbox: Pubkey, admin: Pubkey, recipient: Pubkey, amount: u64) -> Instruction {
+    build(
+        accounts::WithdrawTreasury { solbox, admin, recipient },
+        instruction::WithdrawTreasury { amount },
+    )
+}
+
+pub fn initiate_emergency_withdraw(solbox: Pubkey, admin: Pubkey, recipient: Pubkey) -> Instruction {
+    build(
+        accounts::ProposeEmergencyWithdraw { solbox, admin },
+        instruction::InitiateEmergencyWithdraw { recipient },
+    )
+}
+
+pub fn cancel_emergency_withdraw(solbox: Pubkey, admin: Pubkey) -> Instruction {
+    build(
+        accounts::ProposeEmergencyWithdraw { solbox, admin },
+        instruction::CancelEmergencyWithdraw {},
+    )
+}
+
+// `recipient` must be the one announced by initiate_emergency_withdraw
+pub fn execute_emergency_withdraw(solbox: Pubkey, admin: Pubkey, recipient: Pubkey) -> Instruction {
+    build(
+        accounts::ExecuteEmergencyWithdraw { solbox, admin, recipient },
+        instruction::ExecuteEmergencyWithdraw {},
+    )
+}
+
+pub fn distribute_yield(solbox: Pubkey, cranker: Pubkey) -> Instruction {
+    build(accounts::DistributeYield { solbox, cranker }, instruction::DistributeYield {})
+}
+
+// Pay out residual commission from closed epochs
+pub fn claim_residual(solbox: Pubkey, wallet: Pubkey) -> Instruction {
+    build(
+        accounts::ClaimResidual {
+            solbox,
+            user_account: find_user_address(&solbox, &wallet).0,
+            wallet,
+        },
+        instruction::ClaimResidual {},
+    )
+}
+
+pub fn claim_to_card(solbox: Pubkey, wallet: Pubkey) -> Instruction {
+    build(
+        accounts::ClaimResidual {
+            solbox,
+            user_account: find_user_address(&solbox, &wallet).0,
+            wallet,
+        },
+        instruction::ClaimToCard {},
+    )
+}
+
+pub fn register_merchant(solbox: Pubkey, admin: Pubkey, wallet: Pubkey) -> Instruction {
+    build(
+        accounts::RegisterMerchant {
+            solbox,
+            merchant: find_merchant_address(&solbox, &wallet).0,
+            admin,
+            system_program: system_program::ID,
+        },
+        instruction::RegisterMerchant { wallet },
+    )
+}
+
+// `month` is `month_of` the instance clock; both wallets sign
+pub fn redeem_to_merchant(
+    solbox: Pubkey,
+    wallet: Pubkey,
+    merchant_wallet: Pubkey,
+    month: u32,
+    amount: u64,
+) -> Instruction {
+    let merchant = find_merchant_address(&solbox, &merchant_wallet).0;
+    build(
+        accounts::RedeemToMerchant {
+            solbox,
+            user_account: find_user_address(&solbox, &wallet).0,
+            wallet,
+            merchant,
+            merchant_month: find_merchant_month_address(&merchant, month).0,
+            merchant_wallet,
+            system_program: system_program::ID,
+        },
+        instruction::RedeemToMerchant { amount },
+    )
+}
+
+// `voucher_id` is SolBox.voucher_count at the time of the export
+pub fn export_claim_voucher(
+    solbox: Pubkey,
+    wallet: Pubkey,
+    voucher_id: u64,
+    recipient: [u8; 32],
+    nonce: u32,
+) -> Instruction {
+    let emitter = find_emitter_address(&solbox).0;
+    let bridge = |seeds: &[&[u8]]| Pubkey::find_program_address(seeds, &WORMHOLE_CORE_BRIDGE_ID).0;
+    build(
+        accounts::ExportClaimVoucher {
+            solbox,
+            user_account: find_user_address(&solbox, &wallet).0,
+            wallet,
+            bridge_config: bridge(&[b"Bridge"]),
+            message: find_voucher_message_address(&solbox, voucher_id).0,
+            emitter,
+            sequence: bridge(&[b"Sequence", emitter.as_ref()]),
+            fee_collector: bridge(&[b"fee_collector"]),
+            clock: sysvar::clock::ID,
+            rent: sysvar::rent::ID,
+            wormhole_program: WORMHOLE_CORE_BRIDGE_ID,
+            system_program: system_program::ID,
+        },
+        instruction::ExportClaimVoucher { recipient, nonce },
+    )
+}
+
+// Permissionless; `wallet` receives its queued claims
+pub fn flush_founder_share(solbox: Pubkey, founder: Pubkey) -> Instruction {
+    build(accounts::FlushFounderShare { solbox, founder }, instruction::FlushFounderShare {})
+}
+
+pub fn pay_queued_payout(solbox: Pubkey, wallet: Pubkey) -> Instruction {
+    build(
+        accounts::PayQueuedPayout {
+            solbox,
+            user_account: find_user_address(&solbox, &wallet).0,
+            wallet,
+        },
+        instruction::PayQueuedPayout {},
+    )
+}
+
+// `epoch` must be the current one; resubmitting within it is a no-op
+pub fn claim_yield(solbox: Pubkey, wallet: Pubkey, epoch: u64) -> Instruction {
+    build(
+        accounts::ClaimYield {
+            solbox,
+            user_account: find_user_address(&solbox, &wallet).0,
+            claim_receipt: find_claim_receipt_address(&solbox, &wallet, epoch).0,
+            wallet,
+            system_program: system_program::ID,
+        },
+        instruction::ClaimYield { epoch },
+    )
+}
+
+// Close the finished tier re-qualification periods of a wallet
+pub fn refresh_tier(solbox: Pubkey, wallet: Pubkey) -> Instruction {
+    build(
+        accounts::RefreshTier {
+            solbox,
+            user_account: find_user_address(&solbox, &wallet).0,
+        },
+        instruction::RefreshTier {},
+    )
+}
+
+// Fails unless it costs at most `max_lamports_in` and pays up to at least
+// `min_paid_until`
+pub fn renew_membership(solbox: Pubkey, wallet: Pubkey, max_lamports_in: u64, min_paid_until: i64) -> Instruction {
+    build(
+        accounts::RenewMembership {
+            solbox,
+            blacklist_filter: find_blacklist_filter_address(&solbox).0,
+            user_account: find_user_address(&solbox, &wallet).0,
+            wallet,
+            system_program: system_program::ID,
+        },
+        instruction::RenewMembership { max_lamports_in, min_paid_until },
+    )
+}
+
+// The wallet pays the difference up to `new_package`, failing if that is
+// more than `max_lamports_in`
+pub fn upgrade_package(solbox: Pubkey, wallet: Pubkey, new_package: u64, max_lamports_in: u64) -> Instruction {
+    build(
+        accounts::UpgradePackage {
+            solbox,
+            blacklist_filter: find_blacklist_filter_address(&solbox).0,
+            user: find_user_address(&solbox, &wallet).0,
+            wallet,
+            system_program: system_program::ID,
+        },
+        instruction::UpgradePackage { new_package, max_lamports_in },
+    )
+}
+
+pub fn lapse_membership(solbox: Pubkey, wallet: Pubkey) -> Instruction {
+    build(
+        accounts::RefreshTier {
+            solbox,
+            user_account: find_user_address(&solbox, &wallet).0,
+        },
+        instruction::LapseMembership {},
+    )
+}
+
+pub fn initialize_feature_flags(solbox: Pubkey, admin: Pubkey) -> Instruction {
+    build(
+        accounts::InitializeFeatureFlags {
+            solbox,
+            feature_flags: find_feature_flags_address(&solbox).0,
+            admin,
+            system_program: system_program::ID,
+        },
+        instruction::InitializeFeatureFlags {},
+    )
+}
+
+pub fn propose_feature_flags(solbox: Pubkey, admin: Pubkey, flags: u64) -> Instruction {
+    build(
+        accounts::UpdateFeatureFlags {
+            solbox,
+            feature_flags: find_feature_flags_address(&solbox).0,
+            admin,
+        },
+        instruction::ProposeFeatureFlags { flags },
+    )
+}
+
+pub fn cancel_feature_flags(solbox: Pubkey, admin: Pubkey) -> Instruction {
+    build(
+        accounts::UpdateFeatureFlags {
+            solbox,
+            feature_flags: find_feature_flags_address(&solbox).0,
+            admin,
+        },
+        instruction::CancelFeatureFlags {},
+    )
+}
+
+pub fn apply_feature_flags(solbox: Pubkey) -> Instruction {
+    build(
+        accounts::ApplyFeatureFlags {
+            solbox,
+            feature_flags: find_feature_flags_address(&solbox).0,
+        },
+        instruction::ApplyFeatureFlags {},
+    )
+}
+
+pub fn create_contest(
+    solbox: Pubkey,
+    admin: Pubkey,
+    contest_id: u64,
+    start: i64,
+    end: i64,
+    metric: ContestMetric,
+    prizes: Vec<u64>
+) -> Instruction {
+    build(
+        accounts::CreateContest {
+            solbox,
+            contest: find_contest_address(&solbox, contest_id).0,
+            admin,
+            system_program: system_program::ID,
+        },
+        instruction::CreateContest { contest_id, start, end, metric, prizes },
+    )
+}
+
+pub fn enter_contest(solbox: Pubkey, contest: Pubkey, wallet: Pubkey) -> Instruction {
+    build(
+        accounts::EnterContest {
+            solbox,
+            contest,
+            entry: find_contest_entry_address(&contest, &wallet).0,
+            user_account: find_user_address(&solbox, &wallet).0,
+            wallet,
+            system_program: system_program::ID,
+        },
+        instruction::EnterContest {},
+    )
+}
+
+// `participants` must list every wallet that entered the contest
+pub fn finalize_contest(solbox: Pubkey, contest: Pubkey, creator: Pubkey, participants: &[Pubkey]) -> Instruction {
+    let mut ix = build(accounts::FinalizeContest { solbox, contest, creator }, instruction::FinalizeContest {});
+    for wallet in participants {
+        ix.accounts.push(AccountMeta::new_readonly(find_contest_entry_address(&contest, wallet).0, false));
+        ix.accounts.push(AccountMeta::new_readonly(find_user_address(&solbox, wallet).0, false));
+    }
+    ix
+}
+
+pub fn claim_contest_prize(solbox: Pubkey, contest: Pubkey, wallet: Pubkey) -> Instruction {
+    build(
+        accounts::ClaimContestPrize { solbox, contest, wallet },
+        instruction::ClaimContestPrize {},
+    )
+}
+
+// `recipient` is the default pubkey for a notice to everyone
+pub fn post_notice(
+    solbox: Pubkey,
+    admin: Pubkey,
+    notice_id: u64,
+    recipient: Pubkey,
+    kind: u8,
+    message: String
+) -> Instruction {
+    build(
+        accounts::PostNotice {
+            solbox,
+            notice: find_notice_address(&solbox, notice_id).0,
+            admin,
+            system_program: system_program::ID,
+        },
+        instruction::PostNotice { notice_id, recipient, kind, message },
+    )
+}
+
+// Attest a purchase as the on-ramp partner; `attester` is the integrator's
+// wallet and pays the attestation's rent
+pub fn attest_fiat_purchase(
+    solbox: Pubkey,
+    integrator: Pubkey,
+    attester: Pubkey,
+    purchase_id: u64,
+    buyer: Pubkey,
+    amount: u64,
+    external_ref: [u8; 32]
+) -> Instruction {
+    build(
+        accounts::AttestFiatPurchase {
+            solbox,
+            integrator,
+            receipt: find_receipt_address(&solbox, purchase_id).0,
+            attestation: find_fiat_attestation_address(&solbox, purchase_id).0,
+            attester,
+            system_program: system_program::ID,
+        },
+        instruction::AttestFiatPurchase { purchase_id, buyer, amount, external_ref },
+    )
+}
+
+// Invite a contact ahead of registration; the referrer pays the rent
+pub fn create_invitation(solbox: Pubkey, referrer: Pubkey, identifier: &[u8]) -> Instruction {
+    let identifier_hash = invitation_hash(&solbox, identifier);
+    build(
+        accounts::CreateInvitation {
+            solbox,
+            invitation: find_invitation_address(&solbox, &identifier_hash).0,
+            referrer_user_account: find_user_address(&solbox, &referrer).0,
+            referrer,
+            system_program: system_program::ID,
+        },
+        instruction::CreateInvitation { identifier_hash },
+    )
+}
+
+// Claim the invitation made for `identifier`, before the first purchase
+pub fn claim_invitation(solbox: Pubkey, wallet: Pubkey, identifier: &[u8]) -> Instruction {
+    build(
+        accounts::ClaimInvitation {
+            solbox,
+            invitation: find_invitation_address(&solbox, &invitation_hash(&solbox, identifier)).0,
+            user_account: find_user_address(&solbox, &wallet).0,
+            wallet,
+        },
+        instruction::ClaimInvitation { identifier: identifier.to_vec() },
+    )
+}
+
+pub fn create_package(solbox: Pubkey, admin: Pubkey, amount: u64, category: u8, metadata_uri: String) -> Instruction {
+    build(
+        accounts::CreatePackage {
+            solbox,
+            package: find_package_address(&solbox, amount).0,
+            admin,
+            system_program: system_program::ID,
+        },
+        instruction::CreatePackage { amount, category, metadata_uri },
+    )
+}
+
+pub fn update_package(solbox: Pubkey, admin: Pubkey, amount: u64, category: u8, metadata_uri: String) -> Instruction {
+    build(
+        accounts::UpdatePackage {
+            solbox,
+            package: find_package_address(&solbox, amount).0,
+            admin,
+        },
+        instruction::UpdatePackage { category, metadata_uri },
+    )
+}
+
+pub fn set_package_supply(
+    solbox: Pubkey,
+    admin: Pubkey,
+    amount: u64,
+    remaining: Option<u64>,
+    low_stock_thresholds: [u64; crate::MAX_LOW_STOCK_THRESHOLDS],
+) -> Instruction {
+    build(
+        accounts::SetPackageSupply {
+            solbox,
+            package: find_package_address(&solbox, amount).0,
+            admin,
+        },
+        instruction::SetPackageSupply { remaining, low_stock_thresholds },
+    )
+}
+
+pub fn request_deletion(solbox: Pubkey, wallet: Pubkey) -> Instruction {
+    build(
+        accounts::RequestDeletion {
+            solbox,
+            user_account: find_user_address(&solbox, &wallet).0,
+            wallet,
+        },
+        instruction::RequestDeletion {},
+    )
+}
+
+// `tombstone` is the SolBox's tombstone_count when the deletion lands
+pub fn process_deletion(solbox: Pubkey, admin: Pubkey, wallet: Pubkey, tombstone: u64) -> Instruction {
+    build(
+        accounts::ProcessDeletion {
+            solbox,
+            user_account: find_user_address(&solbox, &wallet).0,
+            tombstone_account: find_user_address(&solbox, &tombstone_key(&solbox, tombstone)).0,
+            wallet,
+            admin,
+            system_program: system_program::ID,
+        },
+        instruction::ProcessDeletion {},
+    )
+}
+
+pub fn schedule_drop(
+    solbox: Pubkey,
+    admin: Pubkey,
+    amount: u64,
+    commit_until_slot: u64,
+    reveal_until_slot: u64
+) -> Instruction {
+    build(
+        accounts::ScheduleDrop {
+            solbox,
+            package: find_package_address(&solbox, amount).0,
+            admin,
+        },
+        instruction::ScheduleDrop { commit_until_slot, reveal_until_slot },
+    )
+}
+
+// `commitment` is `drop_commitment` for drop `drop_id`, with a quantity
+// and salt kept secret until the reveal
+pub fn commit_purchase(solbox: Pubkey, wallet: Pubkey, amount: u64, drop_id: u64, commitment: [u8; 32]) -> Instruction {
+    build(
+        accounts::CommitPurchase {
+            solbox,
+            package: find_package_address(&solbox, amount).0,
+            drop_commit: find_drop_commit_address(&solbox, amount, drop_id, &wallet).0,
+            wallet,
+            system_program: system_program::ID,
+        },
+        instruction::CommitPurchase { commitment },
+    )
+}
+
+pub fn reveal_purchase(
+    solbox: Pubkey,
+    wallet: Pubkey,
+    amount: u64,
+    drop_id: u64,
+    quantity: u64,
+    salt: [u8; 32]
+) -> Instruction {
+    build(
+        accounts::RevealPurchase {
+            solbox,
+            package: find_package_address(&solbox, amount).0,
+            drop_commit: find_drop_commit_address(&solbox, amount, drop_id, &wallet).0,
+            wallet,
+        },
+        instruction::RevealPurchase { quantity, salt },
+    )
+}
+
+// `commits` are DropCommit PDAs in commit order, starting at the
+// package's drop_cursor
+pub fn allocate_drop(solbox: Pubkey, amount: u64, commits: &[Pubkey]) -> Instruction {
+    let mut ix = build(
+        accounts::AllocateDrop {
+            solbox,
+            package: find_package_address(&solbox, amount).0,
+        },
+        instruction::AllocateDrop {},
+    );
+    ix.accounts.extend(commits.iter().map(|commit| AccountMeta::new(*commit, false)));
+    ix
+}
+
+pub fn close_drop_commit(solbox: Pubkey, amount: u64, drop_id: u64, wallet: Pubkey) -> Instruction {
+    build(
+        accounts::CloseDropCommit {
+            solbox,
+            package: find_package_address(&solbox, amount).0,
+            drop_commit: find_drop_commit_address(&solbox, amount, drop_id, &wallet).0,
+            wallet,
+        },
+        instruction::CloseDropCommit {},
+    )
+}
+
+pub fn create_card_mint(solbox: Pubkey, admin: Pubkey, amount: u64) -> Instruction {
+    build(
+        accounts::CreateCardMint {
+            solbox,
+            package: find_package_address(&solbox, amount).0,
+            card_mint: find_card_mint_address(&solbox, amount).0,
+            admin,
+            token_program: anchor_spl::token_2022::ID,
+            system_program: system_program::ID,
+        },
+        instruction::CreateCardMint {},
+    )
+}
+
+// `card_account` is a Token-2022 account of the package's card mint; it
+// may belong to someone other than the buyer
+pub fn issue_card(solbox: Pubkey, buyer: Pubkey, purchase_id: u64, amount: u64, card_account: Pubkey) -> Instruction {
+    build(
+        accounts::IssueCard {
+            solbox,
+            receipt: find_receipt_address(&solbox, purchase_id).0,
+            package: find_package_address(&solbox, amount).0,
+            card_mint: find_card_mint_address(&solbox, amount).0,
+            card_account,
+            buyer,
+            token_program: anchor_spl::token_2022::ID,
+        },
+        instruction::IssueCard {},
+    )
+}
+
+pub fn redeem_card(solbox: Pubkey, holder: Pubkey, amount: u64, card_account: Pubkey) -> Instruction {
+    build(
+        accounts::RedeemCard {
+            solbox,
+            package: find_package_address(&solbox, amount).0,
+            card_mint: find_card_mint_address(&solbox, amount).0,
+            card_account,
+            user_account: find_user_address(&solbox, &holder).0,
+            holder,
+            token_program: anchor_spl::token_2022::ID,
+        },
+        instruction::RedeemCard {},
+    )
+}
+
+// Moves every card in `from` to `to`, both Token-2022 accounts of the
+// package's card mint
+pub fn claw_back_card(
+    solbox: Pubkey,
+    admin: Pubkey,
+    amount: u64,
+    from: Pubkey,
+    to: Pubkey,
+    reason: String,
+) -> Instruction {
+    build(
+        accounts::ClawBackCard {
+            solbox,
+            package: find_package_address(&solbox, amount).0,
+            card_mint: find_card_mint_address(&solbox, amount).0,
+            from,
+            to,
+            admin,
+            token_program: anchor_spl::token_2022::ID,
+        },
+        instruction::ClawBackCard { reason },
+    )
+}
+
+pub fn create_tier_mint(solbox: Pubkey, admin: Pubkey, tier: u8) -> Instruction {
+    build(
+        accounts::CreateTierMint {
+            solbox,
+            tier_mint: find_tier_mint_address(&solbox, tier).0,
+            admin,
+            token_program: anchor_spl::token_2022::ID,
+            system_program: system_program::ID,
+        },
+        instruction::CreateTierMint { tier },
+    )
+}
+
+// `token_account` is the wallet's Token-2022 account of the tier mint
+pub fn sync_tier_token(solbox: Pubkey, wallet: Pubkey, tier: u8, token_account: Pubkey) -> Instruction {
+    build(
+        accounts::SyncTierToken {
+            solbox,
+            user_account: find_user_address(&solbox, &wallet).0,
+            wallet,
+            tier_mint: find_tier_mint_address(&solbox, tier).0,
+            token_account,
+            token_program: anchor_spl::token_2022::ID,
+        },
+        instruction::SyncTierToken { tier },
+    )
+}
+
+pub fn create_position_mint(solbox: Pubkey, wallet: Pubkey) -> Instruction {
+    build(
+        accounts::CreatePositionMint {
+            solbox,
+            user_account: find_user_address(&solbox, &wallet).0,
+            position_mint: find_position_mint_address(&solbox, &wallet).0,
+            wallet,
+            token_program: anchor_spl::token_2022::ID,
+            system_program: system_program::ID,
+        },
+        instruction::CreatePositionMint {},
+    )
+}
+
+// `position_account` is any Token-2022 account of the position mint,
+// which receives the token
+pub fn wrap_position(solbox: Pubkey, wallet: Pubkey, position_account: Pubkey) -> Instruction {
+    build(
+        accounts::WrapPosition {
+            solbox,
+            user_account: find_user_address(&solbox, &wallet).0,
+            position_mint: find_position_mint_address(&solbox, &wallet).0,
+            position_account,
+            wallet,
+            token_program: anchor_spl::token_2022::ID,
+        },
+        instruction::WrapPosition {},
+    )
+}
+
+pub fn unwrap_position(solbox: Pubkey, wallet: Pubkey, position_account: Pubkey) -> Instruction {
+    build(
+        accounts::UnwrapPosition {
+            solbox,
+            user_account: find_user_address(&solbox, &wallet).0,
+            position_mint: find_position_mint_address(&solbox, &wallet).0,
+            position_account,
+            wallet,
+            token_program: anchor_spl::token_2022::ID,
+        },
+        instruction::UnwrapPosition {},
+    )
+}
+
+// `wallet` is the position's wallet; `holder` signs with the token
+// account holding its position token
+pub fn claim_position_residual(
+    solbox: Pubkey,
+    wallet: Pubkey,
+    holder: Pubkey,
+    position_account: Pubkey,
+) -> Instruction {
+    build(
+        accounts::ClaimPositionResidual {
+            solbox,
+            user_account: find_user_address(&solbox, &wallet).0,
+            position_mint: find_position_mint_address(&solbox, &wallet).0,
+            position_account,
+            holder,
+            token_program: anchor_spl::token_2022::ID,
+        },
+        instruction::ClaimPositionResidual {},
+    )
+}
+
+pub fn ack_events(solbox: Pubkey, wallet: Pubkey) -> Instruction {
+    build(
+        accounts::AckEvents {
+            solbox,
+            user_account: find_user_address(&solbox, &wallet).0,
+            wallet,
+        },
+        instruction::AckEvents {},
+    )
+}
+
+pub fn mark_notice_read(solbox: Pubkey, notice_id: u64, recipient: Pubkey) -> Instruction {
+    build(
+        accounts::MarkNoticeRead {
+            notice: find_notice_address(&solbox, notice_id).0,
+            recipient,
+        },
+        instruction::MarkNoticeRead {},
+    )
+}
+
+pub fn close_notice(solbox: Pubkey, notice_id: u64, poster: Pubkey, closer: Pubkey) -> Instruction {
+    build(
+        accounts::CloseNotice {
+            solbox,
+            notice: find_notice_address(&solbox, notice_id).0,
+            poster,
+            closer,
+        },
+        instruction::CloseNotice {},
+    )
+}
+
+pub fn set_guardians(solbox: Pubkey, wallet: Pubkey, guardians: Vec<Pubkey>, threshold: u8) -> Instruction {
+    build(
+        accounts::SetGuardians {
+            solbox,
+            user_account: find_user_address(&solbox, &wallet).0,
+            recovery: find_recovery_address(&solbox, &wallet).0,
+            wallet,
+            system_program: system_program::ID,
+        },
+        instruction::SetGuardians { guardians, threshold },
+    )
+}
+
+pub fn approve_recovery(solbox: Pubkey, wallet: Pubkey, guardian: Pubkey, new_wallet: Pubkey) -> Instruction {
+    build(
+        accounts::ApproveRecovery {
+            solbox,
+            recovery: find_recovery_address(&solbox, &wallet).0,
+            guardian,
+        },
+        instruction::ApproveRecovery { new_wallet },
+    )
+}
+
+pub fn cancel_recovery(solbox: Pubkey, wallet: Pubkey) -> Instruction {
+    build(
+        accounts::CancelRecovery {
+            recovery: find_recovery_address(&solbox, &wallet).0,
+            wallet,
+        },
+        instruction::CancelRecovery {},
+    )
+}
+
+// Signed by the new wallet, which pays for its User PDA
+pub fn execute_recovery(solbox: Pubkey, wallet: Pubkey, new_wallet: Pubkey) -> Instruction {
+    build(
+        accounts::ExecuteRecovery {
+            solbox,
+            blacklist_filter: find_blacklist_filter_address(&solbox).0,
+            recovery: find_recovery_address(&solbox, &wallet).0,
+            old_user_account: find_user_address(&solbox, &wallet).0,
+            new_user_account: find_user_address(&solbox, &new_wallet).0,
+            new_wallet,
+            system_program: system_program::ID,
+        },
+        instruction::ExecuteRecovery {},
+    )
+}
+
+pub fn set_beneficiary(solbox: Pubkey, wallet: Pubkey, beneficiary: Pubkey, inactivity_period: i64) -> Instruction {
+    build(
+        accounts::SetBeneficiary {
+            solbox,
+            user_account: find_user_address(&solbox, &wallet).0,
+            wallet,
+        },
+        instruction::SetBeneficiary { beneficiary, inactivity_period },
+    )
+}
+
+pub fn register_handle(solbox: Pubkey, wallet: Pubkey, handle: &str) -> Instruction {
+    build(
+        accounts::RegisterHandle {
+            solbox,
+            user_account: find_user_address(&solbox, &wallet).0,
+            handle: find_handle_address(&solbox, handle).0,
+            wallet,
+            system_program: system_program::ID,
+        },
+        instruction::RegisterHandle { handle_hash: handle_key(handle), handle: handle.to_string() },
+    )
+}
+
+// Release `wallet`'s handle, signed by the wallet itself
+pub fn release_handle(solbox: Pubkey, wallet: Pubkey, handle: &str) -> Instruction {
+    build(
+        release_handle_accounts(solbox, wallet, handle, wallet),
+        instruction::ReleaseHandle {},
+    )
+}
+
+// Revoke `wallet`'s handle as the admin
+pub fn revoke_handle(solbox: Pubkey, admin: Pubkey, wallet: Pubkey, handle: &str) -> Instruction {
+    build(
+        release_handle_accounts(solbox, wallet, handle, admin),
+        instruction::RevokeHandle {},
+    )
+}
+
+fn release_handle_accounts(solbox: Pubkey, wallet: Pubkey, handle: &str, authority: Pubkey) -> accounts::ReleaseHandle {
+    accounts::ReleaseHandle {
+        solbox,
+        user_account: find_user_address(&solbox, &wallet).0,
+        handle: find_handle_address(&solbox, handle).0,
+        wallet,
+        authority,
+    }
+}
+
+// Signed by the beneficiary, which pays for its User PDA
+pub fn claim_inheritance(solbox: Pubkey, wallet: Pubkey, beneficiary: Pubkey) -> Instruction {
+    build(
+        accounts::ClaimInheritance {
+            solbox,
+            blacklist_filter: find_blacklist_filter_address(&solbox).0,
+            old_user_account: find_user_address(&solbox, &wallet).0,
+            new_user_account: find_user_address(&solbox, &beneficiary).0,
+            beneficiary,
+            system_program: system_program::ID,
+        },
+        instruction::ClaimInheritance {},
+    )
+}
+
+pub fn anchor_statements(solbox: Pubkey, admin: Pubkey, epoch: u64, root: [u8; 32], users: u32) -> Instruction {
+    build(
+        accounts::AnchorStatements {
+            solbox,
+            statements: find_statements_address(&solbox, epoch).0,
+            admin,
+            system_program: system_program::ID,
+        },
+        instruction::AnchorStatements { epoch, root, users },
+    )
+}
+
+pub fn initialize_cold_treasury(solbox: Pubkey, admin: Pubkey) -> Instruction {
+    build(
+        accounts::InitializeColdTreasury {
+            solbox,
+            cold_treasury: find_cold_treasury_address(&solbox).0,
+            admin,
+            system_program: system_program::ID,
+        },
+        instruction::InitializeColdTreasury {},
+    )
+}
+
+pub fn sweep_treasury(solbox: Pubkey) -> Instruction {
+    build(
+        accounts::SweepTreasury { solbox, cold_treasury: find_cold_treasury_address(&solbox).0 },
+        instruction::SweepTreasury {},
+    )
+}
+
+pub fn propose_cold_withdrawal(solbox: Pubkey, admin: Pubkey, recipient: Pubkey, amount: u64) -> Instruction {
+    build(
+        accounts::ProposeColdWithdrawal {
+            solbox,
+            cold_treasury: find_cold_treasury_address(&solbox).0,
+            admin,
+        },
+        instruction::ProposeColdWithdrawal { recipient, amount },
+    )
+}
+
+pub fn approve_cold_withdrawal(solbox: Pubkey, guardian: Pubkey) -> Instruction {
+    build(
+        accounts::ApproveColdWithdrawal {
+            solbox,
+            cold_treasury: find_cold_treasury_address(&solbox).0,
+            guardian,
+        },
+        instruction::ApproveColdWithdrawal {},
+    )
+}
+
+pub fn execute_cold_withdrawal(solbox: Pubkey, admin: Pubkey, recipient: Pubkey) -> Instruction {
+    build(
+        accounts::ExecuteColdWithdrawal {
+            solbox,
+            cold_treasury: find_cold_treasury_address(&solbox).0,
+            admin,
+            recipient,
+        },
+        instruction::ExecuteColdWithdrawal {},
+    )
+}
+
+pub fn checkpoint_tree(solbox: Pubkey, payer: Pubkey, cursor: u64) -> Instruction {
+    build(
+        accounts::CheckpointTree {
+            solbox,
+            tree_checkpoint: find_tree_checkpoint_address(&solbox).0,
+            payer,
+            system_program: system_program::ID,
+        },
+        instruction::CheckpointTree { cursor },
+    )
+}
+
+// Root a finished checkpoint of the tree should hold, for auditors
+// comparing it with the SolBox they read
+pub fn tree_root(solbox: &SolBox) -> [u8; 32] {
+    let leaves: Vec<[u8; 32]> = solbox.referral_relationships
+        .iter()
+        .enumerate()
+        .map(|(index, relationship)| tree_leaf(index as u64, relationship))
+        .collect();
+    merkle_root(&leaves)
+}
+
+pub fn commit_migration_root(solbox: Pubkey, admin: Pubkey, merkle_root: [u8; 32], positions: u32) -> Instruction {
+    build(
+        accounts::CommitMigrationRoot {
+            solbox,
+            migration: find_migration_address(&solbox).0,
+            admin,
+            system_program: system_program::ID,
+        },
+        instruction::CommitMigrationRoot { merkle_root, positions },
+    )
+}
+
+pub fn export_config_hash(solbox: Pubkey, admin: Pubkey, tree_root: [u8; 32], positions: u32) -> Instruction {
+    build(
+        accounts::ExportConfigHash {
+            solbox,
+            config_snapshot: find_config_snapshot_address(&solbox).0,
+            admin,
+            system_program: system_program::ID,
+        },
+        instruction::ExportConfigHash { tree_root, positions },
+    )
+}
+
+// The snapshot export_config_hash hashes for `state`, kept to restore from
+pub fn instance_snapshot(state: &SolBox, source: Pubkey, tree_root: [u8; 32], positions: u32) -> InstanceSnapshot {
+    crate::snapshot_instance(state, source, tree_root, positions)
+}
+
+// Signed by a guardian named at export, approving `target` for the
+// snapshot of `source`
+pub fn approve_restore(source: Pubkey, target: Pubkey, guardian: Pubkey) -> Instruction {
+    build(
+        accounts::ApproveRestore {
+            solbox: target,
+            config_snapshot: find_config_snapshot_address(&source).0,
+            guardian,
+        },
+        instruction::ApproveRestore {},
+    )
+}
+
+pub fn restore_instance(source: Pubkey, target: Pubkey, admin: Pubkey, snapshot: InstanceSnapshot) -> Instruction {
+    build(
+        accounts::RestoreInstance {
+            solbox: target,
+            config_history: find_config_history_address(&target).0,
+            config_snapshot: find_config_snapshot_address(&source).0,
+            migration: find_migration_address(&target).0,
+            admin,
+            system_program: system_program::ID,
+        },
+        instruction::RestoreInstance { snapshot },
+    )
+}
+
+// Signed by the position's wallet. `uplines` are the upline wallets of the
+// sponsor placement (purchase_uplines), empty when the wallet is already
+// placed or has no sponsor
+pub fn claim_migrated_position(
+    solbox: Pubkey,
+    position: &MigratedPosition,
+    proof: Vec<[u8; 32]>,
+    uplines: &[Pubkey]
+) -> Instruction {
+    let mut ix = build(
+        accounts::ClaimMigratedPosition {
+            solbox,
+            migration: find_migration_address(&solbox).0,
+            user_account: find_user_address(&solbox, &position.wallet).0,
+            wallet: position.wallet,
+        },
+        instruction::ClaimMigratedPosition {
+            proof,
+            package: position.package,
+            sponsor: position.sponsor,
+            lifetime_volume: position.lifetime_volume,
+        },
+    );
+    ix.accounts.extend(upline_accounts(&solbox, uplines));
+    ix
+}
+
+// `index` is the number of house positions created so far; `uplines` are
+// the upline wallets of the placement under `sponsor` (purchase_uplines)
+pub fn create_house_position(
+    solbox: Pubkey,
+    admin: Pubkey,
+    index: u8,
+    sponsor: Pubkey,
+    uplines: &[Pubkey]
+) -> Instruction {
+    let house = find_house_address(&solbox, index).0;
+    let mut ix = build(
+        accounts::CreateHousePosition {
+            solbox,
+            house,
+            house_account: find_user_address(&solbox, &house).0,
+            admin,
+            system_program: system_program::ID,
+        },
+        instruction::CreateHousePosition { sponsor },
+    );
+    ix.accounts.extend(upline_accounts(&solbox, uplines));
+    ix
+}
+
+pub fn verify_statement(solbox: Pubkey, statement: Statement, proof: Vec<[u8; 32]>) -> Instruction {
+    build(
+        accounts::VerifyStatement {
+            solbox,
+            statements: find_statements_address(&solbox, statement.epoch).0,
+        },
+        instruction::VerifyStatement { statement, proof },
+    )
+}
+
+pub fn set_pause_guardians(solbox: Pubkey, admin: Pubkey, guardians: Vec<Pubkey>, threshold: u8) -> Instruction {
+    build(
+        accounts::SetPauseGuardians { solbox, admin },
+        instruction::SetPauseGuardians { guardians, threshold },
+    )
+}
+
+pub fn approve_unpause(solbox: Pubkey, guardian: Pubkey) -> Instruction {
+    build(
+        accounts::ApproveUnpause {
+            solbox,
+            approval: find_unpause_approval_address(&solbox, &guardian).0,
+            guardian,
+            system_program: system_program::ID,
+        },
+        instruction::ApproveUnpause {},
+    )
+}
+
+// `guardians` are the approvers whose UnpauseApproval PDAs are passed
+pub fn execute_unpause(solbox: Pubkey, caller: Pubkey, guardians: &[Pubkey]) -> Instruction {
+    let mut ix = build(accounts::ExecuteUnpause { solbox, caller }, instruction::ExecuteUnpause {});
+    for guardian in guardians {
+        ix.accounts.push(AccountMeta::new_readonly(find_unpause_approval_address(&solbox, guardian).0, false));
+    }
+    ix
+}
+
+// Create the SolBox's lookup table at the address derived from
+// `recent_slot`, which is returned alongside the instruction
+pub fn create_lookup_table(solbox: Pubkey, admin: Pubkey, recent_slot: u64) -> (Instruction, Pubkey) {
+    let lookup_authority = find_lookup_authority_address(&solbox).0;
+    let lookup_table = address_lookup_table::instruction::derive_lookup_table_address(&lookup_authority, recent_slot).0;
+    let ix = build(
+        accounts::CreateLookupTable {
+            solbox,
+            lookup_authority,
+            lookup_table,
+            admin,
+            address_lookup_table_program: address_lookup_table::program::ID,
+            system_program: system_program::ID,
+        },
+        instruction::CreateLookupTable { recent_slot },
+    );
+    (ix, lookup_table)
+}
+
+pub fn extend_lookup_table(solbox: Pubkey, admin: Pubkey, lookup_table: Pubkey, addresses: Vec<Pubkey>) -> Instruction {
+    build(
+        accounts::ExtendLookupTable {
+            solbox,
+            lookup_authority: find_lookup_authority_address(&solbox).0,
+            lookup_table,
+            admin,
+            address_lookup_table_program: address_lookup_table::program::ID,
+            system_program: system_program::ID,
+        },
+        instruction::ExtendLookupTable { addresses },
+    )
+}
+
+// Accounts most purchases touch: the SolBox and its singleton PDAs, the
+// founder wallet and stake pool, and the User PDA and wallet of every
+// position within `levels` of a root, where upline chains converge.
+// Pass what the table doesn't hold yet to extend_lookup_table, in chunks
+// of MAX_LOOKUP_EXTEND
+pub fn hot_accounts(solbox: &Pubkey, state: &SolBox, levels: usize) -> Vec<Pubkey> {
+    let mut accounts = vec![
+        *solbox,
+        find_config_history_address(solbox).0,
+        find_blacklist_filter_address(solbox).0,
+        state.founder_wallet,
+        system_program::ID,
+    ];
+    if state.config.stake_pool != Pubkey::default() {
+        accounts.push(state.config.stake_pool);
+    }
+
+    let relationships = &state.referral_relationships;
+    let mut level: Vec<Position> = Vec::new();
+    for relationship in relationships {
+        let position = Position { user: relationship.referrer, cycle: relationship.referrer_cycle };
+        if crate::parent_position(relationships, position).is_none() && !level.contains(&position) {
+            level.push(position);
+        }
+    }
+    for _ in 0..levels {
+        let mut next = Vec::new();
+        for position in level {
+            for address in [find_user_address(solbox, &position.user).0, position.user] {
+                if !accounts.contains(&address) {
+                    accounts.push(address);
+                }
+            }
+            next.extend(crate::position_children(relationships, position));
+        }
+        level = next;
+    }
+    accounts
+}
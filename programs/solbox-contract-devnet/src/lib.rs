@@ -3,7 +3,26 @@
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::system_instruction;
 use anchor_lang::solana_program::program::invoke;
-use std::collections::HashMap;
+use anchor_lang::solana_program::hash::{hash, hashv};
+use anchor_lang::solana_program::instruction::{
+    get_stack_height, AccountMeta, Instruction, TRANSACTION_LEVEL_STACK_HEIGHT,
+};
+use anchor_lang::solana_program::address_lookup_table;
+use anchor_lang::solana_program::program::invoke_signed;
+use anchor_lang::solana_program::{stake, sysvar};
+use anchor_spl::token::{self, Burn, Mint, Token, TokenAccount};
+use anchor_spl::token_2022::Token2022;
+use anchor_spl::token_interface;
+
+pub mod math;
+pub mod statements;
+#[cfg(not(target_os = "solana"))]
+pub mod client;
+#[cfg(not(target_os = "solana"))]
+pub mod scenario;
+
+use math::{capped_fee, package_yield, pro_rata, split_amount, yield_increment};
+use statements::{append_leaf, frontier_root, tree_leaf, verify_proof, MigratedPosition, Statement, TREE_FRONTIER_DEPTH};
 
 declare_id!("D7hxGNmozyBY4T5G2YttUh8ZbErGKXZzGd5z4749on5S");
 
@@ -11,13 +30,32 @@ declare_id!("D7hxGNmozyBY4T5G2YttUh8ZbErGKXZzGd5z4749on5S");
 pub mod solbox_contract_devnet {
     use super::*;
 
+    // `sandbox` marks a non-production instance for QA; it can never be
+    // changed afterwards. `capacity` sizes the SolBox and ConfigHistory
+    // accounts and every User account, see SolBox::space and User::space
     pub fn initialize(
         ctx: Context<Initialize>,
         founder_wallet: Pubkey,
         config: ContractConfig,
+        sandbox: bool,
+        capacity: Capacity,
     ) -> Result<()> {
         let solbox = &mut ctx.accounts.solbox;
         
+        validate_config(&config, &[])?;
+        require!(
+            config.valid_amounts.len() <= capacity.max_amounts as usize,
+            CustomError::TooManyAmounts
+        );
+        require!(
+            capacity.max_levels as usize <= MAX_COMMISSION_LEVELS
+                && config.commission_levels <= capacity.max_levels,
+            CustomError::TooManyLevels
+        );
+        
+        solbox.account_type = ACCOUNT_TYPE_SOLBOX;
+        solbox.account_version = SOLBOX_VERSION;
+        
         // Set the contract owner and founder wallet
         solbox.owner = *ctx.accounts.owner.key;
         solbox.founder_wallet = founder_wallet;
@@ -27,23 +65,234 @@ pub mod solbox_contract_devnet {
         solbox.total_sold = 0;
         solbox.total_commission_distributed = 0;
         solbox.referral_count = 0;
+        solbox.purchase_count = 0;
+        solbox.escrow_balance = 0;
+        solbox.staked_lamports = 0;
+        solbox.total_package_weight = 0;
+        solbox.yield_per_package = 0;
+        solbox.undistributed_yield = 0;
+        solbox.yield_pool_balance = 0;
+        solbox.residual_balance = 0;
+        solbox.queued_payouts = 0;
+        solbox.deferred_founder_share = 0;
+        solbox.next_yield_epoch = 0;
+        solbox.user_count = 0;
+        solbox.active_users = 0;
+        solbox.pending_receipts = 0;
+        solbox.pending_founder_wallet = Pubkey::default();
+        solbox.founder_change_eta = 0;
+        solbox.emergency_recipient = Pubkey::default();
+        solbox.emergency_eta = 0;
+        solbox.loyalty_mint = Pubkey::default();
+        solbox.boost_price = 0;
+        solbox.boost_bps = 0;
+        solbox.boost_duration = 0;
+        solbox.pause_guardians = Vec::new();
+        solbox.unpause_threshold = 0;
+        solbox.pause_count = 0;
+        solbox.sandbox = sandbox;
+        solbox.time_offset = 0;
+        solbox.epoch_offset = 0;
+        solbox.max_amounts = capacity.max_amounts;
+        solbox.max_levels = capacity.max_levels;
+        solbox.lookup_table = Pubkey::default();
+        solbox.house_commission = 0;
+        solbox.brand_code = [0u8; 8];
+        solbox.tranche_balance = 0;
+        solbox.terms_hash = [0u8; 32];
+        solbox.terms_version = 0;
+        solbox.limited_packages = 0;
+        solbox.voucher_balance = 0;
+        solbox.voucher_count = 0;
+        solbox.tombstone_count = 0;
+        solbox.tree_rewrites = 0;
+        solbox.hot_withdrawn_day = 0;
+        solbox.hot_withdrawn = 0;
+        solbox.card_balance_total = 0;
         solbox.config = config;
         
         // Initialize empty collections
         solbox.blacklisted_users = Vec::new();
         solbox.referral_relationships = Vec::new();
+        solbox.holding_tank = Vec::new();
+        solbox.house_positions = Vec::new();
+        
+        let blacklist_filter = &mut ctx.accounts.blacklist_filter;
+        blacklist_filter.account_type = ACCOUNT_TYPE_BLACKLIST_FILTER;
+        blacklist_filter.account_version = BLACKLIST_FILTER_VERSION;
+        blacklist_filter.solbox = solbox.key();
+        blacklist_filter.bump = ctx.bumps.blacklist_filter;
+        blacklist_filter.bits = [0u8; BLACKLIST_FILTER_BYTES];
+        
+        // Record the initial config as version 1
+        let config_history = &mut ctx.accounts.config_history;
+        config_history.account_type = ACCOUNT_TYPE_CONFIG_HISTORY;
+        config_history.account_version = CONFIG_HISTORY_VERSION;
+        config_history.solbox = solbox.key();
+        config_history.bump = ctx.bumps.config_history;
+        config_history.version = 0;
+        config_history.entries = Vec::new();
+        record_config_version(
+            config_history,
+            solbox.config.clone(),
+            *ctx.accounts.owner.key,
+            Clock::get()?.slot,
+        )?;
         
         emit!(InitializeEvent {
             owner: *ctx.accounts.owner.key,
             founder_wallet,
             timestamp: Clock::get()?.unix_timestamp,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    pub fn propose_founder_wallet(ctx: Context<ProposeFounderWallet>, new_wallet: Pubkey) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == solbox.owner,
+            CustomError::Unauthorized
+        );
+        require!(new_wallet != Pubkey::default(), CustomError::InvalidFounderWallet);
+        
+        // A new proposal replaces any pending one and restarts the timelock
+        let now = unix_now(solbox)?;
+        let eta = now
+            .checked_add(FOUNDER_CHANGE_TIMELOCK)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        solbox.pending_founder_wallet = new_wallet;
+        solbox.founder_change_eta = eta;
+        
+        emit!(FounderWalletProposedEvent {
+            admin: *ctx.accounts.admin.key,
+            current_wallet: solbox.founder_wallet,
+            pending_wallet: new_wallet,
+            eta,
+            timestamp: now,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    pub fn cancel_founder_wallet(ctx: Context<ProposeFounderWallet>) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == solbox.owner,
+            CustomError::Unauthorized
+        );
+        require!(
+            solbox.pending_founder_wallet != Pubkey::default(),
+            CustomError::NoPendingFounderWallet
+        );
+        solbox.pending_founder_wallet = Pubkey::default();
+        solbox.founder_change_eta = 0;
+        
+        Ok(())
+    }
+
+    // The proposed wallet signs to prove it can receive payouts
+    pub fn accept_founder_wallet(ctx: Context<AcceptFounderWallet>) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        let now = unix_now(solbox)?;
+        require!(
+            solbox.pending_founder_wallet != Pubkey::default(),
+            CustomError::NoPendingFounderWallet
+        );
+        require!(
+            ctx.accounts.new_founder.key() == solbox.pending_founder_wallet,
+            CustomError::Unauthorized
+        );
+        require!(now >= solbox.founder_change_eta, CustomError::TimelockNotElapsed);
+        
+        let previous_wallet = solbox.founder_wallet;
+        solbox.founder_wallet = solbox.pending_founder_wallet;
+        solbox.pending_founder_wallet = Pubkey::default();
+        solbox.founder_change_eta = 0;
+        
+        emit!(FounderWalletChangedEvent {
+            previous_wallet,
+            new_wallet: solbox.founder_wallet,
+            timestamp: now,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    pub fn configure_boosts(
+        ctx: Context<ConfigureBoosts>,
+        loyalty_mint: Pubkey,
+        price: u64,
+        bonus_bps: u16,
+        duration: i64
+    ) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == solbox.owner,
+            CustomError::Unauthorized
+        );
+        require!(
+            price == 0 || (loyalty_mint != Pubkey::default()
+                && bonus_bps > 0
+                && bonus_bps <= MAX_BOOST_BPS
+                && duration > 0),
+            CustomError::InvalidBoostConfig
+        );
+        
+        // Boosts already active keep their rate until they expire
+        solbox.loyalty_mint = loyalty_mint;
+        solbox.boost_price = price;
+        solbox.boost_bps = bonus_bps;
+        solbox.boost_duration = duration;
+        
+        emit!(BoostsConfiguredEvent {
+            admin: *ctx.accounts.admin.key,
+            loyalty_mint,
+            price,
+            bonus_bps,
+            duration,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Tag this instance's events so indexers watching several tenants can
+    // split the streams. The event carries the previous code as well
+    pub fn set_brand_code(ctx: Context<SetBrandCode>, brand_code: [u8; 8]) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == solbox.owner,
+            CustomError::Unauthorized
+        );
+        
+        let previous = solbox.brand_code;
+        solbox.brand_code = brand_code;
+        
+        emit!(BrandCodeEvent {
+            admin: *ctx.accounts.admin.key,
+            previous,
+            timestamp: unix_now(solbox)?,
+            brand_code,
         });
         
         Ok(())
     }
 
     pub fn update_config(
-        ctx: Context<AdminAction>,
+        ctx: Context<UpdateConfig>,
         new_config: ContractConfig,
     ) -> Result<()> {
         let solbox = &mut ctx.accounts.solbox;
@@ -57,13 +306,34 @@ pub mod solbox_contract_devnet {
         // Ensure contract is not paused
         require!(!solbox.paused, CustomError::ContractPaused);
         
+        // Ensure the existing tree still fits the new matrix
+        validate_config(&new_config, &solbox.referral_relationships)?;
+        require!(
+            new_config.valid_amounts.len() <= solbox.max_amounts as usize,
+            CustomError::TooManyAmounts
+        );
+        require!(
+            new_config.commission_levels <= solbox.max_levels,
+            CustomError::TooManyLevels
+        );
+        
         // Update configuration
         solbox.config = new_config.clone();
         
+        // Keep a copy of the parameters now in force
+        let version = record_config_version(
+            &mut ctx.accounts.config_history,
+            new_config.clone(),
+            *ctx.accounts.admin.key,
+            Clock::get()?.slot,
+        )?;
+        
         emit!(ConfigUpdateEvent {
             admin: *ctx.accounts.admin.key,
             new_config,
-            timestamp: Clock::get()?.unix_timestamp,
+            version,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
         });
         
         Ok(())
@@ -78,31 +348,156 @@ pub mod solbox_contract_devnet {
             CustomError::Unauthorized
         );
         
+        // With a guardian quorum set, only execute_unpause reopens
+        require!(
+            !solbox.paused || solbox.unpause_threshold == 0,
+            CustomError::UnpauseQuorumRequired
+        );
+        
         // Toggle pause state
         solbox.paused = !solbox.paused;
+        if solbox.paused {
+            solbox.pause_count = solbox.pause_count
+                .checked_add(1)
+                .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        }
         
         emit!(PauseEvent {
             admin: *ctx.accounts.admin.key,
             paused: solbox.paused,
-            timestamp: Clock::get()?.unix_timestamp,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // The wallet accepts the terms in force by passing their hash
+    pub fn register_user(ctx: Context<RegisterUser>, terms_hash: [u8; 32]) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        let user_account = &mut ctx.accounts.user_account;
+        init_user(solbox, user_account, ctx.accounts.wallet.key(), ctx.bumps.user_account)?;
+        solbox.user_count = solbox.user_count
+            .checked_add(1)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        accept_current_terms(solbox, user_account, terms_hash)?;
+        
+        emit!(UserRegisteredEvent {
+            user: user_account.key,
+            user_account: user_account.key(),
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Register a downline wallet at the sponsor's expense, for newcomers
+    // without SOL. The sponsor may also pay the first package, placed under
+    // itself (see buy_gift_card); the wallet later signs accept_sponsorship
+    // before it can refer anyone
+    pub fn sponsor_registration(ctx: Context<SponsorRegistration>, wallet: Pubkey) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        let sponsor = ctx.accounts.sponsor.key();
+        
+        // Verify contract is active
+        require!(!solbox.paused, CustomError::ContractPaused);
+        require!(wallet != sponsor, CustomError::SelfReferralNotAllowed);
+        require!(
+            !is_blacklisted(solbox, &ctx.accounts.blacklist_filter, &sponsor),
+            CustomError::UserBlacklisted
+        );
+        
+        let user_account = &mut ctx.accounts.user_account;
+        init_user(solbox, user_account, wallet, ctx.bumps.user_account)?;
+        user_account.sponsored_by = sponsor;
+        solbox.user_count = solbox.user_count
+            .checked_add(1)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        user_account.pending_acceptance = true;
+        
+        let now = unix_now(solbox)?;
+        emit!(UserRegisteredEvent {
+            user: wallet,
+            user_account: user_account.key(),
+            timestamp: now,
+            brand_code: solbox.brand_code,
+        });
+        emit!(SponsorshipEvent {
+            sponsor,
+            user: wallet,
+            accepted: false,
+            timestamp: now,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    pub fn accept_sponsorship(ctx: Context<AcceptSponsorship>) -> Result<()> {
+        let user_account = &mut ctx.accounts.user_account;
+        require!(user_account.pending_acceptance, CustomError::NoPendingSponsorship);
+        user_account.pending_acceptance = false;
+        
+        emit!(SponsorshipEvent {
+            sponsor: user_account.sponsored_by,
+            user: user_account.key,
+            accepted: true,
+            timestamp: unix_now(&ctx.accounts.solbox)?,
+            brand_code: ctx.accounts.solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Admin publishes new terms; users accept them with accept_terms
+    pub fn publish_terms(ctx: Context<PublishTerms>, terms_hash: [u8; 32]) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == solbox.owner,
+            CustomError::Unauthorized
+        );
+        
+        solbox.terms_version = solbox.terms_version
+            .checked_add(1)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        solbox.terms_hash = terms_hash;
+        
+        emit!(TermsPublishedEvent {
+            admin: *ctx.accounts.admin.key,
+            terms_hash,
+            version: solbox.terms_version,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
         });
         
         Ok(())
     }
 
+    // Registered users, and sponsored wallets, accept the terms in force
+    pub fn accept_terms(ctx: Context<AcceptTerms>, terms_hash: [u8; 32]) -> Result<()> {
+        accept_current_terms(&ctx.accounts.solbox, &mut ctx.accounts.user_account, terms_hash)
+    }
+
+    // The step up is priced from the package held when the instruction
+    // lands, which a lapse can drop; max_lamports_in bounds it
     pub fn upgrade_package(
         ctx: Context<UpgradePackage>,
-        new_package: u64
+        new_package: u64,
+        max_lamports_in: u64
     ) -> Result<()> {
-        let solbox = &ctx.accounts.solbox;
+        let solbox = &mut ctx.accounts.solbox;
         let user = &mut ctx.accounts.user;
+        let wallet = ctx.accounts.wallet.key();
         
         // Verify contract is active
         require!(!solbox.paused, CustomError::ContractPaused);
         
         // Check if user is blacklisted
         require!(
-            !solbox.blacklisted_users.contains(&user.key()),
+            !is_blacklisted(solbox, &ctx.accounts.blacklist_filter, &wallet),
             CustomError::UserBlacklisted
         );
         
@@ -112,6 +507,9 @@ pub mod solbox_contract_devnet {
             CustomError::InvalidAmount
         );
         
+        // No new money while an emergency withdrawal is announced
+        require!(solbox.emergency_eta == 0, CustomError::EmergencyWithdrawPending);
+        
         // Ensure upgrade is to a higher package
         require!(
             new_package > user.current_package,
@@ -121,200 +519,1094 @@ pub mod solbox_contract_devnet {
         // Calculate price difference
         let difference = new_package
             .checked_sub(user.current_package)
-            .ok_or(CustomError::ArithmeticError)?;
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        require!(difference <= max_lamports_in, CustomError::SlippageExceeded);
             
-        // Transfer difference amount
+        // Transfer difference amount from the wallet
         invoke(
             &system_instruction::transfer(
-                &user.key(),
+                &wallet,
                 solbox.to_account_info().key,
                 difference
             ),
             &[
-                user.to_account_info(),
+                ctx.accounts.wallet.to_account_info(),
                 solbox.to_account_info(),
                 ctx.accounts.system_program.to_account_info(),
             ],
         )?;
         // Update user's package
         let old_package = user.current_package;
-        user.current_package = new_package;
+        set_package(solbox, user, new_package)?;
         
         emit!(PackageUpgradeEvent {
-            user: user.key(),
+            user: wallet,
             old_package,
             new_package,
             difference,
-            timestamp: Clock::get()?.unix_timestamp,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
         });
         
         Ok(())
     }
 
-    pub fn buy_gift_card(
-        ctx: Context<BuyGiftCard>,
+    pub fn buy_gift_card<'info>(
+        ctx: Context<'_, '_, 'info, 'info, BuyGiftCard<'info>>,
         amount: u64
     ) -> Result<()> {
         let solbox = &mut ctx.accounts.solbox;
         let user = &ctx.accounts.user;
         let referrer = &ctx.accounts.referrer;
         
-        // Verify contract is active
-        require!(!solbox.paused, CustomError::ContractPaused);
+        // Checks shared with simulate_purchase
+        check_purchase(
+            solbox,
+            &ctx.accounts.blacklist_filter,
+            user.key,
+            &ctx.accounts.user_account,
+            referrer.key,
+            &ctx.accounts.referrer_user_account,
+            amount
+        )?;
         
-        // Check if user is blacklisted
-        require!(
-            !solbox.blacklisted_users.contains(user.key),
-            CustomError::UserBlacklisted
-        );
+        // Partner programs may only buy through CPI when allowlisted
+        require_cpi_caller(
+            solbox.key(),
+            ctx.accounts.integrator.as_ref(),
+            ctx.accounts.cpi_authority.as_ref(),
+            INTEGRATOR_ALLOW_BUY_GIFT_CARD
+        )?;
         
-        // Validate purchase amount
-        require!(
-            solbox.config.valid_amounts.contains(&amount),
-            CustomError::InvalidAmount
-        );
+        // Limited editions sell from the package's remaining supply
+        let spent_commit = take_package_unit(
+            solbox,
+            ctx.accounts.package.as_mut(),
+            ctx.accounts.drop_commit.as_mut(),
+            user.key,
+            amount
+        )?;
+        if let Some(commit) = ctx.accounts.drop_commit.as_mut().filter(|_| spent_commit) {
+            commit.close(user.to_account_info())?;
+        }
         
-        // Prevent self-referral
-        require!(
-            user.key() != referrer.key(),
-            CustomError::SelfReferralNotAllowed
-        );
+        // A payer other than the buyer, outside the buyer's own session
+        // budget, funds the purchase
+        let payer = ctx.accounts.payer.key();
+        let funder = (payer != user.key() && ctx.accounts.session_key.is_none()).then_some(payer);
+        flag_purchase(
+            solbox,
+            &mut ctx.accounts.daily_stats,
+            ctx.accounts.integrator.as_mut(),
+            funder,
+            user.key,
+            &mut ctx.accounts.user_account
+        )?;
         
-        // Calculate commissions
-        let commission = amount
-            .checked_mul(solbox.config.commission_percentage)
-            .ok_or(CustomError::ArithmeticError)?
-            .checked_div(100)
-            .ok_or(CustomError::ArithmeticError)?;
-            
-        let bonus = amount
-            .checked_mul(solbox.config.bonus_percentage)
-            .ok_or(CustomError::ArithmeticError)?
-            .checked_div(100)
-            .ok_or(CustomError::ArithmeticError)?;
-            
-        // Handle referral spillover if needed
-        let final_referrer = if solbox.referral_count >= solbox.config.referral_limit as u64 {
-            find_spillover_position(
-                &solbox.referral_relationships,
-                referrer.key(),
-                solbox.config.referral_limit
-            ).ok_or(CustomError::NoSpilloverAvailable)?
-        } else {
-            referrer.key()
-        };
+        // A buyer joins the referral tree on their first purchase
+        let is_new_registration = !solbox.referral_relationships
+            .iter()
+            .any(|r| r.user == *user.key)
+            && !solbox.holding_tank
+                .iter()
+                .any(|entry| entry.user == *user.key);
+        if is_new_registration {
+            check_registration(
+                solbox,
+                &ctx.accounts.user_account,
+                referrer.key,
+                amount,
+                ctx.accounts.humanity_verifier.as_ref().map(|verifier| verifier.key())
+            )?;
+        }
+        
+        // The sponsor of a registration it paid for may also pay the first
+        // package, placed under itself, without the wallet signing
+        let sponsor_funded = !user.is_signer
+            && is_new_registration
+            && ctx.accounts.user_account.pending_acceptance
+            && ctx.accounts.user_account.sponsored_by == ctx.accounts.payer.key()
+            && referrer.key() == ctx.accounts.payer.key();
+        
+        // Work out where the lamports go, as simulate_purchase reports it.
+        // Only a purchase the buyer's wallet pays can spend the card balance
+        let card_balance = (ctx.accounts.session_key.is_none() && !sponsor_funded)
+            .then_some(ctx.accounts.user_account.card_balance);
+        let flows = purchase_flows(
+            solbox,
+            amount,
+            referrer.key(),
+            &ctx.accounts.referrer_user_account,
+            ctx.accounts.referrer_boost.as_deref(),
+            ctx.accounts.integrator.as_deref(),
+            card_balance
+        )?;
+        let final_referrer = flows.referrer;
+        let commission = flows.commission;
+        let house_referrer = flows.house;
+        let residual = flows.residual;
+        let fast_start = flows.fast_start;
+        let drip = flows.dripped;
+        let founder_share = flows.founder_share;
+        let integrator_fee = flows.integrator_fee;
+        let paid = flows.paid;
+        let card_funded = flows.card_funded;
+        
+        // The client passes the Tranche PDA exactly when the commission drips
+        require!(ctx.accounts.tranche.is_some() == drip, CustomError::TrancheMismatch);
         
         // Update contract state
         solbox.total_sold = solbox.total_sold
             .checked_add(amount)
-            .ok_or(CustomError::ArithmeticError)?;
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
             
         solbox.total_commission_distributed = solbox.total_commission_distributed
             .checked_add(commission)
-            .ok_or(CustomError::ArithmeticError)?;
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
             
         solbox.referral_count = solbox.referral_count
             .checked_add(1)
-            .ok_or(CustomError::ArithmeticError)?;
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
             
-        // Record referral relationship
-        solbox.referral_relationships.push(ReferralRelationship {
-            user: *user.key,
-            referrer: final_referrer,
-            timestamp: Clock::get()?.unix_timestamp,
-        });
-        
-        // Update referrer's earnings
+        add_purchase_volume(solbox, &mut ctx.accounts.user_account, amount)?;
+            
+        // Update referrer's earnings, by whether the buyer is placed on
+        // one of the referrer's own legs or spills over below them
         let referrer_account = &mut ctx.accounts.referrer_user_account;
-        referrer_account.total_earnings = referrer_account.total_earnings
-            .checked_add(commission)
-            .ok_or(CustomError::ArithmeticError)?;
+        let spillover = final_referrer != referrer.key();
+        let source = if spillover { EarningSource::Spillover } else { EarningSource::Direct };
+        if !house_referrer {
+            referrer_account.total_earnings = referrer_account.total_earnings
+                .checked_add(commission)
+                .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+            credit_earnings(referrer_account, source, commission)?;
+        }
         
-        // Transfer commission to referrer
-        invoke(
-            &system_instruction::transfer(
-                user.key,
-                &final_referrer,
-                commission
-            ),
-            &[
-                user.to_account_info(),
-                ctx.accounts.referrer.to_account_info(),
-                ctx.accounts.system_program.to_account_info(),
-            ],
-        )?;
+        // Repeat purchases only load the referrer, so it alone sees them;
+        // signups reach every paid upline once they are placed
+        if !is_new_registration {
+            referrer_account.unseen_downline_events = referrer_account.unseen_downline_events.saturating_add(1);
+        }
+        
+        let now = unix_now(solbox)?;
+        
+        // Record referral relationship; buyers hold a single position
+        // until it recycles, so repeat purchases don't take new slots
+        if is_new_registration {
+            if solbox.config.holding_tank_period > 0 {
+                // Park the signup until the sponsor places them
+                require!(
+                    solbox.holding_tank.len() < MAX_HOLDING_TANK_ENTRIES,
+                    CustomError::HoldingTankFull
+                );
+                solbox.holding_tank.push(HoldingTankEntry {
+                    user: *user.key,
+                    sponsor: referrer.key(),
+                    entered_at: now,
+                });
+            } else {
+                // Upline accounts come in remaining_accounts, see take_uplines
+                let vault = solbox.to_account_info();
+                let spot = Position { user: final_referrer, cycle: flows.referrer_cycle };
+                let mut uplines = ctx.remaining_accounts;
+                place_in_matrix(
+                    solbox,
+                    &vault,
+                    &mut uplines,
+                    ctx.remaining_accounts,
+                    *user.key,
+                    spot,
+                    Some(&mut **referrer_account)
+                )?;
+            }
+        }
+        
+        // Integrators are capped per epoch, so a leaked partner key can
+        // only push so much through the matrix before it is revoked
+        if let Some(integrator) = ctx.accounts.integrator.as_deref_mut() {
+            charge_integrator_limits(integrator, current_epoch(solbox)?, amount)?;
+        }
+        let integrator = ctx.accounts.integrator.as_ref();
+        
+        // Purchases are signed by the buyer, or by one of their session
+        // keys within its scope, in which case the session budget pays.
+        // A card balance covering the whole purchase pays for it from the
+        // vault, where its lamports already are
+        let funding = match ctx.accounts.session_key.as_mut() {
+            Some(session) => {
+                require!(
+                    session.solbox == solbox.key()
+                        && session.owner == user.key()
+                        && session.session_key == ctx.accounts.payer.key(),
+                    CustomError::InvalidSessionKey
+                );
+                require!(now < session.expires_at, CustomError::SessionKeyExpired);
+                session.spent = session.spent
+                    .checked_add(paid)
+                    .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+                require!(
+                    session.spent <= session.max_lamports,
+                    CustomError::SessionKeyLimitExceeded
+                );
+                session.to_account_info()
+            }
+            None if card_funded => {
+                require!(user.is_signer, CustomError::Unauthorized);
+                let user_account = &mut ctx.accounts.user_account;
+                user_account.card_balance = user_account.card_balance
+                    .checked_sub(paid)
+                    .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+                solbox.card_balance_total = solbox.card_balance_total
+                    .checked_sub(paid)
+                    .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+                emit!(CardBalanceEvent {
+                    user: user.key(),
+                    amount: paid,
+                    bonus: 0,
+                    balance: user_account.card_balance,
+                    action: CardBalanceAction::Spent,
+                    timestamp: now,
+                    brand_code: solbox.brand_code,
+                });
+                solbox.to_account_info()
+            }
+            None if sponsor_funded => ctx.accounts.payer.to_account_info(),
+            None => {
+                require!(user.is_signer, CustomError::Unauthorized);
+                user.to_account_info()
+            }
+        };
+        let program_funded = ctx.accounts.session_key.is_some() || card_funded;
+        let system_program = ctx.accounts.system_program.to_account_info();
+        
+        // Record the purchase receipt
+        let receipt = &mut ctx.accounts.receipt;
+        receipt.account_type = ACCOUNT_TYPE_RECEIPT;
+        receipt.account_version = RECEIPT_VERSION;
+        receipt.solbox = solbox.key();
+        receipt.buyer = *user.key;
+        receipt.referrer = final_referrer;
+        receipt.payee = referrer.key();
+        receipt.purchase_id = solbox.purchase_count;
+        receipt.amount = amount;
+        receipt.commission = commission;
+        receipt.founder_share = founder_share;
+        receipt.slot = Clock::get()?.slot;
+        receipt.purchased_at = now;
+        receipt.refundable_until = now
+            .checked_add(solbox.config.refund_window)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        receipt.refunded_amount = 0;
+        receipt.integrator = integrator.map(|integrator| integrator.key()).unwrap_or_default();
+        receipt.integrator_fee = integrator_fee;
+        receipt.residual = residual;
+        receipt.spillover = spillover;
+        receipt.card_issued = false;
+        receipt.dripped = drip;
+        receipt.card_funded = card_funded;
+        receipt.registered = is_new_registration;
+        receipt.epoch = current_epoch(solbox)?;
+        receipt.package = ctx.accounts.package
+            .as_ref()
+            .filter(|package| package.limited)
+            .map(|package| package.key())
+            .unwrap_or_default();
+        receipt.bump = ctx.bumps.receipt;
+        
+        let purchase_id = solbox.purchase_count;
+        
+        // Escrowed purchases start dripping once they settle
+        if let Some(tranche) = ctx.accounts.tranche.as_mut() {
+            tranche.account_type = ACCOUNT_TYPE_TRANCHE;
+            tranche.account_version = TRANCHE_VERSION;
+            tranche.solbox = solbox.key();
+            tranche.wallet = receipt.payee;
+            tranche.purchase_id = purchase_id;
+            tranche.total = fast_start;
+            tranche.released = 0;
+            tranche.days = solbox.config.drip_days;
+            tranche.starts_at = if solbox.config.refund_window > 0 { 0 } else { now };
+            tranche.bump = ctx.bumps.tranche.unwrap_or_default();
+        }
+        
+        // Consume the click attribution this purchase came through
+        if let Some(attribution) = ctx.accounts.attribution.as_mut() {
+            require!(
+                attribution.solbox == solbox.key()
+                    && attribution.referrer == referrer.key(),
+                CustomError::InvalidAttribution
+            );
+            require!(
+                attribution.consumed_by == Pubkey::default(),
+                CustomError::AttributionConsumed
+            );
+            attribution.consumed_by = *user.key;
+            attribution.purchase_id = purchase_id;
+        }
         
-        // Calculate and transfer remaining amount to founder
-        let founder_share = amount
-            .checked_sub(commission)
-            .ok_or(CustomError::ArithmeticError)?
-            .checked_sub(bonus)
-            .ok_or(CustomError::ArithmeticError)?;
+        solbox.purchase_count = solbox.purchase_count
+            .checked_add(1)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        
+        if solbox.config.refund_window > 0 {
+            // Hold the funds in the vault until the refund window closes
+            let escrowed = receipt_total(receipt)?;
+            pay_purchase(&funding, program_funded, &solbox.to_account_info(), escrowed, &system_program)?;
             
-        invoke(
-            &system_instruction::transfer(
-                user.key,
-                &solbox.founder_wallet,
-                founder_share
-            ),
-            &[
-                user.to_account_info(),
-                ctx.accounts.founder.to_account_info(),
-                ctx.accounts.system_program.to_account_info(),
-            ],
-        )?;
+            solbox.escrow_balance = solbox.escrow_balance
+                .checked_add(escrowed)
+                .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+            solbox.pending_receipts = solbox.pending_receipts
+                .checked_add(1)
+                .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+            count_pending_receipt(&mut ctx.accounts.user_account, &mut ctx.accounts.referrer_user_account, true)?;
+            receipt.status = ReceiptStatus::Pending;
+        } else {
+            // Transfer the fast-start commission to the referrer and hold
+            // the residual; a house position's commission stays in the
+            // vault for the bonus pool
+            if house_referrer {
+                pay_purchase(&funding, program_funded, &solbox.to_account_info(), commission, &system_program)?;
+                credit_house_commission(solbox, referrer.key(), purchase_id, commission)?;
+            } else if drip {
+                pay_purchase(&funding, program_funded, &solbox.to_account_info(), fast_start, &system_program)?;
+                hold_tranche(solbox, fast_start)?;
+            } else {
+                pay_purchase(&funding, program_funded, &ctx.accounts.referrer, fast_start, &system_program)?;
+            }
+            if residual > 0 {
+                pay_purchase(&funding, program_funded, &solbox.to_account_info(), residual, &system_program)?;
+                accrue_residual(solbox, &mut ctx.accounts.referrer_user_account, residual)?;
+            }
+            
+            // Transfer remaining amount to founder, or hold it in the vault
+            // when the founder wallet can't take it
+            if can_receive(&ctx.accounts.founder, founder_share)? {
+                pay_purchase(&funding, program_funded, &ctx.accounts.founder, founder_share, &system_program)?;
+            } else {
+                pay_purchase(&funding, program_funded, &solbox.to_account_info(), founder_share, &system_program)?;
+                defer_founder_share(solbox, founder_share)?;
+            }
+            
+            // Transfer revshare to the integrator
+            if let Some(integrator) = integrator {
+                let integrator_wallet = ctx.accounts.integrator_wallet
+                    .as_ref()
+                    .filter(|wallet| wallet.key() == integrator.wallet)
+                    .ok_or(CustomError::InvalidIntegrator)?;
+                pay_purchase(&funding, program_funded, integrator_wallet, integrator_fee, &system_program)?;
+            }
+            receipt.status = ReceiptStatus::Settled;
+        }
+        
+        // Update today's analytics bucket
+        let daily_stats = &mut ctx.accounts.daily_stats;
+        if daily_stats.solbox == Pubkey::default() {
+            daily_stats.account_type = ACCOUNT_TYPE_DAILY_STATS;
+            daily_stats.account_version = DAILY_STATS_VERSION;
+            daily_stats.solbox = solbox.key();
+            daily_stats.day = current_day(solbox)?;
+            daily_stats.bump = ctx.bumps.daily_stats;
+        }
+        record_daily_purchase(daily_stats, user.key, amount, commission, is_new_registration)?;
         
         emit!(GiftCardPurchaseEvent {
+            purchase_id,
             user: user.key(),
             referrer: final_referrer,
             amount,
             commission,
             founder_share,
-            timestamp: Clock::get()?.unix_timestamp,
+            integrator: integrator.map(|integrator| integrator.key()),
+            integrator_fee,
+            attribution: ctx.accounts.attribution.as_ref().map(|attribution| attribution.nonce_hash),
+            timestamp: now,
+            brand_code: solbox.brand_code,
         });
+        if !house_referrer {
+            emit!(EarningsEvent {
+                user: referrer.key(),
+                source,
+                amount: commission,
+                reference: purchase_id,
+                timestamp: now,
+                brand_code: solbox.brand_code,
+            });
+        }
+        emit_coverage(solbox)?;
         
         Ok(())
     }
 
-    pub fn grant_package(
-        ctx: Context<AdminAction>,
-        user: Pubkey,
-        package: u64
+    pub fn create_session_key(
+        ctx: Context<CreateSessionKey>,
+        session_key: Pubkey,
+        max_lamports: u64,
+        expires_at: i64
     ) -> Result<()> {
-        let solbox = &ctx.accounts.solbox;
-        
-        // Verify admin authority
+        let now = unix_now(&ctx.accounts.solbox)?;
         require!(
-            ctx.accounts.admin.key() == solbox.owner,
-            CustomError::Unauthorized
+            max_lamports > 0 && expires_at > now,
+            CustomError::InvalidSessionKey
         );
         
-        // Validate package amount
-        require!(
-            solbox.config.valid_amounts.contains(&package),
-            CustomError::InvalidAmount
-        );
+        let session = &mut ctx.accounts.session;
+        session.account_type = ACCOUNT_TYPE_SESSION_KEY;
+        session.account_version = SESSION_KEY_VERSION;
+        session.solbox = ctx.accounts.solbox.key();
+        session.owner = ctx.accounts.owner.key();
+        session.session_key = session_key;
+        session.max_lamports = max_lamports;
+        session.spent = 0;
+        session.expires_at = expires_at;
+        session.bump = ctx.bumps.session;
         
-        // Update user's package
-        let user_account = &mut ctx.accounts.user;
-        user_account.current_package = package;
+        // Fund the session budget up front; revoking returns what's left
+        invoke(
+            &system_instruction::transfer(
+                ctx.accounts.owner.key,
+                &session.key(),
+                max_lamports
+            ),
+            &[
+                ctx.accounts.owner.to_account_info(),
+                session.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+            ],
+        )?;
         
-        emit!(PackageGrantedEvent {
-            admin: *ctx.accounts.admin.key,
-            user,
-            package,
-            timestamp: Clock::get()?.unix_timestamp,
+        emit!(SessionKeyCreatedEvent {
+            owner: session.owner,
+            session_key,
+            max_lamports,
+            expires_at,
+            timestamp: now,
+            brand_code: ctx.accounts.solbox.brand_code,
         });
         
         Ok(())
     }
 
-    pub fn update_commission_config(
-        ctx: Context<AdminAction>,
-        new_percentage: u64,
-        new_levels: u8
-    ) -> Result<()> {
-        let solbox = &mut ctx.accounts.solbox;
+    pub fn revoke_session_key(ctx: Context<RevokeSessionKey>) -> Result<()> {
+        let session = &ctx.accounts.session;
+        
+        emit!(SessionKeyRevokedEvent {
+            owner: session.owner,
+            session_key: session.session_key,
+            spent: session.spent,
+            timestamp: unix_now(&ctx.accounts.solbox)?,
+            brand_code: ctx.accounts.solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    pub fn record_attribution(
+        ctx: Context<RecordAttribution>,
+        nonce_hash: [u8; 32],
+        referrer: Pubkey
+    ) -> Result<()> {
+        let attribution = &mut ctx.accounts.attribution;
+        let now = unix_now(&ctx.accounts.solbox)?;
+        
+        attribution.account_type = ACCOUNT_TYPE_ATTRIBUTION;
+        attribution.account_version = ATTRIBUTION_VERSION;
+        attribution.solbox = ctx.accounts.solbox.key();
+        attribution.nonce_hash = nonce_hash;
+        attribution.referrer = referrer;
+        attribution.recorded_by = ctx.accounts.recorder.key();
+        attribution.recorded_at = now;
+        attribution.consumed_by = Pubkey::default();
+        attribution.purchase_id = 0;
+        attribution.bump = ctx.bumps.attribution;
+        
+        emit!(AttributionRecordedEvent {
+            nonce_hash,
+            referrer,
+            recorded_by: attribution.recorded_by,
+            timestamp: now,
+            brand_code: ctx.accounts.solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Reserve a not-yet-registered contact for the referrer, by the hash
+    // of its normalized email or phone number; see `invitation_hash`
+    pub fn create_invitation(ctx: Context<CreateInvitation>, identifier_hash: [u8; 32]) -> Result<()> {
+        let solbox = &ctx.accounts.solbox;
+        require!(!solbox.paused, CustomError::ContractPaused);
+        
+        let now = unix_now(solbox)?;
+        let invitation = &mut ctx.accounts.invitation;
+        invitation.account_type = ACCOUNT_TYPE_INVITATION;
+        invitation.account_version = INVITATION_VERSION;
+        invitation.solbox = solbox.key();
+        invitation.identifier_hash = identifier_hash;
+        invitation.referrer = ctx.accounts.referrer.key();
+        invitation.created_at = now;
+        invitation.claimed_by = Pubkey::default();
+        invitation.bump = ctx.bumps.invitation;
+        
+        emit!(InvitationEvent {
+            referrer: invitation.referrer,
+            identifier_hash,
+            invitee: None,
+            timestamp: now,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // The invitee presents the identifier the invitation was made for,
+    // which binds their first purchase to the inviting referrer
+    pub fn claim_invitation(ctx: Context<ClaimInvitation>, identifier: Vec<u8>) -> Result<()> {
+        let solbox = &ctx.accounts.solbox;
+        let invitation = &mut ctx.accounts.invitation;
+        require!(
+            identifier.len() <= MAX_INVITATION_IDENTIFIER_LEN
+                && invitation_hash(&solbox.key(), &identifier) == invitation.identifier_hash,
+            CustomError::InvalidInvitation
+        );
+        require!(
+            invitation.claimed_by == Pubkey::default(),
+            CustomError::InvitationClaimed
+        );
+        
+        // Only before the first purchase, which places the wallet
+        let user_account = &mut ctx.accounts.user_account;
+        let placed = solbox.referral_relationships
+            .iter()
+            .any(|r| r.user == user_account.key)
+            || solbox.holding_tank
+                .iter()
+                .any(|entry| entry.user == user_account.key);
+        require!(
+            !placed && user_account.invited_by == Pubkey::default(),
+            CustomError::InvitationClosed
+        );
+        require!(
+            user_account.key != invitation.referrer,
+            CustomError::SelfReferralNotAllowed
+        );
+        invitation.claimed_by = user_account.key;
+        user_account.invited_by = invitation.referrer;
+        
+        emit!(InvitationEvent {
+            referrer: invitation.referrer,
+            identifier_hash: invitation.identifier_hash,
+            invitee: Some(user_account.key),
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // An on-ramp partner's signed statement that it was paid in fiat for a
+    // purchase, kept next to the receipt for later disputes
+    pub fn attest_fiat_purchase(
+        ctx: Context<AttestFiatPurchase>,
+        purchase_id: u64,
+        buyer: Pubkey,
+        amount: u64,
+        external_ref: [u8; 32]
+    ) -> Result<()> {
+        // The partner's integrator wallet is the attesting key
+        let integrator = &ctx.accounts.integrator;
+        require!(
+            integrator.solbox == ctx.accounts.solbox.key()
+                && integrator.allowed_instructions & INTEGRATOR_ALLOW_FIAT_ATTESTATION != 0
+                && integrator.wallet == ctx.accounts.attester.key(),
+            CustomError::InvalidIntegrator
+        );
+        
+        // The statement has to describe the purchase it is attached to
+        let receipt = &ctx.accounts.receipt;
+        require!(
+            receipt.buyer == buyer && receipt.amount == amount,
+            CustomError::AttestationMismatch
+        );
+        
+        let now = unix_now(&ctx.accounts.solbox)?;
+        let attestation = &mut ctx.accounts.attestation;
+        attestation.account_type = ACCOUNT_TYPE_FIAT_ATTESTATION;
+        attestation.account_version = FIAT_ATTESTATION_VERSION;
+        attestation.solbox = ctx.accounts.solbox.key();
+        attestation.integrator = integrator.key();
+        attestation.receipt = receipt.key();
+        attestation.purchase_id = purchase_id;
+        attestation.buyer = buyer;
+        attestation.amount = amount;
+        attestation.external_ref = external_ref;
+        attestation.attested_by = ctx.accounts.attester.key();
+        attestation.attested_at = now;
+        attestation.bump = ctx.bumps.attestation;
+        
+        emit!(FiatPurchaseAttestedEvent {
+            integrator: integrator.key(),
+            receipt: receipt.key(),
+            buyer,
+            amount,
+            external_ref,
+            timestamp: now,
+            brand_code: ctx.accounts.solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Credits a purchase paid at the EVM storefront, posted there through
+    // Wormhole and signed by the guardians. The storefront pays its
+    // commission on its own chain, so no lamports move; the buyer joins the
+    // tree and the volume and earnings count as for any purchase, after the
+    // same checks. Anyone may relay it, once per storefront message
+    pub fn redeem_wormhole_purchase<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RedeemWormholePurchase<'info>>,
+        sequence: u64
+    ) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        require!(solbox.config.storefront_chain != 0, CustomError::CrossChainDisabled);
+        
+        // Only messages the storefront contract emitted count
+        let vaa = read_posted_vaa(&ctx.accounts.posted_vaa)?;
+        require!(
+            vaa.emitter_chain == solbox.config.storefront_chain
+                && vaa.emitter_address == solbox.config.storefront_emitter
+                && vaa.sequence == sequence,
+            CustomError::InvalidVaa
+        );
+        let purchase = StorefrontPurchase::parse(&vaa.payload).ok_or(CustomError::InvalidVaa)?;
+        let buyer = ctx.accounts.buyer.key();
+        let referrer = ctx.accounts.referrer.key();
+        require!(
+            purchase.buyer == buyer && purchase.referrer == referrer,
+            CustomError::InvalidVaa
+        );
+        let amount = purchase.amount;
+        check_purchase(
+            solbox,
+            &ctx.accounts.blacklist_filter,
+            &buyer,
+            &ctx.accounts.user_account,
+            &referrer,
+            &ctx.accounts.referrer_user_account,
+            amount
+        )?;
+        
+        // A sale the storefront routed through an integrator counts
+        // against its limits; its revshare was paid on the storefront's chain
+        require!(
+            ctx.accounts.integrator.as_ref().map(|integrator| integrator.key()) == purchase.integrator,
+            CustomError::InvalidIntegrator
+        );
+        if let Some(integrator) = ctx.accounts.integrator.as_mut() {
+            require!(
+                integrator.solbox == solbox.key()
+                    && integrator.allowed_instructions & INTEGRATOR_ALLOW_BUY_GIFT_CARD != 0,
+                CustomError::InvalidIntegrator
+            );
+            charge_integrator_limits(integrator, current_epoch(solbox)?, amount)?;
+        }
+        
+        // Limited editions sell from the same supply as on Solana
+        let spent_commit = take_package_unit(
+            solbox,
+            ctx.accounts.package.as_mut(),
+            ctx.accounts.drop_commit.as_mut(),
+            &buyer,
+            amount
+        )?;
+        if let Some(commit) = ctx.accounts.drop_commit.as_mut().filter(|_| spent_commit) {
+            commit.close(ctx.accounts.buyer.to_account_info())?;
+        }
+        flag_purchase(
+            solbox,
+            &mut ctx.accounts.daily_stats,
+            ctx.accounts.integrator.as_mut(),
+            None,
+            &buyer,
+            &mut ctx.accounts.user_account
+        )?;
+        
+        let split = calculate_purchase_split(solbox, amount, referrer)?;
+        let final_referrer = split.referrer;
+        let commission = split.commission;
+        let house_referrer = earns_for_pool(solbox, &referrer);
+        let is_new_registration = !solbox.referral_relationships
+            .iter()
+            .any(|r| r.user == buyer)
+            && !solbox.holding_tank
+                .iter()
+                .any(|entry| entry.user == buyer);
+        if is_new_registration {
+            check_registration(
+                solbox,
+                &ctx.accounts.user_account,
+                &referrer,
+                amount,
+                ctx.accounts.humanity_verifier.as_ref().map(|verifier| verifier.key())
+            )?;
+        }
+        
+        solbox.total_sold = solbox.total_sold
+            .checked_add(amount)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        solbox.total_commission_distributed = solbox.total_commission_distributed
+            .checked_add(commission)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        solbox.referral_count = solbox.referral_count
+            .checked_add(1)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        add_purchase_volume(solbox, &mut ctx.accounts.user_account, amount)?;
+        
+        let referrer_account = &mut ctx.accounts.referrer_user_account;
+        let source = if final_referrer != referrer { EarningSource::Spillover } else { EarningSource::Direct };
+        if !house_referrer {
+            referrer_account.total_earnings = referrer_account.total_earnings
+                .checked_add(commission)
+                .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+            credit_earnings(referrer_account, source, commission)?;
+        }
+        if !is_new_registration {
+            referrer_account.unseen_downline_events = referrer_account.unseen_downline_events.saturating_add(1);
+        }
+        
+        // Place the buyer as a purchase on Solana would
+        let now = unix_now(solbox)?;
+        if is_new_registration {
+            if solbox.config.holding_tank_period > 0 {
+                require!(
+                    solbox.holding_tank.len() < MAX_HOLDING_TANK_ENTRIES,
+                    CustomError::HoldingTankFull
+                );
+                solbox.holding_tank.push(HoldingTankEntry {
+                    user: buyer,
+                    sponsor: referrer,
+                    entered_at: now,
+                });
+            } else {
+                let vault = solbox.to_account_info();
+                let spot = Position { user: final_referrer, cycle: split.referrer_cycle };
+                let mut uplines = ctx.remaining_accounts;
+                place_in_matrix(
+                    solbox,
+                    &vault,
+                    &mut uplines,
+                    ctx.remaining_accounts,
+                    buyer,
+                    spot,
+                    Some(&mut **referrer_account)
+                )?;
+            }
+        }
+        
+        let redemption = &mut ctx.accounts.redemption;
+        redemption.account_type = ACCOUNT_TYPE_WORMHOLE_PURCHASE;
+        redemption.account_version = WORMHOLE_PURCHASE_VERSION;
+        redemption.solbox = solbox.key();
+        redemption.emitter_chain = vaa.emitter_chain;
+        redemption.emitter_address = vaa.emitter_address;
+        redemption.sequence = sequence;
+        redemption.buyer = buyer;
+        redemption.referrer = final_referrer;
+        redemption.amount = amount;
+        redemption.commission = commission;
+        redemption.redeemed_at = now;
+        redemption.bump = ctx.bumps.redemption;
+        
+        // Update today's analytics bucket
+        let daily_stats = &mut ctx.accounts.daily_stats;
+        if daily_stats.solbox == Pubkey::default() {
+            daily_stats.account_type = ACCOUNT_TYPE_DAILY_STATS;
+            daily_stats.account_version = DAILY_STATS_VERSION;
+            daily_stats.solbox = solbox.key();
+            daily_stats.day = current_day(solbox)?;
+            daily_stats.bump = ctx.bumps.daily_stats;
+        }
+        record_daily_purchase(daily_stats, &buyer, amount, commission, is_new_registration)?;
+        
+        emit!(WormholePurchaseEvent {
+            sequence,
+            user: buyer,
+            referrer: final_referrer,
+            amount,
+            commission,
+            timestamp: now,
+            brand_code: solbox.brand_code,
+        });
+        if !house_referrer {
+            emit!(EarningsEvent {
+                user: referrer,
+                source,
+                amount: commission,
+                reference: sequence,
+                timestamp: now,
+                brand_code: solbox.brand_code,
+            });
+        }
+        
+        Ok(())
+    }
+
+    pub fn settle_purchase(ctx: Context<SettlePurchase>) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        let receipt = &mut ctx.accounts.receipt;
+        let now = unix_now(solbox)?;
+        
+        require!(
+            receipt.status == ReceiptStatus::Pending,
+            CustomError::ReceiptNotPending
+        );
+        
+        // Funds stay refundable until the window has passed
+        require!(
+            now > receipt.refundable_until,
+            CustomError::RefundWindowOpen
+        );
+        
+        let escrowed = receipt_total(receipt)?;
+        solbox.escrow_balance = solbox.escrow_balance
+            .checked_sub(escrowed)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        
+        // Release escrowed funds to the referrer and founder; the residual
+        // stays in the vault on the referrer's ledger
+        let vault = solbox.to_account_info();
+        if earns_for_pool(solbox, &receipt.payee) {
+            credit_house_commission(solbox, receipt.payee, receipt.purchase_id, receipt.commission)?;
+        } else {
+            // Positions wrapped since the purchase hold all of it
+            let held = if ctx.accounts.referrer_user_account.position_mint != Pubkey::default() {
+                receipt.commission
+            } else {
+                receipt.residual
+            };
+            let fast_start = receipt.commission
+                .checked_sub(held)
+                .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+            if receipt.dripped {
+                // The tranche opened at purchase starts releasing now
+                let tranche = ctx.accounts.tranche.as_mut().ok_or(CustomError::TrancheMismatch)?;
+                tranche.total = fast_start;
+                tranche.starts_at = now;
+                hold_tranche(solbox, fast_start)?;
+            } else {
+                transfer_from_vault(&vault, &ctx.accounts.referrer.to_account_info(), fast_start)?;
+            }
+            accrue_residual(solbox, &mut ctx.accounts.referrer_user_account, held)?;
+        }
+        if can_receive(&ctx.accounts.founder, receipt.founder_share)? {
+            transfer_from_vault(&vault, &ctx.accounts.founder.to_account_info(), receipt.founder_share)?;
+        } else {
+            defer_founder_share(solbox, receipt.founder_share)?;
+        }
+        
+        // Release the integrator's revshare
+        if receipt.integrator_fee > 0 {
+            let integrator = ctx.accounts.integrator
+                .as_ref()
+                .filter(|integrator| integrator.key() == receipt.integrator)
+                .ok_or(CustomError::InvalidIntegrator)?;
+            let integrator_wallet = ctx.accounts.integrator_wallet
+                .as_ref()
+                .filter(|wallet| wallet.key() == integrator.wallet)
+                .ok_or(CustomError::InvalidIntegrator)?;
+            transfer_from_vault(&vault, &integrator_wallet.to_account_info(), receipt.integrator_fee)?;
+        }
+        
+        solbox.pending_receipts = solbox.pending_receipts
+            .checked_sub(1)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        count_pending_receipt(&mut ctx.accounts.buyer_user_account, &mut ctx.accounts.referrer_user_account, false)?;
+        receipt.status = ReceiptStatus::Settled;
+        
+        emit!(PurchaseSettledEvent {
+            purchase_id: receipt.purchase_id,
+            buyer: receipt.buyer,
+            referrer: receipt.payee,
+            commission: receipt.commission,
+            founder_share: receipt.founder_share,
+            integrator_fee: receipt.integrator_fee,
+            timestamp: now,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    pub fn cancel_purchase<'info>(ctx: Context<'_, '_, 'info, 'info, CancelPurchase<'info>>) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        let receipt = &mut ctx.accounts.receipt;
+        let now = unix_now(solbox)?;
+        
+        // Only the buyer can cancel their own purchase
+        require!(
+            ctx.accounts.buyer.key() == receipt.buyer,
+            CustomError::Unauthorized
+        );
+        
+        require!(
+            receipt.status == ReceiptStatus::Pending,
+            CustomError::ReceiptNotPending
+        );
+        
+        require!(
+            now <= receipt.refundable_until,
+            CustomError::RefundWindowClosed
+        );
+        
+        // An issued card would outlive the refund
+        require!(!receipt.card_issued, CustomError::CardIssued);
+        
+        let refund = receipt_total(receipt)?;
+        
+        // Roll back counters updated at purchase time
+        solbox.escrow_balance = solbox.escrow_balance
+            .checked_sub(refund)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+            
+        solbox.total_sold = solbox.total_sold
+            .checked_sub(receipt.amount)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+            
+        solbox.total_commission_distributed = solbox.total_commission_distributed
+            .checked_sub(receipt.commission)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+            
+        solbox.referral_count = solbox.referral_count
+            .checked_sub(1)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        
+        // Roll back the payee's accrued earnings; house positions were
+        // never credited
+        let referrer_account = &mut ctx.accounts.referrer_user_account;
+        if !earns_for_pool(solbox, &receipt.payee) {
+            referrer_account.total_earnings = referrer_account.total_earnings
+                .checked_sub(receipt.commission)
+                .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+            if receipt.spillover {
+                referrer_account.earnings_from_spillover = referrer_account.earnings_from_spillover
+                    .checked_sub(receipt.commission)
+                    .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+            } else {
+                referrer_account.earnings_from_direct = referrer_account.earnings_from_direct
+                    .checked_sub(receipt.commission)
+                    .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+            }
+        }
+        
+        // Take the buyer back out of the tree, or out of the holding tank
+        // if the sponsor hasn't placed them yet
+        if receipt.registered {
+            let tank_size = solbox.holding_tank.len();
+            solbox.holding_tank.retain(|entry| entry.user != receipt.buyer);
+            let placed = Position { user: receipt.buyer, cycle: 0 };
+            let index = solbox.referral_relationships
+                .iter()
+                .position(|r| r.user == placed.user && r.cycle == placed.cycle);
+            if let Some(index) = index.filter(|_| solbox.holding_tank.len() == tank_size) {
+                require!(
+                    !placement_in_use(&solbox.referral_relationships, index),
+                    CustomError::PlacementInUse
+                );
+                
+                // Upline accounts come in remaining_accounts, see take_uplines
+                let mut accounts = ctx.remaining_accounts;
+                let uplines = take_uplines(&mut accounts, solbox, solbox.key(), placed)?;
+                record_downline_cancel(&uplines, Some(&mut **referrer_account))?;
+                
+                solbox.referral_relationships.remove(index);
+                solbox.tree_rewrites = solbox.tree_rewrites.wrapping_add(1);
+            }
+        } else {
+            // Repeat purchases only showed up on the referrer's badge
+            referrer_account.unseen_downline_events = referrer_account.unseen_downline_events.saturating_sub(1);
+        }
+        
+        // Take the purchase back out of its day's analytics and the
+        // integrator's usage for its epoch
+        unrecord_daily_purchase(&mut ctx.accounts.daily_stats, receipt.amount, receipt.commission, receipt.registered)?;
+        if receipt.integrator != Pubkey::default() {
+            let integrator = ctx.accounts.integrator
+                .as_mut()
+                .filter(|integrator| integrator.key() == receipt.integrator)
+                .ok_or(CustomError::InvalidIntegrator)?;
+            if integrator.limit_epoch == receipt.epoch {
+                integrator.epoch_volume = integrator.epoch_volume.saturating_sub(receipt.amount);
+                integrator.epoch_purchases = integrator.epoch_purchases.saturating_sub(1);
+            }
+        }
+        
+        let buyer_account = &mut ctx.accounts.buyer_user_account;
+        buyer_account.lifetime_volume = buyer_account.lifetime_volume
+            .checked_sub(receipt.amount)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        if solbox.config.tier_period > 0
+            && receipt.purchased_at.div_euclid(solbox.config.tier_period) as u64 == buyer_account.period_index
+        {
+            buyer_account.period_volume = buyer_account.period_volume.saturating_sub(receipt.amount);
+        }
+        let day = receipt.purchased_at.div_euclid(SECONDS_PER_DAY) as u64;
+        if day <= buyer_account.volume_day && day + VOLUME_WINDOW_DAYS as u64 > buyer_account.volume_day {
+            let bucket = &mut buyer_account.volume_buckets[day as usize % VOLUME_WINDOW_DAYS];
+            *bucket = bucket.saturating_sub(receipt.amount);
+        }
+        
+        // Return the escrowed funds to the buyer, or to the card balance
+        // that paid for the purchase
+        if receipt.card_funded {
+            buyer_account.card_balance = buyer_account.card_balance
+                .checked_add(refund)
+                .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+            solbox.card_balance_total = solbox.card_balance_total
+                .checked_add(refund)
+                .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+            emit!(CardBalanceEvent {
+                user: receipt.buyer,
+                amount: refund,
+                bonus: 0,
+                balance: buyer_account.card_balance,
+                action: CardBalanceAction::Refunded,
+                timestamp: now,
+                brand_code: solbox.brand_code,
+            });
+        } else {
+            transfer_from_vault(
+                &solbox.to_account_info(),
+                &ctx.accounts.buyer.to_account_info(),
+                refund
+            )?;
+        }
+        
+        restock_unit(ctx.accounts.package.as_mut(), receipt)?;
+        
+        solbox.pending_receipts = solbox.pending_receipts
+            .checked_sub(1)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        count_pending_receipt(&mut ctx.accounts.buyer_user_account, &mut ctx.accounts.referrer_user_account, false)?;
+        receipt.status = ReceiptStatus::Cancelled;
+        
+        emit!(PurchaseCancelledEvent {
+            purchase_id: receipt.purchase_id,
+            buyer: receipt.buyer,
+            refunded: refund,
+            timestamp: now,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    pub fn admin_refund(
+        ctx: Context<AdminRefund>,
+        _purchase_id: u64,
+        amount: u64,
+        reason: String
+    ) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        let receipt = &mut ctx.accounts.receipt;
         
         // Verify admin authority
         require!(
@@ -322,296 +1614,10554 @@ pub mod solbox_contract_devnet {
             CustomError::Unauthorized
         );
         
-        // Update commission configuration
-        solbox.config.commission_percentage = new_percentage;
-        solbox.config.commission_levels = new_levels;
+        require!(
+            reason.len() <= MAX_REFUND_REASON_LEN,
+            CustomError::RefundReasonTooLong
+        );
         
-        emit!(CommissionConfigEvent {
+        // Pending purchases are still cancellable by the buyer and
+        // cancelled ones were already refunded in full
+        require!(
+            receipt.status == ReceiptStatus::Settled,
+            CustomError::ReceiptNotRefundable
+        );
+        require!(!receipt.card_issued, CustomError::CardIssued);
+        
+        // Refunds are capped at what the buyer actually paid
+        let paid = receipt_total(receipt)?;
+        let refunded_amount = receipt.refunded_amount
+            .checked_add(amount)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        require!(
+            amount > 0 && refunded_amount <= paid,
+            CustomError::InvalidRefundAmount
+        );
+        
+        // Pay only from treasury funds, never from escrowed purchases
+        require_coverage(&solbox.to_account_info(), solbox, amount)?;
+        
+        // Reverse the refunded share of the sale and its commission. Shares
+        // are taken of the cumulative refund, so partial refunds adding up
+        // to the whole reverse exactly the sale and its commission
+        let reversed = |total: u64| -> Result<u64> {
+            let before = pro_rata(total, receipt.refunded_amount, paid)?;
+            Ok(pro_rata(total, refunded_amount, paid)? - before)
+        };
+        let sold_reversed = reversed(receipt.amount)?;
+        let commission_reversed = reversed(receipt.commission)?;
+        
+        solbox.total_sold = solbox.total_sold
+            .checked_sub(sold_reversed)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+            
+        solbox.total_commission_distributed = solbox.total_commission_distributed
+            .checked_sub(commission_reversed)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        
+        // The payee keeps what was paid, but its lifetime earnings stop
+        // counting the refunded share; house positions were never credited
+        if !earns_for_pool(solbox, &receipt.payee) {
+            let referrer_account = &mut ctx.accounts.referrer_user_account;
+            referrer_account.total_earnings = referrer_account.total_earnings
+                .checked_sub(commission_reversed)
+                .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+            if receipt.spillover {
+                referrer_account.earnings_from_spillover = referrer_account.earnings_from_spillover
+                    .checked_sub(commission_reversed)
+                    .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+            } else {
+                referrer_account.earnings_from_direct = referrer_account.earnings_from_direct
+                    .checked_sub(commission_reversed)
+                    .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+            }
+        }
+        
+        receipt.refunded_amount = refunded_amount;
+        
+        // Refunded in full, a limited sale gives its unit back
+        if refunded_amount == paid {
+            restock_unit(ctx.accounts.package.as_mut(), receipt)?;
+        }
+        
+        // A card-funded purchase is refunded to the card balance that paid
+        // for it, so its bonus can't come back out as SOL
+        if receipt.card_funded {
+            let buyer_account = &mut ctx.accounts.buyer_user_account;
+            buyer_account.card_balance = buyer_account.card_balance
+                .checked_add(amount)
+                .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+            solbox.card_balance_total = solbox.card_balance_total
+                .checked_add(amount)
+                .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+            emit!(CardBalanceEvent {
+                user: receipt.buyer,
+                amount,
+                bonus: 0,
+                balance: buyer_account.card_balance,
+                action: CardBalanceAction::Refunded,
+                timestamp: unix_now(solbox)?,
+                brand_code: solbox.brand_code,
+            });
+        } else {
+            transfer_from_vault(
+                &solbox.to_account_info(),
+                &ctx.accounts.buyer.to_account_info(),
+                amount
+            )?;
+        }
+        
+        emit!(AdminRefundEvent {
             admin: *ctx.accounts.admin.key,
-            new_percentage,
-            new_levels,
-            timestamp: Clock::get()?.unix_timestamp,
+            purchase_id: receipt.purchase_id,
+            buyer: receipt.buyer,
+            amount,
+            total_refunded: refunded_amount,
+            reason,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    pub fn withdraw_treasury(
+        ctx: Context<WithdrawTreasury>,
+        amount: u64
+    ) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == solbox.owner,
+            CustomError::Unauthorized
+        );
+        require!(amount > 0, CustomError::InvalidAmount);
+        
+        // The hot treasury pays out up to the daily limit
+        if solbox.config.hot_daily_limit > 0 {
+            let day = current_day(solbox)?;
+            if solbox.hot_withdrawn_day != day {
+                solbox.hot_withdrawn_day = day;
+                solbox.hot_withdrawn = 0;
+            }
+            solbox.hot_withdrawn = solbox.hot_withdrawn
+                .checked_add(amount)
+                .filter(|withdrawn| *withdrawn <= solbox.config.hot_daily_limit)
+                .ok_or(CustomError::HotLimitExceeded)?;
+        }
+        
+        // What stays behind must still cover everything owed to users
+        let vault = solbox.to_account_info();
+        require_coverage(&vault, solbox, amount)?;
+        transfer_from_vault(&vault, &ctx.accounts.recipient.to_account_info(), amount)?;
+        
+        emit!(TreasuryWithdrawnEvent {
+            admin: *ctx.accounts.admin.key,
+            recipient: ctx.accounts.recipient.key(),
+            amount,
+            remaining_treasury: treasury_balance(&vault, solbox_liabilities(solbox)?)?,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    pub fn initialize_cold_treasury(ctx: Context<InitializeColdTreasury>) -> Result<()> {
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == ctx.accounts.solbox.owner,
+            CustomError::Unauthorized
+        );
+        let cold = &mut ctx.accounts.cold_treasury;
+        cold.account_type = ACCOUNT_TYPE_COLD_TREASURY;
+        cold.account_version = COLD_TREASURY_VERSION;
+        cold.solbox = ctx.accounts.solbox.key();
+        cold.guardians = Vec::new();
+        cold.threshold = 0;
+        cold.pending_recipient = Pubkey::default();
+        cold.pending_amount = 0;
+        cold.approvals = 0;
+        cold.withdraw_eta = 0;
+        cold.bump = ctx.bumps.cold_treasury;
+        Ok(())
+    }
+
+    // Permissionless; moves the treasury above hot_sweep_threshold from the
+    // vault into the cold treasury. Lamports owed to users stay in the vault
+    pub fn sweep_treasury(ctx: Context<SweepTreasury>) -> Result<()> {
+        let solbox = &ctx.accounts.solbox;
+        require!(solbox.config.hot_sweep_threshold > 0, CustomError::NothingToSweep);
+        
+        let vault = solbox.to_account_info();
+        let amount = treasury_balance(&vault, solbox_liabilities(solbox)?)?
+            .saturating_sub(solbox.config.hot_sweep_threshold);
+        require!(amount > 0, CustomError::NothingToSweep);
+        let cold = ctx.accounts.cold_treasury.to_account_info();
+        transfer_from_vault(&vault, &cold, amount)?;
+        
+        emit!(TreasurySweptEvent {
+            amount,
+            hot_treasury: treasury_balance(&vault, solbox_liabilities(solbox)?)?,
+            cold_treasury: treasury_balance(&cold, 0)?,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Cold withdrawals need the pause guardians: the proposal takes the
+    // current set and threshold, and once enough of them approve it can
+    // execute after COLD_WITHDRAW_TIMELOCK. A new proposal replaces the
+    // pending one and its approvals
+    pub fn propose_cold_withdrawal(
+        ctx: Context<ProposeColdWithdrawal>,
+        recipient: Pubkey,
+        amount: u64
+    ) -> Result<()> {
+        let solbox = &ctx.accounts.solbox;
+        
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == solbox.owner,
+            CustomError::Unauthorized
+        );
+        require!(solbox.unpause_threshold > 0, CustomError::InvalidGuardians);
+        require!(recipient != Pubkey::default(), CustomError::InvalidEmergencyRecipient);
+        let cold_info = ctx.accounts.cold_treasury.to_account_info();
+        require!(
+            amount > 0 && amount <= treasury_balance(&cold_info, 0)?,
+            CustomError::InvalidAmount
+        );
+        
+        let cold = &mut ctx.accounts.cold_treasury;
+        cold.guardians = solbox.pause_guardians.clone();
+        cold.threshold = solbox.unpause_threshold;
+        cold.pending_recipient = recipient;
+        cold.pending_amount = amount;
+        cold.approvals = 0;
+        cold.withdraw_eta = 0;
+        
+        emit!(ColdWithdrawalEvent {
+            action: ColdWithdrawalAction::Proposed,
+            actor: solbox.owner,
+            recipient,
+            amount,
+            approvals: 0,
+            withdraw_eta: 0,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
         });
         
         Ok(())
     }
 
-    pub fn add_to_blacklist(
-        ctx: Context<AdminAction>,
-        user: Pubkey
-    ) -> Result<()> {
-        let solbox = &mut ctx.accounts.solbox;
-        
-        // Verify admin authority
-        require!(
-            ctx.accounts.admin.key() == solbox.owner,
-            CustomError::Unauthorized
-        );
-        
-        // Add to blacklist if not already present
-        if !solbox.blacklisted_users.contains(&user) {
-            solbox.blacklisted_users.push(user);
+    pub fn approve_cold_withdrawal(ctx: Context<ApproveColdWithdrawal>) -> Result<()> {
+        let cold = &mut ctx.accounts.cold_treasury;
+        let guardian = cold.guardians
+            .iter()
+            .position(|guardian| *guardian == ctx.accounts.guardian.key())
+            .ok_or(CustomError::Unauthorized)?;
+        require!(cold.pending_recipient != Pubkey::default(), CustomError::NoPendingColdWithdrawal);
+        cold.approvals |= 1 << guardian;
+        
+        let now = unix_now(&ctx.accounts.solbox)?;
+        if cold.withdraw_eta == 0 && cold.approvals.count_ones() >= cold.threshold as u32 {
+            cold.withdraw_eta = now
+                .checked_add(COLD_WITHDRAW_TIMELOCK)
+                .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        }
+        
+        emit!(ColdWithdrawalEvent {
+            action: ColdWithdrawalAction::Approved,
+            actor: ctx.accounts.guardian.key(),
+            recipient: cold.pending_recipient,
+            amount: cold.pending_amount,
+            approvals: cold.approvals.count_ones() as u8,
+            withdraw_eta: cold.withdraw_eta,
+            timestamp: now,
+            brand_code: ctx.accounts.solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    pub fn execute_cold_withdrawal(ctx: Context<ExecuteColdWithdrawal>) -> Result<()> {
+        let solbox = &ctx.accounts.solbox;
+        
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == solbox.owner,
+            CustomError::Unauthorized
+        );
+        let cold = &mut ctx.accounts.cold_treasury;
+        let now = unix_now(solbox)?;
+        require!(
+            cold.pending_recipient != Pubkey::default() && cold.withdraw_eta != 0,
+            CustomError::NoPendingColdWithdrawal
+        );
+        require!(now >= cold.withdraw_eta, CustomError::TimelockNotElapsed);
+        
+        let amount = cold.pending_amount;
+        let approvals = cold.approvals.count_ones() as u8;
+        require!(
+            amount <= treasury_balance(&cold.to_account_info(), 0)?,
+            CustomError::InsufficientTreasury
+        );
+        transfer_from_vault(&cold.to_account_info(), &ctx.accounts.recipient.to_account_info(), amount)?;
+        cold.pending_recipient = Pubkey::default();
+        cold.pending_amount = 0;
+        cold.approvals = 0;
+        cold.withdraw_eta = 0;
+        
+        emit!(ColdWithdrawalEvent {
+            action: ColdWithdrawalAction::Executed,
+            actor: solbox.owner,
+            recipient: ctx.accounts.recipient.key(),
+            amount,
+            approvals,
+            withdraw_eta: now,
+            timestamp: now,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Last resort: announces that the whole vault, including what is owed
+    // to users, will be withdrawn after EMERGENCY_WITHDRAW_TIMELOCK. New
+    // purchases stop meanwhile, so users can cancel, claim and leave
+    pub fn initiate_emergency_withdraw(ctx: Context<ProposeEmergencyWithdraw>, recipient: Pubkey) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == solbox.owner,
+            CustomError::Unauthorized
+        );
+        require!(recipient != Pubkey::default(), CustomError::InvalidEmergencyRecipient);
+        require!(solbox.emergency_eta == 0, CustomError::EmergencyWithdrawPending);
+        
+        let now = unix_now(solbox)?;
+        let eta = now
+            .checked_add(EMERGENCY_WITHDRAW_TIMELOCK)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        solbox.emergency_recipient = recipient;
+        solbox.emergency_eta = eta;
+        
+        let vault = solbox.to_account_info();
+        emit!(EmergencyWithdrawInitiatedEvent {
+            admin: *ctx.accounts.admin.key,
+            recipient,
+            vault_balance: vault.lamports(),
+            liabilities: solbox_liabilities(solbox)?,
+            eta,
+            timestamp: now,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    pub fn cancel_emergency_withdraw(ctx: Context<ProposeEmergencyWithdraw>) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == solbox.owner,
+            CustomError::Unauthorized
+        );
+        require!(solbox.emergency_eta != 0, CustomError::NoPendingEmergencyWithdraw);
+        solbox.emergency_recipient = Pubkey::default();
+        solbox.emergency_eta = 0;
+        
+        emit!(EmergencyWithdrawCancelledEvent {
+            admin: *ctx.accounts.admin.key,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Moves every lamport above rent to the announced recipient and leaves
+    // the contract paused. Liabilities are kept as they were, as a record
+    // of what users were owed
+    pub fn execute_emergency_withdraw(ctx: Context<ExecuteEmergencyWithdraw>) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        let now = unix_now(solbox)?;
+        
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == solbox.owner,
+            CustomError::Unauthorized
+        );
+        require!(solbox.emergency_eta != 0, CustomError::NoPendingEmergencyWithdraw);
+        require!(now >= solbox.emergency_eta, CustomError::TimelockNotElapsed);
+        
+        let vault = solbox.to_account_info();
+        let amount = treasury_balance(&vault, 0)?;
+        transfer_from_vault(&vault, &ctx.accounts.recipient.to_account_info(), amount)?;
+        
+        solbox.emergency_recipient = Pubkey::default();
+        solbox.emergency_eta = 0;
+        if !solbox.paused {
+            solbox.paused = true;
+            solbox.pause_count = solbox.pause_count
+                .checked_add(1)
+                .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        }
+        
+        emit!(EmergencyWithdrawExecutedEvent {
+            admin: *ctx.accounts.admin.key,
+            recipient: ctx.accounts.recipient.key(),
+            amount,
+            liabilities: solbox_liabilities(solbox)?,
+            timestamp: now,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    pub fn expand_solbox(
+        ctx: Context<ExpandSolBox>,
+        additional_bytes: u32
+    ) -> Result<()> {
+        let solbox = &ctx.accounts.solbox;
+        
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == solbox.owner,
+            CustomError::Unauthorized
+        );
+        require!(additional_bytes > 0, CustomError::InvalidAccountSize);
+        
+        emit!(AccountExpandedEvent {
+            admin: *ctx.accounts.admin.key,
+            account: solbox.key(),
+            new_size: solbox.to_account_info().data_len() as u64,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    pub fn expand_user(
+        ctx: Context<ExpandUser>,
+        additional_bytes: u32
+    ) -> Result<()> {
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == ctx.accounts.solbox.owner,
+            CustomError::Unauthorized
+        );
+        require!(additional_bytes > 0, CustomError::InvalidAccountSize);
+        
+        let user = &ctx.accounts.user;
+        emit!(AccountExpandedEvent {
+            admin: *ctx.accounts.admin.key,
+            account: user.key(),
+            new_size: user.to_account_info().data_len() as u64,
+            timestamp: unix_now(&ctx.accounts.solbox)?,
+            brand_code: ctx.accounts.solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    pub fn register_integrator(
+        ctx: Context<RegisterIntegrator>,
+        name_hash: [u8; 32],
+        wallet: Pubkey,
+        fee_share_bps: u16,
+        allowed_instructions: u32,
+        cpi_authority: Pubkey
+    ) -> Result<()> {
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == ctx.accounts.solbox.owner,
+            CustomError::Unauthorized
+        );
+        require!(
+            fee_share_bps <= MAX_INTEGRATOR_FEE_BPS,
+            CustomError::InvalidIntegratorFee
+        );
+        
+        let integrator = &mut ctx.accounts.integrator;
+        integrator.account_type = ACCOUNT_TYPE_INTEGRATOR;
+        integrator.account_version = INTEGRATOR_VERSION;
+        integrator.solbox = ctx.accounts.solbox.key();
+        integrator.name_hash = name_hash;
+        integrator.wallet = wallet;
+        integrator.fee_share_bps = fee_share_bps;
+        integrator.allowed_instructions = allowed_instructions;
+        integrator.bump = ctx.bumps.integrator;
+        integrator.cpi_authority = cpi_authority;
+        integrator.burst_started_at = 0;
+        integrator.burst_count = 0;
+        integrator.epoch_volume_limit = 0;
+        integrator.epoch_purchase_limit = 0;
+        integrator.limit_epoch = 0;
+        integrator.epoch_volume = 0;
+        integrator.epoch_purchases = 0;
+        
+        emit!(IntegratorUpdatedEvent {
+            admin: *ctx.accounts.admin.key,
+            integrator: integrator.key(),
+            name_hash,
+            wallet,
+            fee_share_bps,
+            allowed_instructions,
+            cpi_authority,
+            timestamp: unix_now(&ctx.accounts.solbox)?,
+            brand_code: ctx.accounts.solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    pub fn update_integrator(
+        ctx: Context<UpdateIntegrator>,
+        wallet: Pubkey,
+        fee_share_bps: u16,
+        allowed_instructions: u32,
+        cpi_authority: Pubkey
+    ) -> Result<()> {
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == ctx.accounts.solbox.owner,
+            CustomError::Unauthorized
+        );
+        require!(
+            fee_share_bps <= MAX_INTEGRATOR_FEE_BPS,
+            CustomError::InvalidIntegratorFee
+        );
+        
+        // Pending receipts keep the fee they were created with
+        let integrator = &mut ctx.accounts.integrator;
+        integrator.wallet = wallet;
+        integrator.fee_share_bps = fee_share_bps;
+        integrator.allowed_instructions = allowed_instructions;
+        integrator.cpi_authority = cpi_authority;
+        
+        emit!(IntegratorUpdatedEvent {
+            admin: *ctx.accounts.admin.key,
+            integrator: integrator.key(),
+            name_hash: integrator.name_hash,
+            wallet,
+            fee_share_bps,
+            allowed_instructions,
+            cpi_authority,
+            timestamp: unix_now(&ctx.accounts.solbox)?,
+            brand_code: ctx.accounts.solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Per-epoch ceilings on what one integrator can route; 0 lifts a ceiling
+    pub fn set_integrator_limits(
+        ctx: Context<UpdateIntegrator>,
+        epoch_volume_limit: u64,
+        epoch_purchase_limit: u32
+    ) -> Result<()> {
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == ctx.accounts.solbox.owner,
+            CustomError::Unauthorized
+        );
+        
+        // The current epoch's usage carries over, so lowering a ceiling
+        // below it stops the integrator until the epoch turns
+        let integrator = &mut ctx.accounts.integrator;
+        integrator.epoch_volume_limit = epoch_volume_limit;
+        integrator.epoch_purchase_limit = epoch_purchase_limit;
+        
+        emit!(IntegratorLimitsEvent {
+            admin: *ctx.accounts.admin.key,
+            integrator: integrator.key(),
+            epoch_volume_limit,
+            epoch_purchase_limit,
+            timestamp: unix_now(&ctx.accounts.solbox)?,
+            brand_code: ctx.accounts.solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    pub fn initialize_feature_flags(ctx: Context<InitializeFeatureFlags>) -> Result<()> {
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == ctx.accounts.solbox.owner,
+            CustomError::Unauthorized
+        );
+        
+        // Every feature starts disabled
+        let feature_flags = &mut ctx.accounts.feature_flags;
+        feature_flags.account_type = ACCOUNT_TYPE_FEATURE_FLAGS;
+        feature_flags.account_version = FEATURE_FLAGS_VERSION;
+        feature_flags.solbox = ctx.accounts.solbox.key();
+        feature_flags.flags = 0;
+        feature_flags.pending_flags = 0;
+        feature_flags.pending_eta = 0;
+        feature_flags.bump = ctx.bumps.feature_flags;
+        
+        Ok(())
+    }
+
+    pub fn propose_feature_flags(ctx: Context<UpdateFeatureFlags>, flags: u64) -> Result<()> {
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == ctx.accounts.solbox.owner,
+            CustomError::Unauthorized
+        );
+        
+        // A new proposal replaces any pending one and restarts the timelock
+        let now = unix_now(&ctx.accounts.solbox)?;
+        let eta = now
+            .checked_add(FEATURE_FLAGS_TIMELOCK)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        let feature_flags = &mut ctx.accounts.feature_flags;
+        feature_flags.pending_flags = flags;
+        feature_flags.pending_eta = eta;
+        
+        emit!(FeatureFlagsProposedEvent {
+            admin: *ctx.accounts.admin.key,
+            current_flags: feature_flags.flags,
+            pending_flags: flags,
+            eta,
+            timestamp: now,
+            brand_code: ctx.accounts.solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    pub fn cancel_feature_flags(ctx: Context<UpdateFeatureFlags>) -> Result<()> {
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == ctx.accounts.solbox.owner,
+            CustomError::Unauthorized
+        );
+        
+        let feature_flags = &mut ctx.accounts.feature_flags;
+        require!(feature_flags.pending_eta != 0, CustomError::NoPendingFeatureFlags);
+        feature_flags.pending_flags = 0;
+        feature_flags.pending_eta = 0;
+        
+        Ok(())
+    }
+
+    // Permissionless once the timelock has passed
+    pub fn apply_feature_flags(ctx: Context<ApplyFeatureFlags>) -> Result<()> {
+        let feature_flags = &mut ctx.accounts.feature_flags;
+        let now = unix_now(&ctx.accounts.solbox)?;
+        require!(feature_flags.pending_eta != 0, CustomError::NoPendingFeatureFlags);
+        require!(now >= feature_flags.pending_eta, CustomError::TimelockNotElapsed);
+        
+        let previous_flags = feature_flags.flags;
+        feature_flags.flags = feature_flags.pending_flags;
+        feature_flags.pending_flags = 0;
+        feature_flags.pending_eta = 0;
+        
+        emit!(FeatureFlagsAppliedEvent {
+            previous_flags,
+            flags: feature_flags.flags,
+            timestamp: now,
+            brand_code: ctx.accounts.solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    pub fn link_tenant(ctx: Context<LinkTenant>, source: Pubkey) -> Result<()> {
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == ctx.accounts.solbox.owner,
+            CustomError::Unauthorized
+        );
+        require!(
+            source != ctx.accounts.solbox.key(),
+            CustomError::InvalidTenant
+        );
+        
+        let tenant_link = &mut ctx.accounts.tenant_link;
+        tenant_link.account_type = ACCOUNT_TYPE_TENANT_LINK;
+        tenant_link.account_version = TENANT_LINK_VERSION;
+        tenant_link.solbox = ctx.accounts.solbox.key();
+        tenant_link.source = source;
+        tenant_link.bump = ctx.bumps.tenant_link;
+        
+        emit!(TenantLinkedEvent {
+            admin: *ctx.accounts.admin.key,
+            source,
+            timestamp: unix_now(&ctx.accounts.solbox)?,
+            brand_code: ctx.accounts.solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    pub fn export_user_proof(ctx: Context<ExportUserProof>) -> Result<()> {
+        // The source tenant authority attests to the user's history
+        require!(
+            ctx.accounts.admin.key() == ctx.accounts.solbox.owner,
+            CustomError::Unauthorized
+        );
+        
+        let user_account = &ctx.accounts.user_account;
+        let proof = &mut ctx.accounts.user_proof;
+        let now = unix_now(&ctx.accounts.solbox)?;
+        
+        proof.account_type = ACCOUNT_TYPE_USER_PROOF;
+        proof.account_version = USER_PROOF_VERSION;
+        proof.solbox = ctx.accounts.solbox.key();
+        proof.user = user_account.key;
+        proof.package = user_account.current_package;
+        proof.lifetime_volume = user_account.lifetime_volume;
+        proof.issued_at = now;
+        proof.bump = ctx.bumps.user_proof;
+        
+        emit!(UserProofExportedEvent {
+            user: proof.user,
+            package: proof.package,
+            lifetime_volume: proof.lifetime_volume,
+            timestamp: now,
+            brand_code: ctx.accounts.solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    pub fn import_user(ctx: Context<ImportUser>) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        let proof = &ctx.accounts.user_proof;
+        let user_account = &mut ctx.accounts.user_account;
+        
+        // Verify contract is active
+        require!(!solbox.paused, CustomError::ContractPaused);
+        
+        // A user carries one history into each tenant
+        require!(
+            user_account.imported_from == Pubkey::default(),
+            CustomError::UserAlreadyImported
+        );
+        
+        // Packages above what this tenant sells can't be carried over
+        if proof.package > user_account.current_package {
+            require!(
+                solbox.config.valid_amounts.contains(&proof.package),
+                CustomError::InvalidAmount
+            );
+            set_package(solbox, user_account, proof.package)?;
+        }
+        user_account.lifetime_volume = user_account.lifetime_volume
+            .checked_add(proof.lifetime_volume)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        user_account.imported_from = proof.solbox;
+        
+        emit!(UserImportedEvent {
+            user: user_account.key,
+            source: proof.solbox,
+            package: user_account.current_package,
+            lifetime_volume: user_account.lifetime_volume,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Commit the Merkle root over legacy positions (see
+    // statements::MigratedPosition). It can be replaced until the first claim
+    pub fn commit_migration_root(
+        ctx: Context<CommitMigrationRoot>,
+        merkle_root: [u8; 32],
+        positions: u32
+    ) -> Result<()> {
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == ctx.accounts.solbox.owner,
+            CustomError::Unauthorized
+        );
+        
+        let migration = &mut ctx.accounts.migration;
+        require!(migration.claimed == 0, CustomError::MigrationStarted);
+        
+        let now = unix_now(&ctx.accounts.solbox)?;
+        migration.account_type = ACCOUNT_TYPE_MIGRATION;
+        migration.account_version = MIGRATION_VERSION;
+        migration.solbox = ctx.accounts.solbox.key();
+        migration.root = merkle_root;
+        migration.positions = positions;
+        migration.committed_at = now;
+        migration.bump = ctx.bumps.migration;
+        
+        emit!(MigrationRootCommittedEvent {
+            admin: *ctx.accounts.admin.key,
+            root: merkle_root,
+            positions,
+            timestamp: now,
+            brand_code: ctx.accounts.solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // A legacy user claims its package, volume and sponsor link with the
+    // proof of its leaf. Upline accounts for the placement come in
+    // remaining_accounts, see take_uplines
+    pub fn claim_migrated_position<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimMigratedPosition<'info>>,
+        proof: Vec<[u8; 32]>,
+        package: u64,
+        sponsor: Pubkey,
+        lifetime_volume: u64
+    ) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        let user_account = &mut ctx.accounts.user_account;
+        let wallet = ctx.accounts.wallet.key();
+        
+        // Verify contract is active
+        require!(!solbox.paused, CustomError::ContractPaused);
+        
+        // The migration counts as the user's one imported history
+        require!(
+            user_account.imported_from == Pubkey::default(),
+            CustomError::UserAlreadyImported
+        );
+        let position = MigratedPosition { wallet, package, sponsor, lifetime_volume };
+        require!(
+            verify_proof(position.leaf(), &proof, ctx.accounts.migration.root),
+            CustomError::InvalidMigrationProof
+        );
+        
+        if package > user_account.current_package {
+            require!(
+                solbox.config.valid_amounts.contains(&package),
+                CustomError::InvalidAmount
+            );
+            set_package(solbox, user_account, package)?;
+        }
+        user_account.lifetime_volume = user_account.lifetime_volume
+            .checked_add(lifetime_volume)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        user_account.imported_from = ctx.accounts.migration.key();
+        
+        // The sponsor link places users not yet in the tree; a position
+        // taken since the migration stays where it is
+        let placed = solbox.referral_relationships.iter().any(|r| r.user == wallet)
+            || solbox.holding_tank.iter().any(|entry| entry.user == wallet);
+        if sponsor != Pubkey::default() && sponsor != wallet && !placed {
+            let spot = resolve_placement(solbox, sponsor)?;
+            let vault = solbox.to_account_info();
+            let mut uplines = ctx.remaining_accounts;
+            place_in_matrix(
+                solbox,
+                &vault,
+                &mut uplines,
+                ctx.remaining_accounts,
+                wallet,
+                spot,
+                None
+            )?;
+        }
+        
+        let migration = &mut ctx.accounts.migration;
+        migration.claimed = migration.claimed
+            .checked_add(1)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        
+        emit!(PositionMigratedEvent {
+            user: wallet,
+            package: ctx.accounts.user_account.current_package,
+            sponsor,
+            lifetime_volume,
+            timestamp: unix_now(&ctx.accounts.solbox)?,
+            brand_code: ctx.accounts.solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Record the hash of this instance's config, totals and tree root, in
+    // case the SolBox is ever corrupted. The snapshot itself goes out in
+    // the event. A new export drops any restore in progress
+    pub fn export_config_hash(
+        ctx: Context<ExportConfigHash>,
+        tree_root: [u8; 32],
+        positions: u32
+    ) -> Result<()> {
+        let solbox = &ctx.accounts.solbox;
+        
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == solbox.owner,
+            CustomError::Unauthorized
+        );
+        
+        // Restores need the pause guardians' quorum, so there must be one
+        require!(solbox.unpause_threshold > 0, CustomError::InvalidGuardians);
+        
+        let snapshot = snapshot_instance(solbox, solbox.key(), tree_root, positions);
+        let hash = snapshot.hash()?;
+        let now = unix_now(solbox)?;
+        let config_snapshot = &mut ctx.accounts.config_snapshot;
+        config_snapshot.account_type = ACCOUNT_TYPE_CONFIG_SNAPSHOT;
+        config_snapshot.account_version = CONFIG_SNAPSHOT_VERSION;
+        config_snapshot.solbox = solbox.key();
+        config_snapshot.admin = *ctx.accounts.admin.key;
+        config_snapshot.hash = hash;
+        config_snapshot.exported_at = now;
+        config_snapshot.guardians = solbox.pause_guardians.clone();
+        config_snapshot.threshold = solbox.unpause_threshold;
+        config_snapshot.target = Pubkey::default();
+        config_snapshot.approvals = 0;
+        config_snapshot.restore_eta = 0;
+        config_snapshot.restored_to = Pubkey::default();
+        config_snapshot.bump = ctx.bumps.config_snapshot;
+        
+        emit!(ConfigExportedEvent {
+            admin: *ctx.accounts.admin.key,
+            snapshot,
+            hash,
+            timestamp: now,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Guardians named at export approve restoring into a specific fresh
+    // instance; approving a different one restarts the vote. The timelock
+    // starts once the threshold is met
+    pub fn approve_restore(ctx: Context<ApproveRestore>) -> Result<()> {
+        let target = ctx.accounts.solbox.key();
+        let config_snapshot = &mut ctx.accounts.config_snapshot;
+        let guardian = config_snapshot.guardians
+            .iter()
+            .position(|guardian| *guardian == ctx.accounts.guardian.key())
+            .ok_or(CustomError::Unauthorized)?;
+        require!(
+            config_snapshot.restored_to == Pubkey::default() && target != config_snapshot.solbox,
+            CustomError::InvalidSnapshot
+        );
+        
+        if config_snapshot.target != target {
+            config_snapshot.target = target;
+            config_snapshot.approvals = 0;
+            config_snapshot.restore_eta = 0;
+        }
+        config_snapshot.approvals |= 1 << guardian;
+        
+        let now = unix_now(&ctx.accounts.solbox)?;
+        if config_snapshot.restore_eta == 0
+            && config_snapshot.approvals.count_ones() >= config_snapshot.threshold as u32
+        {
+            config_snapshot.restore_eta = now
+                .checked_add(RESTORE_TIMELOCK)
+                .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        }
+        
+        emit!(RestoreApprovedEvent {
+            source: config_snapshot.solbox,
+            target,
+            guardian: ctx.accounts.guardian.key(),
+            approvals: config_snapshot.approvals.count_ones() as u8,
+            restore_eta: config_snapshot.restore_eta,
+            timestamp: now,
+            brand_code: ctx.accounts.solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Loads an exported snapshot into a fresh instance once the guardians'
+    // timelock has passed. Positions come back through
+    // claim_migrated_position against the snapshot's tree root
+    pub fn restore_instance(
+        ctx: Context<RestoreInstance>,
+        snapshot: InstanceSnapshot
+    ) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        let config_snapshot = &mut ctx.accounts.config_snapshot;
+        
+        // Verify admin authority, over both instances
+        require!(
+            ctx.accounts.admin.key() == solbox.owner
+                && ctx.accounts.admin.key() == config_snapshot.admin,
+            CustomError::Unauthorized
+        );
+        
+        // Only an instance nobody has bought into is overwritten
+        require!(
+            solbox.purchase_count == 0 && solbox.referral_relationships.is_empty(),
+            CustomError::InstanceNotFresh
+        );
+        
+        let now = unix_now(solbox)?;
+        require!(
+            config_snapshot.target == solbox.key() && config_snapshot.restore_eta != 0,
+            CustomError::NoApprovedRestore
+        );
+        require!(now >= config_snapshot.restore_eta, CustomError::TimelockNotElapsed);
+        require!(
+            snapshot.source == config_snapshot.solbox && snapshot.hash()? == config_snapshot.hash,
+            CustomError::InvalidSnapshot
+        );
+        validate_config(&snapshot.config, &[])?;
+        require!(
+            snapshot.config.valid_amounts.len() <= solbox.max_amounts as usize,
+            CustomError::TooManyAmounts
+        );
+        require!(
+            snapshot.config.commission_levels <= solbox.max_levels,
+            CustomError::TooManyLevels
+        );
+        
+        solbox.config = snapshot.config.clone();
+        solbox.total_sold = snapshot.total_sold;
+        solbox.total_commission_distributed = snapshot.total_commission_distributed;
+        solbox.referral_count = snapshot.referral_count;
+        solbox.purchase_count = snapshot.purchase_count;
+        let version = record_config_version(
+            &mut ctx.accounts.config_history,
+            snapshot.config,
+            *ctx.accounts.admin.key,
+            Clock::get()?.slot,
+        )?;
+        
+        let migration = &mut ctx.accounts.migration;
+        require!(migration.claimed == 0, CustomError::MigrationStarted);
+        migration.account_type = ACCOUNT_TYPE_MIGRATION;
+        migration.account_version = MIGRATION_VERSION;
+        migration.solbox = solbox.key();
+        migration.root = snapshot.tree_root;
+        migration.positions = snapshot.positions;
+        migration.committed_at = now;
+        migration.bump = ctx.bumps.migration;
+        
+        // A snapshot restores once
+        config_snapshot.restored_to = solbox.key();
+        config_snapshot.restore_eta = 0;
+        
+        emit!(InstanceRestoredEvent {
+            admin: *ctx.accounts.admin.key,
+            source: config_snapshot.solbox,
+            hash: config_snapshot.hash,
+            config_version: version,
+            timestamp: now,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    pub fn delegate_to_stake_pool(
+        ctx: Context<StakePoolAction>,
+        lamports: u64
+    ) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == solbox.owner,
+            CustomError::Unauthorized
+        );
+        require!(lamports > 0, CustomError::InvalidAmount);
+        
+        // Only idle treasury SOL can be delegated, so escrowed purchases
+        // always stay liquid, and only up to the configured share
+        let vault = solbox.to_account_info();
+        let treasury = treasury_balance(&vault, solbox_liabilities(solbox)?)?;
+        let staked = solbox.staked_lamports
+            .checked_add(lamports)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        let stake_limit = pro_rata(
+            treasury
+                .checked_add(solbox.staked_lamports)
+                .ok_or_else(|| error!(CustomError::ArithmeticError))?,
+            solbox.config.max_stake_bps as u64,
+            BPS_DENOMINATOR as u64
+        )?;
+        require!(
+            lamports <= treasury && staked <= stake_limit,
+            CustomError::StakeLimitExceeded
+        );
+        
+        require_coverage(&vault, solbox, lamports)?;
+        
+        // The stake pool pulls lamports from a system-owned signer, so
+        // route them through the stake authority PDA
+        let stake_authority = ctx.accounts.stake_authority.to_account_info();
+        transfer_from_vault(&vault, &stake_authority, lamports)?;
+        
+        let accounts = &ctx.accounts;
+        let solbox_key = accounts.solbox.key();
+        let signer_seeds: &[&[u8]] = &[
+            b"stake_authority",
+            solbox_key.as_ref(),
+            &[ctx.bumps.stake_authority],
+        ];
+        invoke_signed(
+            &Instruction {
+                program_id: SPL_STAKE_POOL_PROGRAM_ID,
+                accounts: vec![
+                    AccountMeta::new(accounts.stake_pool.key(), false),
+                    AccountMeta::new_readonly(accounts.stake_pool_withdraw_authority.key(), false),
+                    AccountMeta::new(accounts.reserve_stake.key(), false),
+                    AccountMeta::new(accounts.stake_authority.key(), true),
+                    AccountMeta::new(accounts.pool_token_account.key(), false),
+                    AccountMeta::new(accounts.manager_fee_account.key(), false),
+                    AccountMeta::new(accounts.manager_fee_account.key(), false),
+                    AccountMeta::new(accounts.pool_mint.key(), false),
+                    AccountMeta::new_readonly(accounts.system_program.key(), false),
+                    AccountMeta::new_readonly(accounts.token_program.key(), false),
+                ],
+                data: stake_pool_instruction_data(STAKE_POOL_DEPOSIT_SOL, lamports),
+            },
+            &[
+                accounts.stake_pool.to_account_info(),
+                accounts.stake_pool_withdraw_authority.to_account_info(),
+                accounts.reserve_stake.to_account_info(),
+                stake_authority.clone(),
+                accounts.pool_token_account.to_account_info(),
+                accounts.manager_fee_account.to_account_info(),
+                accounts.pool_mint.to_account_info(),
+                accounts.system_program.to_account_info(),
+                accounts.token_program.to_account_info(),
+                accounts.stake_pool_program.to_account_info(),
+            ],
+            &[signer_seeds],
+        )?;
+        
+        let solbox = &mut ctx.accounts.solbox;
+        solbox.staked_lamports = staked;
+        
+        emit!(StakeDelegatedEvent {
+            admin: *ctx.accounts.admin.key,
+            stake_pool: solbox.config.stake_pool,
+            lamports,
+            staked_lamports: staked,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    pub fn undelegate_from_stake_pool(
+        ctx: Context<StakePoolAction>,
+        pool_tokens: u64
+    ) -> Result<()> {
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == ctx.accounts.solbox.owner,
+            CustomError::Unauthorized
+        );
+        require!(pool_tokens > 0, CustomError::InvalidAmount);
+        
+        let accounts = &ctx.accounts;
+        let stake_authority = accounts.stake_authority.to_account_info();
+        let solbox_key = accounts.solbox.key();
+        let signer_seeds: &[&[u8]] = &[
+            b"stake_authority",
+            solbox_key.as_ref(),
+            &[ctx.bumps.stake_authority],
+        ];
+        
+        // Withdrawn SOL goes straight to the vault; only the pool tokens
+        // need the stake authority's signature
+        let vault = accounts.solbox.to_account_info();
+        let balance_before = vault.lamports();
+        invoke_signed(
+            &Instruction {
+                program_id: SPL_STAKE_POOL_PROGRAM_ID,
+                accounts: vec![
+                    AccountMeta::new(accounts.stake_pool.key(), false),
+                    AccountMeta::new_readonly(accounts.stake_pool_withdraw_authority.key(), false),
+                    AccountMeta::new_readonly(accounts.stake_authority.key(), true),
+                    AccountMeta::new(accounts.pool_token_account.key(), false),
+                    AccountMeta::new(accounts.reserve_stake.key(), false),
+                    AccountMeta::new(solbox_key, false),
+                    AccountMeta::new(accounts.manager_fee_account.key(), false),
+                    AccountMeta::new(accounts.pool_mint.key(), false),
+                    AccountMeta::new_readonly(sysvar::clock::ID, false),
+                    AccountMeta::new_readonly(sysvar::stake_history::ID, false),
+                    AccountMeta::new_readonly(stake::program::ID, false),
+                    AccountMeta::new_readonly(accounts.token_program.key(), false),
+                ],
+                data: stake_pool_instruction_data(STAKE_POOL_WITHDRAW_SOL, pool_tokens),
+            },
+            &[
+                accounts.stake_pool.to_account_info(),
+                accounts.stake_pool_withdraw_authority.to_account_info(),
+                stake_authority.clone(),
+                accounts.pool_token_account.to_account_info(),
+                accounts.reserve_stake.to_account_info(),
+                vault.clone(),
+                accounts.manager_fee_account.to_account_info(),
+                accounts.pool_mint.to_account_info(),
+                accounts.clock.to_account_info(),
+                accounts.stake_history.to_account_info(),
+                accounts.stake_program.to_account_info(),
+                accounts.token_program.to_account_info(),
+                accounts.stake_pool_program.to_account_info(),
+            ],
+            &[signer_seeds],
+        )?;
+        let received = vault.lamports()
+            .checked_sub(balance_before)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        
+        // Anything above the delegated principal is realized yield
+        let solbox = &mut ctx.accounts.solbox;
+        let principal = received.min(solbox.staked_lamports);
+        let yield_lamports = received - principal;
+        solbox.staked_lamports -= principal;
+        
+        // Reserve realized yield for active package holders
+        if solbox.config.yield_sharing {
+            solbox.undistributed_yield = solbox.undistributed_yield
+                .checked_add(yield_lamports)
+                .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+            solbox.yield_pool_balance = solbox.yield_pool_balance
+                .checked_add(yield_lamports)
+                .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        }
+        
+        emit!(StakeUndelegatedEvent {
+            admin: *ctx.accounts.admin.key,
+            stake_pool: solbox.config.stake_pool,
+            pool_tokens,
+            lamports_received: received,
+            yield_lamports,
+            staked_lamports: solbox.staked_lamports,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    pub fn distribute_yield(ctx: Context<DistributeYield>) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        let epoch = current_epoch(solbox)?;
+        
+        // One distribution per epoch
+        require!(solbox.config.yield_sharing, CustomError::YieldSharingDisabled);
+        require!(epoch >= solbox.next_yield_epoch, CustomError::YieldAlreadyDistributed);
+        require!(solbox.total_package_weight > 0, CustomError::NoActivePackages);
+        
+        // Spread the epoch's yield across packages; rounding dust stays
+        // undistributed for the next epoch
+        let (increment, distributed) = yield_increment(
+            solbox.undistributed_yield,
+            solbox.total_package_weight
+        )?;
+        
+        solbox.yield_per_package = solbox.yield_per_package
+            .checked_add(increment)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        solbox.undistributed_yield = solbox.undistributed_yield
+            .checked_sub(distributed)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        solbox.next_yield_epoch = epoch
+            .checked_add(1)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        
+        emit!(YieldDistributionEvent {
+            epoch,
+            amount: distributed,
+            total_package_weight: solbox.total_package_weight,
+            yield_per_package: solbox.yield_per_package,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // One claim per wallet and epoch, recorded in a ClaimReceipt; a retried
+    // submission finds the receipt and returns without paying again
+    pub fn claim_yield(ctx: Context<ClaimYield>, epoch: u64) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        let user_account = &mut ctx.accounts.user_account;
+        
+        // Verify claims are open
+        require!(claims_open(solbox), CustomError::ContractPaused);
+        require!(epoch == current_epoch(solbox)?, CustomError::InvalidClaimEpoch);
+        
+        let receipt = &mut ctx.accounts.claim_receipt;
+        if receipt.claimed_at != 0 {
+            return Ok(());
+        }
+        
+        accrue_yield(solbox, user_account)?;
+        let amount = user_account.unclaimed_yield;
+        require!(amount > 0, CustomError::NothingToClaim);
+        require!(amount >= solbox.config.min_claim, CustomError::ClaimBelowMinimum);
+        
+        user_account.unclaimed_yield = 0;
+        user_account.last_active_at = unix_now(solbox)?;
+        credit_earnings(user_account, EarningSource::Pools, amount)?;
+        solbox.yield_pool_balance = solbox.yield_pool_balance
+            .checked_sub(amount)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        
+        pay_claim(solbox, user_account, &ctx.accounts.wallet.to_account_info(), amount)?;
+        
+        let now = unix_now(solbox)?;
+        receipt.account_type = ACCOUNT_TYPE_CLAIM_RECEIPT;
+        receipt.account_version = CLAIM_RECEIPT_VERSION;
+        receipt.solbox = solbox.key();
+        receipt.wallet = user_account.key;
+        receipt.epoch = epoch;
+        receipt.amount = amount;
+        receipt.claimed_at = now;
+        receipt.bump = ctx.bumps.claim_receipt;
+        
+        emit!(YieldClaimedEvent {
+            user: user_account.key,
+            amount,
+            epoch,
+            receipt: receipt.key(),
+            timestamp: now,
+            brand_code: solbox.brand_code,
+        });
+        emit!(EarningsEvent {
+            user: user_account.key,
+            source: EarningSource::Pools,
+            amount,
+            reference: epoch,
+            timestamp: now,
+            brand_code: solbox.brand_code,
+        });
+        emit_coverage(solbox)?;
+        
+        Ok(())
+    }
+
+    // Pays out residual commission from epochs that have closed
+    pub fn claim_residual(ctx: Context<ClaimResidual>) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        let user_account = &mut ctx.accounts.user_account;
+        
+        // Verify claims are open
+        require!(claims_open(solbox), CustomError::ContractPaused);
+        
+        // The residual of a wrapped position belongs to its token holder
+        require!(
+            user_account.position_mint == Pubkey::default(),
+            CustomError::PositionWrapped
+        );
+        
+        mature_residual(user_account, current_epoch(solbox)?);
+        let amount = user_account.residual_claimable;
+        require!(amount > 0, CustomError::NothingToClaim);
+        require!(amount >= solbox.config.min_claim, CustomError::ClaimBelowMinimum);
+        
+        user_account.residual_claimable = 0;
+        solbox.residual_balance = solbox.residual_balance
+            .checked_sub(amount)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        
+        pay_claim(solbox, user_account, &ctx.accounts.wallet.to_account_info(), amount)?;
+        
+        emit!(ResidualClaimedEvent {
+            user: user_account.key,
+            amount,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        emit_coverage(solbox)?;
+        
+        Ok(())
+    }
+
+    // Converts the wallet's claimable residual into card balance, plus a
+    // config.card_bonus_bps bonus out of the treasury. Nothing leaves the
+    // vault: the balance pays for the wallet's later purchases
+    pub fn claim_to_card(ctx: Context<ClaimResidual>) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        let user_account = &mut ctx.accounts.user_account;
+        
+        // Verify claims are open
+        require!(claims_open(solbox), CustomError::ContractPaused);
+        
+        // The residual of a wrapped position belongs to its token holder
+        require!(
+            user_account.position_mint == Pubkey::default(),
+            CustomError::PositionWrapped
+        );
+        
+        mature_residual(user_account, current_epoch(solbox)?);
+        let amount = user_account.residual_claimable;
+        require!(amount > 0, CustomError::NothingToClaim);
+        
+        // The bonus becomes a liability, so the treasury has to cover it
+        let bonus = pro_rata(amount, solbox.config.card_bonus_bps as u64, BPS_DENOMINATOR as u64)?;
+        require_coverage(&solbox.to_account_info(), solbox, bonus)?;
+        let credited = amount
+            .checked_add(bonus)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        
+        user_account.residual_claimable = 0;
+        solbox.residual_balance = solbox.residual_balance
+            .checked_sub(amount)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        user_account.card_balance = user_account.card_balance
+            .checked_add(credited)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        solbox.card_balance_total = solbox.card_balance_total
+            .checked_add(credited)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        
+        emit!(CardBalanceEvent {
+            user: user_account.key,
+            amount,
+            bonus,
+            balance: user_account.card_balance,
+            action: CardBalanceAction::Converted,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        emit_coverage(solbox)?;
+        
+        Ok(())
+    }
+
+    // Registers a merchant card balances can be redeemed with; its wallet
+    // co-signs redemptions and receives their settlement
+    pub fn register_merchant(ctx: Context<RegisterMerchant>, wallet: Pubkey) -> Result<()> {
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == ctx.accounts.solbox.owner,
+            CustomError::Unauthorized
+        );
+        let merchant = &mut ctx.accounts.merchant;
+        merchant.account_type = ACCOUNT_TYPE_MERCHANT;
+        merchant.account_version = MERCHANT_VERSION;
+        merchant.solbox = ctx.accounts.solbox.key();
+        merchant.wallet = wallet;
+        merchant.redeemed = 0;
+        merchant.fees = 0;
+        merchant.redemptions = 0;
+        merchant.bump = ctx.bumps.merchant;
+        Ok(())
+    }
+
+    // Spends card balance at a merchant, signed by the cardholder and the
+    // merchant. config.redemption_fee_bps of it stays in the treasury and
+    // the rest is paid to the merchant wallet; both are rolled up per
+    // merchant and calendar month for invoicing
+    pub fn redeem_to_merchant(ctx: Context<RedeemToMerchant>, amount: u64) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        let user_account = &mut ctx.accounts.user_account;
+        require!(!solbox.paused, CustomError::ContractPaused);
+        require!(amount > 0, CustomError::InvalidAmount);
+        require!(amount <= user_account.card_balance, CustomError::InsufficientCardBalance);
+        
+        let fee = pro_rata(amount, solbox.config.redemption_fee_bps as u64, BPS_DENOMINATOR as u64)?;
+        let settled = amount
+            .checked_sub(fee)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        
+        user_account.card_balance -= amount;
+        solbox.card_balance_total = solbox.card_balance_total
+            .checked_sub(amount)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        transfer_from_vault(
+            &solbox.to_account_info(),
+            &ctx.accounts.merchant_wallet.to_account_info(),
+            settled
+        )?;
+        
+        let merchant_key = ctx.accounts.merchant.key();
+        let merchant: &mut Merchant = &mut ctx.accounts.merchant;
+        record_redemption(&mut merchant.redeemed, &mut merchant.fees, &mut merchant.redemptions, amount, fee)?;
+        
+        // Update this month's invoicing bucket
+        let now = unix_now(solbox)?;
+        let merchant_month: &mut MerchantMonth = &mut ctx.accounts.merchant_month;
+        if merchant_month.solbox == Pubkey::default() {
+            merchant_month.account_type = ACCOUNT_TYPE_MERCHANT_MONTH;
+            merchant_month.account_version = MERCHANT_MONTH_VERSION;
+            merchant_month.solbox = solbox.key();
+            merchant_month.merchant = merchant_key;
+            merchant_month.month = month_of(now);
+            merchant_month.bump = ctx.bumps.merchant_month;
+        }
+        record_redemption(
+            &mut merchant_month.redeemed,
+            &mut merchant_month.fees,
+            &mut merchant_month.redemptions,
+            amount,
+            fee
+        )?;
+        
+        emit!(CardBalanceEvent {
+            user: user_account.key,
+            amount,
+            bonus: 0,
+            balance: user_account.card_balance,
+            action: CardBalanceAction::Redeemed,
+            timestamp: now,
+            brand_code: solbox.brand_code,
+        });
+        emit!(MerchantRedemptionEvent {
+            merchant: merchant.wallet,
+            user: user_account.key,
+            amount,
+            fee,
+            month: merchant_month.month,
+            timestamp: now,
+            brand_code: solbox.brand_code,
+        });
+        emit_coverage(solbox)?;
+        
+        Ok(())
+    }
+
+    // Gives up the wallet's claimable residual for a voucher, posted as a
+    // Wormhole message, that the sister contract on config.voucher_chain
+    // mints the same amount from. The lamports stay in the vault
+    pub fn export_claim_voucher(
+        ctx: Context<ExportClaimVoucher>,
+        recipient: [u8; 32],
+        nonce: u32
+    ) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        let user_account = &mut ctx.accounts.user_account;
+        
+        // Verify claims are open and vouchers have a chain to go to
+        require!(claims_open(solbox), CustomError::ContractPaused);
+        require!(solbox.config.voucher_chain != 0, CustomError::VouchersDisabled);
+        
+        // The residual of a wrapped position belongs to its token holder
+        require!(
+            user_account.position_mint == Pubkey::default(),
+            CustomError::PositionWrapped
+        );
+        
+        mature_residual(user_account, current_epoch(solbox)?);
+        let amount = user_account.residual_claimable;
+        require!(amount > 0, CustomError::NothingToClaim);
+        require!(amount >= solbox.config.min_claim, CustomError::ClaimBelowMinimum);
+        
+        user_account.residual_claimable = 0;
+        solbox.residual_balance = solbox.residual_balance
+            .checked_sub(amount)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        solbox.voucher_balance = solbox.voucher_balance
+            .checked_add(amount)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        let voucher = ClaimVoucher {
+            voucher_id: solbox.voucher_count,
+            solbox: solbox.key(),
+            wallet: user_account.key,
+            amount,
+            recipient_chain: solbox.config.voucher_chain,
+            recipient,
+        };
+        solbox.voucher_count = solbox.voucher_count
+            .checked_add(1)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        
+        // The bridge takes its message fee from the wallet up front
+        let accounts = &ctx.accounts;
+        let fee = wormhole_message_fee(&accounts.bridge_config.try_borrow_data()?)
+            .ok_or_else(|| error!(CustomError::InvalidBridgeConfig))?;
+        if fee > 0 {
+            invoke(
+                &system_instruction::transfer(accounts.wallet.key, accounts.fee_collector.key, fee),
+                &[
+                    accounts.wallet.to_account_info(),
+                    accounts.fee_collector.to_account_info(),
+                    accounts.system_program.to_account_info(),
+                ],
+            )?;
+        }
+        
+        // The message PDA and the emitter sign for the post
+        let solbox_key = voucher.solbox;
+        let id_bytes = voucher.voucher_id.to_le_bytes();
+        let message_seeds: &[&[u8]] = &[b"voucher", solbox_key.as_ref(), &id_bytes, &[ctx.bumps.message]];
+        let emitter_seeds: &[&[u8]] = &[b"emitter", solbox_key.as_ref(), &[ctx.bumps.emitter]];
+        invoke_signed(
+            &Instruction {
+                program_id: WORMHOLE_CORE_BRIDGE_ID,
+                accounts: vec![
+                    AccountMeta::new(accounts.bridge_config.key(), false),
+                    AccountMeta::new(accounts.message.key(), true),
+                    AccountMeta::new_readonly(accounts.emitter.key(), true),
+                    AccountMeta::new(accounts.sequence.key(), false),
+                    AccountMeta::new(accounts.wallet.key(), true),
+                    AccountMeta::new(accounts.fee_collector.key(), false),
+                    AccountMeta::new_readonly(accounts.clock.key(), false),
+                    AccountMeta::new_readonly(accounts.rent.key(), false),
+                    AccountMeta::new_readonly(accounts.system_program.key(), false),
+                ],
+                data: wormhole_post_message_data(nonce, &voucher.payload()),
+            },
+            &[
+                accounts.bridge_config.to_account_info(),
+                accounts.message.to_account_info(),
+                accounts.emitter.to_account_info(),
+                accounts.sequence.to_account_info(),
+                accounts.wallet.to_account_info(),
+                accounts.fee_collector.to_account_info(),
+                accounts.clock.to_account_info(),
+                accounts.rent.to_account_info(),
+                accounts.system_program.to_account_info(),
+                accounts.wormhole_program.to_account_info(),
+            ],
+            &[message_seeds, emitter_seeds],
+        )?;
+        
+        let solbox = &ctx.accounts.solbox;
+        emit!(ClaimVoucherEvent {
+            wallet: voucher.wallet,
+            voucher_id: voucher.voucher_id,
+            amount,
+            recipient_chain: voucher.recipient_chain,
+            recipient,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        emit_coverage(solbox)?;
+        
+        Ok(())
+    }
+
+    // Permissionless; pays a dripped commission out to its wallet, a day's
+    // share for every full day since the tranche started
+    pub fn release_tranche(ctx: Context<ReleaseTranche>) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        let tranche = &mut ctx.accounts.tranche;
+        require!(claims_open(solbox), CustomError::ContractPaused);
+        require!(tranche.starts_at != 0, CustomError::NothingToClaim);
+        
+        let now = unix_now(solbox)?;
+        let released = tranche_released(tranche, now)?;
+        let amount = released
+            .checked_sub(tranche.released)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        require!(amount > 0, CustomError::NothingToClaim);
+        
+        tranche.released = released;
+        solbox.tranche_balance = solbox.tranche_balance
+            .checked_sub(amount)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        transfer_from_vault(&solbox.to_account_info(), &ctx.accounts.wallet.to_account_info(), amount)?;
+        
+        emit!(TrancheReleasedEvent {
+            wallet: tranche.wallet,
+            purchase_id: tranche.purchase_id,
+            amount,
+            released,
+            total: tranche.total,
+            timestamp: now,
+            brand_code: solbox.brand_code,
+        });
+        emit_coverage(solbox)?;
+        
+        Ok(())
+    }
+
+    // Permissionless; releases every tranche of one wallet passed in
+    // remaining_accounts, up to MAX_RELEASE_BATCH, as a single payment.
+    // Tranches still in escrow or with nothing due yet are skipped
+    pub fn release_tranches<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ReleaseTranches<'info>>
+    ) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        require!(claims_open(solbox), CustomError::ContractPaused);
+        require!(
+            ctx.remaining_accounts.len() <= MAX_RELEASE_BATCH,
+            CustomError::ReleaseBatchTooLarge
+        );
+        
+        let now = unix_now(solbox)?;
+        let wallet = ctx.accounts.wallet.key();
+        let mut total = 0u64;
+        for info in ctx.remaining_accounts.iter() {
+            let mut tranche = Account::<Tranche>::try_from(info)?;
+            require!(
+                tranche.solbox == solbox.key() && tranche.wallet == wallet,
+                CustomError::Unauthorized
+            );
+            if tranche.starts_at == 0 {
+                continue;
+            }
+            
+            let released = tranche_released(&tranche, now)?;
+            let amount = released
+                .checked_sub(tranche.released)
+                .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+            if amount == 0 {
+                continue;
+            }
+            
+            // Written back before the next account is read, so a tranche
+            // passed twice only pays once
+            tranche.released = released;
+            tranche.exit(&crate::ID)?;
+            total = total
+                .checked_add(amount)
+                .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+            
+            emit!(TrancheReleasedEvent {
+                wallet,
+                purchase_id: tranche.purchase_id,
+                amount,
+                released,
+                total: tranche.total,
+                timestamp: now,
+                brand_code: solbox.brand_code,
+            });
+        }
+        require!(total > 0, CustomError::NothingToClaim);
+        
+        solbox.tranche_balance = solbox.tranche_balance
+            .checked_sub(total)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        transfer_from_vault(&solbox.to_account_info(), &ctx.accounts.wallet.to_account_info(), total)?;
+        emit_coverage(solbox)?;
+        
+        Ok(())
+    }
+
+    // Permissionless; pays out claims queued outside the payout window once
+    // the payout day has come
+    pub fn pay_queued_payout(ctx: Context<PayQueuedPayout>) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        require!(claims_open(solbox), CustomError::ContractPaused);
+        require!(ctx.accounts.user_account.queued_payout > 0, CustomError::NothingToClaim);
+        require!(
+            is_payout_day(&solbox.config, unix_now(solbox)?),
+            CustomError::OutsidePayoutWindow
+        );
+        
+        pay_claim(solbox, &mut ctx.accounts.user_account, &ctx.accounts.wallet.to_account_info(), 0)?;
+        emit_coverage(solbox)
+    }
+
+    // Permissionless; pays founder shares held back from purchases to the
+    // current founder wallet once it can take them
+    pub fn flush_founder_share(ctx: Context<FlushFounderShare>) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        let amount = solbox.deferred_founder_share;
+        require!(amount > 0, CustomError::NothingToClaim);
+        require!(
+            can_receive(&ctx.accounts.founder, amount)?,
+            CustomError::FounderCannotReceive
+        );
+        
+        solbox.deferred_founder_share = 0;
+        transfer_from_vault(&solbox.to_account_info(), &ctx.accounts.founder.to_account_info(), amount)?;
+        
+        emit!(DeferredFounderShareEvent {
+            founder: ctx.accounts.founder.key(),
+            amount,
+            paid: true,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        emit_coverage(solbox)
+    }
+
+    // Burns loyalty tokens; activating again while a boost runs extends it.
+    // The bounds protect the buyer from a reconfiguration landing first
+    pub fn activate_boost(
+        ctx: Context<ActivateBoost>,
+        max_tokens_in: u64,
+        min_duration: i64
+    ) -> Result<()> {
+        let solbox = &ctx.accounts.solbox;
+        require!(solbox.boost_price > 0, CustomError::BoostsDisabled);
+        require!(
+            solbox.boost_price <= max_tokens_in && solbox.boost_duration >= min_duration,
+            CustomError::SlippageExceeded
+        );
+        
+        token::burn(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Burn {
+                    mint: ctx.accounts.loyalty_mint.to_account_info(),
+                    from: ctx.accounts.token_account.to_account_info(),
+                    authority: ctx.accounts.wallet.to_account_info(),
+                },
+            ),
+            solbox.boost_price,
+        )?;
+        
+        let now = unix_now(solbox)?;
+        let boost = &mut ctx.accounts.boost;
+        if boost.solbox == Pubkey::default() {
+            boost.account_type = ACCOUNT_TYPE_BOOST;
+            boost.account_version = BOOST_VERSION;
+            boost.solbox = solbox.key();
+            boost.wallet = ctx.accounts.wallet.key();
+            boost.bump = ctx.bumps.boost;
+        }
+        let start = boost.expires_at.max(now);
+        boost.bonus_bps = solbox.boost_bps;
+        boost.expires_at = start
+            .checked_add(solbox.boost_duration)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        
+        emit!(BoostActivatedEvent {
+            wallet: boost.wallet,
+            tokens_burned: solbox.boost_price,
+            bonus_bps: boost.bonus_bps,
+            expires_at: boost.expires_at,
+            timestamp: now,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Prizes are escrowed in the Contest PDA for the whole contest
+    pub fn create_contest(
+        ctx: Context<CreateContest>,
+        contest_id: u64,
+        start: i64,
+        end: i64,
+        metric: ContestMetric,
+        prizes: Vec<u64>
+    ) -> Result<()> {
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == ctx.accounts.solbox.owner,
+            CustomError::Unauthorized
+        );
+        
+        // Entries close at the start, so it must leave time to enter
+        let now = unix_now(&ctx.accounts.solbox)?;
+        require!(
+            start > now
+                && end > start
+                && !prizes.is_empty()
+                && prizes.len() <= MAX_CONTEST_PRIZES
+                && prizes.iter().all(|prize| *prize > 0),
+            CustomError::InvalidContest
+        );
+        let prize_pool = prizes
+            .iter()
+            .try_fold(0u64, |total, prize| total.checked_add(*prize))
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        
+        let contest = &mut ctx.accounts.contest;
+        contest.account_type = ACCOUNT_TYPE_CONTEST;
+        contest.account_version = CONTEST_VERSION;
+        contest.solbox = ctx.accounts.solbox.key();
+        contest.contest_id = contest_id;
+        contest.creator = ctx.accounts.admin.key();
+        contest.start = start;
+        contest.end = end;
+        contest.metric = metric;
+        contest.prizes = prizes;
+        contest.entries = 0;
+        contest.finalized = false;
+        contest.winners = Vec::new();
+        contest.bump = ctx.bumps.contest;
+        
+        invoke(
+            &system_instruction::transfer(
+                ctx.accounts.admin.key,
+                &contest.key(),
+                prize_pool
+            ),
+            &[
+                ctx.accounts.admin.to_account_info(),
+                contest.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+            ],
+        )?;
+        
+        emit!(ContestCreatedEvent {
+            contest: contest.key(),
+            contest_id,
+            start,
+            end,
+            metric,
+            prize_pool,
+            timestamp: now,
+            brand_code: ctx.accounts.solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Snapshots the counter the contest ranks by; scores are its growth
+    // from here until finalization
+    pub fn enter_contest(ctx: Context<EnterContest>) -> Result<()> {
+        let contest = &mut ctx.accounts.contest;
+        require!(
+            unix_now(&ctx.accounts.solbox)? < contest.start,
+            CustomError::ContestEntriesClosed
+        );
+        require!(
+            (contest.entries as usize) < MAX_CONTEST_ENTRIES,
+            CustomError::ContestFull
+        );
+        
+        let entry = &mut ctx.accounts.entry;
+        entry.account_type = ACCOUNT_TYPE_CONTEST_ENTRY;
+        entry.account_version = CONTEST_ENTRY_VERSION;
+        entry.contest = contest.key();
+        entry.wallet = ctx.accounts.wallet.key();
+        entry.index = contest.entries;
+        entry.baseline = contest_metric(contest.metric, &ctx.accounts.user_account);
+        entry.bump = ctx.bumps.entry;
+        
+        contest.entries += 1;
+        
+        Ok(())
+    }
+
+    // Permissionless once the contest has ended. Every entry is passed in
+    // remaining_accounts followed by its User PDA; ties go to the earlier
+    // entry and unawarded prizes return to the creator
+    pub fn finalize_contest<'info>(
+        ctx: Context<'_, '_, 'info, 'info, FinalizeContest<'info>>
+    ) -> Result<()> {
+        let contest = &mut ctx.accounts.contest;
+        let now = unix_now(&ctx.accounts.solbox)?;
+        require!(now >= contest.end, CustomError::ContestNotEnded);
+        require!(!contest.finalized, CustomError::ContestFinalized);
+        require!(
+            ctx.remaining_accounts.len() == contest.entries as usize * 2,
+            CustomError::ContestEntriesMismatch
+        );
+        
+        let contest_key = contest.key();
+        let mut scores: Vec<(u64, u32, Pubkey)> = Vec::new();
+        for pair in ctx.remaining_accounts.chunks(2) {
+            let entry = Account::<ContestEntry>::try_from(&pair[0])?;
+            require!(entry.contest == contest_key, CustomError::ContestEntriesMismatch);
+            require!(
+                !scores.iter().any(|(_, index, _)| *index == entry.index),
+                CustomError::ContestEntriesMismatch
+            );
+            let info = find_user_account(&pair[1..], contest.solbox, entry.wallet)?;
+            let user = Account::<User>::try_from(info)?;
+            let score = contest_metric(contest.metric, &user).saturating_sub(entry.baseline);
+            scores.push((score, entry.index, entry.wallet));
+        }
+        scores.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        
+        let mut winners = Vec::new();
+        let mut awarded = 0u64;
+        let mut unawarded = 0u64;
+        for (rank, prize) in contest.prizes.iter().enumerate() {
+            match scores.get(rank).filter(|(score, _, _)| *score > 0) {
+                Some((score, _, wallet)) => {
+                    winners.push(ContestWinner {
+                        wallet: *wallet,
+                        score: *score,
+                        prize: *prize,
+                        claimed: false,
+                    });
+                    awarded += prize;
+                }
+                None => unawarded += prize,
+            }
+        }
+        contest.winners = winners;
+        contest.finalized = true;
+        
+        if unawarded > 0 {
+            transfer_from_vault(
+                &contest.to_account_info(),
+                &ctx.accounts.creator.to_account_info(),
+                unawarded
+            )?;
+        }
+        
+        emit!(ContestFinalizedEvent {
+            contest: contest_key,
+            winners: contest.winners.len() as u32,
+            awarded,
+            returned: unawarded,
+            timestamp: now,
+            brand_code: ctx.accounts.solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    pub fn claim_contest_prize(ctx: Context<ClaimContestPrize>) -> Result<()> {
+        let contest = &mut ctx.accounts.contest;
+        let wallet = ctx.accounts.wallet.key();
+        let winner = contest.winners
+            .iter_mut()
+            .find(|winner| winner.wallet == wallet && !winner.claimed)
+            .ok_or(CustomError::NothingToClaim)?;
+        winner.claimed = true;
+        let prize = winner.prize;
+        
+        transfer_from_vault(
+            &contest.to_account_info(),
+            &ctx.accounts.wallet.to_account_info(),
+            prize
+        )?;
+        
+        emit!(ContestPrizeClaimedEvent {
+            contest: contest.key(),
+            wallet,
+            prize,
+            timestamp: Clock::get()?.unix_timestamp,
+            brand_code: ctx.accounts.solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Notices addressed to the default pubkey are for everyone; read state
+    // of global notices is kept by the frontend
+    pub fn post_notice(
+        ctx: Context<PostNotice>,
+        notice_id: u64,
+        recipient: Pubkey,
+        kind: u8,
+        message: String
+    ) -> Result<()> {
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == ctx.accounts.solbox.owner,
+            CustomError::Unauthorized
+        );
+        require!(
+            !message.is_empty() && message.len() <= MAX_NOTICE_LEN,
+            CustomError::InvalidNotice
+        );
+        
+        let now = unix_now(&ctx.accounts.solbox)?;
+        let notice = &mut ctx.accounts.notice;
+        notice.account_type = ACCOUNT_TYPE_NOTICE;
+        notice.account_version = NOTICE_VERSION;
+        notice.solbox = ctx.accounts.solbox.key();
+        notice.notice_id = notice_id;
+        notice.poster = ctx.accounts.admin.key();
+        notice.recipient = recipient;
+        notice.kind = kind;
+        notice.message = message;
+        notice.posted_at = now;
+        notice.read_at = 0;
+        notice.bump = ctx.bumps.notice;
+        
+        emit!(NoticePostedEvent {
+            notice: notice.key(),
+            notice_id,
+            recipient,
+            kind,
+            timestamp: now,
+            brand_code: ctx.accounts.solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Reset the wallet's unseen downline activity once a frontend has
+    // shown it
+    pub fn ack_events(ctx: Context<AckEvents>) -> Result<()> {
+        ctx.accounts.user_account.unseen_downline_events = 0;
+        Ok(())
+    }
+
+    pub fn mark_notice_read(ctx: Context<MarkNoticeRead>) -> Result<()> {
+        let notice = &mut ctx.accounts.notice;
+        require!(
+            notice.recipient != Pubkey::default()
+                && notice.recipient == ctx.accounts.recipient.key(),
+            CustomError::Unauthorized
+        );
+        if notice.read_at == 0 {
+            notice.read_at = Clock::get()?.unix_timestamp;
+        }
+        
+        Ok(())
+    }
+
+    // The admin can close any notice, the recipient only one they have
+    // read; rent goes back to the admin who posted it
+    pub fn close_notice(ctx: Context<CloseNotice>) -> Result<()> {
+        let notice = &ctx.accounts.notice;
+        let closer = ctx.accounts.closer.key();
+        require!(
+            closer == ctx.accounts.solbox.owner
+                || (closer == notice.recipient && notice.read_at != 0),
+            CustomError::Unauthorized
+        );
+        
+        Ok(())
+    }
+
+    // Replaces the guardian set and drops any recovery in progress
+    pub fn set_guardians(
+        ctx: Context<SetGuardians>,
+        guardians: Vec<Pubkey>,
+        threshold: u8
+    ) -> Result<()> {
+        let wallet = ctx.accounts.wallet.key();
+        require!(
+            threshold > 0
+                && threshold as usize <= guardians.len()
+                && guardians.len() <= MAX_GUARDIANS
+                && !guardians.contains(&wallet)
+                && guardians.iter().enumerate().all(|(i, guardian)| !guardians[..i].contains(guardian)),
+            CustomError::InvalidGuardians
+        );
+        
+        let recovery = &mut ctx.accounts.recovery;
+        recovery.account_type = ACCOUNT_TYPE_RECOVERY;
+        recovery.account_version = RECOVERY_VERSION;
+        recovery.solbox = ctx.accounts.solbox.key();
+        recovery.wallet = wallet;
+        recovery.guardians = guardians;
+        recovery.threshold = threshold;
+        recovery.pending_wallet = Pubkey::default();
+        recovery.approvals = 0;
+        recovery.recovery_eta = 0;
+        recovery.bump = ctx.bumps.recovery;
+        
+        Ok(())
+    }
+
+    // Guardians approve a specific new wallet; approving a different one
+    // restarts the vote. The timelock starts once the threshold is met
+    pub fn approve_recovery(ctx: Context<ApproveRecovery>, new_wallet: Pubkey) -> Result<()> {
+        let recovery = &mut ctx.accounts.recovery;
+        let guardian = recovery.guardians
+            .iter()
+            .position(|guardian| *guardian == ctx.accounts.guardian.key())
+            .ok_or(CustomError::Unauthorized)?;
+        require!(
+            new_wallet != Pubkey::default() && new_wallet != recovery.wallet,
+            CustomError::InvalidGuardians
+        );
+        
+        if recovery.pending_wallet != new_wallet {
+            recovery.pending_wallet = new_wallet;
+            recovery.approvals = 0;
+            recovery.recovery_eta = 0;
+        }
+        recovery.approvals |= 1 << guardian;
+        
+        let now = unix_now(&ctx.accounts.solbox)?;
+        if recovery.recovery_eta == 0 && recovery.approvals.count_ones() >= recovery.threshold as u32 {
+            recovery.recovery_eta = now
+                .checked_add(RECOVERY_TIMELOCK)
+                .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        }
+        
+        emit!(RecoveryApprovedEvent {
+            wallet: recovery.wallet,
+            guardian: ctx.accounts.guardian.key(),
+            new_wallet,
+            approvals: recovery.approvals.count_ones() as u8,
+            recovery_eta: recovery.recovery_eta,
+            timestamp: now,
+            brand_code: ctx.accounts.solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // The current wallet can veto a recovery during the timelock
+    pub fn cancel_recovery(ctx: Context<CancelRecovery>) -> Result<()> {
+        let recovery = &mut ctx.accounts.recovery;
+        require!(
+            recovery.pending_wallet != Pubkey::default(),
+            CustomError::NoPendingRecovery
+        );
+        recovery.pending_wallet = Pubkey::default();
+        recovery.approvals = 0;
+        recovery.recovery_eta = 0;
+        
+        Ok(())
+    }
+
+    // Moves the User PDA, its package, earnings and unclaimed yield to the
+    // new wallet and swaps the wallet in the referral tree. Receipts,
+    // boosts and contest entries stay with the old wallet
+    pub fn execute_recovery(ctx: Context<ExecuteRecovery>) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        let recovery = &ctx.accounts.recovery;
+        let now = unix_now(solbox)?;
+        require!(
+            recovery.pending_wallet != Pubkey::default() && recovery.recovery_eta != 0,
+            CustomError::NoPendingRecovery
+        );
+        require!(now >= recovery.recovery_eta, CustomError::TimelockNotElapsed);
+        require!(
+            !is_blacklisted(solbox, &ctx.accounts.blacklist_filter, &recovery.wallet),
+            CustomError::UserBlacklisted
+        );
+        
+        let old_wallet = recovery.wallet;
+        let new_wallet = ctx.accounts.new_wallet.key();
+        let mut moved = (*ctx.accounts.old_user_account).clone();
+        moved.key = new_wallet;
+        moved.bump = ctx.bumps.new_user_account;
+        moved.last_active_at = now;
+        ctx.accounts.new_user_account.set_inner(moved);
+        move_position(solbox, old_wallet, new_wallet);
+        
+        emit!(WalletRecoveredEvent {
+            old_wallet,
+            new_wallet,
+            timestamp: now,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // A default beneficiary clears the designation. Setting it again also
+    // counts as activity, as do purchases and yield claims
+    pub fn set_beneficiary(
+        ctx: Context<SetBeneficiary>,
+        beneficiary: Pubkey,
+        inactivity_period: i64
+    ) -> Result<()> {
+        let user_account = &mut ctx.accounts.user_account;
+        require!(
+            beneficiary == Pubkey::default()
+                || (beneficiary != user_account.key && inactivity_period >= MIN_INACTIVITY_PERIOD),
+            CustomError::InvalidBeneficiary
+        );
+        
+        let now = unix_now(&ctx.accounts.solbox)?;
+        user_account.beneficiary = beneficiary;
+        user_account.inactivity_period = if beneficiary == Pubkey::default() { 0 } else { inactivity_period };
+        user_account.last_active_at = now;
+        
+        emit!(BeneficiarySetEvent {
+            user: user_account.key,
+            beneficiary,
+            inactivity_period: user_account.inactivity_period,
+            timestamp: now,
+            brand_code: ctx.accounts.solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // After the inactivity period the beneficiary takes over the User PDA,
+    // with its position, package and unclaimed yield, like a recovery
+    pub fn claim_inheritance(ctx: Context<ClaimInheritance>) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        let old_user = &ctx.accounts.old_user_account;
+        let now = unix_now(solbox)?;
+        require!(
+            old_user.beneficiary != Pubkey::default()
+                && old_user.beneficiary == ctx.accounts.beneficiary.key(),
+            CustomError::Unauthorized
+        );
+        let inactive_until = old_user.last_active_at
+            .checked_add(old_user.inactivity_period)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        require!(now >= inactive_until, CustomError::UserStillActive);
+        require!(
+            !is_blacklisted(solbox, &ctx.accounts.blacklist_filter, &old_user.key),
+            CustomError::UserBlacklisted
+        );
+        
+        let old_wallet = old_user.key;
+        let new_wallet = ctx.accounts.beneficiary.key();
+        let mut moved = (**old_user).clone();
+        moved.key = new_wallet;
+        moved.bump = ctx.bumps.new_user_account;
+        moved.beneficiary = Pubkey::default();
+        moved.inactivity_period = 0;
+        moved.last_active_at = now;
+        ctx.accounts.new_user_account.set_inner(moved);
+        move_position(solbox, old_wallet, new_wallet);
+        
+        emit!(InheritanceClaimedEvent {
+            old_wallet,
+            beneficiary: new_wallet,
+            unclaimed_yield: ctx.accounts.new_user_account.unclaimed_yield,
+            timestamp: now,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Claim a display handle for leaderboards and genealogy views. The
+    // Handle PDA is seeded by the hash of the ASCII-lowercased handle, so
+    // two users can't hold handles differing only in case
+    pub fn register_handle(
+        ctx: Context<RegisterHandle>,
+        handle_hash: [u8; 32],
+        handle: String
+    ) -> Result<()> {
+        require!(
+            is_valid_handle(&handle) && handle_key(&handle) == handle_hash,
+            CustomError::InvalidHandle
+        );
+        let user_account = &mut ctx.accounts.user_account;
+        require!(
+            user_account.handle == Pubkey::default(),
+            CustomError::HandleAlreadySet
+        );
+        
+        let now = unix_now(&ctx.accounts.solbox)?;
+        let handle_account = &mut ctx.accounts.handle;
+        handle_account.account_type = ACCOUNT_TYPE_HANDLE;
+        handle_account.account_version = HANDLE_VERSION;
+        handle_account.solbox = ctx.accounts.solbox.key();
+        handle_account.handle_hash = handle_hash;
+        handle_account.handle = handle.clone();
+        handle_account.registered_at = now;
+        handle_account.bump = ctx.bumps.handle;
+        user_account.handle = handle_account.key();
+        
+        emit!(HandleEvent {
+            wallet: user_account.key,
+            handle,
+            action: HandleAction::Registered,
+            timestamp: now,
+            brand_code: ctx.accounts.solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Give up the handle so it can be registered again; the rent goes back
+    // to the wallet
+    pub fn release_handle(ctx: Context<ReleaseHandle>) -> Result<()> {
+        require!(
+            ctx.accounts.authority.key() == ctx.accounts.user_account.key,
+            CustomError::Unauthorized
+        );
+        clear_handle(ctx.accounts, HandleAction::Released)
+    }
+
+    // Moderation: take an abusive handle away from its user
+    pub fn revoke_handle(ctx: Context<ReleaseHandle>) -> Result<()> {
+        // Verify admin authority
+        require!(
+            ctx.accounts.authority.key() == ctx.accounts.solbox.owner,
+            CustomError::Unauthorized
+        );
+        clear_handle(ctx.accounts, HandleAction::Revoked)
+    }
+
+    // Ask for the wallet's data to be deleted. It stops taking purchases as
+    // buyer or referrer; process_deletion follows once nothing is owed
+    pub fn request_deletion(ctx: Context<RequestDeletion>) -> Result<()> {
+        let user_account = &mut ctx.accounts.user_account;
+        require!(user_account.deletion_requested_at == 0, CustomError::DeletionPending);
+        
+        let now = unix_now(&ctx.accounts.solbox)?;
+        user_account.deletion_requested_at = now;
+        
+        emit!(DeletionRequestedEvent {
+            user: user_account.key,
+            timestamp: now,
+            brand_code: ctx.accounts.solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Close the User PDA of a wallet that asked for deletion and put a
+    // tombstone in its place in the tree. The tombstone keeps the downline
+    // counts and earns for the bonus pool like a house position, so the
+    // payouts around it don't change. Everything owed to the wallet has to
+    // be paid out first, and none of the wallet's purchases or commissions
+    // can be in escrow, since receipts name wallets
+    pub fn process_deletion(ctx: Context<ProcessDeletion>) -> Result<()> {
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == ctx.accounts.solbox.owner,
+            CustomError::Unauthorized
+        );
+        let solbox = &mut ctx.accounts.solbox;
+        let user_account = &mut ctx.accounts.user_account;
+        require!(user_account.deletion_requested_at != 0, CustomError::DeletionNotRequested);
+        
+        // Dropping the package accrues its last yield
+        set_package(solbox, user_account, 0)?;
+        require!(
+            user_account.unclaimed_yield == 0
+                && user_account.residual_pending == 0
+                && user_account.residual_claimable == 0
+                && user_account.queued_payout == 0
+                && user_account.card_balance == 0
+                && user_account.position_mint == Pubkey::default()
+                && user_account.handle == Pubkey::default()
+                && user_account.pending_receipts == 0,
+            CustomError::ObligationsOutstanding
+        );
+        
+        let wallet = user_account.key;
+        let tombstone = tombstone_key(&solbox.key(), solbox.tombstone_count);
+        let tombstone_account = &mut ctx.accounts.tombstone_account;
+        init_user(solbox, tombstone_account, tombstone, ctx.bumps.tombstone_account)?;
+        tombstone_account.downline_counts = user_account.downline_counts.clone();
+        move_position(solbox, wallet, tombstone);
+        solbox.tombstone_count = solbox.tombstone_count
+            .checked_add(1)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        
+        emit!(UserDeletedEvent {
+            tombstone,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Posts the Merkle root over every user's statement for a finished
+    // epoch. The account is never rewritten, so exported statements stay
+    // checkable against what was anchored
+    pub fn anchor_statements(
+        ctx: Context<AnchorStatements>,
+        epoch: u64,
+        root: [u8; 32],
+        users: u32
+    ) -> Result<()> {
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == ctx.accounts.solbox.owner,
+            CustomError::Unauthorized
+        );
+        require!(epoch < current_epoch(&ctx.accounts.solbox)?, CustomError::EpochNotClosed);
+        
+        let now = unix_now(&ctx.accounts.solbox)?;
+        let statements = &mut ctx.accounts.statements;
+        statements.account_type = ACCOUNT_TYPE_STATEMENTS;
+        statements.account_version = STATEMENTS_VERSION;
+        statements.solbox = ctx.accounts.solbox.key();
+        statements.epoch = epoch;
+        statements.root = root;
+        statements.users = users;
+        statements.anchored_at = now;
+        statements.bump = ctx.bumps.statements;
+        
+        emit!(StatementsAnchoredEvent {
+            epoch,
+            root,
+            users,
+            timestamp: now,
+            brand_code: ctx.accounts.solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Permissionless crank hashing the referral tree into this epoch's
+    // checkpoint, up to MAX_CHECKPOINT_BATCH relationships per call from
+    // `cursor`. Cursor 0 starts the run; it completes once it catches up
+    // with the tree, and relationships appended meanwhile are included.
+    // Anything else rewriting the tree voids the run, which then restarts
+    pub fn checkpoint_tree(ctx: Context<CheckpointTree>, cursor: u64) -> Result<()> {
+        let solbox = &ctx.accounts.solbox;
+        let epoch = current_epoch(solbox)?;
+        let checkpoint = &mut ctx.accounts.tree_checkpoint;
+        if cursor == 0 {
+            require!(
+                checkpoint.root_epoch != epoch || checkpoint.checkpointed_at == 0,
+                CustomError::TreeCheckpointed
+            );
+            
+            // Anyone can crank, so a run with progress only restarts once
+            // it's stale: a new epoch, or a rewrite of the tree
+            require!(
+                checkpoint.cursor == 0
+                    || checkpoint.epoch != epoch
+                    || checkpoint.tree_rewrites != solbox.tree_rewrites,
+                CustomError::CheckpointInProgress
+            );
+            checkpoint.account_type = ACCOUNT_TYPE_TREE_CHECKPOINT;
+            checkpoint.account_version = TREE_CHECKPOINT_VERSION;
+            checkpoint.solbox = solbox.key();
+            checkpoint.epoch = epoch;
+            checkpoint.cursor = 0;
+            checkpoint.frontier = [[0; 32]; TREE_FRONTIER_DEPTH];
+            checkpoint.tree_rewrites = solbox.tree_rewrites;
+            checkpoint.bump = ctx.bumps.tree_checkpoint;
+        } else {
+            require!(
+                checkpoint.epoch == epoch
+                    && checkpoint.tree_rewrites == solbox.tree_rewrites
+                    && (checkpoint.root_epoch != epoch || checkpoint.checkpointed_at == 0),
+                CustomError::CheckpointStale
+            );
+            require!(cursor == checkpoint.cursor, CustomError::CheckpointCursorMismatch);
+        }
+        
+        let relationships = &solbox.referral_relationships;
+        let end = relationships.len().min(cursor as usize + MAX_CHECKPOINT_BATCH);
+        for (index, relationship) in relationships.iter().enumerate().take(end).skip(cursor as usize) {
+            append_leaf(&mut checkpoint.frontier, index as u64, tree_leaf(index as u64, relationship));
+        }
+        checkpoint.cursor = end as u64;
+        
+        // Caught up: the root is this epoch's checkpoint
+        if end == relationships.len() {
+            let now = unix_now(solbox)?;
+            checkpoint.root = frontier_root(&checkpoint.frontier, checkpoint.cursor);
+            checkpoint.root_epoch = epoch;
+            checkpoint.relationships = checkpoint.cursor;
+            checkpoint.checkpointed_at = now;
+            
+            emit!(TreeCheckpointEvent {
+                epoch,
+                root: checkpoint.root,
+                relationships: checkpoint.relationships,
+                timestamp: now,
+                brand_code: solbox.brand_code,
+            });
+        }
+        
+        Ok(())
+    }
+
+    // Read-only check of an exported statement against its epoch's root
+    pub fn verify_statement(
+        ctx: Context<VerifyStatement>,
+        statement: Statement,
+        proof: Vec<[u8; 32]>
+    ) -> Result<()> {
+        let statements = &ctx.accounts.statements;
+        require!(statement.epoch == statements.epoch, CustomError::StatementMismatch);
+        require!(
+            verify_proof(statement.leaf(), &proof, statements.root),
+            CustomError::StatementMismatch
+        );
+        Ok(())
+    }
+
+    // Cannot run while paused, so a compromised admin key cannot swap in
+    // its own guardians mid-incident. An empty set with threshold 0 lets
+    // the admin unpause alone again
+    pub fn set_pause_guardians(
+        ctx: Context<SetPauseGuardians>,
+        guardians: Vec<Pubkey>,
+        threshold: u8
+    ) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == solbox.owner,
+            CustomError::Unauthorized
+        );
+        require!(!solbox.paused, CustomError::ContractPaused);
+        require!(
+            (threshold as usize) <= guardians.len()
+                && (threshold == 0) == guardians.is_empty()
+                && guardians.len() <= MAX_GUARDIANS
+                && guardians.iter().enumerate().all(|(i, guardian)| !guardians[..i].contains(guardian)),
+            CustomError::InvalidGuardians
+        );
+        
+        solbox.pause_guardians = guardians.clone();
+        solbox.unpause_threshold = threshold;
+        
+        emit!(PauseGuardiansSetEvent {
+            admin: *ctx.accounts.admin.key,
+            guardians,
+            threshold,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // A guardian's approval to reopen the current pause; approvals from
+    // earlier pauses no longer count
+    pub fn approve_unpause(ctx: Context<ApproveUnpause>) -> Result<()> {
+        let solbox = &ctx.accounts.solbox;
+        let guardian = ctx.accounts.guardian.key();
+        require!(solbox.paused, CustomError::ContractNotPaused);
+        require!(solbox.pause_guardians.contains(&guardian), CustomError::Unauthorized);
+        
+        let now = unix_now(solbox)?;
+        let approval = &mut ctx.accounts.approval;
+        approval.account_type = ACCOUNT_TYPE_UNPAUSE_APPROVAL;
+        approval.account_version = UNPAUSE_APPROVAL_VERSION;
+        approval.solbox = solbox.key();
+        approval.guardian = guardian;
+        approval.pause_count = solbox.pause_count;
+        approval.approved_at = now;
+        approval.bump = ctx.bumps.approval;
+        
+        emit!(UnpauseApprovedEvent {
+            guardian,
+            pause_count: solbox.pause_count,
+            timestamp: now,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Remaining accounts are the guardians' UnpauseApproval PDAs; anyone
+    // can submit them once enough are in
+    pub fn execute_unpause<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ExecuteUnpause<'info>>
+    ) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        require!(solbox.paused, CustomError::ContractNotPaused);
+        require!(solbox.unpause_threshold > 0, CustomError::InvalidGuardians);
+        
+        let solbox_key = solbox.key();
+        let mut approved: Vec<Pubkey> = Vec::new();
+        for info in ctx.remaining_accounts {
+            let approval = Account::<UnpauseApproval>::try_from(info)?;
+            if approval.solbox == solbox_key
+                && approval.pause_count == solbox.pause_count
+                && solbox.pause_guardians.contains(&approval.guardian)
+                && !approved.contains(&approval.guardian)
+            {
+                approved.push(approval.guardian);
+            }
+        }
+        require!(
+            approved.len() >= solbox.unpause_threshold as usize,
+            CustomError::UnpauseQuorumRequired
+        );
+        
+        solbox.paused = false;
+        
+        emit!(PauseEvent {
+            admin: *ctx.accounts.caller.key,
+            paused: false,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Sandbox only: moves the program's clock forward so vesting, expiry
+    // and epoch flows can be exercised without waiting. Time never goes back
+    pub fn warp_time_offset(
+        ctx: Context<WarpTimeOffset>,
+        seconds: i64,
+        epochs: u64
+    ) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == solbox.owner,
+            CustomError::Unauthorized
+        );
+        require!(solbox.sandbox, CustomError::NotSandbox);
+        require!(seconds >= 0, CustomError::InvalidTimeWarp);
+        
+        solbox.time_offset = solbox.time_offset
+            .checked_add(seconds)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        solbox.epoch_offset = solbox.epoch_offset
+            .checked_add(epochs)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        
+        emit!(TimeWarpedEvent {
+            admin: *ctx.accounts.admin.key,
+            time_offset: solbox.time_offset,
+            epoch_offset: solbox.epoch_offset,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Create the SolBox's address lookup table, owned by the
+    // lookup_authority PDA. `recent_slot` is a recent slot the table
+    // address is derived from
+    pub fn create_lookup_table(ctx: Context<CreateLookupTable>, recent_slot: u64) -> Result<()> {
+        let solbox = &ctx.accounts.solbox;
+        
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == solbox.owner,
+            CustomError::Unauthorized
+        );
+        require!(
+            solbox.lookup_table == Pubkey::default(),
+            CustomError::LookupTableExists
+        );
+        
+        let (instruction, table) = address_lookup_table::instruction::create_lookup_table(
+            ctx.accounts.lookup_authority.key(),
+            ctx.accounts.admin.key(),
+            recent_slot
+        );
+        require!(
+            table == ctx.accounts.lookup_table.key(),
+            CustomError::InvalidLookupTable
+        );
+        invoke(
+            &instruction,
+            &[
+                ctx.accounts.lookup_table.to_account_info(),
+                ctx.accounts.lookup_authority.to_account_info(),
+                ctx.accounts.admin.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+                ctx.accounts.address_lookup_table_program.to_account_info(),
+            ],
+        )?;
+        
+        let solbox = &mut ctx.accounts.solbox;
+        solbox.lookup_table = table;
+        
+        emit!(LookupTableEvent {
+            admin: *ctx.accounts.admin.key,
+            table,
+            added: 0,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Add hot accounts (the SolBox, pools, top uplines) to the lookup
+    // table; client::hot_accounts lists the usual ones. The admin pays the
+    // rent for the extra space
+    pub fn extend_lookup_table(ctx: Context<ExtendLookupTable>, addresses: Vec<Pubkey>) -> Result<()> {
+        let solbox = &ctx.accounts.solbox;
+        
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == solbox.owner,
+            CustomError::Unauthorized
+        );
+        require!(
+            !addresses.is_empty() && addresses.len() <= MAX_LOOKUP_EXTEND,
+            CustomError::InvalidLookupTable
+        );
+        
+        let added = addresses.len() as u32;
+        let solbox_key = solbox.key();
+        let signer_seeds: &[&[u8]] = &[
+            b"lookup_authority",
+            solbox_key.as_ref(),
+            &[ctx.bumps.lookup_authority],
+        ];
+        invoke_signed(
+            &address_lookup_table::instruction::extend_lookup_table(
+                ctx.accounts.lookup_table.key(),
+                ctx.accounts.lookup_authority.key(),
+                Some(ctx.accounts.admin.key()),
+                addresses
+            ),
+            &[
+                ctx.accounts.lookup_table.to_account_info(),
+                ctx.accounts.lookup_authority.to_account_info(),
+                ctx.accounts.admin.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+                ctx.accounts.address_lookup_table_program.to_account_info(),
+            ],
+            &[signer_seeds],
+        )?;
+        
+        emit!(LookupTableEvent {
+            admin: *ctx.accounts.admin.key,
+            table: ctx.accounts.lookup_table.key(),
+            added,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Describe a package from the menu for wallets and storefronts: a
+    // category and a URI to the card artwork and terms
+    pub fn create_package(
+        ctx: Context<CreatePackage>,
+        amount: u64,
+        category: u8,
+        metadata_uri: String
+    ) -> Result<()> {
+        // Verify admin authority
+        let solbox = &ctx.accounts.solbox;
+        require!(
+            ctx.accounts.admin.key() == solbox.owner,
+            CustomError::Unauthorized
+        );
+        require!(
+            solbox.config.valid_amounts.contains(&amount),
+            CustomError::InvalidAmount
+        );
+        require!(
+            !metadata_uri.is_empty() && metadata_uri.len() <= MAX_METADATA_URI_LEN,
+            CustomError::InvalidMetadataUri
+        );
+        
+        let package = &mut ctx.accounts.package;
+        package.account_type = ACCOUNT_TYPE_PACKAGE;
+        package.account_version = PACKAGE_VERSION;
+        package.solbox = solbox.key();
+        package.amount = amount;
+        package.category = category;
+        package.metadata_uri = metadata_uri.clone();
+        package.limited = false;
+        package.remaining = 0;
+        package.low_stock_thresholds = [0; MAX_LOW_STOCK_THRESHOLDS];
+        package.commit_until_slot = 0;
+        package.reveal_until_slot = 0;
+        package.drop_id = 0;
+        package.drop_commits = 0;
+        package.drop_cursor = 0;
+        package.drop_unallocated = 0;
+        package.drop_reserved = 0;
+        package.bump = ctx.bumps.package;
+        
+        emit!(PackageMetadataEvent {
+            admin: *ctx.accounts.admin.key,
+            package: package.key(),
+            amount,
+            category,
+            metadata_uri,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    pub fn update_package(
+        ctx: Context<UpdatePackage>,
+        category: u8,
+        metadata_uri: String
+    ) -> Result<()> {
+        // Verify admin authority
+        let solbox = &ctx.accounts.solbox;
+        require!(
+            ctx.accounts.admin.key() == solbox.owner,
+            CustomError::Unauthorized
+        );
+        require!(
+            !metadata_uri.is_empty() && metadata_uri.len() <= MAX_METADATA_URI_LEN,
+            CustomError::InvalidMetadataUri
+        );
+        
+        // Cards already sold resolve to the new metadata too
+        let package = &mut ctx.accounts.package;
+        package.category = category;
+        package.metadata_uri = metadata_uri.clone();
+        
+        emit!(PackageMetadataEvent {
+            admin: *ctx.accounts.admin.key,
+            package: package.key(),
+            amount: package.amount,
+            category,
+            metadata_uri,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Limit the cards a package may still sell, or lift the limit with
+    // None. Restocking sets the new remaining supply; LowStockEvent fires
+    // as a sale brings it down to one of the thresholds
+    pub fn set_package_supply(
+        ctx: Context<SetPackageSupply>,
+        remaining: Option<u64>,
+        low_stock_thresholds: [u64; MAX_LOW_STOCK_THRESHOLDS]
+    ) -> Result<()> {
+        // Verify admin authority
+        let solbox = &mut ctx.accounts.solbox;
+        require!(
+            ctx.accounts.admin.key() == solbox.owner,
+            CustomError::Unauthorized
+        );
+        
+        // Keep count of limited packages, so buys know to pass them
+        let package = &mut ctx.accounts.package;
+        let limited = remaining.is_some();
+        if limited != package.limited {
+            solbox.limited_packages = if limited {
+                solbox.limited_packages.checked_add(1)
+            } else {
+                solbox.limited_packages.checked_sub(1)
+            }
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        }
+        package.limited = limited;
+        package.remaining = remaining.unwrap_or(0);
+        package.low_stock_thresholds = low_stock_thresholds;
+        
+        Ok(())
+    }
+
+    // Open a commit-reveal drop of a limited package: wallets commit to it
+    // up to `commit_until_slot` and reveal up to `reveal_until_slot`, then
+    // allocate_drop hands out the supply in commit order. 0 ends the drop
+    // and sells to anyone again. Either way the commits of an earlier drop
+    // go stale
+    pub fn schedule_drop(
+        ctx: Context<ScheduleDrop>,
+        commit_until_slot: u64,
+        reveal_until_slot: u64
+    ) -> Result<()> {
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == ctx.accounts.solbox.owner,
+            CustomError::Unauthorized
+        );
+        let package = &mut ctx.accounts.package;
+        require!(
+            package.limited || commit_until_slot == 0,
+            CustomError::PackageNotLimited
+        );
+        require!(
+            if commit_until_slot == 0 { reveal_until_slot == 0 } else { reveal_until_slot > commit_until_slot },
+            CustomError::InvalidDropWindow
+        );
+        package.drop_id = package.drop_id
+            .checked_add(1)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        package.commit_until_slot = commit_until_slot;
+        package.reveal_until_slot = reveal_until_slot;
+        package.drop_commits = 0;
+        package.drop_cursor = 0;
+        package.drop_unallocated = package.remaining;
+        package.drop_reserved = 0;
+        Ok(())
+    }
+
+    // Commit to buying from a drop with the hash of the package, the
+    // wallet, the drop and a hidden quantity and salt, see
+    // `drop_commitment`. Commits are numbered in the order they land
+    pub fn commit_purchase(ctx: Context<CommitPurchase>, commitment: [u8; 32]) -> Result<()> {
+        let package = &mut ctx.accounts.package;
+        let slot = Clock::get()?.slot;
+        require!(!ctx.accounts.solbox.paused, CustomError::ContractPaused);
+        require!(
+            package.commit_until_slot > 0 && slot <= package.commit_until_slot,
+            CustomError::CommitWindowClosed
+        );
+        let commit = &mut ctx.accounts.drop_commit;
+        commit.account_type = ACCOUNT_TYPE_DROP_COMMIT;
+        commit.account_version = DROP_COMMIT_VERSION;
+        commit.solbox = ctx.accounts.solbox.key();
+        commit.package = package.key();
+        commit.wallet = ctx.accounts.wallet.key();
+        commit.drop_id = package.drop_id;
+        commit.order = package.drop_commits;
+        commit.commitment = commitment;
+        commit.committed_slot = slot;
+        commit.revealed = false;
+        commit.quantity = 0;
+        commit.allocated = 0;
+        commit.bump = ctx.bumps.drop_commit;
+        package.drop_commits = package.drop_commits
+            .checked_add(1)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        Ok(())
+    }
+
+    // Reveal the quantity and salt of a commit in the reveal window, after
+    // the commit window has passed
+    pub fn reveal_purchase(ctx: Context<RevealPurchase>, quantity: u64, salt: [u8; 32]) -> Result<()> {
+        let package = &ctx.accounts.package;
+        let slot = Clock::get()?.slot;
+        require!(slot > package.commit_until_slot, CustomError::CommitWindowOpen);
+        require!(slot <= package.reveal_until_slot, CustomError::RevealWindowClosed);
+        require!(
+            (1..=MAX_DROP_QUANTITY).contains(&quantity),
+            CustomError::InvalidAmount
+        );
+        let commit = &mut ctx.accounts.drop_commit;
+        let wallet = ctx.accounts.wallet.key();
+        require!(
+            drop_commitment(&package.key(), &wallet, package.drop_id, quantity, &salt) == commit.commitment,
+            CustomError::CommitMismatch
+        );
+        commit.revealed = true;
+        commit.quantity = quantity;
+        Ok(())
+    }
+
+    // Permissionless once the reveal window has passed: walks the drop's
+    // commits, passed in remaining_accounts in commit order from the
+    // cursor, and gives each revealed one its quantity while supply lasts.
+    // Commits never revealed get nothing
+    pub fn allocate_drop<'info>(ctx: Context<'_, '_, 'info, 'info, AllocateDrop<'info>>) -> Result<()> {
+        let package = &mut ctx.accounts.package;
+        require!(
+            package.commit_until_slot > 0 && Clock::get()?.slot > package.reveal_until_slot,
+            CustomError::RevealWindowOpen
+        );
+        require!(
+            ctx.remaining_accounts.len() <= MAX_DROP_ALLOCATION_BATCH,
+            CustomError::AllocationBatchTooLarge
+        );
+        
+        for info in ctx.remaining_accounts.iter() {
+            let mut commit = Account::<DropCommit>::try_from(info)?;
+            require!(
+                commit.package == package.key()
+                    && commit.drop_id == package.drop_id
+                    && commit.order == package.drop_cursor,
+                CustomError::InvalidDropCommit
+            );
+            let allocated = if commit.revealed {
+                commit.quantity.min(package.drop_unallocated)
+            } else {
+                0
+            };
+            package.drop_unallocated -= allocated;
+            package.drop_reserved = package.drop_reserved
+                .checked_add(allocated)
+                .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+            package.drop_cursor += 1;
+            commit.allocated = allocated;
+            commit.exit(&crate::ID)?;
+        }
+        Ok(())
+    }
+
+    // Permissionless; closes a commit that can't buy any more back to its
+    // wallet: one of an earlier or ended drop, one the allocation passed
+    // over, or one whose package sold out
+    pub fn close_drop_commit(ctx: Context<CloseDropCommit>) -> Result<()> {
+        let package = &mut ctx.accounts.package;
+        let commit = &ctx.accounts.drop_commit;
+        let current = package.commit_until_slot > 0 && commit.drop_id == package.drop_id;
+        require!(
+            !current
+                || (commit.order < package.drop_cursor && commit.allocated == 0)
+                || package.remaining == 0,
+            CustomError::CommitInUse
+        );
+        if current {
+            package.drop_reserved = package.drop_reserved.saturating_sub(commit.allocated);
+        }
+        Ok(())
+    }
+
+    // Give a package its card mint: a Token-2022 mint without decimals,
+    // one unit per card, with the Package PDA as mint authority
+    pub fn create_card_mint(ctx: Context<CreateCardMint>) -> Result<()> {
+        // Verify admin authority
+        let solbox = &ctx.accounts.solbox;
+        require!(
+            ctx.accounts.admin.key() == solbox.owner,
+            CustomError::Unauthorized
+        );
+        
+        emit!(CardEvent {
+            card_mint: ctx.accounts.card_mint.key(),
+            wallet: ctx.accounts.admin.key(),
+            amount: ctx.accounts.package.amount,
+            action: CardAction::MintCreated,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Mint the card for a purchase, once, to any token account of the
+    // package's card mint so a buyer can send it as a gift. Issued cards
+    // can no longer be refunded
+    pub fn issue_card(ctx: Context<IssueCard>) -> Result<()> {
+        let solbox = &ctx.accounts.solbox;
+        let receipt = &mut ctx.accounts.receipt;
+        require!(
+            ctx.accounts.buyer.key() == receipt.buyer,
+            CustomError::Unauthorized
+        );
+        require!(
+            receipt.status != ReceiptStatus::Cancelled,
+            CustomError::ReceiptNotRefundable
+        );
+        require!(!receipt.card_issued, CustomError::CardIssued);
+        receipt.card_issued = true;
+        
+        let package = &ctx.accounts.package;
+        let solbox_key = solbox.key();
+        let amount_bytes = package.amount.to_le_bytes();
+        let seeds: &[&[u8]] = &[b"package", solbox_key.as_ref(), &amount_bytes, &[package.bump]];
+        token_interface::mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token_interface::MintTo {
+                    mint: ctx.accounts.card_mint.to_account_info(),
+                    to: ctx.accounts.card_account.to_account_info(),
+                    authority: package.to_account_info(),
+                },
+                &[seeds],
+            ),
+            1,
+        )?;
+        
+        emit!(CardEvent {
+            card_mint: ctx.accounts.card_mint.key(),
+            wallet: ctx.accounts.card_account.owner,
+            amount: package.amount,
+            action: CardAction::Issued,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Burn one card to take up its package, which has to be larger than
+    // the holder's current one
+    pub fn redeem_card(ctx: Context<RedeemCard>) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        require!(!solbox.paused, CustomError::ContractPaused);
+        let amount = ctx.accounts.package.amount;
+        require!(
+            amount > ctx.accounts.user_account.current_package,
+            CustomError::InvalidUpgrade
+        );
+        
+        token_interface::burn(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                token_interface::Burn {
+                    mint: ctx.accounts.card_mint.to_account_info(),
+                    from: ctx.accounts.card_account.to_account_info(),
+                    authority: ctx.accounts.holder.to_account_info(),
+                },
+            ),
+            1,
+        )?;
+        
+        set_package(solbox, &mut ctx.accounts.user_account, amount)?;
+        
+        emit!(CardEvent {
+            card_mint: ctx.accounts.card_mint.key(),
+            wallet: ctx.accounts.holder.key(),
+            amount,
+            action: CardAction::Redeemed,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Force a stolen or phished card to the wallet an appeal found it
+    // belongs to. Card mints name their package as permanent delegate, so
+    // the current holder doesn't sign; the reason is logged with every
+    // forced transfer
+    pub fn claw_back_card(ctx: Context<ClawBackCard>, reason: String) -> Result<()> {
+        // Verify admin authority
+        let solbox = &ctx.accounts.solbox;
+        require!(
+            ctx.accounts.admin.key() == solbox.owner,
+            CustomError::Unauthorized
+        );
+        require!(
+            reason.len() <= MAX_CLAWBACK_REASON_LEN,
+            CustomError::ClawbackReasonTooLong
+        );
+        let units = ctx.accounts.from.amount;
+        require!(units > 0, CustomError::InvalidAmount);
+        
+        let package = &ctx.accounts.package;
+        let solbox_key = solbox.key();
+        let amount_bytes = package.amount.to_le_bytes();
+        let seeds: &[&[u8]] = &[b"package", solbox_key.as_ref(), &amount_bytes, &[package.bump]];
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token_interface::TransferChecked {
+                    from: ctx.accounts.from.to_account_info(),
+                    mint: ctx.accounts.card_mint.to_account_info(),
+                    to: ctx.accounts.to.to_account_info(),
+                    authority: package.to_account_info(),
+                },
+                &[seeds],
+            ),
+            units,
+            0,
+        )?;
+        
+        emit!(CardClawbackEvent {
+            admin: ctx.accounts.admin.key(),
+            card_mint: ctx.accounts.card_mint.key(),
+            from: ctx.accounts.from.owner,
+            to: ctx.accounts.to.owner,
+            units,
+            reason,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Create the position token of a support tier: a non-transferable
+    // Token-2022 mint that is its own mint authority and permanent
+    // delegate, so the program can both mint and burn it
+    pub fn create_tier_mint(ctx: Context<CreateTierMint>, tier: u8) -> Result<()> {
+        // Verify admin authority
+        let solbox = &ctx.accounts.solbox;
+        require!(
+            ctx.accounts.admin.key() == solbox.owner,
+            CustomError::Unauthorized
+        );
+        require!(
+            tier > 0 && tier as usize <= SUPPORT_TIERS,
+            CustomError::InvalidSupportTiers
+        );
+        
+        let mint = ctx.accounts.tier_mint.to_account_info();
+        let token_program = ctx.accounts.token_program.to_account_info();
+        let solbox_key = solbox.key();
+        let seeds: &[&[u8]] = &[b"tier_mint", solbox_key.as_ref(), &[tier], &[ctx.bumps.tier_mint]];
+        let extensions = vec![
+            token_interface::spl_token_2022::extension::ExtensionType::NonTransferable,
+            token_interface::spl_token_2022::extension::ExtensionType::PermanentDelegate,
+        ];
+        let space = token_interface::find_mint_account_size(Some(&extensions))?;
+        invoke_signed(
+            &system_instruction::create_account(
+                ctx.accounts.admin.key,
+                mint.key,
+                Rent::get()?.minimum_balance(space),
+                space as u64,
+                token_program.key
+            ),
+            &[
+                ctx.accounts.admin.to_account_info(),
+                mint.clone(),
+                ctx.accounts.system_program.to_account_info(),
+            ],
+            &[seeds],
+        )?;
+        
+        // Extensions are initialized before the mint itself
+        token_interface::non_transferable_mint_initialize(CpiContext::new(
+            token_program.clone(),
+            token_interface::NonTransferableMintInitialize {
+                token_program_id: token_program.clone(),
+                mint: mint.clone(),
+            },
+        ))?;
+        token_interface::permanent_delegate_initialize(
+            CpiContext::new(
+                token_program.clone(),
+                token_interface::PermanentDelegateInitialize {
+                    token_program_id: token_program.clone(),
+                    mint: mint.clone(),
+                },
+            ),
+            mint.key,
+        )?;
+        token_interface::initialize_mint2(
+            CpiContext::new(token_program, token_interface::InitializeMint2 { mint: mint.clone() }),
+            0,
+            mint.key,
+            None,
+        )?;
+        
+        emit!(TierTokenEvent {
+            wallet: ctx.accounts.admin.key(),
+            tier_mint: mint.key(),
+            tier,
+            held: false,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Permissionless; mints the tier's position token to a wallet whose
+    // support tier reaches it and burns it once the tier drops below,
+    // after a downgrade, decay or lapse
+    pub fn sync_tier_token(ctx: Context<SyncTierToken>, tier: u8) -> Result<()> {
+        let solbox = &ctx.accounts.solbox;
+        let qualifies = ctx.accounts.user_account.support_tier >= tier;
+        let balance = ctx.accounts.token_account.amount;
+        if qualifies == (balance > 0) {
+            return Ok(());
+        }
+        
+        let solbox_key = solbox.key();
+        let seeds: &[&[u8]] = &[b"tier_mint", solbox_key.as_ref(), &[tier], &[ctx.bumps.tier_mint]];
+        let token_program = ctx.accounts.token_program.to_account_info();
+        let mint = ctx.accounts.tier_mint.to_account_info();
+        let account = ctx.accounts.token_account.to_account_info();
+        if qualifies {
+            token_interface::mint_to(
+                CpiContext::new_with_signer(
+                    token_program,
+                    token_interface::MintTo { mint: mint.clone(), to: account, authority: mint },
+                    &[seeds],
+                ),
+                1,
+            )?;
+        } else {
+            // The mint is the permanent delegate of every holder
+            token_interface::burn(
+                CpiContext::new_with_signer(
+                    token_program,
+                    token_interface::Burn { mint: mint.clone(), from: account, authority: mint },
+                    &[seeds],
+                ),
+                balance,
+            )?;
+        }
+        
+        emit!(TierTokenEvent {
+            wallet: ctx.accounts.wallet.key(),
+            tier_mint: ctx.accounts.tier_mint.key(),
+            tier,
+            held: qualifies,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Create the mint a wallet's matrix position can be wrapped into; the
+    // wallet's User PDA is its mint authority
+    pub fn create_position_mint(ctx: Context<CreatePositionMint>) -> Result<()> {
+        let solbox = &ctx.accounts.solbox;
+        emit!(PositionTokenEvent {
+            wallet: ctx.accounts.wallet.key(),
+            position_mint: ctx.accounts.position_mint.key(),
+            holder: ctx.accounts.wallet.key(),
+            action: PositionTokenAction::MintCreated,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Wrap the wallet's position into a single transferable token. From
+    // then on its commission is held in the vault and paid to whoever
+    // holds the token, so an organization can change hands without its
+    // wallet's keys
+    pub fn wrap_position(ctx: Context<WrapPosition>) -> Result<()> {
+        let solbox = &ctx.accounts.solbox;
+        require!(!solbox.paused, CustomError::ContractPaused);
+        let user_account = &mut ctx.accounts.user_account;
+        require!(
+            user_account.position_mint == Pubkey::default(),
+            CustomError::PositionWrapped
+        );
+        user_account.position_mint = ctx.accounts.position_mint.key();
+        
+        let solbox_key = solbox.key();
+        let wallet_key = ctx.accounts.wallet.key();
+        let seeds: &[&[u8]] = &[b"user", solbox_key.as_ref(), wallet_key.as_ref(), &[user_account.bump]];
+        token_interface::mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token_interface::MintTo {
+                    mint: ctx.accounts.position_mint.to_account_info(),
+                    to: ctx.accounts.position_account.to_account_info(),
+                    authority: user_account.to_account_info(),
+                },
+                &[seeds],
+            ),
+            1,
+        )?;
+        
+        emit!(PositionTokenEvent {
+            wallet: wallet_key,
+            position_mint: ctx.accounts.position_mint.key(),
+            holder: ctx.accounts.position_account.owner,
+            action: PositionTokenAction::Wrapped,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Burn the position token and pay commission to the wallet again;
+    // only the position's own wallet can, once it holds the token
+    pub fn unwrap_position(ctx: Context<UnwrapPosition>) -> Result<()> {
+        let solbox = &ctx.accounts.solbox;
+        token_interface::burn(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                token_interface::Burn {
+                    mint: ctx.accounts.position_mint.to_account_info(),
+                    from: ctx.accounts.position_account.to_account_info(),
+                    authority: ctx.accounts.wallet.to_account_info(),
+                },
+            ),
+            1,
+        )?;
+        ctx.accounts.user_account.position_mint = Pubkey::default();
+        
+        emit!(PositionTokenEvent {
+            wallet: ctx.accounts.wallet.key(),
+            position_mint: ctx.accounts.position_mint.key(),
+            holder: ctx.accounts.wallet.key(),
+            action: PositionTokenAction::Unwrapped,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Pay a wrapped position's residual to the current holder of its
+    // token. Only on the payout day, since queued payouts go to the
+    // position's wallet
+    pub fn claim_position_residual(ctx: Context<ClaimPositionResidual>) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        let user_account = &mut ctx.accounts.user_account;
+        let now = unix_now(solbox)?;
+        
+        // Verify claims are open
+        require!(claims_open(solbox), CustomError::ContractPaused);
+        require!(is_payout_day(&solbox.config, now), CustomError::OutsidePayoutWindow);
+        
+        // Verify the signer holds the position token
+        require!(ctx.accounts.position_account.amount == 1, CustomError::Unauthorized);
+        
+        mature_residual(user_account, current_epoch(solbox)?);
+        let amount = user_account.residual_claimable;
+        require!(amount > 0, CustomError::NothingToClaim);
+        require!(amount >= solbox.config.min_claim, CustomError::ClaimBelowMinimum);
+        
+        user_account.residual_claimable = 0;
+        solbox.residual_balance = solbox.residual_balance
+            .checked_sub(amount)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        transfer_from_vault(&solbox.to_account_info(), &ctx.accounts.holder.to_account_info(), amount)?;
+        
+        emit!(ResidualClaimedEvent {
+            user: user_account.key,
+            amount,
+            timestamp: now,
+            brand_code: solbox.brand_code,
+        });
+        emit_coverage(solbox)?;
+        
+        Ok(())
+    }
+
+    // Clients prepend this to their transactions so a frontend built
+    // against an older instruction layout fails before anything else runs
+    pub fn assert_version(_ctx: Context<AssertVersion>, expected_version: u16) -> Result<()> {
+        require!(
+            expected_version == INTERFACE_VERSION,
+            CustomError::StaleClientVersion
+        );
+        Ok(())
+    }
+
+    pub fn simulate_purchase(
+        ctx: Context<SimulatePurchase>,
+        amount: u64,
+        referrer: Pubkey
+    ) -> Result<PurchaseFlows> {
+        let accounts = &ctx.accounts;
+        let solbox = &accounts.solbox;
+        
+        // Mirror the checks buy_gift_card applies before moving funds
+        check_purchase(
+            solbox,
+            &accounts.blacklist_filter,
+            accounts.buyer.key,
+            &accounts.user_account,
+            &referrer,
+            &accounts.referrer_user_account,
+            amount
+        )?;
+        
+        // Returned to the caller through return_data. Assumes the buyer's
+        // wallet pays, so a covering card balance is spent
+        purchase_flows(
+            solbox,
+            amount,
+            referrer,
+            &accounts.referrer_user_account,
+            accounts.referrer_boost.as_deref(),
+            accounts.integrator.as_deref(),
+            Some(accounts.user_account.card_balance)
+        )
+    }
+
+    pub fn preview_placement(
+        ctx: Context<PreviewPlacement>,
+        referrer: Pubkey
+    ) -> Result<PlacementPreview> {
+        let solbox = &ctx.accounts.solbox;
+        
+        let placement = resolve_placement(solbox, referrer)?;
+        let direct_referrals = position_children(&solbox.referral_relationships, placement)
+            .len() as u64;
+        
+        // Returned to the caller through return_data
+        Ok(PlacementPreview {
+            referrer,
+            placed_under: placement.user,
+            placed_under_cycle: placement.cycle,
+            is_spillover: placement.user != referrer,
+            direct_referrals,
+        })
+    }
+
+    // Check the accounts a client means to pass to buy_gift_card before the
+    // buyer signs. Returns a bitmask of PURCHASE_PROBLEM_* flags, 0 when the
+    // purchase should go through; upline accounts come in remaining
+    // accounts as for the purchase. Assumes the buyer pays
+    pub fn validate_purchase_accounts<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ValidatePurchaseAccounts<'info>>,
+        amount: u64
+    ) -> Result<u32> {
+        let accounts = &ctx.accounts;
+        let solbox = &accounts.solbox;
+        let solbox_key = solbox.key();
+        let buyer = accounts.buyer.key();
+        let referrer = accounts.referrer.key();
+        let mut problems = 0;
+        
+        if solbox.paused {
+            problems |= PURCHASE_PROBLEM_PAUSED;
+        }
+        if !solbox.config.valid_amounts.contains(&amount) {
+            problems |= PURCHASE_PROBLEM_INVALID_AMOUNT;
+        }
+        if solbox.emergency_eta != 0 {
+            problems |= PURCHASE_PROBLEM_EMERGENCY_PENDING;
+        }
+        if is_blacklisted(solbox, &accounts.blacklist_filter, &buyer) {
+            problems |= PURCHASE_PROBLEM_BLACKLISTED;
+        }
+        if buyer == referrer {
+            problems |= PURCHASE_PROBLEM_SELF_REFERRAL;
+        }
+        if accounts.buyer.lamports() < amount {
+            problems |= PURCHASE_PROBLEM_INSUFFICIENT_FUNDS;
+        }
+        match read_user_account(&accounts.user_account, solbox_key, buyer) {
+            None => problems |= PURCHASE_PROBLEM_BUYER_NOT_REGISTERED,
+            Some(user) if solbox.config.require_current_terms && user.terms_version != solbox.terms_version => {
+                problems |= PURCHASE_PROBLEM_TERMS_OUTDATED
+            }
+            Some(_) => {}
+        }
+        match read_user_account(&accounts.referrer_user_account, solbox_key, referrer) {
+            None => problems |= PURCHASE_PROBLEM_REFERRER_NOT_REGISTERED,
+            Some(user) if user.pending_acceptance => problems |= PURCHASE_PROBLEM_SPONSORSHIP_PENDING,
+            Some(_) => {}
+        }
+        
+        // Purchase ids and days move on between building and sending
+        let (receipt, _) = Pubkey::find_program_address(
+            &[b"receipt", solbox_key.as_ref(), &solbox.purchase_count.to_le_bytes()],
+            &crate::ID
+        );
+        if accounts.receipt.key() != receipt {
+            problems |= PURCHASE_PROBLEM_STALE_RECEIPT;
+        }
+        let (daily_stats, _) = Pubkey::find_program_address(
+            &[b"daily_stats", solbox_key.as_ref(), &day_seed(solbox)?],
+            &crate::ID
+        );
+        if accounts.daily_stats.key() != daily_stats {
+            problems |= PURCHASE_PROBLEM_STALE_DAY;
+        }
+        
+        // A first purchase placed at once needs the accounts of its paid
+        // uplines; signups waiting in the holding tank are placed later
+        let is_new_registration = !solbox.referral_relationships
+            .iter()
+            .any(|r| r.user == buyer)
+            && !solbox.holding_tank
+                .iter()
+                .any(|entry| entry.user == buyer);
+        if is_new_registration
+            && solbox.config.humanity_verifier != Pubkey::default()
+            && amount >= solbox.config.humanity_threshold
+        {
+            problems |= PURCHASE_PROBLEM_NEEDS_HUMANITY_PROOF;
+        }
+        if is_new_registration
+            && read_user_account(&accounts.user_account, solbox_key, buyer).is_some_and(|user| {
+                user.invited_by != Pubkey::default() && user.invited_by != referrer
+            })
+        {
+            problems |= PURCHASE_PROBLEM_INVITATION_MISMATCH;
+        }
+        if solbox.config.holding_tank_period == 0 {
+            match resolve_placement(solbox, referrer) {
+                Ok(spot) if is_new_registration => {
+                    problems |= check_upline_accounts(solbox, solbox_key, ctx.remaining_accounts, spot);
+                }
+                Ok(_) => {}
+                Err(_) => problems |= PURCHASE_PROBLEM_NO_PLACEMENT,
+            }
+        }
+        
+        // Returned to the caller through return_data
+        Ok(problems)
+    }
+
+    pub fn view_admin_dashboard(ctx: Context<ViewAdminDashboard>) -> Result<AdminDashboard> {
+        let solbox = &ctx.accounts.solbox;
+        let vault = solbox.to_account_info();
+        let liabilities = solbox_liabilities(solbox)?;
+        
+        // Returned to the caller through return_data
+        Ok(AdminDashboard {
+            paused: solbox.paused,
+            user_count: solbox.user_count,
+            active_users: solbox.active_users,
+            total_sold: solbox.total_sold,
+            total_commission_distributed: solbox.total_commission_distributed,
+            purchase_count: solbox.purchase_count,
+            vault_balance: vault.lamports(),
+            liabilities,
+            treasury: treasury_balance(&vault, liabilities)?,
+            staked_lamports: solbox.staked_lamports,
+            pending_receipts: solbox.pending_receipts,
+            escrow_balance: solbox.escrow_balance,
+            holding_tank_len: solbox.holding_tank.len() as u64,
+            epoch: current_epoch(solbox)?,
+            undistributed_yield: solbox.undistributed_yield,
+            yield_pool_balance: solbox.yield_pool_balance,
+            next_yield_epoch: solbox.next_yield_epoch,
+            residual_balance: solbox.residual_balance,
+            tranche_balance: solbox.tranche_balance,
+        })
+    }
+
+    pub fn verify_state<'info>(
+        ctx: Context<'_, '_, 'info, 'info, VerifyState<'info>>
+    ) -> Result<()> {
+        let solbox = &ctx.accounts.solbox;
+        let solbox_key = solbox.key();
+        let vault = solbox.to_account_info();
+        let now = unix_now(solbox)?;
+        let mut violations = 0u32;
+        let mut report = |invariant: u8, account: Pubkey, expected: u64, actual: u64| {
+            violations += 1;
+            emit!(InvariantViolationEvent {
+                invariant,
+                account,
+                expected,
+                actual,
+                timestamp: now,
+                brand_code: solbox.brand_code,
+            });
+        };
+        
+        // The vault covers rent plus everything owed to users
+        let required = solbox_liabilities(solbox)?
+            .checked_add(Rent::get()?.minimum_balance(vault.data_len()))
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        if vault.lamports() < required {
+            report(INVARIANT_VAULT_COVERAGE, solbox_key, required, vault.lamports());
+        }
+        
+        // Yield waiting for distribution is part of the reserved pool
+        if solbox.undistributed_yield > solbox.yield_pool_balance {
+            report(INVARIANT_YIELD_POOL, solbox_key, solbox.yield_pool_balance, solbox.undistributed_yield);
+        }
+        
+        if solbox.active_users > solbox.user_count {
+            report(INVARIANT_ACTIVE_USERS, solbox_key, solbox.user_count, solbox.active_users);
+        }
+        
+        // Escrow is only held for pending purchases
+        if solbox.pending_receipts == 0 && solbox.escrow_balance > 0 {
+            report(INVARIANT_ESCROW, solbox_key, 0, solbox.escrow_balance);
+        }
+        
+        // Every first placement comes from a counted purchase
+        let placements = solbox.referral_relationships
+            .iter()
+            .filter(|r| r.cycle == 0)
+            .count()
+            .checked_add(solbox.holding_tank.len())
+            .ok_or_else(|| error!(CustomError::ArithmeticError))? as u64;
+        if placements > solbox.referral_count {
+            report(INVARIANT_REFERRAL_COUNT, solbox_key, solbox.referral_count, placements);
+        }
+        
+        // Receipts are checked in bounded batches passed in
+        // remaining_accounts; their pending totals can't exceed the ledger
+        require!(
+            ctx.remaining_accounts.len() <= MAX_VERIFY_BATCH,
+            CustomError::VerifyBatchTooLarge
+        );
+        let mut pending_escrow = 0u64;
+        let mut pending_count = 0u64;
+        for info in ctx.remaining_accounts.iter() {
+            let receipt = Account::<Receipt>::try_from(info)?;
+            require!(receipt.solbox == solbox_key, CustomError::InvalidReceipt);
+            
+            let total = receipt_total(&receipt)?;
+            if receipt.refunded_amount > total {
+                report(INVARIANT_RECEIPT_REFUND, info.key(), total, receipt.refunded_amount);
+            }
+            if receipt.status == ReceiptStatus::Pending {
+                pending_escrow = pending_escrow
+                    .checked_add(total)
+                    .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+                pending_count += 1;
+            }
+        }
+        if pending_escrow > solbox.escrow_balance {
+            report(INVARIANT_ESCROW, solbox_key, solbox.escrow_balance, pending_escrow);
+        }
+        if pending_count > solbox.pending_receipts {
+            report(INVARIANT_PENDING_RECEIPTS, solbox_key, solbox.pending_receipts, pending_count);
+        }
+        
+        emit!(StateVerifiedEvent {
+            receipts_checked: ctx.remaining_accounts.len() as u32,
+            pending_escrow,
+            pending_count,
+            violations,
+            timestamp: now,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    pub fn verify_tier(
+        ctx: Context<VerifyTier>,
+        min_tier: u8
+    ) -> Result<SupportTierView> {
+        let solbox = &ctx.accounts.solbox;
+        let user_account = &ctx.accounts.user_account;
+        
+        // Derived from the live config so threshold changes apply at once;
+        // decay counts from the last period refresh_tier or a purchase closed
+        let tier = support_tier(&solbox.config, user_account.current_package)
+            .saturating_sub(user_account.tier_decay);
+        require!(tier >= min_tier, CustomError::SupportTierTooLow);
+        
+        // Returned to the caller through return_data
+        Ok(SupportTierView {
+            user: user_account.key,
+            package: user_account.current_package,
+            support_tier: tier,
+        })
+    }
+
+    // Permissionless; closes the user's finished re-qualification periods
+    pub fn refresh_tier(ctx: Context<RefreshTier>) -> Result<()> {
+        roll_tier_periods(
+            &ctx.accounts.solbox,
+            &mut ctx.accounts.user_account,
+            unix_now(&ctx.accounts.solbox)?
+        )
+    }
+
+    // Extend the package for another membership period. Before the lapse
+    // this costs the full package; within the reactivation window after it,
+    // the reduced reactivation price, and the tree position is kept. Which
+    // one applies depends on when it lands, so the price is bounded by
+    // max_lamports_in and the new paid_until by min_paid_until
+    pub fn renew_membership(
+        ctx: Context<RenewMembership>,
+        max_lamports_in: u64,
+        min_paid_until: i64
+    ) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        let user_account = &mut ctx.accounts.user_account;
+        let wallet = ctx.accounts.wallet.key();
+        
+        // Verify contract is active
+        require!(!solbox.paused, CustomError::ContractPaused);
+        require!(
+            !is_blacklisted(solbox, &ctx.accounts.blacklist_filter, &wallet),
+            CustomError::UserBlacklisted
+        );
+        
+        require!(solbox.emergency_eta == 0, CustomError::EmergencyWithdrawPending);
+        
+        let now = unix_now(solbox)?;
+        let package = user_account.current_package;
+        let lapsed_at = user_account.paid_until;
+        require!(
+            package > 0 && lapsed_at > 0 && now < reactivation_deadline(&solbox.config, lapsed_at)?,
+            CustomError::MembershipNotRenewable
+        );
+        
+        let (action, price, start) = if now < lapsed_at {
+            (MembershipAction::Renewed, package, lapsed_at)
+        } else {
+            let price = pro_rata(package, solbox.config.reactivation_bps as u64, BPS_DENOMINATOR as u64)?;
+            (MembershipAction::Reactivated, price, now)
+        };
+        let paid_until = start
+            .checked_add(solbox.config.membership_period)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        require!(
+            price <= max_lamports_in && paid_until >= min_paid_until,
+            CustomError::SlippageExceeded
+        );
+        user_account.paid_until = paid_until;
+        user_account.last_active_at = now;
+        
+        if price > 0 {
+            invoke(
+                &system_instruction::transfer(&wallet, &solbox.key(), price),
+                &[
+                    ctx.accounts.wallet.to_account_info(),
+                    solbox.to_account_info(),
+                    ctx.accounts.system_program.to_account_info(),
+                ],
+            )?;
+        }
+        
+        emit!(MembershipEvent {
+            user: wallet,
+            action,
+            package,
+            price,
+            paid_until: user_account.paid_until,
+            timestamp: now,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Permissionless; drops the package of a membership that lapsed and was
+    // not reactivated in time. The user's tree position stays where it is,
+    // but a new package has to be bought at the full price
+    pub fn lapse_membership(ctx: Context<RefreshTier>) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        let user_account = &mut ctx.accounts.user_account;
+        let lapsed_at = user_account.paid_until;
+        
+        let now = unix_now(solbox)?;
+        require!(
+            user_account.current_package > 0
+                && lapsed_at > 0
+                && now >= reactivation_deadline(&solbox.config, lapsed_at)?,
+            CustomError::MembershipActive
+        );
+        
+        let package = user_account.current_package;
+        set_package(solbox, user_account, 0)?;
+        
+        emit!(MembershipEvent {
+            user: user_account.key,
+            action: MembershipAction::Lapsed,
+            package,
+            price: 0,
+            paid_until: lapsed_at,
+            timestamp: now,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    pub fn place_user<'info>(
+        ctx: Context<'_, '_, 'info, 'info, PlaceUser<'info>>,
+        user: Pubkey,
+        target: Pubkey,
+        target_cycle: u8
+    ) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        let sponsor = ctx.accounts.sponsor.key();
+        let now = unix_now(solbox)?;
+        
+        require!(!solbox.paused, CustomError::ContractPaused);
+        
+        let index = solbox.holding_tank
+            .iter()
+            .position(|entry| entry.user == user)
+            .ok_or(CustomError::NotInHoldingTank)?;
+        let entry = solbox.holding_tank[index].clone();
+        
+        // Only the sponsor can place, and only while the hold lasts
+        require!(entry.sponsor == sponsor, CustomError::Unauthorized);
+        require!(
+            now < entry.entered_at.saturating_add(solbox.config.holding_tank_period),
+            CustomError::HoldingTankExpired
+        );
+        
+        // Target must be an open leg inside the sponsor's own matrix
+        let relationships = &solbox.referral_relationships;
+        let root = current_position(relationships, sponsor);
+        let spot = Position { user: target, cycle: target_cycle };
+        require!(
+            is_within_downline(relationships, root, spot, solbox.config.matrix_depth - 1)
+                && position_children(relationships, spot).len() < solbox.config.matrix_width as usize,
+            CustomError::InvalidPlacement
+        );
+        
+        solbox.holding_tank.remove(index);
+        
+        let vault = solbox.to_account_info();
+        let mut uplines = ctx.remaining_accounts;
+        place_in_matrix(solbox, &vault, &mut uplines, ctx.remaining_accounts, user, spot, None)?;
+        
+        emit!(PlacementEvent {
+            user,
+            sponsor,
+            placed_under: spot.user,
+            placed_under_cycle: spot.cycle,
+            placed_by: sponsor,
+            timestamp: now,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    // Adds a house position under `sponsor`, taking the first open spot
+    // there like a signup would. House positions absorb spillover while the
+    // tree is balanced during a launch; they hold no package, are not
+    // counted as users and their commission goes to the bonus pool
+    pub fn create_house_position<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CreateHousePosition<'info>>,
+        sponsor: Pubkey
+    ) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        let house = ctx.accounts.house.key();
+        
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == solbox.owner,
+            CustomError::Unauthorized
+        );
+        require!(
+            solbox.house_positions.len() < MAX_HOUSE_POSITIONS,
+            CustomError::HousePositionsFull
+        );
+        
+        let house_account = &mut ctx.accounts.house_account;
+        init_user(solbox, house_account, house, ctx.bumps.house_account)?;
+        solbox.house_positions.push(house);
+        
+        // Upline accounts come in remaining_accounts, see take_uplines
+        let spot = resolve_placement(solbox, sponsor)?;
+        let vault = solbox.to_account_info();
+        let mut uplines = ctx.remaining_accounts;
+        place_in_matrix(solbox, &vault, &mut uplines, ctx.remaining_accounts, house, spot, None)?;
+        
+        emit!(PlacementEvent {
+            user: house,
+            sponsor,
+            placed_under: spot.user,
+            placed_under_cycle: spot.cycle,
+            placed_by: ctx.accounts.admin.key(),
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    pub fn auto_place_expired<'info>(
+        ctx: Context<'_, '_, 'info, 'info, AutoPlace<'info>>,
+        max_entries: u8
+    ) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        let now = unix_now(solbox)?;
+        let vault = solbox.to_account_info();
+        
+        // Each placement takes its own upline accounts, in placement order
+        let mut uplines = ctx.remaining_accounts;
+        let mut placed = 0;
+        let mut index = 0;
+        while index < solbox.holding_tank.len() && placed < max_entries {
+            let entry = solbox.holding_tank[index].clone();
+            let expires_at = entry.entered_at.saturating_add(solbox.config.holding_tank_period);
+            
+            // Entries still on hold, or whose sponsor's matrix is full, stay in the tank
+            let spot = if now >= expires_at {
+                resolve_placement(solbox, entry.sponsor).ok()
+            } else {
+                None
+            };
+            let Some(spot) = spot else {
+                index += 1;
+                continue;
+            };
+            
+            solbox.holding_tank.remove(index);
+            place_in_matrix(solbox, &vault, &mut uplines, ctx.remaining_accounts, entry.user, spot, None)?;
+            placed += 1;
+            
+            emit!(PlacementEvent {
+                user: entry.user,
+                sponsor: entry.sponsor,
+                placed_under: spot.user,
+                placed_under_cycle: spot.cycle,
+                placed_by: ctx.accounts.cranker.key(),
+                timestamp: now,
+                brand_code: solbox.brand_code,
+            });
+        }
+        
+        Ok(())
+    }
+
+    // Review outcome for a flagged buyer: clears every FRAUD_FLAG_* reason
+    pub fn clear_fraud_flags(ctx: Context<AdminAction>) -> Result<()> {
+        let solbox = &ctx.accounts.solbox;
+        
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == solbox.owner,
+            CustomError::Unauthorized
+        );
+        ctx.accounts.user.flagged = 0;
+        
+        emit!(FraudFlagEvent {
+            user: ctx.accounts.user.key,
+            funder: None,
+            integrator: None,
+            reasons: 0,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    pub fn grant_package(
+        ctx: Context<AdminAction>,
+        user: Pubkey,
+        package: u64
+    ) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == solbox.owner,
+            CustomError::Unauthorized
+        );
+        
+        // Partner programs may only grant through CPI when allowlisted
+        require_cpi_caller(
+            solbox.key(),
+            ctx.accounts.integrator.as_ref(),
+            ctx.accounts.cpi_authority.as_ref(),
+            INTEGRATOR_ALLOW_GRANT_PACKAGE
+        )?;
+        
+        // Validate package amount; sandboxes may grant any package
+        require!(
+            solbox.sandbox || solbox.config.valid_amounts.contains(&package),
+            CustomError::InvalidAmount
+        );
+        
+        // Update user's package
+        set_package(solbox, &mut ctx.accounts.user, package)?;
+        
+        emit!(PackageGrantedEvent {
+            admin: *ctx.accounts.admin.key,
+            user,
+            package,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    pub fn update_commission_config(
+        ctx: Context<UpdateConfig>,
+        new_percentage: u64,
+        new_levels: u8
+    ) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == solbox.owner,
+            CustomError::Unauthorized
+        );
+        
+        // Update commission configuration
+        solbox.config.commission_percentage = new_percentage;
+        solbox.config.commission_levels = new_levels;
+        validate_config(&solbox.config, &solbox.referral_relationships)?;
+        
+        let version = record_config_version(
+            &mut ctx.accounts.config_history,
+            solbox.config.clone(),
+            *ctx.accounts.admin.key,
+            Clock::get()?.slot,
+        )?;
+        
+        emit!(CommissionConfigEvent {
+            admin: *ctx.accounts.admin.key,
+            new_percentage,
+            new_levels,
+            version,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    pub fn add_to_blacklist(
+        ctx: Context<UpdateBlacklist>,
+        user: Pubkey
+    ) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == solbox.owner,
+            CustomError::Unauthorized
+        );
+        
+        // Add to blacklist if not already present
+        if !solbox.blacklisted_users.contains(&user) {
+            solbox.blacklisted_users.push(user);
+        }
+        bloom_insert(&mut ctx.accounts.blacklist_filter.bits, &user);
+        
+        emit!(BlacklistEvent {
+            admin: *ctx.accounts.admin.key,
+            user,
+            action: BlacklistAction::Add,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+
+    pub fn remove_from_blacklist(
+        ctx: Context<UpdateBlacklist>,
+        user: Pubkey
+    ) -> Result<()> {
+        let solbox = &mut ctx.accounts.solbox;
+        
+        // Verify admin authority
+        require!(
+            ctx.accounts.admin.key() == solbox.owner,
+            CustomError::Unauthorized
+        );
+        
+        // Remove from blacklist
+        solbox.blacklisted_users.retain(|&x| x != user);
+        
+        // Bloom filters can't unset bits, so rebuild from the remaining list
+        let filter = &mut ctx.accounts.blacklist_filter;
+        filter.bits = [0u8; BLACKLIST_FILTER_BYTES];
+        for blacklisted in solbox.blacklisted_users.iter() {
+            bloom_insert(&mut filter.bits, blacklisted);
+        }
+        
+        emit!(BlacklistEvent {
+            admin: *ctx.accounts.admin.key,
+            user,
+            action: BlacklistAction::Remove,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+        
+        Ok(())
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct SolBox {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub owner: Pubkey,
+    pub founder_wallet: Pubkey,
+    pub paused: bool,
+    pub total_sold: u64,
+    pub total_commission_distributed: u64,
+    pub referral_count: u64,
+    pub purchase_count: u64,
+    pub escrow_balance: u64, // lamports held for purchases inside their refund window
+    pub staked_lamports: u64, // treasury lamports delegated to the stake pool, at cost
+    pub total_package_weight: u64, // sum of current_package over all users
+    pub yield_per_package: u128,   // cumulative yield per package lamport, scaled by YIELD_PRECISION
+    pub undistributed_yield: u64,  // realized yield waiting for the next epoch distribution
+    pub yield_pool_balance: u64,   // lamports reserved for undistributed and unclaimed yield
+    pub residual_balance: u64,     // residual commission held until referrers claim it
+    pub queued_payouts: u64,       // claims made outside the payout window, waiting to be paid
+    pub deferred_founder_share: u64, // founder shares the founder wallet couldn't take, held for flush_founder_share
+    pub next_yield_epoch: u64,
+    pub user_count: u64,       // registered User PDAs
+    pub active_users: u64,     // users holding a package
+    pub pending_receipts: u64, // purchases still inside their refund window
+    pub pending_founder_wallet: Pubkey, // proposed replacement; default when none
+    pub founder_change_eta: i64,        // when the pending wallet may accept
+    pub emergency_recipient: Pubkey,    // receives an announced emergency withdrawal; default when none
+    pub emergency_eta: i64,             // when the announced withdrawal may execute
+    pub loyalty_mint: Pubkey, // token burned to activate boosts
+    pub boost_price: u64,     // tokens burned per boost; 0 disables boosts
+    pub boost_bps: u16,       // extra commission an active boost earns
+    pub boost_duration: i64,  // seconds one activation lasts
+    #[max_len(MAX_GUARDIANS)]
+    pub pause_guardians: Vec<Pubkey>, // approve unpausing once a threshold is set
+    pub unpause_threshold: u8,        // guardian approvals execute_unpause needs; 0 lets the admin unpause
+    pub pause_count: u64,             // pauses so far; approvals only count for the current one
+    pub sandbox: bool,       // non-production instance; set at initialize only
+    pub time_offset: i64,    // seconds warp_time_offset has added to the clock
+    pub epoch_offset: u64,   // epochs warp_time_offset has added
+    pub max_amounts: u8,     // valid_amounts entries the config history has room for
+    pub max_levels: u8,      // commission levels User accounts have room for
+    pub lookup_table: Pubkey, // address lookup table of hot accounts; default when none
+    pub house_commission: u64, // commission earned by house positions, routed to the bonus pool
+    pub brand_code: [u8; 8],   // tenant tag carried by every event; zeroes when unset
+    pub tranche_balance: u64,  // dripped commission not yet released from tranches
+    pub terms_hash: [u8; 32],  // hash of the terms in force; zeroes before any are published
+    pub terms_version: u32,    // terms published so far
+    pub limited_packages: u16, // packages with a limited supply; buys pass the Package PDA while nonzero
+    pub voucher_balance: u64,  // residual exported as claim vouchers, kept in the vault to back them
+    pub voucher_count: u64,    // claim vouchers posted so far; the next voucher's id
+    pub tombstone_count: u64,  // deleted users so far; the next tombstone's index
+    pub tree_rewrites: u64,    // edits to referral_relationships other than appends; restart tree checkpoints
+    pub hot_withdrawn_day: u64, // day index hot_withdrawn counts for
+    pub hot_withdrawn: u64,     // withdraw_treasury total on that day, against hot_daily_limit
+    pub card_balance_total: u64, // card balances of all users, kept in the vault to back them
+    pub config: ContractConfig,
+    #[max_len(0)] // sized by SolBox::space
+    pub blacklisted_users: Vec<Pubkey>,
+    #[max_len(0)] // sized by SolBox::space
+    pub referral_relationships: Vec<ReferralRelationship>,
+    #[max_len(MAX_HOLDING_TANK_ENTRIES)]
+    pub holding_tank: Vec<HoldingTankEntry>,
+    #[max_len(MAX_HOUSE_POSITIONS)]
+    pub house_positions: Vec<Pubkey>, // wallets of admin-created house positions
+    pub _reserved: [u8; RESERVED_SPACE],
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct User {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub solbox: Pubkey,
+    pub key: Pubkey,
+    pub bump: u8,
+    pub current_package: u64,
+    pub total_earnings: u64,
+    #[max_len(0)] // sized by User::space
+    pub downline_counts: Vec<u32>, // signups per level below this user
+    pub yield_debt: u128,      // yield already accounted for at the current package
+    pub unclaimed_yield: u64,
+    pub lifetime_volume: u64,  // purchase volume, including volume imported from another tenant
+    pub imported_from: Pubkey, // source tenant of an imported history; default if none
+    pub support_tier: u8,      // derived from current_package when it changes, less tier_decay
+    pub period_index: u64,     // re-qualification period period_volume belongs to; 0 before tracking
+    pub period_volume: u64,    // purchase volume within that period
+    pub missed_periods: u8,    // consecutive periods without qualifying volume
+    pub tier_decay: u8,        // tiers lost to missed periods
+    pub beneficiary: Pubkey,   // takes over after inactivity_period; default when none
+    pub inactivity_period: i64,
+    pub last_active_at: i64,   // last purchase, yield claim or beneficiary update
+    pub residual_pending: u64,   // residual commission earned in residual_epoch
+    pub residual_epoch: u64,
+    pub residual_claimable: u64, // residual from closed epochs
+    pub handle: Pubkey,          // Handle PDA of the display handle; default when none
+    pub sponsored_by: Pubkey,    // sponsor that paid the registration; default when self-registered
+    pub pending_acceptance: bool, // sponsored registration the wallet hasn't accepted yet
+    pub paid_until: i64,         // when the package lapses unless renewed; 0 when it doesn't
+    pub queued_payout: u64,      // claimed outside the payout window, paid on the payout day
+    pub earnings_from_direct: u64,    // commission on buyers placed on this user's own legs
+    pub earnings_from_spillover: u64, // commission on buyers that spilled over below them
+    pub earnings_from_pools: u64,     // yield claimed from the bonus pool
+    pub position_mint: Pubkey,   // token whose holder is paid the position's commission; default when not wrapped
+    pub unseen_downline_events: u32, // downline signups and purchases since the wallet last called ack_events
+    pub terms_hash: [u8; 32],    // terms the wallet last accepted
+    pub terms_version: u32,      // SolBox.terms_version they were accepted at; 0 before any
+    pub terms_accepted_at: i64,
+    pub volume_day: u64,         // day index of the newest volume bucket
+    pub volume_buckets: [u64; VOLUME_WINDOW_DAYS], // purchase volume per day, a ring indexed by day
+    pub deletion_requested_at: i64, // when the wallet asked for its data to be deleted; 0 when it hasn't
+    pub flagged: u8,             // FRAUD_FLAG_* reasons raised by purchase heuristics, until the admin clears them
+    pub invited_by: Pubkey,      // referrer of a claimed invitation the first purchase must go under; default when none
+    pub card_balance: u64,       // lamports converted from commission by claim_to_card, spent on purchases
+    pub pending_receipts: u32,   // purchases in escrow naming this wallet as buyer or payee
+    pub _reserved: [u8; RESERVED_SPACE],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, InitSpace)]
+pub struct ContractConfig {
+    pub matrix_width: u8,  // legs per position
+    pub matrix_depth: u8,  // levels searched for spillover below a sponsor
+    pub commission_percentage: u64,
+    pub commission_levels: u8,
+    pub bonus_percentage: u64,
+    #[max_len(0)] // sized by ContractConfig::space
+    pub valid_amounts: Vec<u64>,
+    pub refund_window: i64, // seconds a buyer can cancel; 0 pays out immediately
+    pub recycling: bool,    // re-enter positions whose matrix fills up
+    pub cycle_bonus: u64,   // paid from the treasury on each cycle
+    pub holding_tank_period: i64, // seconds sponsors can place new signups; 0 places immediately
+    pub stake_pool: Pubkey,   // SPL stake pool idle treasury SOL may be delegated to
+    pub max_stake_bps: u16,   // share of treasury SOL that may be delegated
+    pub yield_sharing: bool,  // share realized staking yield with package holders
+    pub support_tier_thresholds: [u64; SUPPORT_TIERS], // minimum package per tier; 0 disables a tier
+    pub tier_period: i64,        // seconds per tier re-qualification period; 0 keeps tiers for life
+    pub tier_grace_periods: u8,  // missed periods tolerated before a tier decays
+    pub residual_bps: u16,       // share of commission held as residual until the epoch closes
+    pub membership_period: i64,  // seconds a package lasts before it has to be renewed; 0 never lapses
+    pub reactivation_window: i64, // seconds after a lapse the membership can still be reactivated
+    pub reactivation_bps: u16,   // reactivation price as a share of the package
+    pub min_claim: u64,          // smallest yield or residual claim; 0 allows any amount
+    pub payout_weekday: u8,      // 1 (Monday) to 7 (Sunday) when claims pay out; 0 pays every day
+    pub claims_during_pause: bool, // earned yield, residual and queued payouts stay claimable while paused
+    pub humanity_verifier: Pubkey, // co-signs large first purchases as proof of humanity; default disables it
+    pub humanity_threshold: u64,   // smallest first purchase that needs the verifier
+    pub drip_threshold: u64, // commissions above this pay out over drip_days; 0 pays every commission at once
+    pub drip_days: u16,      // days a dripped commission is released over, a share per day
+    pub require_current_terms: bool, // buyers must have accepted the latest published terms
+    pub tier_window_days: u16, // qualifying volume is the trailing days before each period close; 0 counts the period's own volume
+    pub voucher_chain: u16,    // Wormhole chain id of the contract redeeming claim vouchers; 0 disables export
+    pub storefront_chain: u16, // Wormhole chain id of the EVM storefront; 0 disables redeem_wormhole_purchase
+    pub storefront_emitter: [u8; 32], // Wormhole emitter address of the storefront contract
+    pub hot_daily_limit: u64,     // lamports withdraw_treasury may take per day; 0 for no limit
+    pub hot_sweep_threshold: u64, // treasury kept hot; sweep_treasury moves the rest to cold. 0 disables sweeps
+    pub flag_funder_threshold: u16, // purchases one payer may fund for others per day before flagging; 0 disables
+    pub flag_burst_threshold: u16,  // purchases through one integrator per FRAUD_BURST_WINDOW before flagging; 0 disables
+    pub block_flagged: bool,        // refuse purchases by flagged buyers instead of only flagging them
+    pub card_bonus_bps: u16,        // bonus on commission claimed to card balance, up to MAX_CARD_BONUS_BPS
+    pub redemption_fee_bps: u16,    // platform fee on card balance redeemed with merchants, up to MAX_REDEMPTION_FEE_BPS
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct ConfigHistory {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub solbox: Pubkey,
+    pub bump: u8,
+    pub version: u64,
+    #[max_len(CONFIG_HISTORY_LEN)]
+    pub entries: Vec<ConfigVersion>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, InitSpace)]
+pub struct ConfigVersion {
+    pub version: u64,
+    pub config: ContractConfig,
+    pub activation_slot: u64,
+    pub admin: Pubkey,
+}
+
+// Breakdown of a gift card purchase's lamports, as buy_gift_card moves
+// them and simulate_purchase returns them
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct PurchaseFlows {
+    pub amount: u64,
+    pub payee: Pubkey,          // direct referrer, credited the commission
+    pub referrer: Pubkey,       // placement parent after spillover
+    pub referrer_cycle: u8,
+    pub commission: u64,        // including the boost bonus
+    pub boost_bonus: u64,       // taken from the founder share
+    pub house: bool,            // commission goes to the bonus pool
+    pub residual: u64,          // held until the epoch closes
+    pub fast_start: u64,        // paid to the payee, or in tranches
+    pub dripped: bool,
+    pub bonus: u64,             // withheld from the founder share
+    pub founder_share: u64,     // after the boost and integrator fee
+    pub integrator_fee: u64,
+    pub paid: u64,              // charged for the purchase
+    pub card_funded: bool,      // paid from the buyer's card balance
+    pub escrowed: bool,         // held until the refund window closes
+}
+
+// Commission split of a purchase before boosts and integrator fees
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct PurchaseSplit {
+    pub amount: u64,
+    pub referrer: Pubkey,   // referrer after spillover placement
+    pub referrer_cycle: u8, // which of the referrer's positions is used
+    pub commission: u64,    // paid to the referrer
+    pub bonus: u64,         // withheld from the founder share
+    pub founder_share: u64, // paid to the founder wallet
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Receipt {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub solbox: Pubkey,
+    pub buyer: Pubkey,
+    pub referrer: Pubkey, // placement parent; below the payee on spillover
+    pub payee: Pubkey,    // direct referrer, credited and paid the commission
+    pub purchase_id: u64,
+    pub amount: u64,
+    pub commission: u64,
+    pub founder_share: u64,
+    pub slot: u64, // resolves the config version in force via ConfigHistory
+    pub purchased_at: i64,
+    pub refundable_until: i64,
+    pub refunded_amount: u64,
+    pub integrator: Pubkey,  // default when not routed through an integrator
+    pub integrator_fee: u64, // revshare carved out of the founder share
+    pub residual: u64,       // part of commission held as residual on settlement
+    pub spillover: bool,     // the buyer spilled over below the sponsor's own legs
+    pub card_issued: bool,   // a card token was minted for this purchase
+    pub dripped: bool,       // the fast-start commission pays out through a Tranche
+    pub card_funded: bool,   // paid from the buyer's card balance
+    pub registered: bool,    // the purchase was the buyer's first, entering the tree
+    pub epoch: u64,          // epoch the integrator's usage was counted in
+    pub package: Pubkey,     // limited package the sale took a unit of; default otherwise
+    pub status: ReceiptStatus,
+    pub bump: u8,
+    pub _reserved: [u8; RECEIPT_RESERVED],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum ReceiptStatus {
+    Pending,   // funds escrowed in the vault
+    Settled,   // funds paid to referrer and founder
+    Cancelled, // funds returned to the buyer
+}
+
+// Per-day sales analytics bucket, seeded by day number since the unix epoch
+#[account]
+#[derive(InitSpace)]
+pub struct DailyStats {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub solbox: Pubkey,
+    pub day: u64,
+    pub bump: u8,
+    pub purchase_count: u64,
+    pub volume: u64,
+    pub commission: u64,
+    pub new_registrations: u64,
+    pub buyer_sketch: [u8; BUYER_SKETCH_REGISTERS], // HyperLogLog registers
+    pub funder_sketch: [u16; FUNDER_SKETCH_WIDTH],  // distinct wallets paid for, counted per payer hash
+    pub funder_pairs: [u8; FUNDER_PAIR_FILTER_BYTES], // bloom filter of (payer, buyer) pairs already counted
+    pub _reserved: [u8; DAILY_STATS_RESERVED],
+}
+
+// Bloom filter over blacklisted keys, checked before the exact list so
+// clean buyers never pay for a scan of the blacklist
+#[account]
+#[derive(InitSpace)]
+pub struct BlacklistFilter {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub solbox: Pubkey,
+    pub bump: u8,
+    pub bits: [u8; BLACKLIST_FILTER_BYTES],
+}
+
+// Partner registered by the admin to route purchases for a revshare
+#[account]
+#[derive(InitSpace)]
+pub struct Integrator {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub solbox: Pubkey,
+    pub name_hash: [u8; 32],
+    pub wallet: Pubkey,
+    pub fee_share_bps: u16,
+    pub allowed_instructions: u32, // INTEGRATOR_ALLOW_* bitmask
+    pub bump: u8,
+    pub cpi_authority: Pubkey, // PDA the partner program signs CPIs with
+    pub burst_started_at: i64, // start of the FRAUD_BURST_WINDOW burst_count covers
+    pub burst_count: u32,      // purchases through this integrator in that window
+    pub epoch_volume_limit: u64,   // lamports per epoch; 0 for no ceiling
+    pub epoch_purchase_limit: u32, // purchases per epoch; 0 for no ceiling
+    pub limit_epoch: u64,          // epoch the usage below is counted for
+    pub epoch_volume: u64,
+    pub epoch_purchases: u32,
+    pub _reserved: [u8; INTEGRATOR_RESERVED],
+}
+
+// Off-chain click id anchored to a referrer ahead of the purchase
+#[account]
+#[derive(InitSpace)]
+pub struct Attribution {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub solbox: Pubkey,
+    pub nonce_hash: [u8; 32],
+    pub referrer: Pubkey,
+    pub recorded_by: Pubkey,
+    pub recorded_at: i64,
+    pub consumed_by: Pubkey, // buyer whose purchase used it; default until then
+    pub purchase_id: u64,
+    pub bump: u8,
+}
+
+// Contact a referrer invited ahead of registration, by identifier hash
+#[account]
+#[derive(InitSpace)]
+pub struct Invitation {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub solbox: Pubkey,
+    pub identifier_hash: [u8; 32],
+    pub referrer: Pubkey,
+    pub created_at: i64,
+    pub claimed_by: Pubkey, // wallet that presented the identifier; default until then
+    pub bump: u8,
+}
+
+// Merchant card balances are redeemed with, and its running totals
+#[account]
+#[derive(InitSpace)]
+pub struct Merchant {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub solbox: Pubkey,
+    pub wallet: Pubkey,   // co-signs redemptions and receives their settlement
+    pub redeemed: u64,    // card balance redeemed, fees included
+    pub fees: u64,        // part of redeemed kept by the treasury
+    pub redemptions: u64,
+    pub bump: u8,
+}
+
+// A merchant's redemptions in one calendar month, see `month_of`
+#[account]
+#[derive(InitSpace)]
+pub struct MerchantMonth {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub solbox: Pubkey,
+    pub merchant: Pubkey,
+    pub month: u32,
+    pub redeemed: u64,
+    pub fees: u64,
+    pub redemptions: u64,
+    pub bump: u8,
+}
+
+// On-ramp partner's statement that a purchase was paid for in fiat
+#[account]
+#[derive(InitSpace)]
+pub struct FiatAttestation {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub solbox: Pubkey,
+    pub integrator: Pubkey,
+    pub receipt: Pubkey,
+    pub purchase_id: u64,
+    pub buyer: Pubkey,
+    pub amount: u64,
+    pub external_ref: [u8; 32], // partner's order reference, hashed
+    pub attested_by: Pubkey,
+    pub attested_at: i64,
+    pub bump: u8,
+}
+
+// Display handle, unique per SolBox by its seeds
+#[account]
+#[derive(InitSpace)]
+pub struct Handle {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub solbox: Pubkey,
+    pub handle_hash: [u8; 32], // hash of the ASCII-lowercased handle
+    #[max_len(MAX_HANDLE_LEN)]
+    pub handle: String,        // as registered, case preserved
+    pub registered_at: i64,
+    pub bump: u8,
+}
+
+// Source tenant whose user proofs this instance accepts
+#[account]
+#[derive(InitSpace)]
+pub struct TenantLink {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub solbox: Pubkey,
+    pub source: Pubkey,
+    pub bump: u8,
+}
+
+// User history attested by a tenant authority for import elsewhere
+#[account]
+#[derive(InitSpace)]
+pub struct UserProof {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub solbox: Pubkey, // issuing tenant
+    pub user: Pubkey,
+    pub package: u64,
+    pub lifetime_volume: u64,
+    pub issued_at: i64,
+    pub bump: u8,
+}
+
+// Temporary key allowed to make purchases for its owner from a funded budget
+#[account]
+#[derive(InitSpace)]
+pub struct SessionKey {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub solbox: Pubkey,
+    pub owner: Pubkey,
+    pub session_key: Pubkey,
+    pub max_lamports: u64, // budget deposited at creation
+    pub spent: u64,
+    pub expires_at: i64,
+    pub bump: u8,
+}
+
+// Runtime switches for subsystems of one SolBox; changes wait out
+// FEATURE_FLAGS_TIMELOCK before they apply
+#[account]
+#[derive(InitSpace)]
+pub struct FeatureFlags {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub solbox: Pubkey,
+    pub flags: u64,         // FEATURE_* bitmask in effect
+    pub pending_flags: u64, // bitmask waiting for the timelock
+    pub pending_eta: i64,   // when pending_flags may apply; 0 when none
+    pub bump: u8,
+    pub _reserved: [u8; FEATURE_FLAGS_RESERVED],
+}
+
+// Time-boxed commission increase bought by burning loyalty tokens
+#[account]
+#[derive(InitSpace)]
+pub struct Boost {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub solbox: Pubkey,
+    pub wallet: Pubkey,
+    pub bonus_bps: u16, // extra commission, in basis points of the purchase
+    pub expires_at: i64,
+    pub bump: u8,
+}
+
+// Referral competition over a fixed window, holding its prize pool
+#[account]
+#[derive(InitSpace)]
+pub struct Contest {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub solbox: Pubkey,
+    pub contest_id: u64,
+    pub creator: Pubkey, // funded the prizes; receives unawarded ones
+    pub start: i64,      // entries close here
+    pub end: i64,
+    pub metric: ContestMetric,
+    #[max_len(MAX_CONTEST_PRIZES)]
+    pub prizes: Vec<u64>, // lamports by rank, first place first
+    pub entries: u32,
+    pub finalized: bool,
+    #[max_len(MAX_CONTEST_PRIZES)]
+    pub winners: Vec<ContestWinner>, // filled by finalize_contest
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum ContestMetric {
+    Directs, // first-level signups placed under the participant
+    Volume,  // the participant's own purchase volume
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq, InitSpace)]
+pub struct ContestWinner {
+    pub wallet: Pubkey,
+    pub score: u64,
+    pub prize: u64,
+    pub claimed: bool,
+}
+
+// A participant's counter at the time they entered a contest
+#[account]
+#[derive(InitSpace)]
+pub struct ContestEntry {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub contest: Pubkey,
+    pub wallet: Pubkey,
+    pub index: u32,    // order of entry, breaks ties
+    pub baseline: u64, // metric value at entry
+    pub bump: u8,
+}
+
+// Announcement from the admin, to one user or to everyone
+#[account]
+#[derive(InitSpace)]
+pub struct Notice {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub solbox: Pubkey,
+    pub notice_id: u64,
+    pub poster: Pubkey,    // admin who paid the rent
+    pub recipient: Pubkey, // default for a global notice
+    pub kind: u8,          // NOTICE_* category
+    #[max_len(MAX_NOTICE_LEN)]
+    pub message: String,
+    pub posted_at: i64,
+    pub read_at: i64,      // set by the recipient; 0 while unread
+    pub bump: u8,
+}
+
+// Guardians able to move a user to a new wallet if its key is lost
+#[account]
+#[derive(InitSpace)]
+pub struct Recovery {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub solbox: Pubkey,
+    pub wallet: Pubkey,
+    #[max_len(MAX_GUARDIANS)]
+    pub guardians: Vec<Pubkey>,
+    pub threshold: u8,          // approvals needed
+    pub pending_wallet: Pubkey, // wallet being approved; default when none
+    pub approvals: u8,          // bit per guardian index
+    pub recovery_eta: i64,      // when the recovery may execute; 0 below threshold
+    pub bump: u8,
+}
+
+// Merkle root over the earnings statements of one closed epoch
+#[account]
+#[derive(InitSpace)]
+pub struct EpochStatements {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub solbox: Pubkey,
+    pub epoch: u64,
+    pub root: [u8; 32], // see statements::Statement for the leaf layout
+    pub users: u32,     // statements under the root
+    pub anchored_at: i64,
+    pub bump: u8,
+}
+
+// Merkle root of the referral tree as of an epoch, built over
+// checkpoint_tree calls; the root stays readable while the next run builds
+#[account]
+#[derive(InitSpace)]
+pub struct TreeCheckpoint {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub solbox: Pubkey,
+    pub epoch: u64,         // epoch of the run in progress or last finished
+    pub cursor: u64,        // relationships hashed into the frontier so far
+    pub frontier: [[u8; 32]; TREE_FRONTIER_DEPTH], // see statements::append_leaf
+    pub tree_rewrites: u64, // SolBox.tree_rewrites when the run started
+    pub root: [u8; 32],     // see statements::tree_leaf for the leaf layout
+    pub root_epoch: u64,
+    pub relationships: u64, // relationships under the root
+    pub checkpointed_at: i64, // 0 before the first run finishes
+    pub bump: u8,
+}
+
+// Root over positions migrated from the legacy platform
+#[account]
+#[derive(InitSpace)]
+pub struct Migration {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub solbox: Pubkey,
+    pub root: [u8; 32],  // see statements::MigratedPosition for the leaf layout
+    pub positions: u32,  // leaves under the root
+    pub claimed: u32,    // positions claimed so far
+    pub committed_at: i64,
+    pub bump: u8,
+}
+
+// A large fast-start commission released to its wallet over drip_days
+#[account]
+#[derive(InitSpace)]
+pub struct Tranche {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub solbox: Pubkey,
+    pub wallet: Pubkey,
+    pub purchase_id: u64,
+    pub total: u64,
+    pub released: u64,  // paid out so far
+    pub days: u16,      // drip_days when the commission was earned
+    pub starts_at: i64, // 0 until an escrowed purchase settles
+    pub bump: u8,
+}
+
+// Hash of an exported InstanceSnapshot and the guardians' vote to restore it
+#[account]
+#[derive(InitSpace)]
+pub struct ConfigSnapshot {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub solbox: Pubkey,      // instance the snapshot was exported from
+    pub admin: Pubkey,       // admin that exported it, and may restore it
+    pub hash: [u8; 32],      // see InstanceSnapshot::hash
+    pub exported_at: i64,
+    #[max_len(MAX_GUARDIANS)]
+    pub guardians: Vec<Pubkey>, // pause guardians at export
+    pub threshold: u8,          // approvals needed
+    pub target: Pubkey,         // fresh instance being approved; default when none
+    pub approvals: u8,          // bit per guardian index
+    pub restore_eta: i64,       // when the restore may execute; 0 below threshold
+    pub restored_to: Pubkey,    // instance restored into; default until then
+    pub bump: u8,
+}
+
+// Treasury held away from the vault. Its lamports above rent are the cold
+// balance; withdrawals wait on guardians and a timelock
+#[account]
+#[derive(InitSpace)]
+pub struct ColdTreasury {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub solbox: Pubkey,
+    #[max_len(MAX_GUARDIANS)]
+    pub guardians: Vec<Pubkey>,    // pause guardians when the pending withdrawal was proposed
+    pub threshold: u8,             // approvals needed
+    pub pending_recipient: Pubkey, // default when no withdrawal is pending
+    pub pending_amount: u64,
+    pub approvals: u8,             // bit per guardian index
+    pub withdraw_eta: i64,         // when the withdrawal may execute; 0 below threshold
+    pub bump: u8,
+}
+
+// A wallet's commitment to buy from a commit-reveal drop
+#[account]
+#[derive(InitSpace)]
+pub struct DropCommit {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub solbox: Pubkey,
+    pub package: Pubkey,
+    pub wallet: Pubkey,
+    pub drop_id: u64,         // Package.drop_id of the drop it was made for
+    pub order: u64,           // position among the drop's commits
+    pub commitment: [u8; 32], // drop_commitment of the package, wallet, drop, quantity and salt
+    pub committed_slot: u64,
+    pub revealed: bool,
+    pub quantity: u64,        // cards asked for, known once revealed
+    pub allocated: u64,       // cards allocate_drop gave it that are still unbought
+    pub bump: u8,
+}
+
+// A storefront purchase credited from its Wormhole message
+#[account]
+#[derive(InitSpace)]
+pub struct WormholePurchase {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub solbox: Pubkey,
+    pub emitter_chain: u16,        // storefront that emitted the message
+    pub emitter_address: [u8; 32],
+    pub sequence: u64,   // storefront emitter's message sequence
+    pub buyer: Pubkey,
+    pub referrer: Pubkey, // placement referrer, after spillover
+    pub amount: u64,
+    pub commission: u64, // paid by the storefront on its chain
+    pub redeemed_at: i64,
+    pub bump: u8,
+}
+
+// Residual a wallet exported for the sister contract to mint on
+// `recipient_chain`. The payload's integers are big-endian, as EVM
+// contracts decode them
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ClaimVoucher {
+    pub voucher_id: u64,
+    pub solbox: Pubkey,
+    pub wallet: Pubkey,
+    pub amount: u64,
+    pub recipient_chain: u16,
+    pub recipient: [u8; 32], // a 20-byte EVM address is left-padded with zeroes
+}
+
+impl ClaimVoucher {
+    pub fn payload(&self) -> Vec<u8> {
+        let mut payload = vec![VOUCHER_PAYLOAD_ID];
+        payload.extend_from_slice(&self.voucher_id.to_be_bytes());
+        payload.extend_from_slice(self.solbox.as_ref());
+        payload.extend_from_slice(self.wallet.as_ref());
+        payload.extend_from_slice(&self.amount.to_be_bytes());
+        payload.extend_from_slice(&self.recipient_chain.to_be_bytes());
+        payload.extend_from_slice(&self.recipient);
+        payload
+    }
+}
+
+// Purchase the EVM storefront posts for redeem_wormhole_purchase. The
+// payload is a type byte, the buyer and referrer wallets and the
+// big-endian amount, followed by the Integrator PDA for a sale routed
+// through one
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct StorefrontPurchase {
+    pub buyer: Pubkey,
+    pub referrer: Pubkey,
+    pub amount: u64,
+    pub integrator: Option<Pubkey>,
+}
+
+impl StorefrontPurchase {
+    pub fn payload(&self) -> Vec<u8> {
+        let mut payload = vec![PURCHASE_PAYLOAD_ID];
+        payload.extend_from_slice(self.buyer.as_ref());
+        payload.extend_from_slice(self.referrer.as_ref());
+        payload.extend_from_slice(&self.amount.to_be_bytes());
+        if let Some(integrator) = self.integrator {
+            payload.extend_from_slice(integrator.as_ref());
+        }
+        payload
+    }
+    
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        let (&id, rest) = payload.split_first()?;
+        if id != PURCHASE_PAYLOAD_ID || (rest.len() != 72 && rest.len() != 104) {
+            return None;
+        }
+        Some(Self {
+            buyer: Pubkey::try_from(&rest[..32]).ok()?,
+            referrer: Pubkey::try_from(&rest[32..64]).ok()?,
+            amount: u64::from_be_bytes(rest[64..72].try_into().ok()?),
+            integrator: rest.get(72..).filter(|key| !key.is_empty()).map(Pubkey::try_from).transpose().ok()?,
+        })
+    }
+}
+
+// A message the core bridge verified, as stored in its PostedVAA account
+// after the "vaa" prefix
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PostedVaa {
+    pub vaa_version: u8,
+    pub consistency_level: u8,
+    pub vaa_time: u32,
+    pub vaa_signature_account: Pubkey,
+    pub submission_time: u32,
+    pub nonce: u32,
+    pub sequence: u64,
+    pub emitter_chain: u16,
+    pub emitter_address: [u8; 32],
+    pub payload: Vec<u8>,
+}
+
+// What export_config_hash hashes and restore_instance loads
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct InstanceSnapshot {
+    pub source: Pubkey,
+    pub config: ContractConfig,
+    pub total_sold: u64,
+    pub total_commission_distributed: u64,
+    pub referral_count: u64,
+    pub purchase_count: u64,
+    pub tree_root: [u8; 32], // see statements::MigratedPosition for the leaf layout
+    pub positions: u32,      // leaves under the tree root
+}
+
+impl InstanceSnapshot {
+    pub fn hash(&self) -> Result<[u8; 32]> {
+        Ok(hash(&self.try_to_vec()?).to_bytes())
+    }
+}
+
+// Record of a wallet's yield claim in one epoch
+#[account]
+#[derive(InitSpace)]
+pub struct ClaimReceipt {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub solbox: Pubkey,
+    pub wallet: Pubkey,
+    pub epoch: u64,
+    pub amount: u64,
+    pub claimed_at: i64, // 0 until the claim pays out
+    pub bump: u8,
+}
+
+// One pause guardian's vote to reopen the contract
+#[account]
+#[derive(InitSpace)]
+pub struct UnpauseApproval {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub solbox: Pubkey,
+    pub guardian: Pubkey,
+    pub pause_count: u64, // SolBox.pause_count the approval was given for
+    pub approved_at: i64,
+    pub bump: u8,
+}
+
+// Wallet- and storefront-facing description of a package amount
+#[account]
+#[derive(InitSpace)]
+pub struct Package {
+    pub account_type: u8,
+    pub account_version: u8,
+    pub solbox: Pubkey,
+    pub amount: u64,    // entry in ContractConfig.valid_amounts
+    pub category: u8,   // PACKAGE_* category
+    #[max_len(MAX_METADATA_URI_LEN)]
+    pub metadata_uri: String,
+    pub limited: bool,  // sells from `remaining`; false sells without limit
+    pub remaining: u64, // cards left to sell while limited
+    pub low_stock_thresholds: [u64; MAX_LOW_STOCK_THRESHOLDS], // remaining counts emitting LowStockEvent; zero slots unused
+    pub commit_until_slot: u64, // commit-reveal drop: last slot taking commits; 0 when off
+    pub reveal_until_slot: u64, // last slot taking reveals, after which allocate_drop runs
+    pub drop_id: u64,           // bumped by each schedule_drop; commits of other drops are stale
+    pub drop_commits: u64,      // commits taken in the drop, the order of the next one
+    pub drop_cursor: u64,       // order of the next commit allocate_drop walks
+    pub drop_unallocated: u64,  // cards allocate_drop can still hand out
+    pub drop_reserved: u64,     // cards allocated to commits and not yet bought
+    pub bump: u8,
+}
+
+// Account sizes without the 8-byte discriminator. The variable-length
+// parts are sized by the capacity chosen at initialize
+
+impl ContractConfig {
+    pub const fn space(max_amounts: usize) -> usize {
+        Self::INIT_SPACE + max_amounts * 8
+    }
+}
+
+impl ConfigHistory {
+    pub const fn space(max_amounts: usize) -> usize {
+        Self::INIT_SPACE + CONFIG_HISTORY_LEN * max_amounts * 8
+    }
+}
+
+impl User {
+    pub const fn space(max_levels: usize) -> usize {
+        Self::INIT_SPACE + max_levels * 4
+    }
+}
+
+impl SolBox {
+    pub const fn space(max_amounts: usize, max_relationships: usize, max_blacklisted: usize) -> usize {
+        Self::INIT_SPACE +
+            max_amounts * 8 +
+            max_relationships * ReferralRelationship::INIT_SPACE +
+            max_blacklisted * 32
+    }
+}
+
+impl FeatureFlags {
+    // Subsystems check their FEATURE_* bit before running
+    pub fn is_enabled(&self, feature: u64) -> bool {
+        self.flags & feature != 0
+    }
+}
+
+// Operational rollup returned by view_admin_dashboard
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct AdminDashboard {
+    pub paused: bool,
+    pub user_count: u64,
+    pub active_users: u64,
+    pub total_sold: u64,
+    pub total_commission_distributed: u64,
+    pub purchase_count: u64,
+    pub vault_balance: u64, // lamports, including rent
+    pub liabilities: u64,   // escrow plus reserved yield and residuals
+    pub treasury: u64,      // vault balance above rent and liabilities
+    pub staked_lamports: u64,
+    pub pending_receipts: u64,
+    pub escrow_balance: u64,
+    pub holding_tank_len: u64,
+    pub epoch: u64,
+    pub undistributed_yield: u64,
+    pub yield_pool_balance: u64,
+    pub next_yield_epoch: u64,
+    pub residual_balance: u64,
+    pub tranche_balance: u64,
+}
+
+// Support entitlement of a user, as verified by verify_tier
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct SupportTierView {
+    pub user: Pubkey,
+    pub package: u64,
+    pub support_tier: u8,
+}
+
+// Where the next signup under a referrer would be placed
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct PlacementPreview {
+    pub referrer: Pubkey,
+    pub placed_under: Pubkey,   // referrer after spillover placement
+    pub placed_under_cycle: u8, // which of their positions the signup lands under
+    pub is_spillover: bool,
+    pub direct_referrals: u64,  // signups already placed under `placed_under`
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, InitSpace)]
+pub struct ReferralRelationship {
+    pub user: Pubkey,
+    pub referrer: Pubkey,
+    pub timestamp: i64,
+    pub cycle: u8,          // position of `user`; bumped each time they recycle
+    pub referrer_cycle: u8, // position of `referrer` this one sits under
+    pub set_off_recycle: bool, // placing this position recycled full ones above it
+}
+
+// A signup waiting for their sponsor to place them in the matrix
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, InitSpace)]
+pub struct HoldingTankEntry {
+    pub user: Pubkey,
+    pub sponsor: Pubkey,
+    pub entered_at: i64,
+}
+
+// Room reserved at initialize. Relationships and blacklist entries beyond
+// it need expand_solbox; amounts are capped because ConfigHistory keeps a
+// copy of the config per version and cannot grow, and levels because every
+// User account is allocated with room for them
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Capacity {
+    pub max_amounts: u8,        // entries config.valid_amounts may hold
+    pub max_levels: u8,         // config.commission_levels may reach, up to MAX_COMMISSION_LEVELS
+    pub max_relationships: u16, // referral relationships
+    pub max_blacklisted: u16,   // blacklisted wallets
+}
+
+// A matrix position: users hold one position per cycle
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Position {
+    pub user: Pubkey,
+    pub cycle: u8,
+}
+
+#[derive(Accounts)]
+#[instruction(founder_wallet: Pubkey, config: ContractConfig, sandbox: bool, capacity: Capacity)]
+pub struct Initialize<'info> {
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + SolBox::space(
+            capacity.max_amounts as usize,
+            capacity.max_relationships as usize,
+            capacity.max_blacklisted as usize
+        )
+    )]
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + ConfigHistory::space(capacity.max_amounts as usize),
+        seeds = [b"config_history", solbox.key().as_ref()],
+        bump
+    )]
+    pub config_history: Account<'info, ConfigHistory>,
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + BlacklistFilter::INIT_SPACE,
+        seeds = [b"blacklist_filter", solbox.key().as_ref()],
+        bump
+    )]
+    pub blacklist_filter: Account<'info, BlacklistFilter>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterUser<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        init,
+        payer = wallet,
+        space = 8 + User::space(solbox.max_levels as usize),
+        seeds = [b"user", solbox.key().as_ref(), wallet.key().as_ref()],
+        bump
+    )]
+    pub user_account: Account<'info, User>,
+    #[account(mut)]
+    pub wallet: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(wallet: Pubkey)]
+pub struct SponsorRegistration<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        seeds = [b"blacklist_filter", solbox.key().as_ref()],
+        bump = blacklist_filter.bump
+    )]
+    pub blacklist_filter: Account<'info, BlacklistFilter>,
+    #[account(
+        init,
+        payer = sponsor,
+        space = 8 + User::space(solbox.max_levels as usize),
+        seeds = [b"user", solbox.key().as_ref(), wallet.as_ref()],
+        bump
+    )]
+    pub user_account: Account<'info, User>,
+    #[account(
+        seeds = [b"user", solbox.key().as_ref(), sponsor.key().as_ref()],
+        bump = sponsor_user_account.bump
+    )]
+    pub sponsor_user_account: Account<'info, User>,
+    #[account(mut)]
+    pub sponsor: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptSponsorship<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"user", solbox.key().as_ref(), wallet.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, User>,
+    pub wallet: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct PublishTerms<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptTerms<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"user", solbox.key().as_ref(), wallet.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, User>,
+    pub wallet: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeFounderWallet<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptFounderWallet<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    pub new_founder: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigureBoosts<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetBrandCode<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ActivateBoost<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        init_if_needed,
+        payer = wallet,
+        space = 8 + Boost::INIT_SPACE,
+        seeds = [b"boost", solbox.key().as_ref(), wallet.key().as_ref()],
+        bump
+    )]
+    pub boost: Account<'info, Boost>,
+    #[account(
+        mut,
+        address = solbox.loyalty_mint @ CustomError::InvalidBoost
+    )]
+    pub loyalty_mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        token::mint = loyalty_mint,
+        token::authority = wallet
+    )]
+    pub token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub wallet: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"config_history", solbox.key().as_ref()],
+        bump = config_history.bump
+    )]
+    pub config_history: Account<'info, ConfigHistory>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AdminAction<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(mut)]
+    pub user: Account<'info, User>,
+    pub system_program: Program<'info, System>,
+    // Allowlisted integrator and its authority when called through CPI
+    pub integrator: Option<Account<'info, Integrator>>,
+    pub cpi_authority: Option<Signer<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct PlaceUser<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    pub sponsor: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateHousePosition<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    /// CHECK: address of the house position; never holds funds or signs
+    #[account(
+        seeds = [b"house", solbox.key().as_ref(), &[solbox.house_positions.len() as u8]],
+        bump
+    )]
+    pub house: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + User::space(solbox.max_levels as usize),
+        seeds = [b"user", solbox.key().as_ref(), house.key().as_ref()],
+        bump
+    )]
+    pub house_account: Account<'info, User>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AutoPlace<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    pub cranker: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateBlacklist<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"blacklist_filter", solbox.key().as_ref()],
+        bump = blacklist_filter.bump
+    )]
+    pub blacklist_filter: Account<'info, BlacklistFilter>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpgradePackage<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        seeds = [b"blacklist_filter", solbox.key().as_ref()],
+        bump = blacklist_filter.bump
+    )]
+    pub blacklist_filter: Account<'info, BlacklistFilter>,
+    #[account(
+        mut,
+        seeds = [b"user", solbox.key().as_ref(), wallet.key().as_ref()],
+        bump = user.bump
+    )]
+    pub user: Account<'info, User>,
+    // Pays the difference; the User PDA is program-owned and can't
+    #[account(mut, address = user.key @ CustomError::Unauthorized)]
+    pub wallet: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64)]
+pub struct BuyGiftCard<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        seeds = [b"blacklist_filter", solbox.key().as_ref()],
+        bump = blacklist_filter.bump
+    )]
+    pub blacklist_filter: Account<'info, BlacklistFilter>,
+    // Signs unless the purchase is made with a session key
+    #[account(mut)]
+    pub user: SystemAccount<'info>,
+    // The buyer, or the session key acting for them
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"user", solbox.key().as_ref(), user.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, User>,
+    #[account(mut)]
+    pub referrer: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"user", solbox.key().as_ref(), referrer.key().as_ref()],
+        bump = referrer_user_account.bump
+    )]
+    pub referrer_user_account: Account<'info, User>,
+    // Read from state on every purchase so a rotated wallet takes effect at once
+    #[account(mut, address = solbox.founder_wallet @ CustomError::InvalidFounderWallet)]
+    pub founder: SystemAccount<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + Receipt::INIT_SPACE,
+        seeds = [b"receipt", solbox.key().as_ref(), &solbox.purchase_count.to_le_bytes()],
+        bump
+    )]
+    pub receipt: Account<'info, Receipt>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + DailyStats::INIT_SPACE,
+        seeds = [b"daily_stats", solbox.key().as_ref(), &day_seed(&solbox)?[..]],
+        bump
+    )]
+    pub daily_stats: Account<'info, DailyStats>,
+    #[account(mut)]
+    pub integrator: Option<Account<'info, Integrator>>,
+    #[account(mut)]
+    pub integrator_wallet: Option<SystemAccount<'info>>,
+    #[account(mut)]
+    pub attribution: Option<Account<'info, Attribution>>,
+    #[account(mut)]
+    pub session_key: Option<Account<'info, SessionKey>>,
+    pub system_program: Program<'info, System>,
+    // Signs for the integrator when called through CPI
+    pub cpi_authority: Option<Signer<'info>>,
+    // Boost of the referrer, raising their commission while active
+    pub referrer_boost: Option<Account<'info, Boost>>,
+    // Proof-of-humanity verifier, co-signing large first purchases
+    pub humanity_verifier: Option<Signer<'info>>,
+    // Releases a commission above drip_threshold, see release_tranche
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + Tranche::INIT_SPACE,
+        seeds = [b"tranche", solbox.key().as_ref(), &solbox.purchase_count.to_le_bytes()],
+        bump
+    )]
+    pub tranche: Option<Account<'info, Tranche>>,
+    // Package PDA of the amount, required while any package is limited
+    #[account(
+        mut,
+        seeds = [b"package", solbox.key().as_ref(), &amount.to_le_bytes()],
+        bump = package.bump
+    )]
+    pub package: Option<Account<'info, Package>>,
+    // Allocated commit of a commit-reveal drop, closed to the buyer once
+    // it has bought its cards
+    #[account(mut)]
+    pub drop_commit: Option<Account<'info, DropCommit>>,
+}
+
+#[derive(Accounts)]
+#[instruction(session_key: Pubkey)]
+pub struct CreateSessionKey<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + SessionKey::INIT_SPACE,
+        seeds = [b"session_key", solbox.key().as_ref(), owner.key().as_ref(), session_key.as_ref()],
+        bump
+    )]
+    pub session: Account<'info, SessionKey>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeSessionKey<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"session_key", solbox.key().as_ref(), owner.key().as_ref(), session.session_key.as_ref()],
+        bump = session.bump
+    )]
+    pub session: Account<'info, SessionKey>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(nonce_hash: [u8; 32])]
+pub struct RecordAttribution<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        init,
+        payer = recorder,
+        space = 8 + Attribution::INIT_SPACE,
+        seeds = [b"attribution", solbox.key().as_ref(), nonce_hash.as_ref()],
+        bump
+    )]
+    pub attribution: Account<'info, Attribution>,
+    #[account(mut)]
+    pub recorder: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(identifier_hash: [u8; 32])]
+pub struct CreateInvitation<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        init,
+        payer = referrer,
+        space = 8 + Invitation::INIT_SPACE,
+        seeds = [b"invitation", solbox.key().as_ref(), identifier_hash.as_ref()],
+        bump
+    )]
+    pub invitation: Account<'info, Invitation>,
+    // Only registered wallets invite
+    #[account(
+        seeds = [b"user", solbox.key().as_ref(), referrer.key().as_ref()],
+        bump = referrer_user_account.bump
+    )]
+    pub referrer_user_account: Account<'info, User>,
+    #[account(mut)]
+    pub referrer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimInvitation<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"invitation", solbox.key().as_ref(), invitation.identifier_hash.as_ref()],
+        bump = invitation.bump
+    )]
+    pub invitation: Account<'info, Invitation>,
+    #[account(
+        mut,
+        seeds = [b"user", solbox.key().as_ref(), wallet.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, User>,
+    pub wallet: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(purchase_id: u64)]
+pub struct AttestFiatPurchase<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        seeds = [b"integrator", solbox.key().as_ref(), integrator.name_hash.as_ref()],
+        bump = integrator.bump
+    )]
+    pub integrator: Account<'info, Integrator>,
+    #[account(
+        seeds = [b"receipt", solbox.key().as_ref(), &purchase_id.to_le_bytes()],
+        bump = receipt.bump
+    )]
+    pub receipt: Account<'info, Receipt>,
+    #[account(
+        init,
+        payer = attester,
+        space = 8 + FiatAttestation::INIT_SPACE,
+        seeds = [b"fiat_attestation", solbox.key().as_ref(), &purchase_id.to_le_bytes()],
+        bump
+    )]
+    pub attestation: Account<'info, FiatAttestation>,
+    #[account(mut)]
+    pub attester: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SettlePurchase<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"receipt", solbox.key().as_ref(), &receipt.purchase_id.to_le_bytes()],
+        bump = receipt.bump
+    )]
+    pub receipt: Account<'info, Receipt>,
+    #[account(mut, address = receipt.payee @ CustomError::InvalidReferrer)]
+    pub referrer: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"user", solbox.key().as_ref(), receipt.payee.as_ref()],
+        bump = referrer_user_account.bump
+    )]
+    pub referrer_user_account: Account<'info, User>,
+    #[account(
+        mut,
+        seeds = [b"user", solbox.key().as_ref(), receipt.buyer.as_ref()],
+        bump = buyer_user_account.bump
+    )]
+    pub buyer_user_account: Account<'info, User>,
+    #[account(mut, address = solbox.founder_wallet @ CustomError::InvalidFounderWallet)]
+    pub founder: SystemAccount<'info>,
+    pub integrator: Option<Account<'info, Integrator>>,
+    #[account(mut)]
+    pub integrator_wallet: Option<SystemAccount<'info>>,
+    #[account(
+        mut,
+        seeds = [b"tranche", solbox.key().as_ref(), &receipt.purchase_id.to_le_bytes()],
+        bump = tranche.bump
+    )]
+    pub tranche: Option<Account<'info, Tranche>>,
+}
+
+#[derive(Accounts)]
+#[instruction(sequence: u64)]
+pub struct RedeemWormholePurchase<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        seeds = [b"blacklist_filter", solbox.key().as_ref()],
+        bump = blacklist_filter.bump
+    )]
+    pub blacklist_filter: Account<'info, BlacklistFilter>,
+    /// CHECK: contents checked by read_posted_vaa
+    #[account(owner = WORMHOLE_CORE_BRIDGE_ID @ CustomError::InvalidVaa)]
+    pub posted_vaa: UncheckedAccount<'info>,
+    // One per storefront message, so it is only credited once. The
+    // storefront's chain and emitter are part of the seeds, so a new
+    // storefront's sequences start over
+    #[account(
+        init,
+        payer = relayer,
+        space = 8 + WormholePurchase::INIT_SPACE,
+        seeds = [
+            b"wormhole_purchase",
+            solbox.key().as_ref(),
+            &solbox.config.storefront_chain.to_le_bytes(),
+            solbox.config.storefront_emitter.as_ref(),
+            &sequence.to_le_bytes()
+        ],
+        bump
+    )]
+    pub redemption: Account<'info, WormholePurchase>,
+    // Gets back the rent of a spent drop commit
+    #[account(mut)]
+    pub buyer: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"user", solbox.key().as_ref(), buyer.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, User>,
+    pub referrer: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"user", solbox.key().as_ref(), referrer.key().as_ref()],
+        bump = referrer_user_account.bump
+    )]
+    pub referrer_user_account: Account<'info, User>,
+    #[account(mut)]
+    pub relayer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    #[account(
+        init_if_needed,
+        payer = relayer,
+        space = 8 + DailyStats::INIT_SPACE,
+        seeds = [b"daily_stats", solbox.key().as_ref(), &day_seed(&solbox)?[..]],
+        bump
+    )]
+    pub daily_stats: Account<'info, DailyStats>,
+    // Integrator the storefront message names, if any
+    #[account(mut)]
+    pub integrator: Option<Account<'info, Integrator>>,
+    // Proof-of-humanity verifier, co-signing large first purchases
+    pub humanity_verifier: Option<Signer<'info>>,
+    // Package PDA of the message's amount, required while any package is
+    // limited; the amount is only known from the message
+    #[account(mut)]
+    pub package: Option<Account<'info, Package>>,
+    // Allocated commit of a commit-reveal drop, closed to the buyer once
+    // it has bought its cards
+    #[account(mut)]
+    pub drop_commit: Option<Account<'info, DropCommit>>,
+}
+
+#[derive(Accounts)]
+pub struct ReleaseTranche<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"tranche", solbox.key().as_ref(), &tranche.purchase_id.to_le_bytes()],
+        bump = tranche.bump
+    )]
+    pub tranche: Account<'info, Tranche>,
+    #[account(mut, address = tranche.wallet @ CustomError::Unauthorized)]
+    pub wallet: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ReleaseTranches<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(mut)]
+    pub wallet: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelPurchase<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"receipt", solbox.key().as_ref(), &receipt.purchase_id.to_le_bytes()],
+        bump = receipt.bump
+    )]
+    pub receipt: Account<'info, Receipt>,
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"user", solbox.key().as_ref(), buyer.key().as_ref()],
+        bump = buyer_user_account.bump
+    )]
+    pub buyer_user_account: Account<'info, User>,
+    #[account(
+        mut,
+        seeds = [b"user", solbox.key().as_ref(), receipt.payee.as_ref()],
+        bump = referrer_user_account.bump
+    )]
+    pub referrer_user_account: Account<'info, User>,
+    #[account(
+        mut,
+        seeds = [b"daily_stats", solbox.key().as_ref(), &purchase_day_seed(&receipt)[..]],
+        bump = daily_stats.bump
+    )]
+    pub daily_stats: Account<'info, DailyStats>,
+    // Integrator the purchase was routed through, whose usage is refunded
+    #[account(mut)]
+    pub integrator: Option<Account<'info, Integrator>>,
+    // Package PDA a limited sale took its unit from
+    #[account(mut, address = receipt.package @ CustomError::PackageRequired)]
+    pub package: Option<Account<'info, Package>>,
+}
+
+#[derive(Accounts)]
+#[instruction(purchase_id: u64)]
+pub struct AdminRefund<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"receipt", solbox.key().as_ref(), &purchase_id.to_le_bytes()],
+        bump = receipt.bump
+    )]
+    pub receipt: Account<'info, Receipt>,
+    pub admin: Signer<'info>,
+    #[account(mut, address = receipt.buyer)]
+    pub buyer: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"user", solbox.key().as_ref(), receipt.buyer.as_ref()],
+        bump = buyer_user_account.bump
+    )]
+    pub buyer_user_account: Account<'info, User>,
+    #[account(
+        mut,
+        seeds = [b"user", solbox.key().as_ref(), receipt.payee.as_ref()],
+        bump = referrer_user_account.bump
+    )]
+    pub referrer_user_account: Account<'info, User>,
+    // Package PDA a limited sale took its unit from
+    #[account(mut, address = receipt.package @ CustomError::PackageRequired)]
+    pub package: Option<Account<'info, Package>>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeEmergencyWithdraw<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteEmergencyWithdraw<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    pub admin: Signer<'info>,
+    #[account(mut, address = solbox.emergency_recipient @ CustomError::InvalidEmergencyRecipient)]
+    pub recipient: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawTreasury<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    pub admin: Signer<'info>,
+    #[account(mut)]
+    pub recipient: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeColdTreasury<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + ColdTreasury::INIT_SPACE,
+        seeds = [b"cold_treasury", solbox.key().as_ref()],
+        bump
+    )]
+    pub cold_treasury: Account<'info, ColdTreasury>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SweepTreasury<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"cold_treasury", solbox.key().as_ref()],
+        bump = cold_treasury.bump
+    )]
+    pub cold_treasury: Account<'info, ColdTreasury>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeColdWithdrawal<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"cold_treasury", solbox.key().as_ref()],
+        bump = cold_treasury.bump
+    )]
+    pub cold_treasury: Account<'info, ColdTreasury>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveColdWithdrawal<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"cold_treasury", solbox.key().as_ref()],
+        bump = cold_treasury.bump
+    )]
+    pub cold_treasury: Account<'info, ColdTreasury>,
+    pub guardian: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteColdWithdrawal<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"cold_treasury", solbox.key().as_ref()],
+        bump = cold_treasury.bump
+    )]
+    pub cold_treasury: Account<'info, ColdTreasury>,
+    pub admin: Signer<'info>,
+    #[account(mut, address = cold_treasury.pending_recipient @ CustomError::InvalidEmergencyRecipient)]
+    pub recipient: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(additional_bytes: u32)]
+pub struct ExpandSolBox<'info> {
+    #[account(
+        mut,
+        realloc = solbox.to_account_info().data_len() + additional_bytes as usize,
+        realloc::payer = admin,
+        realloc::zero = true
+    )]
+    pub solbox: Account<'info, SolBox>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(additional_bytes: u32)]
+pub struct ExpandUser<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        realloc = user.to_account_info().data_len() + additional_bytes as usize,
+        realloc::payer = admin,
+        realloc::zero = true
+    )]
+    pub user: Account<'info, User>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(name_hash: [u8; 32])]
+pub struct RegisterIntegrator<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + Integrator::INIT_SPACE,
+        seeds = [b"integrator", solbox.key().as_ref(), name_hash.as_ref()],
+        bump
+    )]
+    pub integrator: Account<'info, Integrator>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateIntegrator<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"integrator", solbox.key().as_ref(), integrator.name_hash.as_ref()],
+        bump = integrator.bump
+    )]
+    pub integrator: Account<'info, Integrator>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeFeatureFlags<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + FeatureFlags::INIT_SPACE,
+        seeds = [b"feature_flags", solbox.key().as_ref()],
+        bump
+    )]
+    pub feature_flags: Account<'info, FeatureFlags>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateFeatureFlags<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"feature_flags", solbox.key().as_ref()],
+        bump = feature_flags.bump
+    )]
+    pub feature_flags: Account<'info, FeatureFlags>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ApplyFeatureFlags<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"feature_flags", solbox.key().as_ref()],
+        bump = feature_flags.bump
+    )]
+    pub feature_flags: Account<'info, FeatureFlags>,
+}
+
+#[derive(Accounts)]
+#[instruction(source: Pubkey)]
+pub struct LinkTenant<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + TenantLink::INIT_SPACE,
+        seeds = [b"tenant_link", solbox.key().as_ref(), source.as_ref()],
+        bump
+    )]
+    pub tenant_link: Account<'info, TenantLink>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExportUserProof<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        seeds = [b"user", solbox.key().as_ref(), user_account.key.as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, User>,
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + UserProof::INIT_SPACE,
+        seeds = [b"user_proof", solbox.key().as_ref(), user_account.key.as_ref()],
+        bump
+    )]
+    pub user_proof: Account<'info, UserProof>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ImportUser<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        seeds = [b"tenant_link", solbox.key().as_ref(), user_proof.solbox.as_ref()],
+        bump = tenant_link.bump
+    )]
+    pub tenant_link: Account<'info, TenantLink>,
+    #[account(
+        seeds = [b"user_proof", user_proof.solbox.as_ref(), wallet.key().as_ref()],
+        bump = user_proof.bump
+    )]
+    pub user_proof: Account<'info, UserProof>,
+    #[account(
+        mut,
+        seeds = [b"user", solbox.key().as_ref(), wallet.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, User>,
+    pub wallet: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CommitMigrationRoot<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + Migration::INIT_SPACE,
+        seeds = [b"migration", solbox.key().as_ref()],
+        bump
+    )]
+    pub migration: Account<'info, Migration>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExportConfigHash<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + ConfigSnapshot::INIT_SPACE,
+        seeds = [b"config_snapshot", solbox.key().as_ref()],
+        bump
+    )]
+    pub config_snapshot: Account<'info, ConfigSnapshot>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveRestore<'info> {
+    // The fresh instance to restore into
+    pub solbox: Account<'info, SolBox>,
+    #[account(mut)]
+    pub config_snapshot: Account<'info, ConfigSnapshot>,
+    pub guardian: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RestoreInstance<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"config_history", solbox.key().as_ref()],
+        bump = config_history.bump
+    )]
+    pub config_history: Account<'info, ConfigHistory>,
+    #[account(mut)]
+    pub config_snapshot: Account<'info, ConfigSnapshot>,
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + Migration::INIT_SPACE,
+        seeds = [b"migration", solbox.key().as_ref()],
+        bump
+    )]
+    pub migration: Account<'info, Migration>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimMigratedPosition<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"migration", solbox.key().as_ref()],
+        bump = migration.bump
+    )]
+    pub migration: Account<'info, Migration>,
+    #[account(
+        mut,
+        seeds = [b"user", solbox.key().as_ref(), wallet.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, User>,
+    pub wallet: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct StakePoolAction<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    /// CHECK: system-owned PDA that funds pool deposits and signs for pool tokens
+    #[account(
+        mut,
+        seeds = [b"stake_authority", solbox.key().as_ref()],
+        bump
+    )]
+    pub stake_authority: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        token::mint = pool_mint,
+        token::authority = stake_authority
+    )]
+    pub pool_token_account: Account<'info, TokenAccount>,
+    /// CHECK: must match config; validated by the stake pool program
+    #[account(mut, address = solbox.config.stake_pool)]
+    pub stake_pool: UncheckedAccount<'info>,
+    /// CHECK: validated by the stake pool program
+    pub stake_pool_withdraw_authority: UncheckedAccount<'info>,
+    /// CHECK: validated by the stake pool program
+    #[account(mut)]
+    pub reserve_stake: UncheckedAccount<'info>,
+    /// CHECK: validated by the stake pool program
+    #[account(mut)]
+    pub manager_fee_account: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub pool_mint: Account<'info, Mint>,
+    pub admin: Signer<'info>,
+    pub clock: Sysvar<'info, Clock>,
+    /// CHECK: address checked
+    #[account(address = sysvar::stake_history::ID)]
+    pub stake_history: UncheckedAccount<'info>,
+    /// CHECK: address checked
+    #[account(address = stake::program::ID)]
+    pub stake_program: UncheckedAccount<'info>,
+    /// CHECK: address checked
+    #[account(address = SPL_STAKE_POOL_PROGRAM_ID)]
+    pub stake_pool_program: UncheckedAccount<'info>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DistributeYield<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    pub cranker: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(epoch: u64)]
+pub struct ClaimYield<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"user", solbox.key().as_ref(), wallet.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, User>,
+    #[account(
+        init_if_needed,
+        payer = wallet,
+        space = 8 + ClaimReceipt::INIT_SPACE,
+        seeds = [b"claim_receipt", solbox.key().as_ref(), wallet.key().as_ref(), &epoch.to_le_bytes()],
+        bump
+    )]
+    pub claim_receipt: Account<'info, ClaimReceipt>,
+    #[account(mut)]
+    pub wallet: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PayQueuedPayout<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"user", solbox.key().as_ref(), wallet.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, User>,
+    #[account(mut)]
+    pub wallet: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct FlushFounderShare<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(mut, address = solbox.founder_wallet @ CustomError::InvalidFounderWallet)]
+    pub founder: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimResidual<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"user", solbox.key().as_ref(), wallet.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, User>,
+    #[account(mut)]
+    pub wallet: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(wallet: Pubkey)]
+pub struct RegisterMerchant<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + Merchant::INIT_SPACE,
+        seeds = [b"merchant", solbox.key().as_ref(), wallet.as_ref()],
+        bump
+    )]
+    pub merchant: Account<'info, Merchant>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RedeemToMerchant<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"user", solbox.key().as_ref(), wallet.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, User>,
+    pub wallet: Signer<'info>,
+    // Seeded by the merchant wallet, so only that wallet can co-sign
+    #[account(
+        mut,
+        seeds = [b"merchant", solbox.key().as_ref(), merchant_wallet.key().as_ref()],
+        bump = merchant.bump
+    )]
+    pub merchant: Account<'info, Merchant>,
+    #[account(
+        init_if_needed,
+        payer = merchant_wallet,
+        space = 8 + MerchantMonth::INIT_SPACE,
+        seeds = [b"merchant_month", merchant.key().as_ref(), &month_seed(&solbox)?[..]],
+        bump
+    )]
+    pub merchant_month: Account<'info, MerchantMonth>,
+    #[account(mut)]
+    pub merchant_wallet: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExportClaimVoucher<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"user", solbox.key().as_ref(), wallet.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, User>,
+    #[account(mut)]
+    pub wallet: Signer<'info>,
+    /// CHECK: core bridge config; validated by the bridge
+    #[account(mut, seeds = [b"Bridge"], bump, seeds::program = WORMHOLE_CORE_BRIDGE_ID)]
+    pub bridge_config: UncheckedAccount<'info>,
+    /// CHECK: message account the bridge creates, one per voucher
+    #[account(
+        mut,
+        seeds = [b"voucher", solbox.key().as_ref(), &solbox.voucher_count.to_le_bytes()],
+        bump
+    )]
+    pub message: UncheckedAccount<'info>,
+    /// CHECK: PDA signing as the SolBox's Wormhole emitter
+    #[account(seeds = [b"emitter", solbox.key().as_ref()], bump)]
+    pub emitter: UncheckedAccount<'info>,
+    /// CHECK: emitter sequence; validated by the bridge
+    #[account(mut, seeds = [b"Sequence", emitter.key().as_ref()], bump, seeds::program = WORMHOLE_CORE_BRIDGE_ID)]
+    pub sequence: UncheckedAccount<'info>,
+    /// CHECK: bridge fee collector; validated by the bridge
+    #[account(mut, seeds = [b"fee_collector"], bump, seeds::program = WORMHOLE_CORE_BRIDGE_ID)]
+    pub fee_collector: UncheckedAccount<'info>,
+    /// CHECK: address checked
+    #[account(address = sysvar::clock::ID)]
+    pub clock: UncheckedAccount<'info>,
+    /// CHECK: address checked
+    #[account(address = sysvar::rent::ID)]
+    pub rent: UncheckedAccount<'info>,
+    /// CHECK: address checked
+    #[account(address = WORMHOLE_CORE_BRIDGE_ID)]
+    pub wormhole_program: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(contest_id: u64)]
+pub struct CreateContest<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + Contest::INIT_SPACE,
+        seeds = [b"contest", solbox.key().as_ref(), &contest_id.to_le_bytes()],
+        bump
+    )]
+    pub contest: Account<'info, Contest>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct EnterContest<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        constraint = contest.solbox == solbox.key() @ CustomError::InvalidContest
+    )]
+    pub contest: Account<'info, Contest>,
+    #[account(
+        init,
+        payer = wallet,
+        space = 8 + ContestEntry::INIT_SPACE,
+        seeds = [b"contest_entry", contest.key().as_ref(), wallet.key().as_ref()],
+        bump
+    )]
+    pub entry: Account<'info, ContestEntry>,
+    #[account(
+        seeds = [b"user", solbox.key().as_ref(), wallet.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, User>,
+    #[account(mut)]
+    pub wallet: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FinalizeContest<'info> {
+    #[account(address = contest.solbox @ CustomError::InvalidContest)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(mut)]
+    pub contest: Account<'info, Contest>,
+    #[account(
+        mut,
+        address = contest.creator @ CustomError::InvalidContest
+    )]
+    pub creator: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimContestPrize<'info> {
+    #[account(address = contest.solbox @ CustomError::InvalidContest)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(mut)]
+    pub contest: Account<'info, Contest>,
+    #[account(mut)]
+    pub wallet: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(notice_id: u64)]
+pub struct PostNotice<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + Notice::INIT_SPACE,
+        seeds = [b"notice", solbox.key().as_ref(), &notice_id.to_le_bytes()],
+        bump
+    )]
+    pub notice: Account<'info, Notice>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AckEvents<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"user", solbox.key().as_ref(), wallet.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, User>,
+    pub wallet: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MarkNoticeRead<'info> {
+    #[account(mut)]
+    pub notice: Account<'info, Notice>,
+    pub recipient: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseNotice<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        close = poster,
+        seeds = [b"notice", solbox.key().as_ref(), &notice.notice_id.to_le_bytes()],
+        bump = notice.bump
+    )]
+    pub notice: Account<'info, Notice>,
+    #[account(
+        mut,
+        address = notice.poster @ CustomError::InvalidNotice
+    )]
+    pub poster: SystemAccount<'info>,
+    pub closer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetGuardians<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        seeds = [b"user", solbox.key().as_ref(), wallet.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, User>,
+    #[account(
+        init_if_needed,
+        payer = wallet,
+        space = 8 + Recovery::INIT_SPACE,
+        seeds = [b"recovery", solbox.key().as_ref(), wallet.key().as_ref()],
+        bump
+    )]
+    pub recovery: Account<'info, Recovery>,
+    #[account(mut)]
+    pub wallet: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveRecovery<'info> {
+    #[account(address = recovery.solbox @ CustomError::InvalidGuardians)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(mut)]
+    pub recovery: Account<'info, Recovery>,
+    pub guardian: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelRecovery<'info> {
+    #[account(
+        mut,
+        has_one = wallet @ CustomError::Unauthorized
+    )]
+    pub recovery: Account<'info, Recovery>,
+    pub wallet: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteRecovery<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        seeds = [b"blacklist_filter", solbox.key().as_ref()],
+        bump = blacklist_filter.bump
+    )]
+    pub blacklist_filter: Account<'info, BlacklistFilter>,
+    #[account(
+        mut,
+        close = new_wallet,
+        seeds = [b"recovery", solbox.key().as_ref(), recovery.wallet.as_ref()],
+        bump = recovery.bump,
+        constraint = recovery.pending_wallet == new_wallet.key() @ CustomError::Unauthorized
+    )]
+    pub recovery: Account<'info, Recovery>,
+    #[account(
+        mut,
+        close = new_wallet,
+        seeds = [b"user", solbox.key().as_ref(), recovery.wallet.as_ref()],
+        bump = old_user_account.bump
+    )]
+    pub old_user_account: Account<'info, User>,
+    #[account(
+        init,
+        payer = new_wallet,
+        space = 8 + User::space(solbox.max_levels as usize),
+        seeds = [b"user", solbox.key().as_ref(), new_wallet.key().as_ref()],
+        bump
+    )]
+    pub new_user_account: Account<'info, User>,
+    #[account(mut)]
+    pub new_wallet: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetBeneficiary<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"user", solbox.key().as_ref(), wallet.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, User>,
+    pub wallet: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RequestDeletion<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"user", solbox.key().as_ref(), wallet.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, User>,
+    pub wallet: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(handle_hash: [u8; 32])]
+pub struct RegisterHandle<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"user", solbox.key().as_ref(), wallet.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, User>,
+    #[account(
+        init,
+        payer = wallet,
+        space = 8 + Handle::INIT_SPACE,
+        seeds = [b"handle", solbox.key().as_ref(), handle_hash.as_ref()],
+        bump
+    )]
+    pub handle: Account<'info, Handle>,
+    #[account(mut)]
+    pub wallet: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+// Shared by release_handle (the user) and revoke_handle (the admin)
+#[derive(Accounts)]
+pub struct ReleaseHandle<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"user", solbox.key().as_ref(), user_account.key.as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, User>,
+    #[account(
+        mut,
+        close = wallet,
+        address = user_account.handle @ CustomError::InvalidHandle
+    )]
+    pub handle: Account<'info, Handle>,
+    #[account(mut, address = user_account.key)]
+    pub wallet: SystemAccount<'info>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ProcessDeletion<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        close = wallet,
+        seeds = [b"user", solbox.key().as_ref(), user_account.key.as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, User>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + User::space(solbox.max_levels as usize),
+        seeds = [b"user", solbox.key().as_ref(), &tombstone_seed(&solbox)[..]],
+        bump
+    )]
+    pub tombstone_account: Account<'info, User>,
+    #[account(mut, address = user_account.key)]
+    pub wallet: SystemAccount<'info>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimInheritance<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        seeds = [b"blacklist_filter", solbox.key().as_ref()],
+        bump = blacklist_filter.bump
+    )]
+    pub blacklist_filter: Account<'info, BlacklistFilter>,
+    #[account(
+        mut,
+        close = beneficiary,
+        seeds = [b"user", solbox.key().as_ref(), old_user_account.key.as_ref()],
+        bump = old_user_account.bump
+    )]
+    pub old_user_account: Account<'info, User>,
+    #[account(
+        init,
+        payer = beneficiary,
+        space = 8 + User::space(solbox.max_levels as usize),
+        seeds = [b"user", solbox.key().as_ref(), beneficiary.key().as_ref()],
+        bump
+    )]
+    pub new_user_account: Account<'info, User>,
+    #[account(mut)]
+    pub beneficiary: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(epoch: u64)]
+pub struct AnchorStatements<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + EpochStatements::INIT_SPACE,
+        seeds = [b"statements", solbox.key().as_ref(), &epoch.to_le_bytes()],
+        bump
+    )]
+    pub statements: Account<'info, EpochStatements>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CheckpointTree<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + TreeCheckpoint::INIT_SPACE,
+        seeds = [b"tree_checkpoint", solbox.key().as_ref()],
+        bump
+    )]
+    pub tree_checkpoint: Account<'info, TreeCheckpoint>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct VerifyStatement<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        seeds = [b"statements", solbox.key().as_ref(), &statements.epoch.to_le_bytes()],
+        bump = statements.bump
+    )]
+    pub statements: Account<'info, EpochStatements>,
+}
+
+#[derive(Accounts)]
+pub struct SetPauseGuardians<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveUnpause<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        init_if_needed,
+        payer = guardian,
+        space = 8 + UnpauseApproval::INIT_SPACE,
+        seeds = [b"unpause_approval", solbox.key().as_ref(), guardian.key().as_ref()],
+        bump
+    )]
+    pub approval: Account<'info, UnpauseApproval>,
+    #[account(mut)]
+    pub guardian: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteUnpause<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    pub caller: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct WarpTimeOffset<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateLookupTable<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    /// CHECK: PDA that owns the lookup table; holds no data
+    #[account(seeds = [b"lookup_authority", solbox.key().as_ref()], bump)]
+    pub lookup_authority: UncheckedAccount<'info>,
+    /// CHECK: created by the lookup table program; address checked against the derivation
+    #[account(mut)]
+    pub lookup_table: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    /// CHECK: address checked
+    #[account(address = address_lookup_table::program::ID)]
+    pub address_lookup_table_program: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExtendLookupTable<'info> {
+    pub solbox: Account<'info, SolBox>,
+    /// CHECK: PDA that owns the lookup table; holds no data
+    #[account(seeds = [b"lookup_authority", solbox.key().as_ref()], bump)]
+    pub lookup_authority: UncheckedAccount<'info>,
+    /// CHECK: must be the table recorded on the SolBox
+    #[account(mut, address = solbox.lookup_table @ CustomError::InvalidLookupTable)]
+    pub lookup_table: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    /// CHECK: address checked
+    #[account(address = address_lookup_table::program::ID)]
+    pub address_lookup_table_program: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64)]
+pub struct CreatePackage<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + Package::INIT_SPACE,
+        seeds = [b"package", solbox.key().as_ref(), &amount.to_le_bytes()],
+        bump
+    )]
+    pub package: Account<'info, Package>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdatePackage<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"package", solbox.key().as_ref(), &package.amount.to_le_bytes()],
+        bump = package.bump
+    )]
+    pub package: Account<'info, Package>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetPackageSupply<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"package", solbox.key().as_ref(), &package.amount.to_le_bytes()],
+        bump = package.bump
+    )]
+    pub package: Account<'info, Package>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ScheduleDrop<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"package", solbox.key().as_ref(), &package.amount.to_le_bytes()],
+        bump = package.bump
+    )]
+    pub package: Account<'info, Package>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CommitPurchase<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"package", solbox.key().as_ref(), &package.amount.to_le_bytes()],
+        bump = package.bump
+    )]
+    pub package: Account<'info, Package>,
+    #[account(
+        init,
+        payer = wallet,
+        space = 8 + DropCommit::INIT_SPACE,
+        seeds = [b"drop_commit", package.key().as_ref(), &package.drop_id.to_le_bytes(), wallet.key().as_ref()],
+        bump
+    )]
+    pub drop_commit: Account<'info, DropCommit>,
+    #[account(mut)]
+    pub wallet: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevealPurchase<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        seeds = [b"package", solbox.key().as_ref(), &package.amount.to_le_bytes()],
+        bump = package.bump
+    )]
+    pub package: Account<'info, Package>,
+    // Only the current drop's commit derives here
+    #[account(
+        mut,
+        seeds = [b"drop_commit", package.key().as_ref(), &package.drop_id.to_le_bytes(), wallet.key().as_ref()],
+        bump = drop_commit.bump
+    )]
+    pub drop_commit: Account<'info, DropCommit>,
+    pub wallet: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AllocateDrop<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"package", solbox.key().as_ref(), &package.amount.to_le_bytes()],
+        bump = package.bump
+    )]
+    pub package: Account<'info, Package>,
+}
+
+#[derive(Accounts)]
+pub struct CloseDropCommit<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"package", solbox.key().as_ref(), &package.amount.to_le_bytes()],
+        bump = package.bump
+    )]
+    pub package: Account<'info, Package>,
+    #[account(
+        mut,
+        close = wallet,
+        seeds = [b"drop_commit", package.key().as_ref(), &drop_commit.drop_id.to_le_bytes(), wallet.key().as_ref()],
+        bump = drop_commit.bump
+    )]
+    pub drop_commit: Account<'info, DropCommit>,
+    // Gets back the rent it paid for the commit
+    #[account(mut)]
+    pub wallet: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateCardMint<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        seeds = [b"package", solbox.key().as_ref(), &package.amount.to_le_bytes()],
+        bump = package.bump
+    )]
+    pub package: Account<'info, Package>,
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"card_mint", package.key().as_ref()],
+        bump,
+        mint::decimals = 0,
+        mint::authority = package,
+        mint::token_program = token_program,
+        extensions::permanent_delegate::delegate = package
+    )]
+    pub card_mint: InterfaceAccount<'info, token_interface::Mint>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct IssueCard<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"receipt", solbox.key().as_ref(), &receipt.purchase_id.to_le_bytes()],
+        bump = receipt.bump
+    )]
+    pub receipt: Account<'info, Receipt>,
+    #[account(
+        seeds = [b"package", solbox.key().as_ref(), &receipt.amount.to_le_bytes()],
+        bump = package.bump
+    )]
+    pub package: Account<'info, Package>,
+    #[account(
+        mut,
+        seeds = [b"card_mint", package.key().as_ref()],
+        bump
+    )]
+    pub card_mint: InterfaceAccount<'info, token_interface::Mint>,
+    #[account(
+        mut,
+        token::mint = card_mint,
+        token::token_program = token_program
+    )]
+    pub card_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+    pub buyer: Signer<'info>,
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct RedeemCard<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        seeds = [b"package", solbox.key().as_ref(), &package.amount.to_le_bytes()],
+        bump = package.bump
+    )]
+    pub package: Account<'info, Package>,
+    #[account(
+        mut,
+        seeds = [b"card_mint", package.key().as_ref()],
+        bump
+    )]
+    pub card_mint: InterfaceAccount<'info, token_interface::Mint>,
+    #[account(
+        mut,
+        token::mint = card_mint,
+        token::authority = holder,
+        token::token_program = token_program
+    )]
+    pub card_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"user", solbox.key().as_ref(), holder.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, User>,
+    pub holder: Signer<'info>,
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct ClawBackCard<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        seeds = [b"package", solbox.key().as_ref(), &package.amount.to_le_bytes()],
+        bump = package.bump
+    )]
+    pub package: Account<'info, Package>,
+    #[account(
+        seeds = [b"card_mint", package.key().as_ref()],
+        bump
+    )]
+    pub card_mint: InterfaceAccount<'info, token_interface::Mint>,
+    #[account(
+        mut,
+        token::mint = card_mint,
+        token::token_program = token_program
+    )]
+    pub from: InterfaceAccount<'info, token_interface::TokenAccount>,
+    #[account(
+        mut,
+        token::mint = card_mint,
+        token::token_program = token_program
+    )]
+    pub to: InterfaceAccount<'info, token_interface::TokenAccount>,
+    pub admin: Signer<'info>,
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+#[instruction(tier: u8)]
+pub struct CreateTierMint<'info> {
+    pub solbox: Account<'info, SolBox>,
+    /// CHECK: created and initialized as a Token-2022 mint by the handler
+    #[account(
+        mut,
+        seeds = [b"tier_mint", solbox.key().as_ref(), &[tier]],
+        bump
+    )]
+    pub tier_mint: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(tier: u8)]
+pub struct SyncTierToken<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        seeds = [b"user", solbox.key().as_ref(), wallet.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, User>,
+    pub wallet: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"tier_mint", solbox.key().as_ref(), &[tier]],
+        bump
+    )]
+    pub tier_mint: InterfaceAccount<'info, token_interface::Mint>,
+    #[account(
+        mut,
+        token::mint = tier_mint,
+        token::authority = wallet,
+        token::token_program = token_program
+    )]
+    pub token_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct CreatePositionMint<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        seeds = [b"user", solbox.key().as_ref(), wallet.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, User>,
+    #[account(
+        init,
+        payer = wallet,
+        seeds = [b"position_mint", user_account.key().as_ref()],
+        bump,
+        mint::decimals = 0,
+        mint::authority = user_account,
+        mint::token_program = token_program
+    )]
+    pub position_mint: InterfaceAccount<'info, token_interface::Mint>,
+    #[account(mut)]
+    pub wallet: Signer<'info>,
+    pub token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WrapPosition<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"user", solbox.key().as_ref(), wallet.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, User>,
+    #[account(
+        mut,
+        seeds = [b"position_mint", user_account.key().as_ref()],
+        bump
+    )]
+    pub position_mint: InterfaceAccount<'info, token_interface::Mint>,
+    #[account(
+        mut,
+        token::mint = position_mint,
+        token::token_program = token_program
+    )]
+    pub position_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+    pub wallet: Signer<'info>,
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct UnwrapPosition<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"user", solbox.key().as_ref(), wallet.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, User>,
+    #[account(
+        mut,
+        address = user_account.position_mint @ CustomError::PositionNotWrapped
+    )]
+    pub position_mint: InterfaceAccount<'info, token_interface::Mint>,
+    #[account(
+        mut,
+        token::mint = position_mint,
+        token::authority = wallet,
+        token::token_program = token_program
+    )]
+    pub position_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+    pub wallet: Signer<'info>,
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimPositionResidual<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"user", solbox.key().as_ref(), user_account.key.as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, User>,
+    #[account(address = user_account.position_mint @ CustomError::PositionNotWrapped)]
+    pub position_mint: InterfaceAccount<'info, token_interface::Mint>,
+    #[account(
+        token::mint = position_mint,
+        token::authority = holder,
+        token::token_program = token_program
+    )]
+    pub position_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+    #[account(mut)]
+    pub holder: Signer<'info>,
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct AssertVersion<'info> {
+    // Unused; declare_program! in Anchor 0.30 can't generate an
+    // instruction without accounts
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64, referrer: Pubkey)]
+pub struct SimulatePurchase<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        seeds = [b"blacklist_filter", solbox.key().as_ref()],
+        bump = blacklist_filter.bump
+    )]
+    pub blacklist_filter: Account<'info, BlacklistFilter>,
+    /// CHECK: only its address is read
+    pub buyer: UncheckedAccount<'info>,
+    #[account(
+        seeds = [b"user", solbox.key().as_ref(), buyer.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, User>,
+    #[account(
+        seeds = [b"user", solbox.key().as_ref(), referrer.as_ref()],
+        bump = referrer_user_account.bump
+    )]
+    pub referrer_user_account: Account<'info, User>,
+    // The boost and integrator the purchase would pass
+    pub referrer_boost: Option<Account<'info, Boost>>,
+    pub integrator: Option<Account<'info, Integrator>>,
+}
+
+#[derive(Accounts)]
+pub struct PreviewPlacement<'info> {
+    pub solbox: Account<'info, SolBox>,
+}
+
+#[derive(Accounts)]
+pub struct ValidatePurchaseAccounts<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        seeds = [b"blacklist_filter", solbox.key().as_ref()],
+        bump = blacklist_filter.bump
+    )]
+    pub blacklist_filter: Account<'info, BlacklistFilter>,
+    /// CHECK: only its balance is read
+    pub buyer: UncheckedAccount<'info>,
+    /// CHECK: reported by the handler when it isn't the buyer's User PDA
+    pub user_account: UncheckedAccount<'info>,
+    /// CHECK: only its address is read
+    pub referrer: UncheckedAccount<'info>,
+    /// CHECK: reported by the handler when it isn't the referrer's User PDA
+    pub referrer_user_account: UncheckedAccount<'info>,
+    /// CHECK: compared against the next receipt PDA
+    pub receipt: UncheckedAccount<'info>,
+    /// CHECK: compared against today's DailyStats PDA
+    pub daily_stats: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ViewAdminDashboard<'info> {
+    pub solbox: Account<'info, SolBox>,
+}
+
+#[derive(Accounts)]
+pub struct VerifyState<'info> {
+    pub solbox: Account<'info, SolBox>,
+}
+
+#[derive(Accounts)]
+pub struct VerifyTier<'info> {
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        seeds = [b"user", solbox.key().as_ref(), user_account.key.as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, User>,
+}
+
+#[derive(Accounts)]
+pub struct RenewMembership<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        seeds = [b"blacklist_filter", solbox.key().as_ref()],
+        bump = blacklist_filter.bump
+    )]
+    pub blacklist_filter: Account<'info, BlacklistFilter>,
+    #[account(
+        mut,
+        seeds = [b"user", solbox.key().as_ref(), wallet.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, User>,
+    #[account(mut)]
+    pub wallet: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RefreshTier<'info> {
+    #[account(mut)]
+    pub solbox: Account<'info, SolBox>,
+    #[account(
+        mut,
+        seeds = [b"user", solbox.key().as_ref(), user_account.key.as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, User>,
+}
+
+// Account header. Every account starts with a stable type tag and a layout
+// version right after the Anchor discriminator so indexers can decode
+// historical snapshots. Tags are never reused; fields are only ever appended
+// and fixed-size accounts carry reserved tail bytes to grow into.
+pub const ACCOUNT_TYPE_SOLBOX: u8 = 1;
+pub const ACCOUNT_TYPE_USER: u8 = 2;
+pub const ACCOUNT_TYPE_CONFIG_HISTORY: u8 = 3;
+pub const ACCOUNT_TYPE_RECEIPT: u8 = 4;
+pub const ACCOUNT_TYPE_DAILY_STATS: u8 = 5;
+pub const ACCOUNT_TYPE_BLACKLIST_FILTER: u8 = 6;
+pub const ACCOUNT_TYPE_INTEGRATOR: u8 = 7;
+pub const ACCOUNT_TYPE_ATTRIBUTION: u8 = 8;
+pub const ACCOUNT_TYPE_TENANT_LINK: u8 = 9;
+pub const ACCOUNT_TYPE_USER_PROOF: u8 = 10;
+pub const ACCOUNT_TYPE_SESSION_KEY: u8 = 11;
+pub const ACCOUNT_TYPE_FEATURE_FLAGS: u8 = 12;
+pub const ACCOUNT_TYPE_BOOST: u8 = 13;
+pub const ACCOUNT_TYPE_CONTEST: u8 = 14;
+pub const ACCOUNT_TYPE_CONTEST_ENTRY: u8 = 15;
+pub const ACCOUNT_TYPE_NOTICE: u8 = 16;
+pub const ACCOUNT_TYPE_RECOVERY: u8 = 17;
+pub const ACCOUNT_TYPE_STATEMENTS: u8 = 18;
+pub const ACCOUNT_TYPE_UNPAUSE_APPROVAL: u8 = 19;
+pub const ACCOUNT_TYPE_CLAIM_RECEIPT: u8 = 20;
+pub const ACCOUNT_TYPE_PACKAGE: u8 = 21;
+pub const ACCOUNT_TYPE_FIAT_ATTESTATION: u8 = 22;
+pub const ACCOUNT_TYPE_HANDLE: u8 = 23;
+pub const ACCOUNT_TYPE_MIGRATION: u8 = 24;
+pub const ACCOUNT_TYPE_CONFIG_SNAPSHOT: u8 = 25;
+pub const ACCOUNT_TYPE_TRANCHE: u8 = 26;
+pub const ACCOUNT_TYPE_WORMHOLE_PURCHASE: u8 = 27;
+pub const ACCOUNT_TYPE_DROP_COMMIT: u8 = 28;
+pub const ACCOUNT_TYPE_TREE_CHECKPOINT: u8 = 29;
+pub const ACCOUNT_TYPE_COLD_TREASURY: u8 = 30;
+pub const ACCOUNT_TYPE_INVITATION: u8 = 31;
+pub const ACCOUNT_TYPE_MERCHANT: u8 = 32;
+pub const ACCOUNT_TYPE_MERCHANT_MONTH: u8 = 33;
+
+pub const SOLBOX_VERSION: u8 = 1;
+pub const USER_VERSION: u8 = 1;
+pub const CONFIG_HISTORY_VERSION: u8 = 1;
+pub const RECEIPT_VERSION: u8 = 1;
+pub const DAILY_STATS_VERSION: u8 = 1;
+pub const BLACKLIST_FILTER_VERSION: u8 = 1;
+pub const INTEGRATOR_VERSION: u8 = 1;
+pub const ATTRIBUTION_VERSION: u8 = 1;
+pub const TENANT_LINK_VERSION: u8 = 1;
+pub const USER_PROOF_VERSION: u8 = 1;
+pub const SESSION_KEY_VERSION: u8 = 1;
+pub const FEATURE_FLAGS_VERSION: u8 = 1;
+pub const BOOST_VERSION: u8 = 1;
+pub const CONTEST_VERSION: u8 = 1;
+pub const CONTEST_ENTRY_VERSION: u8 = 1;
+pub const NOTICE_VERSION: u8 = 1;
+pub const RECOVERY_VERSION: u8 = 1;
+pub const STATEMENTS_VERSION: u8 = 1;
+pub const UNPAUSE_APPROVAL_VERSION: u8 = 1;
+pub const CLAIM_RECEIPT_VERSION: u8 = 1;
+pub const PACKAGE_VERSION: u8 = 1;
+pub const FIAT_ATTESTATION_VERSION: u8 = 1;
+pub const HANDLE_VERSION: u8 = 1;
+pub const MIGRATION_VERSION: u8 = 1;
+pub const CONFIG_SNAPSHOT_VERSION: u8 = 1;
+pub const TRANCHE_VERSION: u8 = 1;
+pub const WORMHOLE_PURCHASE_VERSION: u8 = 1;
+pub const DROP_COMMIT_VERSION: u8 = 1;
+pub const TREE_CHECKPOINT_VERSION: u8 = 1;
+pub const COLD_TREASURY_VERSION: u8 = 1;
+pub const INVITATION_VERSION: u8 = 1;
+pub const MERCHANT_VERSION: u8 = 1;
+pub const MERCHANT_MONTH_VERSION: u8 = 1;
+
+// Version of the instruction and account layouts; bump on every upgrade
+// that changes them so stale clients are rejected by assert_version
+pub const INTERFACE_VERSION: u16 = 1;
+
+pub const RECEIPT_RESERVED: usize = 64;
+
+// Reserved padding on SolBox and User; beyond this, grow with expand_* instructions
+pub const RESERVED_SPACE: usize = 128;
+
+// Upper bound for config.commission_levels
+pub const MAX_COMMISSION_LEVELS: usize = 15;
+
+pub const DAILY_STATS_RESERVED: usize = 64;
+
+pub const BPS_DENOMINATOR: u16 = 10_000;
+
+// SPL stake pool program and the instruction tags used for treasury staking
+pub const SPL_STAKE_POOL_PROGRAM_ID: Pubkey = anchor_lang::pubkey!("SPoo1Ku8WFXoNDMHPsrGSTSG1Y47rzgn41SLUNakuHy");
+pub const STAKE_POOL_DEPOSIT_SOL: u8 = 14;
+pub const STAKE_POOL_WITHDRAW_SOL: u8 = 16;
+
+// Wormhole core bridge on devnet, and what claim vouchers post through it.
+// The message fee follows the guardian set index, last lamports and guardian
+// set expiration in the bridge config
+pub const WORMHOLE_CORE_BRIDGE_ID: Pubkey = anchor_lang::pubkey!("3u8hJUVTA4jH1wYAyUur7FFZVQ8H635K3tSHHF4ssjQ5");
+pub const WORMHOLE_POST_MESSAGE: u8 = 1;
+pub const WORMHOLE_CONSISTENCY_FINALIZED: u8 = 1;
+pub const WORMHOLE_FEE_OFFSET: usize = 16;
+pub const VOUCHER_PAYLOAD_ID: u8 = 1;
+pub const PURCHASE_PAYLOAD_ID: u8 = 2;
+
+// Invariants checked by verify_state, reported in InvariantViolationEvent
+pub const INVARIANT_VAULT_COVERAGE: u8 = 1;
+pub const INVARIANT_YIELD_POOL: u8 = 2;
+pub const INVARIANT_ACTIVE_USERS: u8 = 3;
+pub const INVARIANT_ESCROW: u8 = 4;
+pub const INVARIANT_REFERRAL_COUNT: u8 = 5;
+pub const INVARIANT_RECEIPT_REFUND: u8 = 6;
+pub const INVARIANT_PENDING_RECEIPTS: u8 = 7;
+
+// Addresses added per extend_lookup_table call, so the transaction stays
+// within the size limit
+pub const MAX_LOOKUP_EXTEND: usize = 20;
+
+// Upper bound on receipts checked per verify_state call
+pub const MAX_VERIFY_BATCH: usize = 20;
+
+// Upper bound on tranches paid per release_tranches call
+pub const MAX_RELEASE_BATCH: usize = 20;
+
+// Upper bound on commits walked per allocate_drop call
+pub const MAX_DROP_ALLOCATION_BATCH: usize = 20;
+
+// Most cards one drop commit may ask for
+pub const MAX_DROP_QUANTITY: u64 = 10;
+
+// Relationships hashed per checkpoint_tree call
+pub const MAX_CHECKPOINT_BATCH: usize = 32;
+
+// Number of priority support tiers above the base tier
+pub const SUPPORT_TIERS: usize = 3;
+
+// Days of purchase volume a User keeps for rolling tier windows
+pub const VOLUME_WINDOW_DAYS: usize = 30;
+
+// Fixed-point scale for the bonus pool's yield_per_package accumulator
+pub const YIELD_PRECISION: u128 = 1_000_000_000_000;
+
+// Capacity the CLI and tests initialize with: room for the package menu
+// and for the blacklist and referral tree of a small launch
+pub const DEFAULT_CAPACITY: Capacity = Capacity {
+    max_amounts: 8,
+    max_levels: MAX_COMMISSION_LEVELS as u8,
+    max_relationships: 64,
+    max_blacklisted: 32,
+};
+
+// Blacklist bloom filter: 8192 bits probed by 3 hashes
+pub const BLACKLIST_FILTER_BYTES: usize = 1024;
+pub const BLACKLIST_FILTER_HASHES: usize = 3;
+
+pub const MAX_HOLDING_TANK_ENTRIES: usize = 16;
+
+// House positions an admin can add to balance the tree
+pub const MAX_HOUSE_POSITIONS: usize = 16;
+pub const TOMBSTONE_PREFIX: &[u8; 16] = b"solbox:tombstone";
+
+pub const MAX_REFUND_REASON_LEN: usize = 64;
+pub const MAX_CLAWBACK_REASON_LEN: usize = 64;
+// Longest email address; phone numbers fit well within it
+pub const MAX_INVITATION_IDENTIFIER_LEN: usize = 254;
+
+pub const INTEGRATOR_RESERVED: usize = 32;
+
+pub const FEATURE_FLAGS_RESERVED: usize = 32;
+
+// Ranks with a prize per contest
+pub const MAX_CONTEST_PRIZES: usize = 10;
+
+// Entries per contest; finalize_contest loads every entry with its User
+// PDA in one transaction
+pub const MAX_CONTEST_ENTRIES: usize = 24;
+
+pub const MAX_NOTICE_LEN: usize = 280;
+
+// Notice categories frontends can filter on
+pub const NOTICE_GENERAL: u8 = 0;
+pub const NOTICE_CONFIG_CHANGE: u8 = 1;
+pub const NOTICE_CAMPAIGN: u8 = 2;
+
+// Display handle length bounds, in bytes
+pub const MIN_HANDLE_LEN: usize = 3;
+pub const MAX_HANDLE_LEN: usize = 32;
+
+// Longest package metadata URI; fits an https or ipfs/arweave link
+pub const MAX_METADATA_URI_LEN: usize = 64;
+
+// Low-stock thresholds per package
+pub const MAX_LOW_STOCK_THRESHOLDS: usize = 3;
+
+// Package categories wallets and the storefront can group cards by
+pub const PACKAGE_GENERAL: u8 = 0;
+pub const PACKAGE_SEASONAL: u8 = 1;
+pub const PACKAGE_PROMOTIONAL: u8 = 2;
+
+// Guardians per user; approvals are tracked in a u8 bitmask
+pub const MAX_GUARDIANS: usize = 7;
+
+// Delay between guardians reaching the threshold and the wallet moving,
+// leaving the current wallet time to cancel
+pub const RECOVERY_TIMELOCK: i64 = 3 * SECONDS_PER_DAY;
+
+// Delay between guardians approving a restore and the snapshot loading
+pub const RESTORE_TIMELOCK: i64 = 3 * SECONDS_PER_DAY;
+
+// Shortest inactivity period a beneficiary designation may use
+pub const MIN_INACTIVITY_PERIOD: i64 = 90 * SECONDS_PER_DAY;
+
+// Upper bound on the commission a boost adds
+pub const MAX_BOOST_BPS: u16 = 1_000;
+
+// Delay between proposing and applying a feature flag change
+pub const FEATURE_FLAGS_TIMELOCK: i64 = 2 * SECONDS_PER_DAY;
+
+// Delay between proposing and accepting a new founder wallet
+pub const FOUNDER_CHANGE_TIMELOCK: i64 = 2 * SECONDS_PER_DAY;
+
+// Delay between announcing and executing an emergency withdrawal, long
+// enough for users to notice and get their funds out
+pub const EMERGENCY_WITHDRAW_TIMELOCK: i64 = 14 * SECONDS_PER_DAY;
+
+// Delay between guardians approving a cold treasury withdrawal and its
+// execution
+pub const COLD_WITHDRAW_TIMELOCK: i64 = 2 * SECONDS_PER_DAY;
+
+// Feature bits for subsystems rolled out per tenant
+pub const FEATURE_TOKEN_PAYMENTS: u64 = 1 << 0;
+pub const FEATURE_BINARY_MODE: u64 = 1 << 1;
+pub const FEATURE_CNFT_CARDS: u64 = 1 << 2;
+
+// Upper bound on an integrator's revshare of the purchase amount
+pub const MAX_INTEGRATOR_FEE_BPS: u16 = 1_000;
+pub const MAX_CARD_BONUS_BPS: u16 = 2_000;
+pub const MAX_REDEMPTION_FEE_BPS: u16 = 1_000;
+
+// Instructions an integrator may route
+pub const INTEGRATOR_ALLOW_BUY_GIFT_CARD: u32 = 1 << 0;
+pub const INTEGRATOR_ALLOW_GRANT_PACKAGE: u32 = 1 << 1;
+pub const INTEGRATOR_ALLOW_FIAT_ATTESTATION: u32 = 1 << 2;
+
+// Problems validate_purchase_accounts reports about a purchase
+pub const PURCHASE_PROBLEM_PAUSED: u32 = 1 << 0;
+pub const PURCHASE_PROBLEM_INVALID_AMOUNT: u32 = 1 << 1;
+pub const PURCHASE_PROBLEM_EMERGENCY_PENDING: u32 = 1 << 2;
+pub const PURCHASE_PROBLEM_BLACKLISTED: u32 = 1 << 3;
+pub const PURCHASE_PROBLEM_SELF_REFERRAL: u32 = 1 << 4;
+pub const PURCHASE_PROBLEM_INSUFFICIENT_FUNDS: u32 = 1 << 5;
+pub const PURCHASE_PROBLEM_BUYER_NOT_REGISTERED: u32 = 1 << 6;
+pub const PURCHASE_PROBLEM_REFERRER_NOT_REGISTERED: u32 = 1 << 7;
+pub const PURCHASE_PROBLEM_SPONSORSHIP_PENDING: u32 = 1 << 8;
+pub const PURCHASE_PROBLEM_STALE_RECEIPT: u32 = 1 << 9;
+pub const PURCHASE_PROBLEM_STALE_DAY: u32 = 1 << 10;
+pub const PURCHASE_PROBLEM_NEEDS_HUMANITY_PROOF: u32 = 1 << 11;
+pub const PURCHASE_PROBLEM_NO_PLACEMENT: u32 = 1 << 12;
+pub const PURCHASE_PROBLEM_UPLINE_MISSING: u32 = 1 << 13;
+pub const PURCHASE_PROBLEM_UPLINE_MISMATCH: u32 = 1 << 14;
+pub const PURCHASE_PROBLEM_TERMS_OUTDATED: u32 = 1 << 15;
+pub const PURCHASE_PROBLEM_INVITATION_MISMATCH: u32 = 1 << 16;
+
+pub const SECONDS_PER_DAY: i64 = 86_400;
+
+// Register count of the unique buyer sketch (must be a power of two)
+pub const BUYER_SKETCH_REGISTERS: usize = 64;
+
+// Counters in DailyStats.funder_sketch
+pub const FUNDER_SKETCH_WIDTH: usize = 64;
+
+// DailyStats.funder_pairs bloom filter: 2048 bits probed by 3 hashes
+pub const FUNDER_PAIR_FILTER_BYTES: usize = 256;
+pub const FUNDER_PAIR_FILTER_HASHES: usize = 3;
+
+// Reasons in User.flagged
+pub const FRAUD_FLAG_SHARED_FUNDER: u8 = 1; // payer funded purchases for many wallets today
+pub const FRAUD_FLAG_BURST: u8 = 2;         // integrator's cohort bought in a burst
+
+// Seconds an integrator's purchase burst is counted over
+pub const FRAUD_BURST_WINDOW: i64 = 60;
+
+// Number of config versions kept in the history ring buffer
+pub const CONFIG_HISTORY_LEN: usize = 8;
+
+// Events
+#[event]
+pub struct InitializeEvent {
+    pub owner: Pubkey,
+    pub founder_wallet: Pubkey,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct UserRegisteredEvent {
+    pub user: Pubkey,
+    pub user_account: Pubkey,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct ConfigUpdateEvent {
+    pub admin: Pubkey,
+    pub new_config: ContractConfig,
+    pub version: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct PauseEvent {
+    pub admin: Pubkey,
+    pub paused: bool,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct PackageUpgradeEvent {
+    pub user: Pubkey,
+    pub old_package: u64,
+    pub new_package: u64,
+    pub difference: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct GiftCardPurchaseEvent {
+    pub purchase_id: u64,
+    pub user: Pubkey,
+    pub referrer: Pubkey,
+    pub amount: u64,
+    pub commission: u64,
+    pub founder_share: u64,
+    pub integrator: Option<Pubkey>,
+    pub integrator_fee: u64,
+    pub attribution: Option<[u8; 32]>, // nonce hash of the consumed click
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct TenantLinkedEvent {
+    pub admin: Pubkey,
+    pub source: Pubkey,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct UserProofExportedEvent {
+    pub user: Pubkey,
+    pub package: u64,
+    pub lifetime_volume: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct UserImportedEvent {
+    pub user: Pubkey,
+    pub source: Pubkey,
+    pub package: u64,
+    pub lifetime_volume: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct SessionKeyCreatedEvent {
+    pub owner: Pubkey,
+    pub session_key: Pubkey,
+    pub max_lamports: u64,
+    pub expires_at: i64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct SessionKeyRevokedEvent {
+    pub owner: Pubkey,
+    pub session_key: Pubkey,
+    pub spent: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct AttributionRecordedEvent {
+    pub nonce_hash: [u8; 32],
+    pub referrer: Pubkey,
+    pub recorded_by: Pubkey,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct PurchaseSettledEvent {
+    pub purchase_id: u64,
+    pub buyer: Pubkey,
+    pub referrer: Pubkey,
+    pub commission: u64,
+    pub founder_share: u64,
+    pub integrator_fee: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct IntegratorUpdatedEvent {
+    pub admin: Pubkey,
+    pub integrator: Pubkey,
+    pub name_hash: [u8; 32],
+    pub wallet: Pubkey,
+    pub fee_share_bps: u16,
+    pub allowed_instructions: u32,
+    pub cpi_authority: Pubkey,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct IntegratorLimitsEvent {
+    pub admin: Pubkey,
+    pub integrator: Pubkey,
+    pub epoch_volume_limit: u64,
+    pub epoch_purchase_limit: u32,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct InvitationEvent {
+    pub referrer: Pubkey,
+    pub identifier_hash: [u8; 32],
+    pub invitee: Option<Pubkey>, // set when the invitation is claimed
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct PurchaseCancelledEvent {
+    pub purchase_id: u64,
+    pub buyer: Pubkey,
+    pub refunded: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct AdminRefundEvent {
+    pub admin: Pubkey,
+    pub purchase_id: u64,
+    pub buyer: Pubkey,
+    pub amount: u64,
+    pub total_refunded: u64,
+    pub reason: String,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct TreasuryWithdrawnEvent {
+    pub admin: Pubkey,
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub remaining_treasury: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct InvariantViolationEvent {
+    pub invariant: u8, // INVARIANT_* code
+    pub account: Pubkey,
+    pub expected: u64,
+    pub actual: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct StateVerifiedEvent {
+    pub receipts_checked: u32,
+    pub pending_escrow: u64, // escrow of the pending receipts in this batch
+    pub pending_count: u64,
+    pub violations: u32,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct AccountExpandedEvent {
+    pub admin: Pubkey,
+    pub account: Pubkey,
+    pub new_size: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct PlacementEvent {
+    pub user: Pubkey,
+    pub sponsor: Pubkey,
+    pub placed_under: Pubkey,
+    pub placed_under_cycle: u8,
+    pub placed_by: Pubkey,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct CycleEvent {
+    pub user: Pubkey,
+    pub cycle: u8,
+    pub placed_under: Pubkey,
+    pub placed_under_cycle: u8,
+    pub bonus: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct StakeDelegatedEvent {
+    pub admin: Pubkey,
+    pub stake_pool: Pubkey,
+    pub lamports: u64,
+    pub staked_lamports: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct StakeUndelegatedEvent {
+    pub admin: Pubkey,
+    pub stake_pool: Pubkey,
+    pub pool_tokens: u64,
+    pub lamports_received: u64,
+    pub yield_lamports: u64,
+    pub staked_lamports: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct YieldDistributionEvent {
+    pub epoch: u64,
+    pub amount: u64,
+    pub total_package_weight: u64,
+    pub yield_per_package: u128,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct YieldClaimedEvent {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub epoch: u64,
+    pub receipt: Pubkey, // ClaimReceipt PDA, for reconciling payment exports
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct PackageGrantedEvent {
+    pub admin: Pubkey,
+    pub user: Pubkey,
+    pub package: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct CommissionConfigEvent {
+    pub admin: Pubkey,
+    pub new_percentage: u64,
+    pub new_levels: u8,
+    pub version: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub enum BlacklistAction {
+    Add,
+    Remove,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub enum HandleAction {
+    Registered,
+    Released,
+    Revoked,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub enum MembershipAction {
+    Renewed,     // before the lapse, at the full price
+    Reactivated, // within the reactivation window, at the reduced price
+    Lapsed,      // the reactivation window passed and the package was dropped
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub enum ColdWithdrawalAction {
+    Proposed,
+    Approved,
+    Executed,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub enum CardBalanceAction {
+    Converted, // residual claimed to the balance, with its bonus
+    Spent,     // paid for a purchase
+    Refunded,  // a cancelled purchase it paid for
+    Redeemed,  // spent at a merchant, see MerchantRedemptionEvent
+}
+
+#[event]
+pub struct FeatureFlagsProposedEvent {
+    pub admin: Pubkey,
+    pub current_flags: u64,
+    pub pending_flags: u64,
+    pub eta: i64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct FeatureFlagsAppliedEvent {
+    pub previous_flags: u64,
+    pub flags: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct FounderWalletProposedEvent {
+    pub admin: Pubkey,
+    pub current_wallet: Pubkey,
+    pub pending_wallet: Pubkey,
+    pub eta: i64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct FounderWalletChangedEvent {
+    pub previous_wallet: Pubkey,
+    pub new_wallet: Pubkey,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct BoostsConfiguredEvent {
+    pub admin: Pubkey,
+    pub loyalty_mint: Pubkey,
+    pub price: u64,
+    pub bonus_bps: u16,
+    pub duration: i64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct BoostActivatedEvent {
+    pub wallet: Pubkey,
+    pub tokens_burned: u64,
+    pub bonus_bps: u16,
+    pub expires_at: i64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct ContestCreatedEvent {
+    pub contest: Pubkey,
+    pub contest_id: u64,
+    pub start: i64,
+    pub end: i64,
+    pub metric: ContestMetric,
+    pub prize_pool: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct ContestFinalizedEvent {
+    pub contest: Pubkey,
+    pub winners: u32,
+    pub awarded: u64,
+    pub returned: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct ContestPrizeClaimedEvent {
+    pub contest: Pubkey,
+    pub wallet: Pubkey,
+    pub prize: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct TierDecayWarningEvent {
+    pub user: Pubkey,
+    pub missed_periods: u8,
+    pub grace_periods_left: u8,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct TierDecayedEvent {
+    pub user: Pubkey,
+    pub support_tier: u8,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct NoticePostedEvent {
+    pub notice: Pubkey,
+    pub notice_id: u64,
+    pub recipient: Pubkey,
+    pub kind: u8,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct RecoveryApprovedEvent {
+    pub wallet: Pubkey,
+    pub guardian: Pubkey,
+    pub new_wallet: Pubkey,
+    pub approvals: u8,
+    pub recovery_eta: i64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct WalletRecoveredEvent {
+    pub old_wallet: Pubkey,
+    pub new_wallet: Pubkey,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct BeneficiarySetEvent {
+    pub user: Pubkey,
+    pub beneficiary: Pubkey,
+    pub inactivity_period: i64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct InheritanceClaimedEvent {
+    pub old_wallet: Pubkey,
+    pub beneficiary: Pubkey,
+    pub unclaimed_yield: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct StatementsAnchoredEvent {
+    pub epoch: u64,
+    pub root: [u8; 32],
+    pub users: u32,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct PauseGuardiansSetEvent {
+    pub admin: Pubkey,
+    pub guardians: Vec<Pubkey>,
+    pub threshold: u8,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct UnpauseApprovedEvent {
+    pub guardian: Pubkey,
+    pub pause_count: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct ResidualClaimedEvent {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct TimeWarpedEvent {
+    pub admin: Pubkey,
+    pub time_offset: i64,
+    pub epoch_offset: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct LookupTableEvent {
+    pub admin: Pubkey,
+    pub table: Pubkey,
+    pub added: u32, // addresses added; 0 when the table is created
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct PackageMetadataEvent {
+    pub admin: Pubkey,
+    pub package: Pubkey,
+    pub amount: u64,
+    pub category: u8,
+    pub metadata_uri: String,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct FiatPurchaseAttestedEvent {
+    pub integrator: Pubkey,
+    pub receipt: Pubkey,
+    pub buyer: Pubkey,
+    pub amount: u64,
+    pub external_ref: [u8; 32],
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct HandleEvent {
+    pub wallet: Pubkey,
+    pub handle: String,
+    pub action: HandleAction,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct MigrationRootCommittedEvent {
+    pub admin: Pubkey,
+    pub root: [u8; 32],
+    pub positions: u32,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct PositionMigratedEvent {
+    pub user: Pubkey,
+    pub package: u64,
+    pub sponsor: Pubkey,
+    pub lifetime_volume: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct SponsorshipEvent {
+    pub sponsor: Pubkey,
+    pub user: Pubkey,
+    pub accepted: bool, // false when the sponsor registers the wallet
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct MembershipEvent {
+    pub user: Pubkey,
+    pub action: MembershipAction,
+    pub package: u64,
+    pub price: u64,      // paid for the renewal; 0 when lapsed
+    pub paid_until: i64, // new lapse time; the old one when lapsed
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct HouseCommissionEvent {
+    pub house: Pubkey,
+    pub purchase_id: u64,
+    pub amount: u64,
+    pub pooled: bool, // false when yield sharing is off and it stays in the treasury
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct QueuedPayoutEvent {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub paid: bool, // false when the claim was queued, true when the queue was paid out
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct CoverageEvent {
+    pub vault_balance: u64, // lamports above rent
+    pub liabilities: u64,   // escrow, reserved yield, residuals and queued payouts
+    pub coverage_bps: u64,  // vault_balance over liabilities; u64::MAX without liabilities
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct EmergencyWithdrawInitiatedEvent {
+    pub admin: Pubkey,
+    pub recipient: Pubkey,
+    pub vault_balance: u64, // lamports, including rent
+    pub liabilities: u64,   // owed to users and at risk
+    pub eta: i64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct EmergencyWithdrawCancelledEvent {
+    pub admin: Pubkey,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct EmergencyWithdrawExecutedEvent {
+    pub admin: Pubkey,
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub liabilities: u64, // owed to users and left unbacked
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct BrandCodeEvent {
+    pub admin: Pubkey,
+    pub previous: [u8; 8],
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub enum EarningSource {
+    Direct,    // commission on a buyer placed on the earner's own legs
+    Spillover, // commission on a buyer placed further down the matrix
+    Pools,     // yield claimed from the bonus pool
+}
+
+#[event]
+pub struct EarningsEvent {
+    pub user: Pubkey,
+    pub source: EarningSource,
+    pub amount: u64,
+    pub reference: u64, // purchase id, or the epoch of a pool claim
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct DeferredFounderShareEvent {
+    pub founder: Pubkey,
+    pub amount: u64,
+    pub paid: bool, // false when a share was held back, true when the held shares were paid
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub enum CardAction {
+    MintCreated, // the package got its card mint
+    Issued,      // a card was minted for a purchase
+    Redeemed,    // a card was burned for its package
+}
+
+#[event]
+pub struct CardEvent {
+    pub card_mint: Pubkey,
+    pub wallet: Pubkey, // admin, card recipient or redeeming holder
+    pub amount: u64,    // package the card stands for
+    pub action: CardAction,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct CardClawbackEvent {
+    pub admin: Pubkey,
+    pub card_mint: Pubkey,
+    pub from: Pubkey, // owner of the account the cards were taken from
+    pub to: Pubkey,   // owner of the account they were returned to
+    pub units: u64,
+    pub reason: String,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct TierTokenEvent {
+    pub wallet: Pubkey, // holder, or the admin when the mint is created
+    pub tier_mint: Pubkey,
+    pub tier: u8,
+    pub held: bool, // whether the wallet holds the token afterwards
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub enum PositionTokenAction {
+    MintCreated, // the wallet's position mint was created
+    Wrapped,     // the position token was minted
+    Unwrapped,   // the wallet burned it and is paid directly again
+}
+
+#[event]
+pub struct PositionTokenEvent {
+    pub wallet: Pubkey, // wallet the position belongs to
+    pub position_mint: Pubkey,
+    pub holder: Pubkey, // owner of the token account it was minted to or burned from
+    pub action: PositionTokenAction,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct TermsPublishedEvent {
+    pub admin: Pubkey,
+    pub terms_hash: [u8; 32],
+    pub version: u32,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct TermsAcceptedEvent {
+    pub user: Pubkey,
+    pub terms_hash: [u8; 32],
+    pub version: u32,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct TrancheReleasedEvent {
+    pub wallet: Pubkey,
+    pub purchase_id: u64,
+    pub amount: u64,
+    pub released: u64,
+    pub total: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct ConfigExportedEvent {
+    pub admin: Pubkey,
+    pub snapshot: InstanceSnapshot,
+    pub hash: [u8; 32],
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct RestoreApprovedEvent {
+    pub source: Pubkey,
+    pub target: Pubkey,
+    pub guardian: Pubkey,
+    pub approvals: u8,
+    pub restore_eta: i64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct InstanceRestoredEvent {
+    pub admin: Pubkey,
+    pub source: Pubkey,
+    pub hash: [u8; 32],
+    pub config_version: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct LowStockEvent {
+    pub package: Pubkey,
+    pub amount: u64,
+    pub remaining: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct ClaimVoucherEvent {
+    pub wallet: Pubkey,
+    pub voucher_id: u64,
+    pub amount: u64,
+    pub recipient_chain: u16,
+    pub recipient: [u8; 32],
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct WormholePurchaseEvent {
+    pub sequence: u64,
+    pub user: Pubkey,
+    pub referrer: Pubkey,
+    pub amount: u64,
+    pub commission: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct DeletionRequestedEvent {
+    pub user: Pubkey,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct UserDeletedEvent {
+    pub tombstone: Pubkey,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct TreeCheckpointEvent {
+    pub epoch: u64,
+    pub root: [u8; 32],
+    pub relationships: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct TreasurySweptEvent {
+    pub amount: u64,
+    pub hot_treasury: u64,  // vault treasury left above liabilities
+    pub cold_treasury: u64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct ColdWithdrawalEvent {
+    pub action: ColdWithdrawalAction,
+    pub actor: Pubkey, // the admin, or the approving guardian
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub approvals: u8,
+    pub withdraw_eta: i64,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct FraudFlagEvent {
+    pub user: Pubkey,
+    pub funder: Option<Pubkey>, // third party that paid for the purchase
+    pub integrator: Option<Pubkey>,
+    pub reasons: u8, // FRAUD_FLAG_* tripped by the purchase; 0 when the admin clears the flags
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct CardBalanceEvent {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub bonus: u64, // part of amount the treasury added; 0 unless converted
+    pub balance: u64,
+    pub action: CardBalanceAction,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct MerchantRedemptionEvent {
+    pub merchant: Pubkey, // merchant wallet
+    pub user: Pubkey,
+    pub amount: u64,
+    pub fee: u64, // part of amount kept by the treasury
+    pub month: u32,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[event]
+pub struct BlacklistEvent {
+    pub admin: Pubkey,
+    pub user: Pubkey,
+    pub action: BlacklistAction,
+    pub timestamp: i64,
+    pub brand_code: [u8; 8],
+}
+
+#[error_code]
+pub enum CustomError {
+    #[msg("Contract is paused")]
+    ContractPaused,
+    #[msg("Unauthorized access")]
+    Unauthorized,
+    #[msg("Invalid gift card amount")]
+    InvalidAmount,
+    #[msg("Invalid upgrade - new package must be higher")]
+    InvalidUpgrade,
+    #[msg("Self-referral not allowed")]
+    SelfReferralNotAllowed,
+    #[msg("Arithmetic error")]
+    ArithmeticError,
+    #[msg("No spillover position available")]
+    NoSpilloverAvailable,
+    #[msg("Invalid referrer")]
+    InvalidReferrer,
+    #[msg("User is blacklisted")]
+    UserBlacklisted,
+    #[msg("Purchase is not pending")]
+    ReceiptNotPending,
+    #[msg("Refund window is still open")]
+    RefundWindowOpen,
+    #[msg("Refund window has closed")]
+    RefundWindowClosed,
+    #[msg("Purchase cannot be refunded")]
+    ReceiptNotRefundable,
+    #[msg("Invalid refund amount")]
+    InvalidRefundAmount,
+    #[msg("Refund reason too long")]
+    RefundReasonTooLong,
+    #[msg("Insufficient treasury balance")]
+    InsufficientTreasury,
+    #[msg("Invalid account size")]
+    InvalidAccountSize,
+    #[msg("Invalid matrix width or depth")]
+    InvalidMatrixConfig,
+    #[msg("Existing referral tree exceeds the matrix width")]
+    MatrixWidthViolated,
+    #[msg("Wallet of a cycling user was not provided")]
+    CycleAccountMissing,
+    #[msg("Holding tank is full")]
+    HoldingTankFull,
+    #[msg("User is not in the holding tank")]
+    NotInHoldingTank,
+    #[msg("Holding period has expired")]
+    HoldingTankExpired,
+    #[msg("Invalid placement position")]
+    InvalidPlacement,
+    #[msg("Upline user account was not provided")]
+    UplineAccountMissing,
+    #[msg("Invalid stake pool config")]
+    InvalidStakeConfig,
+    #[msg("Delegation exceeds the stakeable treasury share")]
+    StakeLimitExceeded,
+    #[msg("Yield sharing is disabled")]
+    YieldSharingDisabled,
+    #[msg("Yield already distributed this epoch")]
+    YieldAlreadyDistributed,
+    #[msg("No active packages to share yield with")]
+    NoActivePackages,
+    #[msg("Nothing to claim")]
+    NothingToClaim,
+    #[msg("Integrator fee share too high")]
+    InvalidIntegratorFee,
+    #[msg("Invalid or unauthorized integrator")]
+    InvalidIntegrator,
+    #[msg("Attribution does not match this purchase")]
+    InvalidAttribution,
+    #[msg("Attribution already consumed")]
+    AttributionConsumed,
+    #[msg("Invalid source tenant")]
+    InvalidTenant,
+    #[msg("User history already imported")]
+    UserAlreadyImported,
+    #[msg("Invalid session key")]
+    InvalidSessionKey,
+    #[msg("Session key expired")]
+    SessionKeyExpired,
+    #[msg("Session key spending limit exceeded")]
+    SessionKeyLimitExceeded,
+    #[msg("Support tier thresholds must be ascending")]
+    InvalidSupportTiers,
+    #[msg("Support tier too low")]
+    SupportTierTooLow,
+    #[msg("Vault balance would not cover outstanding liabilities")]
+    InsufficientCoverage,
+    #[msg("Too many accounts in verification batch")]
+    VerifyBatchTooLarge,
+    #[msg("Receipt does not belong to this SolBox")]
+    InvalidReceipt,
+    #[msg("Calling program is not allowlisted for this instruction")]
+    CpiCallerNotAllowed,
+    #[msg("No feature flag change is pending")]
+    NoPendingFeatureFlags,
+    #[msg("Timelock has not elapsed")]
+    TimelockNotElapsed,
+    #[msg("Client was built for a different program version")]
+    StaleClientVersion,
+    #[msg("Founder account does not match the founder wallet")]
+    InvalidFounderWallet,
+    #[msg("No founder wallet change is pending")]
+    NoPendingFounderWallet,
+    #[msg("Boosts are not enabled")]
+    BoostsDisabled,
+    #[msg("Invalid boost configuration")]
+    InvalidBoostConfig,
+    #[msg("Boost does not belong to the referrer")]
+    InvalidBoost,
+    #[msg("Invalid contest")]
+    InvalidContest,
+    #[msg("Contest entries are closed")]
+    ContestEntriesClosed,
+    #[msg("Contest has no room for more entries")]
+    ContestFull,
+    #[msg("Contest has not ended")]
+    ContestNotEnded,
+    #[msg("Contest is already finalized")]
+    ContestFinalized,
+    #[msg("Passed entries do not match the contest")]
+    ContestEntriesMismatch,
+    #[msg("Invalid notice")]
+    InvalidNotice,
+    #[msg("Invalid guardian set or recovery wallet")]
+    InvalidGuardians,
+    #[msg("No wallet recovery is pending")]
+    NoPendingRecovery,
+    #[msg("Invalid beneficiary or inactivity period")]
+    InvalidBeneficiary,
+    #[msg("User has been active within the inactivity period")]
+    UserStillActive,
+    #[msg("Epoch has not closed yet")]
+    EpochNotClosed,
+    #[msg("Statement does not match the anchored root")]
+    StatementMismatch,
+    #[msg("Unpausing needs the pause guardians' quorum")]
+    UnpauseQuorumRequired,
+    #[msg("Contract is not paused")]
+    ContractNotPaused,
+    #[msg("Price or terms moved beyond the given bounds")]
+    SlippageExceeded,
+    #[msg("Claims must name the current epoch")]
+    InvalidClaimEpoch,
+    #[msg("Residual share exceeds the commission")]
+    InvalidResidualConfig,
+    #[msg("Only sandbox instances allow this")]
+    NotSandbox,
+    #[msg("Time can only be moved forward")]
+    InvalidTimeWarp,
+    #[msg("More package amounts than the config history has room for")]
+    TooManyAmounts,
+    #[msg("Upline accounts do not match the referral chain")]
+    UplineAccountMismatch,
+    #[msg("The SolBox already has a lookup table")]
+    LookupTableExists,
+    #[msg("Invalid lookup table or addresses")]
+    InvalidLookupTable,
+    #[msg("Package metadata URI is empty or too long")]
+    InvalidMetadataUri,
+    #[msg("Attestation does not match the purchase")]
+    AttestationMismatch,
+    #[msg("Invalid display handle")]
+    InvalidHandle,
+    #[msg("User already has a display handle")]
+    HandleAlreadySet,
+    #[msg("Migration proof does not verify")]
+    InvalidMigrationProof,
+    #[msg("Migration root is locked once positions are claimed")]
+    MigrationStarted,
+    #[msg("Referrer has not accepted its sponsored registration")]
+    SponsorshipPending,
+    #[msg("No sponsored registration to accept")]
+    NoPendingSponsorship,
+    #[msg("Invalid membership period or reactivation price")]
+    InvalidMembershipConfig,
+    #[msg("No package to renew, or its reactivation window has passed")]
+    MembershipNotRenewable,
+    #[msg("Membership is still within its reactivation window")]
+    MembershipActive,
+    #[msg("House position limit reached")]
+    HousePositionsFull,
+    #[msg("Payout day must be 0 or a weekday from 1 to 7")]
+    InvalidPayoutDay,
+    #[msg("Claim is below the minimum payout")]
+    ClaimBelowMinimum,
+    #[msg("Queued payouts are only paid on the payout day")]
+    OutsidePayoutWindow,
+    #[msg("Invalid emergency withdrawal recipient")]
+    InvalidEmergencyRecipient,
+    #[msg("An emergency withdrawal is pending")]
+    EmergencyWithdrawPending,
+    #[msg("No emergency withdrawal is pending")]
+    NoPendingEmergencyWithdraw,
+    #[msg("Founder wallet cannot take the deferred founder share yet")]
+    FounderCannotReceive,
+    #[msg("A card was already issued for this purchase")]
+    CardIssued,
+    #[msg("Position is wrapped; its token holder claims the commission")]
+    PositionWrapped,
+    #[msg("Position is not wrapped into a token")]
+    PositionNotWrapped,
+    #[msg("Clawback reason too long")]
+    ClawbackReasonTooLong,
+    #[msg("First purchase needs the proof-of-humanity verifier's signature")]
+    HumanityProofRequired,
+    #[msg("Snapshot doesn't match the exported hash or was already restored")]
+    InvalidSnapshot,
+    #[msg("Only an instance without purchases can be restored into")]
+    InstanceNotFresh,
+    #[msg("No restore approved for this instance")]
+    NoApprovedRestore,
+    #[msg("Dripped commissions need drip_days")]
+    InvalidDripConfig,
+    #[msg("Tranche account must be passed exactly when the commission drips")]
+    TrancheMismatch,
+    #[msg("Terms hash doesn't match the terms in force")]
+    TermsMismatch,
+    #[msg("Buyer has not accepted the current terms")]
+    TermsOutdated,
+    #[msg("Package is sold out")]
+    SoldOut,
+    #[msg("Package account is required while packages have a limited supply")]
+    PackageRequired,
+    #[msg("Claim vouchers are not enabled")]
+    VouchersDisabled,
+    #[msg("Wormhole bridge config could not be read")]
+    InvalidBridgeConfig,
+    #[msg("Cross-chain purchases are not enabled")]
+    CrossChainDisabled,
+    #[msg("VAA is not a storefront purchase for these accounts")]
+    InvalidVaa,
+    #[msg("Commit-reveal drops need a limited package")]
+    PackageNotLimited,
+    #[msg("Drop is not taking commits")]
+    CommitWindowClosed,
+    #[msg("Commits can't be revealed before the commit window closes")]
+    CommitWindowOpen,
+    #[msg("Salt doesn't match the commit")]
+    CommitMismatch,
+    #[msg("Drop purchases need the buyer's revealed commit")]
+    CommitRequired,
+    #[msg("Wallet has asked for its data to be deleted")]
+    DeletionPending,
+    #[msg("Wallet hasn't asked for its data to be deleted")]
+    DeletionNotRequested,
+    #[msg("Claims, handle, wrapped position or escrow still outstanding")]
+    ObligationsOutstanding,
+    #[msg("Referral tree already checkpointed this epoch")]
+    TreeCheckpointed,
+    #[msg("Checkpoint run is void; restart it from cursor 0")]
+    CheckpointStale,
+    #[msg("Cursor doesn't match the checkpoint run")]
+    CheckpointCursorMismatch,
+    #[msg("Withdrawal exceeds the hot treasury's daily limit")]
+    HotLimitExceeded,
+    #[msg("No hot treasury above the sweep threshold")]
+    NothingToSweep,
+    #[msg("No cold treasury withdrawal pending")]
+    NoPendingColdWithdrawal,
+    #[msg("Buyer is flagged for review")]
+    UserFlagged,
+    #[msg("Integrator has reached its limit for this epoch")]
+    IntegratorLimitExceeded,
+    #[msg("Identifier doesn't match the invitation")]
+    InvalidInvitation,
+    #[msg("Invitation already claimed")]
+    InvitationClaimed,
+    #[msg("Invitations can only be claimed before the first purchase")]
+    InvitationClosed,
+    #[msg("First purchase must be placed under the inviting referrer")]
+    InvitationMismatch,
+    #[msg("Card bonus exceeds the maximum")]
+    InvalidCardBonus,
+    #[msg("Too many tranches in release batch")]
+    ReleaseBatchTooLarge,
+    #[msg("Redemption fee exceeds the maximum")]
+    InvalidRedemptionFee,
+    #[msg("Card balance too low")]
+    InsufficientCardBalance,
+    #[msg("More commission levels than user accounts have room for")]
+    TooManyLevels,
+    #[msg("Signups or recycles have been placed on the purchase's position")]
+    PlacementInUse,
+    #[msg("A tree checkpoint run is in progress; continue it from its cursor")]
+    CheckpointInProgress,
+    #[msg("Drop reveal window must end after its commit window")]
+    InvalidDropWindow,
+    #[msg("Drop commit belongs to an earlier drop")]
+    StaleCommit,
+    #[msg("Drop is not taking reveals any more")]
+    RevealWindowClosed,
+    #[msg("Drop can't be allocated before the reveal window closes")]
+    RevealWindowOpen,
+    #[msg("Drop commits must be allocated in commit order")]
+    InvalidDropCommit,
+    #[msg("Too many drop commits in one allocate_drop call")]
+    AllocationBatchTooLarge,
+    #[msg("Drop commit can still buy")]
+    CommitInUse,
+}
+
+// Helper function to compute how a purchase is split between referrer and founder
+fn calculate_purchase_split(
+    solbox: &SolBox,
+    amount: u64,
+    referrer: Pubkey
+) -> Result<PurchaseSplit> {
+    let split = split_amount(
+        amount,
+        solbox.config.commission_percentage,
+        solbox.config.bonus_percentage
+    )?;
+        
+    // Signups held in the tank are placed later, so pay the sponsor directly
+    let placement = if solbox.config.holding_tank_period > 0 {
+        current_position(&solbox.referral_relationships, referrer)
+    } else {
+        resolve_placement(solbox, referrer)?
+    };
+    
+    Ok(PurchaseSplit {
+        amount,
+        referrer: placement.user,
+        referrer_cycle: placement.cycle,
+        commission: split.commission,
+        bonus: split.bonus,
+        founder_share: split.founder_share,
+    })
+}
+
+// Helper function for the checks a purchase has to pass before anything
+// moves, shared by buy_gift_card and simulate_purchase
+fn check_purchase(
+    solbox: &SolBox,
+    filter: &BlacklistFilter,
+    buyer: &Pubkey,
+    buyer_account: &User,
+    referrer: &Pubkey,
+    referrer_account: &User,
+    amount: u64
+) -> Result<()> {
+    // Verify contract is active
+    require!(!solbox.paused, CustomError::ContractPaused);
+    
+    // Check if user is blacklisted
+    require!(
+        !is_blacklisted(solbox, filter, buyer),
+        CustomError::UserBlacklisted
+    );
+    
+    // Validate purchase amount
+    require!(
+        solbox.config.valid_amounts.contains(&amount),
+        CustomError::InvalidAmount
+    );
+    
+    // No new money while an emergency withdrawal is announced
+    require!(solbox.emergency_eta == 0, CustomError::EmergencyWithdrawPending);
+    
+    // Prevent self-referral
+    require!(buyer != referrer, CustomError::SelfReferralNotAllowed);
+    
+    // Sponsored wallets refer others only once they have accepted
+    require!(
+        !referrer_account.pending_acceptance,
+        CustomError::SponsorshipPending
+    );
+    
+    // Wallets waiting on deletion take no new purchases either way
+    require!(
+        buyer_account.deletion_requested_at == 0
+            && referrer_account.deletion_requested_at == 0,
+        CustomError::DeletionPending
+    );
+    
+    // Buyers may have to accept the terms in force first
+    require!(
+        !solbox.config.require_current_terms
+            || buyer_account.terms_version == solbox.terms_version,
+        CustomError::TermsOutdated
+    );
+    Ok(())
+}
+
+// Helper function for the checks on a buyer's first purchase, which
+// places them in the tree
+fn check_registration(
+    solbox: &SolBox,
+    buyer_account: &User,
+    referrer: &Pubkey,
+    amount: u64,
+    humanity_verifier: Option<Pubkey>
+) -> Result<()> {
+    // An invited wallet is placed under the referrer that invited it,
+    // whichever link it arrives through
+    require!(
+        buyer_account.invited_by == Pubkey::default() || buyer_account.invited_by == *referrer,
+        CustomError::InvitationMismatch
+    );
+    
+    // Large first purchases need the proof-of-humanity verifier to
+    // co-sign, so launch-day bots can't fill the matrix
+    if solbox.config.humanity_verifier != Pubkey::default()
+        && amount >= solbox.config.humanity_threshold
+    {
+        require!(
+            humanity_verifier == Some(solbox.config.humanity_verifier),
+            CustomError::HumanityProofRequired
+        );
+    }
+    Ok(())
+}
+
+// Helper function to sell a purchase a unit of its package while any
+// package is limited. During a commit-reveal drop the unit comes out of
+// the buyer's allocated commit; once every commit is allocated, units no
+// commit holds sell to anyone. Returns whether a commit passed along is
+// spent and should be closed
+fn take_package_unit(
+    solbox: &Account<SolBox>,
+    package: Option<&mut Account<Package>>,
+    mut drop_commit: Option<&mut Account<DropCommit>>,
+    buyer: &Pubkey,
+    amount: u64
+) -> Result<bool> {
+    require!(
+        match package.as_ref() {
+            Some(package) => package.solbox == solbox.key() && package.amount == amount,
+            None => solbox.limited_packages == 0,
+        },
+        CustomError::PackageRequired
+    );
+    if let Some(commit) = drop_commit.as_ref() {
+        require!(
+            commit.solbox == solbox.key()
+                && commit.wallet == *buyer
+                && package.as_ref().is_some_and(|package| commit.package == package.key()),
+            CustomError::CommitRequired
+        );
+    }
+    let mut keep_commit = false;
+    if let Some(package) = package.filter(|package| package.limited) {
+        if package.commit_until_slot > 0 {
+            match drop_commit.as_mut() {
+                Some(commit) => {
+                    require!(commit.drop_id == package.drop_id, CustomError::StaleCommit);
+                    require!(commit.allocated > 0, CustomError::CommitRequired);
+                    commit.allocated -= 1;
+                    package.drop_reserved = package.drop_reserved.saturating_sub(1);
+                    keep_commit = commit.allocated > 0;
+                }
+                None => require!(
+                    package.drop_cursor == package.drop_commits
+                        && Clock::get()?.slot > package.reveal_until_slot
+                        && package.remaining > package.drop_reserved,
+                    CustomError::CommitRequired
+                ),
+            }
+        }
+        require!(package.remaining > 0, CustomError::SoldOut);
+        package.remaining -= 1;
+        if package.remaining > 0 && package.low_stock_thresholds.contains(&package.remaining) {
+            emit!(LowStockEvent {
+                package: package.key(),
+                amount,
+                remaining: package.remaining,
+                timestamp: unix_now(solbox)?,
+                brand_code: solbox.brand_code,
+            });
+        }
+    }
+    Ok(drop_commit.is_some() && !keep_commit)
+}
+
+// Helper function to run the fraud heuristics on a purchase. They flag
+// the buyer for review; flagged buyers are only refused when the config
+// says so
+fn flag_purchase(
+    solbox: &SolBox,
+    stats: &mut DailyStats,
+    integrator: Option<&mut Account<Integrator>>,
+    funder: Option<Pubkey>,
+    buyer: &Pubkey,
+    buyer_account: &mut User
+) -> Result<()> {
+    let integrator_key = integrator.as_ref().map(|integrator| integrator.key());
+    let fraud_flags = fraud_heuristics(
+        &solbox.config,
+        stats,
+        integrator.map(|integrator| &mut **integrator),
+        funder.as_ref(),
+        buyer,
+        unix_now(solbox)?
+    );
+    buyer_account.flagged |= fraud_flags;
+    require!(
+        !solbox.config.block_flagged || buyer_account.flagged == 0,
+        CustomError::UserFlagged
+    );
+    if fraud_flags != 0 {
+        emit!(FraudFlagEvent {
+            user: *buyer,
+            funder,
+            integrator: integrator_key,
+            reasons: fraud_flags,
+            timestamp: unix_now(solbox)?,
+            brand_code: solbox.brand_code,
+        });
+    }
+    Ok(())
+}
+
+// Helper function to work out where a purchase's lamports go, shared by
+// buy_gift_card and simulate_purchase so the preview is what gets paid.
+// `card_balance` is the buyer's, when the buyer's wallet pays
+fn purchase_flows(
+    solbox: &Account<SolBox>,
+    amount: u64,
+    payee: Pubkey,
+    payee_account: &User,
+    boost: Option<&Boost>,
+    integrator: Option<&Integrator>,
+    card_balance: Option<u64>
+) -> Result<PurchaseFlows> {
+    // Calculate commissions and resolve spillover placement
+    let split = calculate_purchase_split(solbox, amount, payee)?;
+    
+    // An active boost of the referrer adds to their commission, paid
+    // out of the founder share
+    let boost_bonus = match boost {
+        Some(boost) => {
+            require!(
+                boost.solbox == solbox.key() && boost.wallet == payee,
+                CustomError::InvalidBoost
+            );
+            if unix_now(solbox)? < boost.expires_at {
+                capped_fee(amount, boost.bonus_bps, split.founder_share)?
+            } else {
+                0
+            }
+        }
+        None => 0,
+    };
+    let commission = split.commission
+        .checked_add(boost_bonus)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+    
+    // House positions earn for the bonus pool, not a wallet
+    let house = earns_for_pool(solbox, &payee);
+    
+    // The residual part is held in the vault until the epoch closes;
+    // the rest is the referrer's fast-start payout. A wrapped position
+    // holds all of it for whoever has its token
+    let wrapped = payee_account.position_mint != Pubkey::default();
+    let residual = if house {
+        0
+    } else if wrapped {
+        commission
+    } else {
+        pro_rata(commission, solbox.config.residual_bps as u64, BPS_DENOMINATOR as u64)?
+    };
+    
+    // Large commissions reach the referrer in daily tranches instead of
+    // all at once
+    let fast_start = commission - residual;
+    let dripped = !house && drips(&solbox.config, commission, fast_start);
+    
+    // Purchases routed through an integrator pay its revshare out of
+    // the founder share
+    let mut founder_share = split.founder_share - boost_bonus;
+    let integrator_fee = match integrator {
+        Some(integrator) => {
+            require!(
+                integrator.solbox == solbox.key()
+                    && integrator.allowed_instructions & INTEGRATOR_ALLOW_BUY_GIFT_CARD != 0,
+                CustomError::InvalidIntegrator
+            );
+            capped_fee(amount, integrator.fee_share_bps, founder_share)?
+        }
+        None => 0,
+    };
+    founder_share -= integrator_fee;
+    
+    let paid = commission
+        .checked_add(founder_share)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?
+        .checked_add(integrator_fee)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+    
+    Ok(PurchaseFlows {
+        amount,
+        payee,
+        referrer: split.referrer,
+        referrer_cycle: split.referrer_cycle,
+        commission,
+        boost_bonus,
+        house,
+        residual,
+        fast_start,
+        dripped,
+        bonus: split.bonus,
+        founder_share,
+        integrator_fee,
+        paid,
+        card_funded: card_balance.is_some_and(|balance| balance >= paid),
+        escrowed: solbox.config.refund_window > 0,
+    })
+}
+
+// Helper function to resolve where a signup under `referrer` is placed
+fn resolve_placement(solbox: &SolBox, referrer: Pubkey) -> Result<Position> {
+    let start = current_position(&solbox.referral_relationships, referrer);
+    let position = find_matrix_position(
+        &solbox.referral_relationships,
+        start,
+        solbox.config.matrix_width,
+        solbox.config.matrix_depth
+    ).ok_or(CustomError::NoSpilloverAvailable)?;
+    Ok(position)
+}
+
+// Helper function to get a user's active (most recent) position
+fn current_position(relationships: &[ReferralRelationship], user: Pubkey) -> Position {
+    let cycle = relationships
+        .iter()
+        .filter(|r| r.user == user)
+        .map(|r| r.cycle)
+        .max()
+        .unwrap_or(0);
+    Position { user, cycle }
+}
+
+// Helper function to get the positions placed directly under a position
+fn position_children(relationships: &[ReferralRelationship], position: Position) -> Vec<Position> {
+    relationships
+        .iter()
+        .filter(|r| r.referrer == position.user && r.referrer_cycle == position.cycle)
+        .map(|r| Position { user: r.user, cycle: r.cycle })
+        .collect()
+}
+
+// Helper function to get the position a position is placed under
+fn parent_position(relationships: &[ReferralRelationship], position: Position) -> Option<Position> {
+    relationships
+        .iter()
+        .find(|r| r.user == position.user && r.cycle == position.cycle)
+        .map(|r| Position { user: r.referrer, cycle: r.referrer_cycle })
+}
+
+// Helper function to find the first open matrix position under `start`,
+// searching breadth-first (left to right) down to `depth` levels
+fn find_matrix_position(
+    relationships: &[ReferralRelationship],
+    start: Position,
+    width: u8,
+    depth: u8
+) -> Option<Position> {
+    let mut frontier = vec![start];
+    
+    for _ in 0..depth {
+        let mut next_level = Vec::new();
+        for node in frontier {
+            let children = position_children(relationships, node);
+                
+            // Place directly under this node if it has a free leg
+            if children.len() < width as usize {
+                return Some(node);
+            }
+            next_level.extend(children);
+        }
+        frontier = next_level;
+    }
+    
+    None // Matrix is full down to the maximum depth
+}
+
+// Helper function to check whether `node` sits at most `levels` levels below `root`
+fn is_within_downline(
+    relationships: &[ReferralRelationship],
+    root: Position,
+    node: Position,
+    levels: u8
+) -> bool {
+    let mut current = Some(node);
+    for _ in 0..=levels {
+        match current {
+            Some(position) if position == root => return true,
+            Some(position) => current = parent_position(relationships, position),
+            None => return false,
+        }
+    }
+    false
+}
+
+// Helper function to add a user's first position under `spot`, count the
+// signup on its uplines and recycle any positions that filled up as a result.
+// `vault` is the SolBox account itself and `loaded` is an upline User already held by the instruction context, which
+// must be updated in place so its own serialization doesn't undo the change.
+// The placement's upline accounts are taken off the front of `uplines`;
+// wallets of positions that cycle are looked up anywhere in `wallets`
+fn place_in_matrix<'info>(
+    solbox: &mut SolBox,
+    vault: &AccountInfo<'info>,
+    uplines: &mut &'info [AccountInfo<'info>],
+    wallets: &'info [AccountInfo<'info>],
+    user: Pubkey,
+    spot: Position,
+    loaded: Option<&mut User>
+) -> Result<()> {
+    let index = solbox.referral_relationships.len();
+    solbox.referral_relationships.push(ReferralRelationship {
+        user,
+        referrer: spot.user,
+        timestamp: unix_now(solbox)?,
+        cycle: 0,
+        referrer_cycle: spot.cycle,
+        set_off_recycle: false,
+    });
+    
+    let position = Position { user, cycle: 0 };
+    let paid = take_uplines(uplines, solbox, *vault.key, position)?;
+    record_downline_signup(&paid, loaded)?;
+    
+    // Mark the placement if it filled positions that recycled, so a
+    // cancel knows the tree has moved on from it
+    if solbox.config.recycling {
+        process_cycles(solbox, vault, wallets, position)?;
+        solbox.referral_relationships[index].set_off_recycle = solbox.referral_relationships.len() > index + 1;
+    }
+    Ok(())
+}
+
+// An upline within the paid levels and the accounts passed for it
+struct Upline<'info> {
+    wallet: &'info AccountInfo<'info>,
+    user: &'info AccountInfo<'info>,
+}
+
+// Helper function to take the accounts for the paid levels above
+// `position` off the front of `accounts`. Convention: one pair per level,
+// nearest upline first, made of the upline's User PDA followed by its
+// wallet; the chain ends early at the root. Every pair must match the
+// stored referral chain, so clients can derive it from SolBox alone (see
+// client::upline_wallets)
+fn take_uplines<'info>(
+    accounts: &mut &'info [AccountInfo<'info>],
+    solbox: &SolBox,
+    solbox_key: Pubkey,
+    position: Position
+) -> Result<Vec<Upline<'info>>> {
+    let mut uplines = Vec::new();
+    let mut node = parent_position(&solbox.referral_relationships, position);
+    
+    for _ in 0..solbox.config.commission_levels {
+        let Some(upline) = node else { break };
+        node = parent_position(&solbox.referral_relationships, upline);
+        
+        // Failures name the level, counted from 1, and the wallet expected there
+        let level = uplines.len() + 1;
+        let [user, wallet, rest @ ..] = *accounts else {
+            msg!("Upline accounts missing from level {}, expected {}", level, upline.user);
+            return err!(CustomError::UplineAccountMissing);
+        };
+        if wallet.key != &upline.user || !is_user_account(user, solbox_key, upline.user) {
+            msg!("Upline accounts at level {} don't match the referral chain", level);
+            return Err(error!(CustomError::UplineAccountMismatch).with_pubkeys((*wallet.key, upline.user)));
+        }
+        *accounts = rest;
+        uplines.push(Upline { wallet, user });
+    }
+    Ok(uplines)
+}
+
+// Helper function to increment the per-level downline counters and the
+// unseen activity badge of every upline within the paid levels
+fn record_downline_signup(uplines: &[Upline], loaded: Option<&mut User>) -> Result<()> {
+    update_uplines(uplines, loaded, |user, level| {
+        user.downline_counts[level] = user.downline_counts[level]
+            .checked_add(1)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        user.unseen_downline_events = user.unseen_downline_events.saturating_add(1);
+        Ok(())
+    })
+}
+
+// Helper function to take a cancelled signup back off the counters
+// record_downline_signup incremented. The badge may have been acked since
+fn record_downline_cancel(uplines: &[Upline], loaded: Option<&mut User>) -> Result<()> {
+    update_uplines(uplines, loaded, |user, level| {
+        user.downline_counts[level] = user.downline_counts[level]
+            .checked_sub(1)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        user.unseen_downline_events = user.unseen_downline_events.saturating_sub(1);
+        Ok(())
+    })
+}
+
+// Helper function to apply `update` to the User of every upline, with its
+// level counted from 0. `loaded` is updated in place, see place_in_matrix
+fn update_uplines(
+    uplines: &[Upline],
+    mut loaded: Option<&mut User>,
+    mut update: impl FnMut(&mut User, usize) -> Result<()>
+) -> Result<()> {
+    for (level, upline) in uplines.iter().enumerate() {
+        if let Some(user) = loaded.as_deref_mut().filter(|user| &user.key == upline.wallet.key) {
+            update(user, level)?;
+            continue;
+        }
+        
+        let mut account = Account::<User>::try_from(upline.user)?;
+        update(&mut account, level)?;
+        account.exit(&crate::ID)?;
+    }
+    Ok(())
+}
+
+// Helper function to check whether anything in the tree builds on the
+// placement at `index`: positions placed under it since, or recycles it
+// set off. Relationships keep their order, so anything later came after
+// the placement
+fn placement_in_use(relationships: &[ReferralRelationship], index: usize) -> bool {
+    let placed = &relationships[index];
+    placed.set_off_recycle
+        || relationships[index + 1..]
+            .iter()
+            .any(|r| r.referrer == placed.user && r.referrer_cycle == placed.cycle)
+}
+
+// Helper function to find the User PDA of `wallet` among passed accounts
+fn find_user_account<'info>(
+    accounts: &'info [AccountInfo<'info>],
+    solbox: Pubkey,
+    wallet: Pubkey
+) -> Result<&'info AccountInfo<'info>> {
+    accounts
+        .iter()
+        .find(|info| is_user_account(info, solbox, wallet))
+        .ok_or(error!(CustomError::UplineAccountMissing))
+}
+
+// Helper function to check that an account is the User PDA of `wallet`
+fn is_user_account(info: &AccountInfo, solbox: Pubkey, wallet: Pubkey) -> bool {
+    read_user_account(info, solbox, wallet).is_some()
+}
+
+// Helper function to read `info` as the User PDA of `wallet`, if it is one
+fn read_user_account(info: &AccountInfo, solbox: Pubkey, wallet: Pubkey) -> Option<User> {
+    if info.owner != &crate::ID {
+        return None;
+    }
+    let account = User::try_deserialize(&mut &info.try_borrow_data().ok()?[..]).ok()?;
+    if account.solbox != solbox || account.key != wallet {
+        return None;
+    }
+    
+    // The stored bump must re-derive this exact address
+    let expected = Pubkey::create_program_address(
+        &[b"user", solbox.as_ref(), wallet.as_ref(), &[account.bump]],
+        &crate::ID
+    );
+    (expected.as_ref() == Ok(info.key)).then_some(account)
+}
+
+// Helper function to check the upline accounts a placement under `spot`
+// needs, the way take_uplines takes them, as PURCHASE_PROBLEM_* flags
+fn check_upline_accounts(
+    solbox: &SolBox,
+    solbox_key: Pubkey,
+    mut accounts: &[AccountInfo],
+    spot: Position
+) -> u32 {
+    let mut node = Some(spot);
+    for _ in 0..solbox.config.commission_levels {
+        let Some(upline) = node else { break };
+        node = parent_position(&solbox.referral_relationships, upline);
+        
+        let [user, wallet, rest @ ..] = accounts else {
+            return PURCHASE_PROBLEM_UPLINE_MISSING;
+        };
+        if wallet.key != &upline.user || !is_user_account(user, solbox_key, upline.user) {
+            return PURCHASE_PROBLEM_UPLINE_MISMATCH;
+        }
+        accounts = rest;
+    }
+    0
+}
+
+// Helper function to check whether every leg below a position is taken
+// down to `depth` levels
+fn is_position_full(
+    relationships: &[ReferralRelationship],
+    position: Position,
+    width: u8,
+    depth: u8
+) -> bool {
+    find_matrix_position(relationships, position, width, depth).is_none()
+}
+
+// Helper function to recycle positions whose matrix filled up: the owner
+// re-enters at the first open spot under their sponsor and is paid the
+// cycle bonus from the treasury. New positions can fill further matrices,
+// so this keeps going until no more positions cycle.
+fn process_cycles<'info>(
+    solbox: &mut SolBox,
+    vault: &AccountInfo<'info>,
+    wallets: &[AccountInfo<'info>],
+    placed: Position
+) -> Result<()> {
+    let width = solbox.config.matrix_width;
+    let depth = solbox.config.matrix_depth;
+    let mut pending = vec![placed];
+    
+    while let Some(position) = pending.pop() {
+        // Only ancestors within `depth` levels can have filled up
+        let mut ancestor = parent_position(&solbox.referral_relationships, position);
+        for _ in 0..depth {
+            let Some(node) = ancestor else { break };
+            ancestor = parent_position(&solbox.referral_relationships, node);
+            
+            // Positions that already cycled stay full forever
+            let relationships = &solbox.referral_relationships;
+            if current_position(relationships, node.user) != node
+                || !is_position_full(relationships, node, width, depth)
+            {
+                continue;
+            }
+            
+            // Root positions have no sponsor tree to re-enter
+            let Some(sponsor) = ancestor else { continue };
+            let sponsor = current_position(relationships, sponsor.user);
+            let Some(spot) = find_matrix_position(relationships, sponsor, width, depth) else {
+                continue;
+            };
+            
+            let cycle = node.cycle
+                .checked_add(1)
+                .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+            solbox.referral_relationships.push(ReferralRelationship {
+                user: node.user,
+                referrer: spot.user,
+                timestamp: unix_now(solbox)?,
+                cycle,
+                referrer_cycle: spot.cycle,
+                set_off_recycle: false,
+            });
+            
+            // Pay what the treasury can cover of the cycle bonus; house
+            // positions and tombstones recycle without one
+            let treasury = treasury_balance(vault, solbox_liabilities(solbox)?)?;
+            let bonus = if earns_for_pool(solbox, &node.user) {
+                0
+            } else {
+                solbox.config.cycle_bonus.min(treasury)
+            };
+            if bonus > 0 {
+                let wallet = wallets
+                    .iter()
+                    .find(|account| account.key == &node.user)
+                    .ok_or(CustomError::CycleAccountMissing)?;
+                transfer_from_vault(vault, wallet, bonus)?;
+            }
+            
+            emit!(CycleEvent {
+                user: node.user,
+                cycle,
+                placed_under: spot.user,
+                placed_under_cycle: spot.cycle,
+                bonus,
+                timestamp: unix_now(solbox)?,
+                brand_code: solbox.brand_code,
+            });
+            
+            pending.push(Position { user: node.user, cycle });
+        }
+    }
+    Ok(())
+}
+
+// Helper function to validate config parameters against the existing tree
+fn validate_config(
+    config: &ContractConfig,
+    relationships: &[ReferralRelationship]
+) -> Result<()> {
+    check_config(config.max_stake_bps <= BPS_DENOMINATOR, "max_stake_bps", CustomError::InvalidStakeConfig)?;
+    check_config(config.residual_bps <= BPS_DENOMINATOR, "residual_bps", CustomError::InvalidResidualConfig)?;
+    check_config(config.membership_period >= 0, "membership_period", CustomError::InvalidMembershipConfig)?;
+    check_config(config.reactivation_window >= 0, "reactivation_window", CustomError::InvalidMembershipConfig)?;
+    check_config(
+        config.reactivation_bps <= BPS_DENOMINATOR,
+        "reactivation_bps",
+        CustomError::InvalidMembershipConfig
+    )?;
+    check_config(config.payout_weekday <= 7, "payout_weekday", CustomError::InvalidPayoutDay)?;
+    check_config(
+        config.drip_threshold == 0 || config.drip_days > 0,
+        "drip_days",
+        CustomError::InvalidDripConfig
+    )?;
+    check_config(
+        config.card_bonus_bps <= MAX_CARD_BONUS_BPS,
+        "card_bonus_bps",
+        CustomError::InvalidCardBonus
+    )?;
+    check_config(
+        config.redemption_fee_bps <= MAX_REDEMPTION_FEE_BPS,
+        "redemption_fee_bps",
+        CustomError::InvalidRedemptionFee
+    )?;
+    
+    // Higher support tiers need larger packages
+    check_config(
+        config.support_tier_thresholds
+            .windows(2)
+            .all(|pair| pair[1] == 0 || (pair[0] > 0 && pair[1] > pair[0])),
+        "support_tier_thresholds",
+        CustomError::InvalidSupportTiers
+    )?;
+    check_config(config.tier_period >= 0, "tier_period", CustomError::InvalidSupportTiers)?;
+    check_config(
+        config.tier_window_days as usize <= VOLUME_WINDOW_DAYS,
+        "tier_window_days",
+        CustomError::InvalidSupportTiers
+    )?;
+    
+    check_config(config.matrix_width > 0, "matrix_width", CustomError::InvalidMatrixConfig)?;
+    check_config(config.matrix_depth > 0, "matrix_depth", CustomError::InvalidMatrixConfig)?;
+    
+    // Paid levels can't reach past the bottom of the matrix
+    check_config(
+        config.commission_levels <= config.matrix_depth
+            && config.commission_levels as usize <= MAX_COMMISSION_LEVELS,
+        "commission_levels",
+        CustomError::InvalidMatrixConfig
+    )?;
+    
+    // Narrowing the matrix must not orphan legs that already exist
+    for relationship in relationships {
+        let parent = Position {
+            user: relationship.referrer,
+            cycle: relationship.referrer_cycle,
+        };
+        let children = position_children(relationships, parent).len();
+        require!(
+            children <= config.matrix_width as usize,
+            CustomError::MatrixWidthViolated
+        );
+    }
+    Ok(())
+}
+
+// Helper function to check one config bound. Several fields share an
+// error code, so the field that broke it goes to the log
+fn check_config(valid: bool, field: &str, error: CustomError) -> Result<()> {
+    if !valid {
+        msg!("Config field out of bounds: {}", field);
+        return Err(error.into());
+    }
+    Ok(())
+}
+
+// Helper function to build the snapshot export_config_hash hashes
+fn snapshot_instance(
+    solbox: &SolBox,
+    source: Pubkey,
+    tree_root: [u8; 32],
+    positions: u32
+) -> InstanceSnapshot {
+    InstanceSnapshot {
+        source,
+        config: solbox.config.clone(),
+        total_sold: solbox.total_sold,
+        total_commission_distributed: solbox.total_commission_distributed,
+        referral_count: solbox.referral_count,
+        purchase_count: solbox.purchase_count,
+        tree_root,
+        positions,
+    }
+}
+
+// Helper function to append a config version to the history ring buffer,
+// overwriting the oldest entry once the buffer is full
+fn record_config_version(
+    history: &mut ConfigHistory,
+    config: ContractConfig,
+    admin: Pubkey,
+    activation_slot: u64
+) -> Result<u64> {
+    let version = history.version
+        .checked_add(1)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+    
+    let entry = ConfigVersion {
+        version,
+        config,
+        activation_slot,
+        admin,
+    };
+    
+    // Versions start at 1, so version N lives in slot (N - 1) % LEN
+    let index = ((version - 1) % CONFIG_HISTORY_LEN as u64) as usize;
+    if index < history.entries.len() {
+        history.entries[index] = entry;
+    } else {
+        history.entries.push(entry);
+    }
+    
+    history.version = version;
+    Ok(version)
+}
+
+// Helper function to hand a wallet's positions in the tree to another wallet
+fn move_position(solbox: &mut SolBox, old_wallet: Pubkey, new_wallet: Pubkey) {
+    solbox.tree_rewrites = solbox.tree_rewrites.wrapping_add(1);
+    for relationship in solbox.referral_relationships.iter_mut() {
+        if relationship.user == old_wallet {
+            relationship.user = new_wallet;
+        }
+        if relationship.referrer == old_wallet {
+            relationship.referrer = new_wallet;
+        }
+    }
+    for entry in solbox.holding_tank.iter_mut() {
+        if entry.user == old_wallet {
+            entry.user = new_wallet;
+        }
+        if entry.sponsor == old_wallet {
+            entry.sponsor = new_wallet;
+        }
+    }
+}
+
+// Helper function to read the counter a contest ranks by
+fn contest_metric(metric: ContestMetric, user: &User) -> u64 {
+    match metric {
+        ContestMetric::Directs => user.downline_counts.first().copied().unwrap_or(0) as u64,
+        ContestMetric::Volume => user.lifetime_volume,
+    }
+}
+
+// Helper function to pay lamports out of a program-owned account
+fn transfer_from_vault(
+    vault: &AccountInfo,
+    recipient: &AccountInfo,
+    amount: u64
+) -> Result<()> {
+    let vault_balance = vault.lamports()
+        .checked_sub(amount)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+    let recipient_balance = recipient.lamports()
+        .checked_add(amount)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        
+    **vault.try_borrow_mut_lamports()? = vault_balance;
+    **recipient.try_borrow_mut_lamports()? = recipient_balance;
+    Ok(())
+}
+
+// Helper function to get the vault balance not reserved for rent or escrow
+fn treasury_balance(vault: &AccountInfo, liabilities: u64) -> Result<u64> {
+    let rent_exempt = Rent::get()?.minimum_balance(vault.data_len());
+    let balance = vault.lamports()
+        .saturating_sub(rent_exempt)
+        .saturating_sub(liabilities);
+    Ok(balance)
+}
+
+// Helper function to report the vault's balance above rent against its
+// liabilities, so solvency can be charted from the event stream
+fn emit_coverage(solbox: &Account<SolBox>) -> Result<()> {
+    let vault = solbox.to_account_info();
+    let rent_exempt = Rent::get()?.minimum_balance(vault.data_len());
+    let balance = vault.lamports().saturating_sub(rent_exempt);
+    let liabilities = solbox_liabilities(solbox)?;
+    
+    // Any balance covers a vault that owes nothing
+    let coverage_bps = match liabilities {
+        0 => u64::MAX,
+        owed => u64::try_from(balance as u128 * BPS_DENOMINATOR as u128 / owed as u128)
+            .unwrap_or(u64::MAX),
+    };
+    
+    emit!(CoverageEvent {
+        vault_balance: balance,
+        liabilities,
+        coverage_bps,
+        timestamp: unix_now(solbox)?,
+        brand_code: solbox.brand_code,
+    });
+    Ok(())
+}
+
+// Helper function to pay part of a purchase from the buyer's wallet, or
+// from a program account: the session budget or, for a card balance, the vault
+fn pay_purchase<'info>(
+    source: &AccountInfo<'info>,
+    program_funded: bool,
+    recipient: &AccountInfo<'info>,
+    amount: u64,
+    system_program: &AccountInfo<'info>
+) -> Result<()> {
+    // Shares a card balance pays into the vault are already there
+    if source.key == recipient.key {
+        return Ok(());
+    }
+    if program_funded {
+        return transfer_from_vault(source, recipient, amount);
+    }
+    invoke(
+        &system_instruction::transfer(source.key, recipient.key, amount),
+        &[source.clone(), recipient.clone(), system_program.clone()],
+    )?;
+    Ok(())
+}
+
+// Helper function to check a CPI caller is allowlisted for an instruction;
+// transaction-level calls pass through
+fn require_cpi_caller(
+    solbox: Pubkey,
+    integrator: Option<&Account<Integrator>>,
+    cpi_authority: Option<&Signer>,
+    instruction: u32
+) -> Result<()> {
+    if get_stack_height() <= TRANSACTION_LEVEL_STACK_HEIGHT {
+        return Ok(());
+    }
+    // Only the partner program can sign for its authority PDA
+    let allowed = match (integrator, cpi_authority) {
+        (Some(integrator), Some(authority)) => {
+            integrator.solbox == solbox
+                && integrator.allowed_instructions & instruction != 0
+                && integrator.cpi_authority != Pubkey::default()
+                && integrator.cpi_authority == authority.key()
+        }
+        _ => false,
+    };
+    require!(allowed, CustomError::CpiCallerNotAllowed);
+    Ok(())
+}
+
+// Helper function to total what the buyer paid for a purchase
+fn receipt_total(receipt: &Receipt) -> Result<u64> {
+    let total = receipt.commission
+        .checked_add(receipt.founder_share)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?
+        .checked_add(receipt.integrator_fee)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+    Ok(total)
+}
+
+// Helper function to check the vault still covers rent and every
+// liability after `amount` leaves the treasury
+fn require_coverage(vault: &AccountInfo, solbox: &SolBox, amount: u64) -> Result<()> {
+    let rent_exempt = Rent::get()?.minimum_balance(vault.data_len());
+    let required = solbox_liabilities(solbox)?
+        .checked_add(rent_exempt)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+    let remaining = vault.lamports()
+        .checked_sub(amount)
+        .ok_or(CustomError::InsufficientCoverage)?;
+    require!(remaining >= required, CustomError::InsufficientCoverage);
+    Ok(())
+}
+
+// Helper function to sum the vault lamports owed to buyers and users
+fn solbox_liabilities(solbox: &SolBox) -> Result<u64> {
+    let liabilities = solbox.escrow_balance
+        .checked_add(solbox.yield_pool_balance)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?
+        .checked_add(solbox.residual_balance)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?
+        .checked_add(solbox.queued_payouts)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?
+        .checked_add(solbox.deferred_founder_share)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?
+        .checked_add(solbox.tranche_balance)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?
+        .checked_add(solbox.voucher_balance)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?
+        .checked_add(solbox.card_balance_total)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+    Ok(liabilities)
+}
+
+// Helper function to check whether a system account can take `amount`
+// lamports. A transfer that leaves an account below the rent-exempt
+// minimum fails, which would fail the whole purchase
+fn can_receive(account: &AccountInfo, amount: u64) -> Result<bool> {
+    let balance = account.lamports()
+        .checked_add(amount)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+    Ok(amount == 0 || balance >= Rent::get()?.minimum_balance(account.data_len()))
+}
+
+// Helper function to hold a founder share in the vault until
+// flush_founder_share pays it out
+fn defer_founder_share(solbox: &mut SolBox, amount: u64) -> Result<()> {
+    solbox.deferred_founder_share = solbox.deferred_founder_share
+        .checked_add(amount)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+    emit!(DeferredFounderShareEvent {
+        founder: solbox.founder_wallet,
+        amount,
+        paid: false,
+        timestamp: unix_now(solbox)?,
+        brand_code: solbox.brand_code,
+    });
+    Ok(())
+}
+
+// Helper function to check whether earned balances can be claimed. A
+// pause stops claims too unless the config exempts them
+fn claims_open(solbox: &SolBox) -> bool {
+    !solbox.paused || solbox.config.claims_during_pause
+}
+
+// Helper function to check whether `now` falls on the configured payout
+// day. Day 0 of the Unix clock was a Thursday
+fn is_payout_day(config: &ContractConfig, now: i64) -> bool {
+    let weekday = (now.div_euclid(SECONDS_PER_DAY) + 3).rem_euclid(7) as u8 + 1;
+    config.payout_weekday == 0 || config.payout_weekday == weekday
+}
+
+// Helper function to pay a claimed amount, already taken off its pool, to
+// the wallet together with anything queued for it. Outside the payout
+// window the amount joins the queue instead
+fn pay_claim(
+    solbox: &mut Account<SolBox>,
+    user: &mut User,
+    wallet: &AccountInfo,
+    amount: u64
+) -> Result<()> {
+    let now = unix_now(solbox)?;
+    if !is_payout_day(&solbox.config, now) {
+        user.queued_payout = user.queued_payout
+            .checked_add(amount)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        solbox.queued_payouts = solbox.queued_payouts
+            .checked_add(amount)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        emit!(QueuedPayoutEvent {
+            user: user.key,
+            amount,
+            paid: false,
+            timestamp: now,
+            brand_code: solbox.brand_code,
+        });
+        return Ok(());
+    }
+    
+    let queued = user.queued_payout;
+    if queued > 0 {
+        user.queued_payout = 0;
+        solbox.queued_payouts = solbox.queued_payouts
+            .checked_sub(queued)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        emit!(QueuedPayoutEvent {
+            user: user.key,
+            amount: queued,
+            paid: true,
+            timestamp: now,
+            brand_code: solbox.brand_code,
+        });
+    }
+    let total = amount
+        .checked_add(queued)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+    transfer_from_vault(&solbox.to_account_info(), wallet, total)
+}
+
+// Helper function to move a user's residual from closed epochs to claimable
+fn mature_residual(user: &mut User, epoch: u64) {
+    if user.residual_epoch < epoch {
+        user.residual_claimable = user.residual_claimable.saturating_add(user.residual_pending);
+        user.residual_pending = 0;
+        user.residual_epoch = epoch;
+    }
+}
+
+// Helper function to put residual commission on a referrer's ledger
+fn accrue_residual(solbox: &mut SolBox, referrer: &mut User, residual: u64) -> Result<()> {
+    mature_residual(referrer, current_epoch(solbox)?);
+    referrer.residual_pending = referrer.residual_pending
+        .checked_add(residual)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+    solbox.residual_balance = solbox.residual_balance
+        .checked_add(residual)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+    Ok(())
+}
+
+// Helper function to tell whether a commission pays out through a tranche
+fn drips(config: &ContractConfig, commission: u64, fast_start: u64) -> bool {
+    config.drip_threshold > 0 && commission > config.drip_threshold && fast_start > 0
+}
+
+// Helper function to hold a dripped commission in the vault until
+// release_tranche pays it out
+fn hold_tranche(solbox: &mut SolBox, amount: u64) -> Result<()> {
+    solbox.tranche_balance = solbox.tranche_balance
+        .checked_add(amount)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+    Ok(())
+}
+
+// Helper function to get the part of a tranche released by `now`
+fn tranche_released(tranche: &Tranche, now: i64) -> Result<u64> {
+    let days = now.saturating_sub(tranche.starts_at) / SECONDS_PER_DAY;
+    let days = (days.max(0) as u64).min(tranche.days as u64);
+    pro_rata(tranche.total, days, tranche.days as u64)
+}
+
+// Helper function to credit a user with bonus pool yield accrued on their package
+fn accrue_yield(solbox: &SolBox, user: &mut User) -> Result<()> {
+    let accrued = package_yield(user.current_package, solbox.yield_per_package)?;
+    let owed = accrued
+        .checked_sub(user.yield_debt)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+    user.unclaimed_yield = user.unclaimed_yield
+        .checked_add(u64::try_from(owed).map_err(|_| error!(CustomError::ArithmeticError))?)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+    user.yield_debt = accrued;
+    Ok(())
+}
+
+// Helper function to change a user's package while keeping yield weights in sync
+fn set_package(solbox: &mut SolBox, user: &mut User, package: u64) -> Result<()> {
+    accrue_yield(solbox, user)?;
+    if user.current_package == 0 && package > 0 {
+        solbox.active_users = solbox.active_users
+            .checked_add(1)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        
+        // A package taken up from nothing starts a new membership period
+        user.paid_until = match solbox.config.membership_period {
+            0 => 0,
+            period => unix_now(solbox)?
+                .checked_add(period)
+                .ok_or_else(|| error!(CustomError::ArithmeticError))?,
+        };
+    } else if user.current_package > 0 && package == 0 {
+        solbox.active_users = solbox.active_users
+            .checked_sub(1)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        user.paid_until = 0;
+    }
+    solbox.total_package_weight = solbox.total_package_weight
+        .checked_sub(user.current_package)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?
+        .checked_add(package)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+    user.current_package = package;
+    user.support_tier = support_tier(&solbox.config, package).saturating_sub(user.tier_decay);
+    user.yield_debt = package_yield(package, solbox.yield_per_package)?;
+    Ok(())
+}
+
+// Helper function to close the user's finished tier re-qualification
+// periods. A period qualifies with purchase volume of at least the
+// threshold of the tier the package unlocks, which restores the full tier;
+// each missed period beyond the grace periods costs one tier
+fn roll_tier_periods(solbox: &SolBox, user: &mut User, now: i64) -> Result<()> {
+    let config = &solbox.config;
+    if config.tier_period == 0 {
+        return Ok(());
+    }
+    let period = now.div_euclid(config.tier_period) as u64;
+    
+    // Tracking starts with the first period a user is seen in
+    if user.period_index == 0 {
+        user.period_index = period;
+        return Ok(());
+    }
+    if period <= user.period_index {
+        return Ok(());
+    }
+    
+    let full_tier = support_tier(config, user.current_package);
+    let required = match full_tier {
+        0 => 0,
+        tier => config.support_tier_thresholds[tier as usize - 1],
+    };
+    
+    // Only the first closed period can hold volume of its own, and a
+    // rolling window only loses volume as closes move on; past the grace
+    // periods and a full decay nothing changes, so the loop stays bounded
+    let closed = period - user.period_index;
+    let bound = config.tier_grace_periods as u64 + SUPPORT_TIERS as u64 + 1;
+    for index in 0..closed.min(bound) {
+        let volume = if config.tier_window_days > 0 {
+            let close = ((user.period_index + index + 1) as i64).saturating_mul(config.tier_period);
+            window_volume(user, close.div_euclid(SECONDS_PER_DAY) as u64, config.tier_window_days as u64)
+        } else if index == 0 {
+            user.period_volume
+        } else {
+            0
+        };
+        if volume >= required {
+            user.missed_periods = 0;
+            user.tier_decay = 0;
+        } else {
+            user.missed_periods = user.missed_periods.saturating_add(1);
+            if user.missed_periods <= config.tier_grace_periods {
+                emit!(TierDecayWarningEvent {
+                    user: user.key,
+                    missed_periods: user.missed_periods,
+                    grace_periods_left: config.tier_grace_periods - user.missed_periods,
+                    timestamp: now,
+                    brand_code: solbox.brand_code,
+                });
+            } else if user.tier_decay < full_tier {
+                user.tier_decay += 1;
+                emit!(TierDecayedEvent {
+                    user: user.key,
+                    support_tier: full_tier - user.tier_decay,
+                    timestamp: now,
+                    brand_code: solbox.brand_code,
+                });
+            }
+        }
+    }
+    
+    user.period_index = period;
+    user.period_volume = 0;
+    user.support_tier = full_tier.saturating_sub(user.tier_decay);
+    Ok(())
+}
+
+// Helper function to track a buyer's lifetime volume and the volume that
+// re-qualifies their tier
+fn add_purchase_volume(solbox: &SolBox, user: &mut User, amount: u64) -> Result<()> {
+    let now = unix_now(solbox)?;
+    user.lifetime_volume = user.lifetime_volume
+        .checked_add(amount)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+    roll_tier_periods(solbox, user, now)?;
+    user.period_volume = user.period_volume
+        .checked_add(amount)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+    add_window_volume(user, now, amount)?;
+    user.last_active_at = now;
+    Ok(())
+}
+
+// Helper function to add a purchase to the user's daily volume ring,
+// clearing the days skipped since its newest bucket
+fn add_window_volume(user: &mut User, now: i64, amount: u64) -> Result<()> {
+    let day = now.div_euclid(SECONDS_PER_DAY) as u64;
+    if day > user.volume_day {
+        for skipped in (user.volume_day + 1..=day).rev().take(VOLUME_WINDOW_DAYS) {
+            user.volume_buckets[skipped as usize % VOLUME_WINDOW_DAYS] = 0;
+        }
+        user.volume_day = day;
+    }
+    let bucket = &mut user.volume_buckets[day as usize % VOLUME_WINDOW_DAYS];
+    *bucket = bucket
+        .checked_add(amount)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+    Ok(())
+}
+
+// Helper function to sum the user's volume over the `days` days before
+// `end_day`, as far as the ring still holds them
+fn window_volume(user: &User, end_day: u64, days: u64) -> u64 {
+    (end_day.saturating_sub(days)..end_day)
+        .filter(|&day| day <= user.volume_day && day + VOLUME_WINDOW_DAYS as u64 > user.volume_day)
+        .fold(0u64, |sum, day| sum.saturating_add(user.volume_buckets[day as usize % VOLUME_WINDOW_DAYS]))
+}
+
+// Helper function to get the end of the reactivation window of a
+// membership that lapses at `lapsed_at`
+fn reactivation_deadline(config: &ContractConfig, lapsed_at: i64) -> Result<i64> {
+    lapsed_at
+        .checked_add(config.reactivation_window)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))
+}
+
+// Helper function to derive the support tier a package size unlocks
+fn support_tier(config: &ContractConfig, package: u64) -> u8 {
+    config.support_tier_thresholds
+        .iter()
+        .take_while(|&&threshold| threshold > 0 && package >= threshold)
+        .count() as u8
+}
+
+// Helper function to count a receipt entering or leaving escrow on the
+// wallets it names
+fn count_pending_receipt(buyer: &mut User, payee: &mut User, pending: bool) -> Result<()> {
+    for user in [buyer, payee] {
+        user.pending_receipts = if pending {
+            user.pending_receipts.checked_add(1)
+        } else {
+            user.pending_receipts.checked_sub(1)
         }
-        
-        emit!(BlacklistEvent {
-            admin: *ctx.accounts.admin.key,
-            user,
-            action: BlacklistAction::Add,
-            timestamp: Clock::get()?.unix_timestamp,
-        });
-        
-        Ok(())
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+    }
+    Ok(())
+}
+
+// Helper function to give a limited sale's unit back to its package. A
+// package no longer limited has no supply to return it to
+fn restock_unit(package: Option<&mut Account<Package>>, receipt: &Receipt) -> Result<()> {
+    if receipt.package == Pubkey::default() {
+        return Ok(());
+    }
+    let package = package.ok_or(CustomError::PackageRequired)?;
+    if package.limited {
+        package.remaining = package.remaining
+            .checked_add(1)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
     }
+    Ok(())
+}
 
-    pub fn remove_from_blacklist(
-        ctx: Context<AdminAction>,
-        user: Pubkey
-    ) -> Result<()> {
-        let solbox = &mut ctx.accounts.solbox;
-        
-        // Verify admin authority
-        require!(
-            ctx.accounts.admin.key() == solbox.owner,
-            CustomError::Unauthorized
-        );
+// Helper function to read the clock, pushed forward by a sandbox's offset
+fn unix_now(solbox: &SolBox) -> Result<i64> {
+    let now = Clock::get()?.unix_timestamp;
+    Ok(now.saturating_add(solbox.time_offset))
+}
+
+// Helper function to read the epoch, pushed forward by a sandbox's offset
+fn current_epoch(solbox: &SolBox) -> Result<u64> {
+    let epoch = Clock::get()?.epoch;
+    Ok(epoch.saturating_add(solbox.epoch_offset))
+}
+
+// Helper function to get the current day number since the unix epoch,
+// on the same clock as unix_now
+fn current_day(solbox: &SolBox) -> Result<u64> {
+    Ok(unix_now(solbox)?.div_euclid(SECONDS_PER_DAY) as u64)
+}
+
+// Helper function to get the seed of today's DailyStats PDA. Clock-derived
+// seeds are a plain call rather than a method chain, which IDL resolution
+// skips instead of trying to evaluate it outside the instruction
+fn day_seed(solbox: &SolBox) -> Result<[u8; 8]> {
+    Ok(current_day(solbox)?.to_le_bytes())
+}
+
+// Helper function to get the seed of the DailyStats PDA a purchase was
+// counted in, a plain call for the same reason as `day_seed`
+fn purchase_day_seed(receipt: &Receipt) -> [u8; 8] {
+    (receipt.purchased_at.div_euclid(SECONDS_PER_DAY) as u64).to_le_bytes()
+}
+
+// Helper function to get the seed of this month's MerchantMonth PDA, see
+// `month_of` and `day_seed`
+fn month_seed(solbox: &SolBox) -> Result<[u8; 4]> {
+    Ok(month_of(unix_now(solbox)?).to_le_bytes())
+}
+
+// Helper function to add a redemption to a merchant's totals
+fn record_redemption(redeemed: &mut u64, fees: &mut u64, redemptions: &mut u64, amount: u64, fee: u64) -> Result<()> {
+    *redeemed = redeemed
+        .checked_add(amount)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+    *fees = fees
+        .checked_add(fee)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+    *redemptions = redemptions
+        .checked_add(1)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+    Ok(())
+}
+
+// Helper function to add a purchase to a daily analytics bucket
+fn record_daily_purchase(
+    stats: &mut DailyStats,
+    buyer: &Pubkey,
+    amount: u64,
+    commission: u64,
+    is_new_registration: bool
+) -> Result<()> {
+    stats.purchase_count = stats.purchase_count
+        .checked_add(1)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
         
-        // Remove from blacklist
-        solbox.blacklisted_users.retain(|&x| x != user);
+    stats.volume = stats.volume
+        .checked_add(amount)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
         
-        emit!(BlacklistEvent {
-            admin: *ctx.accounts.admin.key,
-            user,
-            action: BlacklistAction::Remove,
-            timestamp: Clock::get()?.unix_timestamp,
-        });
+    stats.commission = stats.commission
+        .checked_add(commission)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
         
-        Ok(())
+    if is_new_registration {
+        stats.new_registrations = stats.new_registrations
+            .checked_add(1)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
     }
+    
+    // HyperLogLog update: low bits pick the register, the rank of the
+    // remaining bits is the position of their lowest set bit
+    let digest = hash(buyer.as_ref()).to_bytes();
+    let mut word = [0u8; 8];
+    word.copy_from_slice(&digest[..8]);
+    let value = u64::from_le_bytes(word);
+    
+    let index_bits = BUYER_SKETCH_REGISTERS.trailing_zeros();
+    let index = (value as usize) & (BUYER_SKETCH_REGISTERS - 1);
+    let rank = ((value >> index_bits).trailing_zeros() + 1)
+        .min(64 - index_bits + 1) as u8;
+    
+    if rank > stats.buyer_sketch[index] {
+        stats.buyer_sketch[index] = rank;
+    }
+    Ok(())
 }
 
-#[account]
-pub struct SolBox {
-    pub owner: Pubkey,
-    pub founder_wallet: Pubkey,
-    pub paused: bool,
-    pub total_sold: u64,
-    pub total_commission_distributed: u64,
-    pub referral_count: u64,
-    pub config: ContractConfig,
-    pub blacklisted_users: Vec<Pubkey>,
-    pub referral_relationships: Vec<ReferralRelationship>,
+// Helper function to take a cancelled purchase back out of its daily
+// bucket. The buyer sketch can't forget a buyer, so the unique buyer
+// estimate still counts them
+fn unrecord_daily_purchase(
+    stats: &mut DailyStats,
+    amount: u64,
+    commission: u64,
+    is_new_registration: bool
+) -> Result<()> {
+    stats.purchase_count = stats.purchase_count
+        .checked_sub(1)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+    stats.volume = stats.volume
+        .checked_sub(amount)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+    stats.commission = stats.commission
+        .checked_sub(commission)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+    if is_new_registration {
+        stats.new_registrations = stats.new_registrations
+            .checked_sub(1)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+    }
+    Ok(())
 }
 
-#[account]
-pub struct User {
-    pub key: Pubkey,
-    pub current_package: u64,
-    pub total_earnings: u64,
+// Helper function to count a purchase against the integrator's per-epoch
+// ceilings, starting the count over when the epoch turns
+fn charge_integrator_limits(integrator: &mut Integrator, epoch: u64, amount: u64) -> Result<()> {
+    if integrator.limit_epoch != epoch {
+        integrator.limit_epoch = epoch;
+        integrator.epoch_volume = 0;
+        integrator.epoch_purchases = 0;
+    }
+    integrator.epoch_volume = integrator.epoch_volume
+        .checked_add(amount)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+    integrator.epoch_purchases = integrator.epoch_purchases.saturating_add(1);
+    require!(
+        integrator.epoch_volume_limit == 0 || integrator.epoch_volume <= integrator.epoch_volume_limit,
+        CustomError::IntegratorLimitExceeded
+    );
+    require!(
+        integrator.epoch_purchase_limit == 0 || integrator.epoch_purchases <= integrator.epoch_purchase_limit,
+        CustomError::IntegratorLimitExceeded
+    );
+    Ok(())
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
-pub struct ContractConfig {
-    pub referral_limit: u8,
-    pub commission_percentage: u64,
-    pub commission_levels: u8,
-    pub bonus_percentage: u64,
-    pub valid_amounts: Vec<u64>,
+// Helper function to run the suspicious-activity heuristics over a
+// purchase and return the FRAUD_FLAG_* reasons it trips. The payer count
+// is a one-row count-min sketch, so colliding payers only ever flag early
+fn fraud_heuristics(
+    config: &ContractConfig,
+    stats: &mut DailyStats,
+    integrator: Option<&mut Integrator>,
+    funder: Option<&Pubkey>,
+    buyer: &Pubkey,
+    now: i64
+) -> u8 {
+    let mut reasons = 0;
+    
+    // One payer funding purchases for many wallets. The payer's counter
+    // only moves for a (payer, buyer) pair the day's filter hasn't seen,
+    // so repeat purchases for one wallet count once; a false positive
+    // can only under-count
+    if let Some(funder) = funder.filter(|_| config.flag_funder_threshold > 0) {
+        let pair = hashv(&[funder.as_ref(), buyer.as_ref()]).to_bytes();
+        let mut new_pair = false;
+        for i in 0..FUNDER_PAIR_FILTER_HASHES {
+            let mut word = [0u8; 4];
+            word.copy_from_slice(&pair[i * 4..i * 4 + 4]);
+            let bit = u32::from_le_bytes(word) as usize % (FUNDER_PAIR_FILTER_BYTES * 8);
+            let mask = 1u8 << (bit % 8);
+            new_pair |= stats.funder_pairs[bit / 8] & mask == 0;
+            stats.funder_pairs[bit / 8] |= mask;
+        }
+        
+        let digest = hash(funder.as_ref()).to_bytes();
+        let index = digest[0] as usize % FUNDER_SKETCH_WIDTH;
+        if new_pair {
+            stats.funder_sketch[index] = stats.funder_sketch[index].saturating_add(1);
+        }
+        if stats.funder_sketch[index] > config.flag_funder_threshold {
+            reasons |= FRAUD_FLAG_SHARED_FUNDER;
+        }
+    }
+    
+    // A burst of purchases from one integrator's attested cohort
+    if let Some(integrator) = integrator.filter(|_| config.flag_burst_threshold > 0) {
+        if now - integrator.burst_started_at >= FRAUD_BURST_WINDOW {
+            integrator.burst_started_at = now;
+            integrator.burst_count = 0;
+        }
+        integrator.burst_count = integrator.burst_count.saturating_add(1);
+        if integrator.burst_count > config.flag_burst_threshold as u32 {
+            reasons |= FRAUD_FLAG_BURST;
+        }
+    }
+    reasons
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
-pub struct ReferralRelationship {
-    pub user: Pubkey,
-    pub referrer: Pubkey,
-    pub timestamp: i64,
+// Estimates the number of unique buyers recorded in a daily sketch.
+// Intended for off-chain readers of DailyStats.
+pub fn estimate_unique_buyers(sketch: &[u8; BUYER_SKETCH_REGISTERS]) -> u64 {
+    let m = BUYER_SKETCH_REGISTERS as f64;
+    let alpha = 0.709; // bias correction for 64 registers
+    
+    let sum: f64 = sketch.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+    let estimate = alpha * m * m / sum;
+    
+    // Small range correction via linear counting
+    let empty = sketch.iter().filter(|&&r| r == 0).count();
+    if estimate <= 2.5 * m && empty > 0 {
+        return (m * (m / empty as f64).ln()).round() as u64;
+    }
+    estimate.round() as u64
 }
 
-#[derive(Accounts)]
-pub struct Initialize<'info> {
-    #[account(
-        init,
-        payer = owner,
-        space = 8 +    // discriminator
-                32 +   // owner pubkey
-                32 +   // founder_wallet pubkey
-                1 +    // paused bool
-                8 +    // total_sold
-                8 +    // total_commission_distributed
-                8 +    // referral_count
-                CONFIG_SPACE + // config
-                BLACKLIST_SPACE + // blacklisted users
-                REFERRAL_RELATIONSHIPS_SPACE // relationships
-    )]
-    pub solbox: Account<'info, SolBox>,
-    #[account(mut)]
-    pub owner: Signer<'info>,
-    pub system_program: Program<'info, System>,
+// Helper function to get the bloom filter bit positions for a key
+fn bloom_positions(key: &Pubkey) -> [usize; BLACKLIST_FILTER_HASHES] {
+    let digest = hash(key.as_ref()).to_bytes();
+    let bit_count = BLACKLIST_FILTER_BYTES * 8;
+    
+    let mut positions = [0usize; BLACKLIST_FILTER_HASHES];
+    for (i, position) in positions.iter_mut().enumerate() {
+        let mut word = [0u8; 4];
+        word.copy_from_slice(&digest[i * 4..i * 4 + 4]);
+        *position = u32::from_le_bytes(word) as usize % bit_count;
+    }
+    positions
 }
 
-#[derive(Accounts)]
-pub struct AdminAction<'info> {
-    #[account(mut)]
-    pub solbox: Account<'info, SolBox>,
-    #[account(mut)]
-    pub admin: Signer<'info>,
-    #[account(mut)]
-    pub user: Account<'info, User>,
-    pub system_program: Program<'info, System>,
+// Helper function to add a key to the blacklist bloom filter
+fn bloom_insert(bits: &mut [u8; BLACKLIST_FILTER_BYTES], key: &Pubkey) {
+    for position in bloom_positions(key) {
+        bits[position / 8] |= 1 << (position % 8);
+    }
 }
 
-#[derive(Accounts)]
-pub struct UpgradePackage<'info> {
-    #[account(mut)]
-    pub solbox: Account<'info, SolBox>,
-    #[account(mut)]
-    pub user: Account<'info, User>,
-    pub system_program: Program<'info, System>,
+// Helper function to test bloom filter membership (false positives possible)
+fn bloom_might_contain(bits: &[u8; BLACKLIST_FILTER_BYTES], key: &Pubkey) -> bool {
+    bloom_positions(key)
+        .iter()
+        .all(|&position| bits[position / 8] & (1 << (position % 8)) != 0)
 }
 
-#[derive(Accounts)]
-pub struct BuyGiftCard<'info> {
-    #[account(mut)]
-    pub solbox: Account<'info, SolBox>,
-    #[account(mut)]
-    pub user: Signer<'info>,
-    #[account(mut)]
-    pub user_account: Account<'info, User>,
-    #[account(mut)]
-    pub referrer: SystemAccount<'info>,
-    #[account(mut)]
-    pub referrer_user_account: Account<'info, User>,
-    #[account(mut)]
-    pub founder: SystemAccount<'info>,
-    pub system_program: Program<'info, System>,
+// Helper function to check the blacklist, only scanning the list on filter hits
+fn is_blacklisted(solbox: &SolBox, filter: &BlacklistFilter, key: &Pubkey) -> bool {
+    bloom_might_contain(&filter.bits, key) && solbox.blacklisted_users.contains(key)
 }
 
-// Space allocation constants
-pub const CONFIG_SPACE: usize = 1 +  // referral_limit
-                               8 +  // commission_percentage
-                               1 +  // commission_levels
-                               8 +  // bonus_percentage
-                               32;  // valid_amounts vector space
+// Helper function to encode a stake pool instruction taking a single u64
+fn stake_pool_instruction_data(tag: u8, amount: u64) -> Vec<u8> {
+    let mut data = Vec::with_capacity(9);
+    data.push(tag);
+    data.extend_from_slice(&amount.to_le_bytes());
+    data
+}
 
-pub const BLACKLIST_SPACE: usize = 1000; // Space for blacklisted users
-pub const REFERRAL_RELATIONSHIPS_SPACE: usize = 2000; // Space for referral data
+// Helper function to encode a core bridge post_message: nonce, payload
+// and consistency level, Borsh-style
+fn wormhole_post_message_data(nonce: u32, payload: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(10 + payload.len());
+    data.push(WORMHOLE_POST_MESSAGE);
+    data.extend_from_slice(&nonce.to_le_bytes());
+    data.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    data.extend_from_slice(payload);
+    data.push(WORMHOLE_CONSISTENCY_FINALIZED);
+    data
+}
 
-// Events
-#[event]
-pub struct InitializeEvent {
-    pub owner: Pubkey,
-    pub founder_wallet: Pubkey,
-    pub timestamp: i64,
+// Wallet a deleted user's position is handed to: the prefix marks it, and
+// nothing in it derives from the deleted wallet
+pub fn tombstone_key(solbox: &Pubkey, index: u64) -> Pubkey {
+    let mut key = [0u8; 32];
+    key[..16].copy_from_slice(TOMBSTONE_PREFIX);
+    key[16..24].copy_from_slice(&index.to_le_bytes());
+    key[24..].copy_from_slice(&solbox.to_bytes()[..8]);
+    Pubkey::new_from_array(key)
 }
 
-#[event]
-pub struct ConfigUpdateEvent {
-    pub admin: Pubkey,
-    pub new_config: ContractConfig,
-    pub timestamp: i64,
+// Helper function to get the seed of the next tombstone's User PDA, as a
+// call for the same reason as `day_seed`
+fn tombstone_seed(solbox: &Account<SolBox>) -> [u8; 32] {
+    tombstone_key(&solbox.key(), solbox.tombstone_count).to_bytes()
 }
 
-#[event]
-pub struct PauseEvent {
-    pub admin: Pubkey,
-    pub paused: bool,
-    pub timestamp: i64,
+pub fn is_tombstone(wallet: &Pubkey) -> bool {
+    wallet.as_ref().starts_with(TOMBSTONE_PREFIX)
 }
 
-#[event]
-pub struct PackageUpgradeEvent {
-    pub user: Pubkey,
-    pub old_package: u64,
-    pub new_package: u64,
-    pub difference: u64,
-    pub timestamp: i64,
+// Helper function to tell positions whose commission goes to the bonus
+// pool: house positions and the tombstones of deleted users
+fn earns_for_pool(solbox: &SolBox, wallet: &Pubkey) -> bool {
+    solbox.house_positions.contains(wallet) || is_tombstone(wallet)
 }
 
-#[event]
-pub struct GiftCardPurchaseEvent {
-    pub user: Pubkey,
-    pub referrer: Pubkey,
-    pub amount: u64,
-    pub commission: u64,
-    pub founder_share: u64,
-    pub timestamp: i64,
+// Hash a drop commit is made with: the Package PDA, the committing
+// wallet and the drop, so a commit can't be copied to another wallet or
+// drop, and the hidden quantity with a secret salt
+pub fn drop_commitment(package: &Pubkey, wallet: &Pubkey, drop_id: u64, quantity: u64, salt: &[u8; 32]) -> [u8; 32] {
+    hashv(&[package.as_ref(), wallet.as_ref(), &drop_id.to_le_bytes(), &quantity.to_le_bytes(), salt]).to_bytes()
 }
 
-#[event]
-pub struct PackageGrantedEvent {
-    pub admin: Pubkey,
-    pub user: Pubkey,
-    pub package: u64,
-    pub timestamp: i64,
+// Hash an invitation is made with: the identifier, normalized off-chain
+// (lowercased email, E.164 phone number), salted with the SolBox
+pub fn invitation_hash(solbox: &Pubkey, identifier: &[u8]) -> [u8; 32] {
+    hash(&[solbox.as_ref(), identifier].concat()).to_bytes()
 }
 
-#[event]
-pub struct CommissionConfigEvent {
-    pub admin: Pubkey,
-    pub new_percentage: u64,
-    pub new_levels: u8,
-    pub timestamp: i64,
+// Calendar months since January 1970 (UTC), which MerchantMonth buckets
+// are keyed by; the date math is Howard Hinnant's civil_from_days
+pub fn month_of(unix_timestamp: i64) -> u32 {
+    let z = unix_timestamp.div_euclid(SECONDS_PER_DAY) + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    ((year - 1970) * 12 + month - 1).max(0) as u32
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
-pub enum BlacklistAction {
-    Add,
-    Remove,
+// Helper function to decode a PostedVAA account of the core bridge; the
+// accounts struct checks its owner
+fn read_posted_vaa(info: &AccountInfo) -> Result<PostedVaa> {
+    let data = info.try_borrow_data()?;
+    let body = data.strip_prefix(b"vaa").ok_or(CustomError::InvalidVaa)?;
+    PostedVaa::deserialize(&mut &body[..]).map_err(|_| error!(CustomError::InvalidVaa))
 }
 
-#[event]
-pub struct BlacklistEvent {
-    pub admin: Pubkey,
-    pub user: Pubkey,
-    pub action: BlacklistAction,
-    pub timestamp: i64,
+// Helper function to read the message fee from the core bridge config
+fn wormhole_message_fee(bridge_config: &[u8]) -> Option<u64> {
+    let bytes = bridge_config.get(WORMHOLE_FEE_OFFSET..WORMHOLE_FEE_OFFSET + 8)?;
+    Some(u64::from_le_bytes(bytes.try_into().ok()?))
 }
 
-#[error_code]
-pub enum CustomError {
-    #[msg("Contract is paused")]
-    ContractPaused,
-    #[msg("Unauthorized access")]
-    Unauthorized,
-    #[msg("Invalid gift card amount")]
-    InvalidAmount,
-    #[msg("Invalid upgrade - new package must be higher")]
-    InvalidUpgrade,
-    #[msg("Self-referral not allowed")]
-    SelfReferralNotAllowed,
-    #[msg("Arithmetic error")]
-    ArithmeticError,
-    #[msg("No spillover position available")]
-    NoSpilloverAvailable,
-    #[msg("Invalid referrer")]
-    InvalidReferrer,
-    #[msg("User is blacklisted")]
-    UserBlacklisted,
+// Helper function to check a display handle: bounded length, no spaces or
+// control characters. Any script is allowed
+fn is_valid_handle(handle: &str) -> bool {
+    (MIN_HANDLE_LEN..=MAX_HANDLE_LEN).contains(&handle.len())
+        && !handle.chars().any(|c| c.is_whitespace() || c.is_control())
 }
 
-// Helper function to find spillover referrer position
-fn find_spillover_position(
-    relationships: &Vec<ReferralRelationship>,
-    referrer: Pubkey,
-    limit: u8
-) -> Option<Pubkey> {
-    let mut referral_counts: HashMap<Pubkey, u8> = HashMap::new();
-    
-    // Count existing referrals for each referrer
-    for relationship in relationships {
-        *referral_counts.entry(relationship.referrer).or_insert(0) += 1;
-    }
-    
-    // First try the original referrer if they haven't reached limit
-    if referral_counts.get(&referrer).unwrap_or(&0) < &limit {
-        return Some(referrer);
+// Hash a display handle the way its Handle PDA is seeded. Only ASCII is
+// case-folded, so the result doesn't depend on a locale
+pub fn handle_key(handle: &str) -> [u8; 32] {
+    hash(handle.to_ascii_lowercase().as_bytes()).to_bytes()
+}
+
+// Helper function to detach a handle from its user; the account closes
+// to the user's wallet
+fn clear_handle(accounts: &mut ReleaseHandle, action: HandleAction) -> Result<()> {
+    accounts.user_account.handle = Pubkey::default();
+    emit!(HandleEvent {
+        wallet: accounts.user_account.key,
+        handle: accounts.handle.handle.clone(),
+        action,
+        timestamp: unix_now(&accounts.solbox)?,
+        brand_code: accounts.solbox.brand_code,
+    });
+    Ok(())
+}
+
+// Helper function to book commission earned by a house position, already
+// held in the vault, as bonus pool yield. Without yield sharing there is
+// no pool, so it stays in the treasury
+fn credit_house_commission(solbox: &mut SolBox, house: Pubkey, purchase_id: u64, amount: u64) -> Result<()> {
+    solbox.house_commission = solbox.house_commission
+        .checked_add(amount)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+    if solbox.config.yield_sharing {
+        solbox.undistributed_yield = solbox.undistributed_yield
+            .checked_add(amount)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+        solbox.yield_pool_balance = solbox.yield_pool_balance
+            .checked_add(amount)
+            .ok_or_else(|| error!(CustomError::ArithmeticError))?;
     }
     
-    // Otherwise find first available referrer
-    for relationship in relationships {
-        let count = referral_counts.get(&relationship.referrer).unwrap_or(&0);
-        if *count < limit {
-            return Some(relationship.referrer);
-        }
-    }
+    emit!(HouseCommissionEvent {
+        house,
+        purchase_id,
+        amount,
+        pooled: solbox.config.yield_sharing,
+        timestamp: unix_now(solbox)?,
+        brand_code: solbox.brand_code,
+    });
+    Ok(())
+}
+
+// Helper function to add earnings to the user's bucket for their source
+fn credit_earnings(user: &mut User, source: EarningSource, amount: u64) -> Result<()> {
+    let bucket = match source {
+        EarningSource::Direct => &mut user.earnings_from_direct,
+        EarningSource::Spillover => &mut user.earnings_from_spillover,
+        EarningSource::Pools => &mut user.earnings_from_pools,
+    };
+    *bucket = bucket.checked_add(amount).ok_or_else(|| error!(CustomError::ArithmeticError))?;
+    Ok(())
+}
+
+// Helper function to set up a new User PDA
+fn init_user(solbox: &mut Account<SolBox>, user_account: &mut User, wallet: Pubkey, bump: u8) -> Result<()> {
+    user_account.account_type = ACCOUNT_TYPE_USER;
+    user_account.account_version = USER_VERSION;
+    user_account.solbox = solbox.key();
+    user_account.key = wallet;
+    user_account.bump = bump;
+    user_account.current_package = 0;
+    user_account.total_earnings = 0;
+    user_account.downline_counts = vec![0; solbox.max_levels as usize];
+    user_account.yield_debt = 0;
+    user_account.unclaimed_yield = 0;
+    user_account.lifetime_volume = 0;
+    user_account.imported_from = Pubkey::default();
+    user_account.support_tier = 0;
+    user_account.period_index = 0;
+    user_account.period_volume = 0;
+    user_account.missed_periods = 0;
+    user_account.tier_decay = 0;
+    user_account.beneficiary = Pubkey::default();
+    user_account.inactivity_period = 0;
+    user_account.last_active_at = unix_now(solbox)?;
+    user_account.residual_pending = 0;
+    user_account.residual_epoch = 0;
+    user_account.residual_claimable = 0;
+    user_account.handle = Pubkey::default();
+    user_account.sponsored_by = Pubkey::default();
+    user_account.pending_acceptance = false;
+    user_account.paid_until = 0;
+    user_account.queued_payout = 0;
+    user_account.earnings_from_direct = 0;
+    user_account.earnings_from_spillover = 0;
+    user_account.earnings_from_pools = 0;
+    user_account.position_mint = Pubkey::default();
+    user_account.unseen_downline_events = 0;
+    user_account.terms_hash = [0u8; 32];
+    user_account.terms_version = 0;
+    user_account.terms_accepted_at = 0;
+    user_account.volume_day = 0;
+    user_account.volume_buckets = [0; VOLUME_WINDOW_DAYS];
+    user_account.deletion_requested_at = 0;
+    user_account.flagged = 0;
+    user_account.invited_by = Pubkey::default();
+    user_account.card_balance = 0;
+    user_account.pending_receipts = 0;
+    Ok(())
+}
+
+// Helper function to record a wallet's acceptance of the terms in force,
+// which it proves by passing their hash
+fn accept_current_terms(solbox: &SolBox, user_account: &mut User, terms_hash: [u8; 32]) -> Result<()> {
+    require!(terms_hash == solbox.terms_hash, CustomError::TermsMismatch);
+    let now = unix_now(solbox)?;
+    user_account.terms_hash = terms_hash;
+    user_account.terms_version = solbox.terms_version;
+    user_account.terms_accepted_at = now;
     
-    None // No available referrer found
-}
\ No newline at end of file
+    emit!(TermsAcceptedEvent {
+        user: user_account.key,
+        terms_hash,
+        version: solbox.terms_version,
+        timestamp: now,
+        brand_code: solbox.brand_code,
+    });
+    Ok(())
+}
@@ -0,0 +1,173 @@
+// Comp-plan arithmetic, kept free of accounts and contexts so it can be
+// checked directly
+
+use anchor_lang::prelude::*;
+
+use crate::{CustomError, BPS_DENOMINATOR, YIELD_PRECISION};
+
+// How a purchase amount divides before any integrator fee
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AmountSplit {
+    pub commission: u64,
+    pub bonus: u64,
+    pub founder_share: u64,
+}
+
+// Split a purchase into referrer commission, withheld bonus and founder share
+pub fn split_amount(
+    amount: u64,
+    commission_percentage: u64,
+    bonus_percentage: u64
+) -> Result<AmountSplit> {
+    let commission = amount
+        .checked_mul(commission_percentage)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?
+        .checked_div(100)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+
+    let bonus = amount
+        .checked_mul(bonus_percentage)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?
+        .checked_div(100)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+
+    let founder_share = amount
+        .checked_sub(commission)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?
+        .checked_sub(bonus)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?;
+
+    Ok(AmountSplit {
+        commission,
+        bonus,
+        founder_share,
+    })
+}
+
+// Fee of `fee_bps` on `amount`, capped at the share it is carved out of
+pub fn capped_fee(amount: u64, fee_bps: u16, share: u64) -> Result<u64> {
+    let fee = pro_rata(amount, fee_bps as u64, BPS_DENOMINATOR as u64)?;
+    Ok(fee.min(share))
+}
+
+// Scale `value` by `part / whole` without intermediate overflow
+pub fn pro_rata(value: u64, part: u64, whole: u64) -> Result<u64> {
+    if whole == 0 {
+        return Ok(0);
+    }
+    let scaled = (value as u128)
+        .checked_mul(part as u128)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?
+        / whole as u128;
+    u64::try_from(scaled).map_err(|_| error!(CustomError::ArithmeticError))
+}
+
+// Yield accrued by a package since the accumulator started
+pub fn package_yield(package: u64, yield_per_package: u128) -> Result<u128> {
+    let accrued = (package as u128)
+        .checked_mul(yield_per_package)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?
+        / YIELD_PRECISION;
+    Ok(accrued)
+}
+
+// Accumulator increment for spreading `undistributed` over `total_weight`,
+// and the lamports that increment actually hands out; rounding dust is left
+// for the next distribution
+pub fn yield_increment(undistributed: u64, total_weight: u64) -> Result<(u128, u64)> {
+    require!(total_weight > 0, CustomError::NoActivePackages);
+    let increment = (undistributed as u128)
+        .checked_mul(YIELD_PRECISION)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?
+        / total_weight as u128;
+    let distributed = increment
+        .checked_mul(total_weight as u128)
+        .ok_or_else(|| error!(CustomError::ArithmeticError))?
+        / YIELD_PRECISION;
+    let distributed = u64::try_from(distributed).map_err(|_| error!(CustomError::ArithmeticError))?;
+    Ok((increment, distributed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn split_sums_to_amount(
+            // Any amount whose percentage products fit in u64
+            amount in 0u64..=u64::MAX / 100,
+            (commission_percentage, bonus_percentage) in (0u64..=100)
+                .prop_flat_map(|commission| (Just(commission), 0..=100 - commission))
+        ) {
+            let split = split_amount(amount, commission_percentage, bonus_percentage).unwrap();
+            prop_assert_eq!(
+                split.commission as u128 + split.bonus as u128 + split.founder_share as u128,
+                amount as u128
+            );
+        }
+
+        #[test]
+        fn split_never_panics(
+            amount in any::<u64>(),
+            commission_percentage in any::<u64>(),
+            bonus_percentage in any::<u64>()
+        ) {
+            if let Ok(split) = split_amount(amount, commission_percentage, bonus_percentage) {
+                prop_assert_eq!(
+                    split.commission as u128 + split.bonus as u128 + split.founder_share as u128,
+                    amount as u128
+                );
+            }
+        }
+
+        #[test]
+        fn fee_is_capped_by_share(
+            amount in any::<u64>(),
+            fee_bps in 0u16..=BPS_DENOMINATOR,
+            share in any::<u64>()
+        ) {
+            let fee = capped_fee(amount, fee_bps, share).unwrap();
+            prop_assert!(fee <= share);
+            prop_assert!(fee <= amount);
+        }
+
+        #[test]
+        fn pro_rata_stays_within_value(
+            value in any::<u64>(),
+            part in any::<u64>(),
+            whole in any::<u64>()
+        ) {
+            let scaled = pro_rata(value, part.min(whole), whole).unwrap();
+            prop_assert!(scaled <= value);
+        }
+
+        #[test]
+        fn distributed_yield_covers_every_claim(
+            undistributed in 0u64..=1_000_000_000_000_000,
+            packages in prop::collection::vec(1u64..=1_000_000_000_000, 1..32)
+        ) {
+            let total_weight: u64 = packages.iter().sum();
+            let (increment, distributed) = yield_increment(undistributed, total_weight).unwrap();
+            prop_assert!(distributed <= undistributed);
+
+            // Holders can never claim more than was handed out
+            let claimable: u128 = packages
+                .iter()
+                .map(|&package| package_yield(package, increment).unwrap())
+                .sum();
+            prop_assert!(claimable <= distributed as u128);
+        }
+    }
+
+    #[test]
+    fn split_rejects_percentages_over_hundred() {
+        assert!(split_amount(1_000, 80, 30).is_err());
+    }
+
+    #[test]
+    fn yield_increment_requires_weight() {
+        assert!(yield_increment(1_000, 0).is_err());
+    }
+}
@@ -0,0 +1,132 @@
+// Deterministic test scenarios: a referral tree and purchase history built
+// from a fixed seed, so localnet runs and benchmarks start from identical
+// state. Users are referred to by index; runners map each index to a wallet
+// derived from its seed.
+
+// Shape of the generated scenario
+#[derive(Clone, Debug)]
+pub struct ScenarioParams {
+    pub seed: u64,
+    // Users including the root, who refers others but never buys
+    pub users: usize,
+    // Deepest level below the root the tree reaches
+    pub depth: usize,
+    // Purchases on top of each user's first one
+    pub repeat_purchases: usize,
+    // Package amounts purchases choose from; must be valid in the config
+    pub amounts: Vec<u64>,
+    // Matrix width and paid levels of the target config
+    pub matrix_width: usize,
+    pub commission_levels: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Step {
+    Register { user: usize },
+    // `uplines` are the users whose downline counters the purchase
+    // touches, starting with the referrer, nearest first
+    Buy { buyer: usize, referrer: usize, amount: u64, uplines: Vec<usize> },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Scenario {
+    // 32-byte seed per user, usable as an ed25519 keypair seed
+    pub wallet_seeds: Vec<[u8; 32]>,
+    // Referrer of every user but the root
+    pub referrers: Vec<Option<usize>>,
+    pub steps: Vec<Step>,
+}
+
+// SplitMix64, small and stable across platforms and releases
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+}
+
+// Build a scenario; None when the tree cannot hold `users` within `depth`
+// or there are no amounts to buy with.
+//
+// Referrers are only picked while they have a free leg, so every signup is
+// placed directly under its referrer and no spillover happens. The first
+// users form a chain down to `depth`, so the tree always reaches it.
+pub fn generate(params: &ScenarioParams) -> Option<Scenario> {
+    if params.users == 0 || params.amounts.is_empty() || params.matrix_width == 0 {
+        return None;
+    }
+    let mut rng = Rng(params.seed);
+
+    let wallet_seeds = (0..params.users)
+        .map(|_| {
+            let mut seed = [0u8; 32];
+            for chunk in seed.chunks_mut(8) {
+                chunk.copy_from_slice(&rng.next().to_le_bytes());
+            }
+            seed
+        })
+        .collect();
+
+    let mut referrers: Vec<Option<usize>> = vec![None];
+    let mut levels = vec![0usize];
+    let mut legs = vec![0usize];
+    let mut steps: Vec<Step> = (0..params.users).map(|user| Step::Register { user }).collect();
+
+    for buyer in 1..params.users {
+        let referrer = if buyer <= params.depth {
+            buyer - 1
+        } else {
+            let open: Vec<usize> = (0..buyer)
+                .filter(|&user| legs[user] < params.matrix_width && levels[user] < params.depth)
+                .collect();
+            if open.is_empty() {
+                return None;
+            }
+            open[rng.below(open.len())]
+        };
+        referrers.push(Some(referrer));
+        levels.push(levels[referrer] + 1);
+        legs.push(0);
+        legs[referrer] += 1;
+
+        // Every paid level comes in as remaining accounts, including the
+        // referrer the buyer is placed under
+        let mut uplines = Vec::new();
+        let mut node = Some(referrer);
+        while let Some(upline) = node {
+            if uplines.len() >= params.commission_levels {
+                break;
+            }
+            uplines.push(upline);
+            node = referrers[upline];
+        }
+
+        let amount = params.amounts[rng.below(params.amounts.len())];
+        steps.push(Step::Buy { buyer, referrer, amount, uplines });
+    }
+
+    // Repeat purchases reuse the buyer's position, so no uplines are needed
+    if params.users > 1 {
+        for _ in 0..params.repeat_purchases {
+            let buyer = 1 + rng.below(params.users - 1);
+            let referrer = referrers[buyer].expect("non-root users have a referrer");
+            let amount = params.amounts[rng.below(params.amounts.len())];
+            steps.push(Step::Buy { buyer, referrer, amount, uplines: Vec::new() });
+        }
+    }
+
+    Some(Scenario {
+        wallet_seeds,
+        referrers,
+        steps,
+    })
+}
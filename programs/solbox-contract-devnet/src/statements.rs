@@ -0,0 +1,205 @@
+// Earnings statements anchored at epoch close. Each user's statement is a
+// leaf; the admin posts the Merkle root over all leaves for the epoch, and
+// an exported statement verifies against it with the proof from its leaf
+// up. Pairs are hashed in sorted order, so proofs need no left/right flags.
+//
+// Positions migrated from the legacy platform use the same tree, with
+// their own leaf tag, and so do referral tree checkpoints. Those are built
+// a batch of leaves at a time by appending to a frontier: the roots of the
+// full subtrees so far, one per set bit of the leaf count.
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hashv;
+
+use crate::ReferralRelationship;
+
+// Domain tags keep a leaf from ever hashing like an inner node
+const LEAF_TAG: &[u8] = &[0];
+const NODE_TAG: &[u8] = &[1];
+const MIGRATION_TAG: &[u8] = &[2];
+const TREE_TAG: &[u8] = &[3];
+
+// Leaves per subtree the frontier has room for: 2^32 relationships
+pub const TREE_FRONTIER_DEPTH: usize = 32;
+
+// Figures a statement for one user and epoch reports
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Statement {
+    pub wallet: Pubkey,
+    pub epoch: u64,
+    pub total_earnings: u64,
+    pub unclaimed_yield: u64,
+    pub lifetime_volume: u64,
+}
+
+impl Statement {
+    pub fn leaf(&self) -> [u8; 32] {
+        hashv(&[
+            LEAF_TAG,
+            self.wallet.as_ref(),
+            &self.epoch.to_le_bytes(),
+            &self.total_earnings.to_le_bytes(),
+            &self.unclaimed_yield.to_le_bytes(),
+            &self.lifetime_volume.to_le_bytes(),
+        ])
+        .to_bytes()
+    }
+}
+
+// A legacy user's history, claimed once through claim_migrated_position
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MigratedPosition {
+    pub wallet: Pubkey,
+    pub package: u64,
+    pub sponsor: Pubkey, // default for a user without a sponsor
+    pub lifetime_volume: u64,
+}
+
+impl MigratedPosition {
+    pub fn leaf(&self) -> [u8; 32] {
+        hashv(&[
+            MIGRATION_TAG,
+            self.wallet.as_ref(),
+            &self.package.to_le_bytes(),
+            self.sponsor.as_ref(),
+            &self.lifetime_volume.to_le_bytes(),
+        ])
+        .to_bytes()
+    }
+}
+
+// Leaf of the relationship at `index` of SolBox.referral_relationships;
+// the index pins its place, since pairs hash in sorted order
+pub fn tree_leaf(index: u64, relationship: &ReferralRelationship) -> [u8; 32] {
+    hashv(&[
+        TREE_TAG,
+        &index.to_le_bytes(),
+        relationship.user.as_ref(),
+        relationship.referrer.as_ref(),
+        &relationship.timestamp.to_le_bytes(),
+        &[relationship.cycle, relationship.referrer_cycle],
+    ])
+    .to_bytes()
+}
+
+fn node(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (low, high) = if a <= b { (a, b) } else { (b, a) };
+    hashv(&[NODE_TAG, low, high]).to_bytes()
+}
+
+// Hash each pair; an odd node out moves up a level unpaired
+fn next_level(level: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [a, b] => node(a, b),
+            [a] => *a,
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+// Root over `leaves` in order; an empty epoch has the zero root
+pub fn merkle_root(leaves: &[[u8; 32]]) -> [u8; 32] {
+    if leaves.is_empty() {
+        return [0; 32];
+    }
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    level[0]
+}
+
+// Sibling hashes from the leaf at `index` up to the root
+pub fn merkle_proof(leaves: &[[u8; 32]], mut index: usize) -> Vec<[u8; 32]> {
+    let mut proof = Vec::new();
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        if let Some(sibling) = level.get(index ^ 1) {
+            proof.push(*sibling);
+        }
+        level = next_level(&level);
+        index /= 2;
+    }
+    proof
+}
+
+// Add the leaf after the first `count` to the frontier, merging the full
+// subtrees it completes like a binary carry
+pub fn append_leaf(frontier: &mut [[u8; 32]; TREE_FRONTIER_DEPTH], count: u64, leaf: [u8; 32]) {
+    let mut hash = leaf;
+    let mut level = 0;
+    while count >> level & 1 == 1 {
+        hash = node(&frontier[level], &hash);
+        level += 1;
+    }
+    frontier[level] = hash;
+}
+
+// Root over the `count` leaves appended to the frontier, equal to
+// merkle_root over the same leaves: odd nodes moving up unpaired leave
+// the smaller subtrees to join the larger ones last
+pub fn frontier_root(frontier: &[[u8; 32]; TREE_FRONTIER_DEPTH], count: u64) -> [u8; 32] {
+    (0..TREE_FRONTIER_DEPTH)
+        .filter(|level| count >> level & 1 == 1)
+        .map(|level| frontier[level])
+        .reduce(|smaller, larger| node(&larger, &smaller))
+        .unwrap_or([0; 32])
+}
+
+pub fn verify_proof(leaf: [u8; 32], proof: &[[u8; 32]], root: [u8; 32]) -> bool {
+    proof.iter().fold(leaf, |hash, sibling| node(&hash, sibling)) == root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn statement(seed: u8, earnings: u64) -> Statement {
+        Statement {
+            wallet: Pubkey::new_from_array([seed; 32]),
+            epoch: 7,
+            total_earnings: earnings,
+            unclaimed_yield: 0,
+            lifetime_volume: earnings * 10,
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn every_statement_proves_against_the_root(
+            earnings in prop::collection::vec(0u64..1_000_000_000, 1..40)
+        ) {
+            let leaves: Vec<[u8; 32]> = earnings
+                .iter()
+                .enumerate()
+                .map(|(i, &earned)| statement(i as u8, earned).leaf())
+                .collect();
+            let root = merkle_root(&leaves);
+            for (index, leaf) in leaves.iter().enumerate() {
+                prop_assert!(verify_proof(*leaf, &merkle_proof(&leaves, index), root));
+            }
+        }
+    }
+
+    #[test]
+    fn frontier_matches_the_full_tree() {
+        let leaves: Vec<[u8; 32]> = (0..70).map(|i| statement(i, 100).leaf()).collect();
+        let mut frontier = [[0; 32]; TREE_FRONTIER_DEPTH];
+        assert_eq!(frontier_root(&frontier, 0), merkle_root(&[]));
+        for (count, leaf) in leaves.iter().enumerate() {
+            append_leaf(&mut frontier, count as u64, *leaf);
+            assert_eq!(frontier_root(&frontier, count as u64 + 1), merkle_root(&leaves[..=count]));
+        }
+    }
+
+    #[test]
+    fn altered_statement_fails_to_verify() {
+        let leaves: Vec<[u8; 32]> = (0..5).map(|i| statement(i, 100).leaf()).collect();
+        let root = merkle_root(&leaves);
+        let proof = merkle_proof(&leaves, 2);
+        assert!(!verify_proof(statement(2, 101).leaf(), &proof, root));
+    }
+}
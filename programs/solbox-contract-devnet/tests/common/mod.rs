@@ -0,0 +1,996 @@
+// In-process harness that runs the program entrypoint against an in-memory
+// account store, with syscall stubs standing in for the runtime: clock and
+// rent sysvars, logging, and the system program, token burns, lookup
+// tables, stake pool SOL deposits and Wormhole messages for CPIs.
+//
+// The runtime's account rules are checked as well. A CPI can't sign for or
+// write to an account its caller couldn't, and an instruction only commits
+// if it leaves read-only accounts alone, conserves lamports, debits only
+// accounts the program owns and leaves every writable account rent-exempt
+
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::address_lookup_table;
+use anchor_lang::solana_program::address_lookup_table::instruction::ProgramInstruction;
+use anchor_lang::solana_program::address_lookup_table::state::{AddressLookupTable, LookupTableMeta};
+use anchor_lang::solana_program::entrypoint::{ProgramResult, MAX_PERMITTED_DATA_INCREASE};
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::program_stubs::{self, SyscallStubs};
+use anchor_lang::solana_program::program_utils::limited_deserialize;
+use anchor_lang::solana_program::system_instruction::SystemInstruction;
+use anchor_lang::solana_program::system_program;
+use anchor_lang::solana_program::sysvar;
+use anchor_lang::solana_program::program_option::COption;
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_lang::{AccountDeserialize, AccountSerialize, InstructionData, ToAccountMetas};
+use anchor_spl::token::spl_token;
+use anchor_spl::token::spl_token::instruction::TokenInstruction;
+use anchor_spl::token_2022::spl_token_2022;
+use anchor_spl::token_2022::spl_token_2022::extension::non_transferable::NonTransferable;
+use anchor_spl::token_2022::spl_token_2022::extension::permanent_delegate::PermanentDelegate;
+use anchor_spl::token_2022::spl_token_2022::extension::{
+    BaseStateWithExtensions, BaseStateWithExtensionsMut, ExtensionType, StateWithExtensions, StateWithExtensionsMut,
+};
+use anchor_spl::token_2022::spl_token_2022::instruction::TokenInstruction as Token2022Instruction;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+use solbox_contract_devnet::scenario::{Scenario, Step};
+use solbox_contract_devnet::{
+    client, ContractConfig, PostedVaa, DEFAULT_CAPACITY, SPL_STAKE_POOL_PROGRAM_ID, STAKE_POOL_DEPOSIT_SOL,
+    STAKE_POOL_WITHDRAW_SOL, SUPPORT_TIERS, WORMHOLE_CORE_BRIDGE_ID, WORMHOLE_FEE_OFFSET,
+};
+
+pub const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+pub const PACKAGE: u64 = LAMPORTS_PER_SOL;
+
+// Syscall stubs are process-wide, so tests take turns
+static RUNTIME: Mutex<()> = Mutex::new(());
+
+#[derive(Clone)]
+struct StoredAccount {
+    lamports: u64,
+    data: Vec<u8>,
+    owner: Pubkey,
+    executable: bool,
+}
+
+struct Stubs {
+    clock: Arc<Mutex<Clock>>,
+    logs: Arc<Mutex<Vec<String>>>,
+    stack_height: Arc<Mutex<u64>>,
+    return_data: Arc<Mutex<Vec<u8>>>,
+    posted: Arc<Mutex<Vec<Vec<u8>>>>,
+    cpi_lamports: Arc<Mutex<HashMap<Pubkey, i128>>>,
+}
+
+impl SyscallStubs for Stubs {
+    fn sol_log(&self, message: &str) {
+        self.logs.lock().unwrap().push(message.to_string());
+    }
+
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        let encoded: Vec<String> = fields.iter().map(|field| STANDARD.encode(field)).collect();
+        self.logs.lock().unwrap().push(format!("Program data: {}", encoded.join(" ")));
+    }
+
+    fn sol_set_return_data(&self, data: &[u8]) {
+        *self.return_data.lock().unwrap() = data.to_vec();
+    }
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { *(var_addr as *mut Clock) = self.clock.lock().unwrap().clone() };
+        0
+    }
+
+    fn sol_get_stack_height(&self) -> u64 {
+        *self.stack_height.lock().unwrap()
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { *(var_addr as *mut Rent) = Rent::default() };
+        0
+    }
+
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        check_privileges(instruction, account_infos, signers_seeds)?;
+        
+        // Lamports moved by the callee, so the caller's own debits can be
+        // told apart from them once the instruction ends
+        let before: HashMap<Pubkey, u64> = account_infos
+            .iter()
+            .map(|info| (*info.key, info.lamports()))
+            .collect();
+        let result = self.invoke(instruction, account_infos);
+        let mut moved = self.cpi_lamports.lock().unwrap();
+        for (key, lamports) in before {
+            let info = account_infos.iter().find(|info| *info.key == key).unwrap();
+            *moved.entry(key).or_default() += info.lamports() as i128 - lamports as i128;
+        }
+        result
+    }
+}
+
+impl Stubs {
+    fn invoke(&self, instruction: &Instruction, account_infos: &[AccountInfo]) -> ProgramResult {
+        let account = |index: usize| -> std::result::Result<&AccountInfo, ProgramError> {
+            let key = instruction.accounts
+                .get(index)
+                .ok_or(ProgramError::NotEnoughAccountKeys)?
+                .pubkey;
+            account_infos
+                .iter()
+                .find(|info| info.key == &key)
+                .ok_or(ProgramError::NotEnoughAccountKeys)
+        };
+
+        if instruction.program_id == spl_token::ID {
+            return match TokenInstruction::unpack(&instruction.data)? {
+                TokenInstruction::Burn { amount } => burn(account(0)?, account(1)?, amount),
+                _ => Err(ProgramError::InvalidInstructionData),
+            };
+        }
+        if instruction.program_id == spl_token_2022::ID {
+            return token_2022(&instruction.data, account(0)?, account(1).ok(), account(2).ok(), account(3).ok());
+        }
+        if instruction.program_id == address_lookup_table::program::ID {
+            return lookup_table(&instruction.data, account(0)?, account(1)?, account(2)?);
+        }
+        if instruction.program_id == SPL_STAKE_POOL_PROGRAM_ID {
+            return stake_pool(&instruction.data, account);
+        }
+        if instruction.program_id == WORMHOLE_CORE_BRIDGE_ID {
+            let payload = post_message_payload(&instruction.data).ok_or(ProgramError::InvalidInstructionData)?;
+            self.posted.lock().unwrap().push(payload);
+            return Ok(());
+        }
+        if instruction.program_id != system_program::ID {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        // The system program acts only on accounts that signed for it
+        let signed = |index: usize| -> ProgramResult {
+            match instruction.accounts.get(index) {
+                Some(meta) if meta.is_signer => Ok(()),
+                _ => Err(ProgramError::MissingRequiredSignature),
+            }
+        };
+        let system_instruction: SystemInstruction = limited_deserialize(&instruction.data, 1232)
+            .map_err(|_| ProgramError::InvalidInstructionData)?;
+        match system_instruction {
+            SystemInstruction::CreateAccount { lamports, space, owner } => {
+                signed(0)?;
+                signed(1)?;
+                let to = account(1)?;
+                if to.lamports() > 0 {
+                    return Err(ProgramError::AccountAlreadyInitialized);
+                }
+                move_lamports(account(0)?, to, lamports)?;
+                resize(to, space as usize);
+                to.assign(&owner);
+            }
+            SystemInstruction::Transfer { lamports } => {
+                signed(0)?;
+                let from = account(0)?;
+                if from.owner != &system_program::ID || !from.data_is_empty() {
+                    return Err(ProgramError::InvalidAccountOwner);
+                }
+                move_lamports(from, account(1)?, lamports)?;
+            }
+            SystemInstruction::Allocate { space } => {
+                signed(0)?;
+                let to = account(0)?;
+                if to.owner != &system_program::ID || !to.data_is_empty() {
+                    return Err(ProgramError::AccountAlreadyInitialized);
+                }
+                resize(to, space as usize);
+            }
+            SystemInstruction::Assign { owner } => {
+                signed(0)?;
+                account(0)?.assign(&owner);
+            }
+            _ => return Err(ProgramError::InvalidInstructionData),
+        }
+        Ok(())
+    }
+}
+
+// A CPI passes on the caller's privileges and nothing more: every signer
+// signed the outer instruction or is a PDA of the program's signer seeds,
+// and every writable account was writable to the caller
+fn check_privileges(instruction: &Instruction, account_infos: &[AccountInfo], signers_seeds: &[&[&[u8]]]) -> ProgramResult {
+    let signers: Vec<Pubkey> = signers_seeds
+        .iter()
+        .map(|seeds| Pubkey::create_program_address(seeds, &solbox_contract_devnet::ID))
+        .collect::<std::result::Result<_, _>>()
+        .map_err(|_| ProgramError::InvalidSeeds)?;
+    for meta in &instruction.accounts {
+        let info = account_infos
+            .iter()
+            .find(|info| info.key == &meta.pubkey)
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+        if meta.is_signer && !info.is_signer && !signers.contains(&meta.pubkey) {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if meta.is_writable && !info.is_writable {
+            return Err(ProgramError::InvalidAccountData);
+        }
+    }
+    Ok(())
+}
+
+// Core bridge post_message: the payload between the nonce and the
+// consistency level
+fn post_message_payload(data: &[u8]) -> Option<Vec<u8>> {
+    let (&tag, rest) = data.split_first()?;
+    let len = u32::from_le_bytes(rest.get(4..8)?.try_into().ok()?) as usize;
+    (tag == 1).then(|| rest.get(8..8 + len).map(<[u8]>::to_vec))?
+}
+
+fn move_lamports(from: &AccountInfo, to: &AccountInfo, lamports: u64) -> ProgramResult {
+    let from_balance = from.lamports()
+        .checked_sub(lamports)
+        .ok_or(ProgramError::InsufficientFunds)?;
+    **from.try_borrow_mut_lamports()? = from_balance;
+    **to.try_borrow_mut_lamports()? += lamports;
+    Ok(())
+}
+
+// Address lookup table program: create and extend, with rent paid by the payer
+fn lookup_table(data: &[u8], table: &AccountInfo, authority: &AccountInfo, payer: &AccountInfo) -> ProgramResult {
+    let mut addresses: Vec<Pubkey> = Vec::new();
+    match limited_deserialize(data, 1232).map_err(|_| ProgramError::InvalidInstructionData)? {
+        ProgramInstruction::CreateLookupTable { .. } => {
+            if !table.data_is_empty() {
+                return Err(ProgramError::AccountAlreadyInitialized);
+            }
+        }
+        ProgramInstruction::ExtendLookupTable { new_addresses } => {
+            let data = table.data.borrow();
+            let existing = AddressLookupTable::deserialize(&data)
+                .map_err(|_| ProgramError::InvalidAccountData)?;
+            if existing.meta.authority != Some(*authority.key) {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            addresses.extend_from_slice(&existing.addresses);
+            addresses.extend(new_addresses);
+        }
+        _ => return Err(ProgramError::InvalidInstructionData),
+    }
+    let serialized = AddressLookupTable {
+        meta: LookupTableMeta::new(*authority.key),
+        addresses: addresses.into(),
+    }
+    .serialize_for_tests()
+    .map_err(|_| ProgramError::InvalidAccountData)?;
+    let rent = Rent::default().minimum_balance(serialized.len()).saturating_sub(table.lamports());
+    move_lamports(payer, table, rent)?;
+    *table.data.borrow_mut() = leak_data(&serialized);
+    table.assign(&address_lookup_table::program::ID);
+    Ok(())
+}
+
+// SPL stake pool: SOL deposits into and withdrawals from the reserve, at
+// the reserve's lamports per pool token, so lamports added to the reserve
+// stand in for staking rewards
+fn stake_pool<'a, 'info: 'a>(
+    data: &[u8],
+    account: impl Fn(usize) -> std::result::Result<&'a AccountInfo<'info>, ProgramError>,
+) -> ProgramResult {
+    let (&tag, amount) = data.split_first().ok_or(ProgramError::InvalidInstructionData)?;
+    let amount = u64::from_le_bytes(amount.try_into().map_err(|_| ProgramError::InvalidInstructionData)?);
+    let mint = account(7)?;
+    let supply = spl_token::state::Mint::unpack(&mint.data.borrow())?.supply;
+    match tag {
+        STAKE_POOL_DEPOSIT_SOL => {
+            let (reserve, depositor, to) = (account(2)?, account(3)?, account(4)?);
+            let tokens = match supply {
+                0 => amount,
+                supply => (amount as u128 * supply as u128 / reserve.lamports() as u128) as u64,
+            };
+            move_lamports(depositor, reserve, amount)?;
+            mint_to(to, mint, tokens)
+        }
+        STAKE_POOL_WITHDRAW_SOL => {
+            let (from, reserve, to) = (account(3)?, account(4)?, account(5)?);
+            let lamports = (amount as u128 * reserve.lamports() as u128 / supply.max(1) as u128) as u64;
+            burn(from, mint, amount)?;
+            move_lamports(reserve, to, lamports)
+        }
+        _ => Err(ProgramError::InvalidInstructionData),
+    }
+}
+
+fn mint_to(to: &AccountInfo, mint: &AccountInfo, amount: u64) -> ProgramResult {
+    let mut holder = spl_token::state::Account::unpack(&to.data.borrow())?;
+    let mut supply = spl_token::state::Mint::unpack(&mint.data.borrow())?;
+    holder.amount += amount;
+    supply.supply += amount;
+    spl_token::state::Account::pack(holder, &mut to.data.borrow_mut())?;
+    spl_token::state::Mint::pack(supply, &mut mint.data.borrow_mut())
+}
+
+fn burn(from: &AccountInfo, mint: &AccountInfo, amount: u64) -> ProgramResult {
+    let mut holder = spl_token::state::Account::unpack(&from.data.borrow())?;
+    let mut supply = spl_token::state::Mint::unpack(&mint.data.borrow())?;
+    holder.amount = holder.amount.checked_sub(amount).ok_or(ProgramError::InsufficientFunds)?;
+    supply.supply -= amount;
+    spl_token::state::Account::pack(holder, &mut from.data.borrow_mut())?;
+    spl_token::state::Mint::pack(supply, &mut mint.data.borrow_mut())
+}
+
+// Token-2022: mints with the non-transferable and permanent delegate
+// extensions, minting, burning and transfers. Accounts carry their
+// extensions after the base layout, which is the token program's
+fn token_2022(
+    data: &[u8],
+    first: &AccountInfo,
+    second: Option<&AccountInfo>,
+    third: Option<&AccountInfo>,
+    fourth: Option<&AccountInfo>,
+) -> ProgramResult {
+    let second = || second.ok_or(ProgramError::NotEnoughAccountKeys);
+    let third = || third.ok_or(ProgramError::NotEnoughAccountKeys);
+    let fourth = || fourth.ok_or(ProgramError::NotEnoughAccountKeys);
+    match Token2022Instruction::unpack(data)? {
+        Token2022Instruction::InitializeNonTransferableMint => {
+            let mut data = first.data.borrow_mut();
+            let mut mint = StateWithExtensionsMut::<spl_token_2022::state::Mint>::unpack_uninitialized(&mut data)?;
+            mint.init_extension::<NonTransferable>(true)?;
+        }
+        Token2022Instruction::InitializePermanentDelegate { delegate } => {
+            let mut data = first.data.borrow_mut();
+            let mut mint = StateWithExtensionsMut::<spl_token_2022::state::Mint>::unpack_uninitialized(&mut data)?;
+            mint.init_extension::<PermanentDelegate>(true)?.delegate = Some(delegate).try_into()?;
+        }
+        Token2022Instruction::InitializeMint2 { decimals, mint_authority, freeze_authority } => {
+            let mut data = first.data.borrow_mut();
+            let mut mint = StateWithExtensionsMut::<spl_token_2022::state::Mint>::unpack_uninitialized(&mut data)?;
+            mint.base = spl_token_2022::state::Mint {
+                mint_authority: COption::Some(mint_authority),
+                decimals,
+                is_initialized: true,
+                freeze_authority,
+                supply: 0,
+            };
+            mint.pack_base();
+            mint.init_account_type()?;
+        }
+        Token2022Instruction::MintTo { amount } => {
+            let (mint, to, authority) = (first, second()?, third()?);
+            let mut mint_data = mint.data.borrow_mut();
+            let mut supply = StateWithExtensionsMut::<spl_token_2022::state::Mint>::unpack(&mut mint_data)?;
+            if supply.base.mint_authority != COption::Some(*authority.key) {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            let mut to_data = to.data.borrow_mut();
+            let mut holder = StateWithExtensionsMut::<spl_token_2022::state::Account>::unpack(&mut to_data)?;
+            holder.base.amount += amount;
+            supply.base.supply += amount;
+            holder.pack_base();
+            supply.pack_base();
+        }
+        Token2022Instruction::Burn { amount } => {
+            let (from, mint, authority) = (first, second()?, third()?);
+            let mut mint_data = mint.data.borrow_mut();
+            let mut supply = StateWithExtensionsMut::<spl_token_2022::state::Mint>::unpack(&mut mint_data)?;
+            let delegate = supply
+                .get_extension::<PermanentDelegate>()
+                .ok()
+                .and_then(|extension| Option::<Pubkey>::from(extension.delegate));
+            let mut from_data = from.data.borrow_mut();
+            let mut holder = StateWithExtensionsMut::<spl_token_2022::state::Account>::unpack(&mut from_data)?;
+            if holder.base.owner != *authority.key && delegate != Some(*authority.key) {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            holder.base.amount = holder.base.amount.checked_sub(amount).ok_or(ProgramError::InsufficientFunds)?;
+            supply.base.supply -= amount;
+            holder.pack_base();
+            supply.pack_base();
+        }
+        Token2022Instruction::TransferChecked { amount, .. } => {
+            let (from, mint, to, authority) = (first, second()?, third()?, fourth()?);
+            let mint_data = mint.data.borrow();
+            let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint_data)?;
+            if mint.get_extension::<NonTransferable>().is_ok() {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            let delegate = mint
+                .get_extension::<PermanentDelegate>()
+                .ok()
+                .and_then(|extension| Option::<Pubkey>::from(extension.delegate));
+            let mut from_data = from.data.borrow_mut();
+            let mut source = StateWithExtensionsMut::<spl_token_2022::state::Account>::unpack(&mut from_data)?;
+            if source.base.owner != *authority.key && delegate != Some(*authority.key) {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            source.base.amount = source.base.amount.checked_sub(amount).ok_or(ProgramError::InsufficientFunds)?;
+            source.pack_base();
+            let mut to_data = to.data.borrow_mut();
+            let mut destination = StateWithExtensionsMut::<spl_token_2022::state::Account>::unpack(&mut to_data)?;
+            destination.base.amount += amount;
+            destination.pack_base();
+        }
+        _ => return Err(ProgramError::InvalidInstructionData),
+    }
+    Ok(())
+}
+
+fn resize(info: &AccountInfo, space: usize) {
+    *info.data.borrow_mut() = leak_data(&vec![0u8; space]);
+}
+
+// Account data laid out like the runtime's input buffer: the length sits
+// right before the data and realloc may grow into the padding after it
+fn leak_data(data: &[u8]) -> &'static mut [u8] {
+    let words = (8 + data.len() + MAX_PERMITTED_DATA_INCREASE).div_ceil(8);
+    let buffer: &'static mut [u64] = Box::leak(vec![0u64; words].into_boxed_slice());
+    let bytes = unsafe { std::slice::from_raw_parts_mut(buffer.as_mut_ptr() as *mut u8, words * 8) };
+    bytes[..8].copy_from_slice(&(data.len() as u64).to_le_bytes());
+    bytes[8..8 + data.len()].copy_from_slice(data);
+    &mut bytes[8..8 + data.len()]
+}
+
+pub struct Harness {
+    accounts: HashMap<Pubkey, StoredAccount>,
+    clock: Arc<Mutex<Clock>>,
+    logs: Arc<Mutex<Vec<String>>>,
+    stack_height: Arc<Mutex<u64>>,
+    return_data: Arc<Mutex<Vec<u8>>>,
+    posted: Arc<Mutex<Vec<Vec<u8>>>>,
+    cpi_lamports: Arc<Mutex<HashMap<Pubkey, i128>>>,
+    _guard: MutexGuard<'static, ()>,
+    pub owner: Pubkey,
+    pub founder: Pubkey,
+    pub solbox: Pubkey,
+}
+
+impl Harness {
+    pub fn new() -> Self {
+        let guard = RUNTIME.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let clock = Arc::new(Mutex::new(Clock {
+            slot: 1,
+            unix_timestamp: 1_700_000_000,
+            ..Clock::default()
+        }));
+        let logs = Arc::new(Mutex::new(Vec::new()));
+        let stack_height = Arc::new(Mutex::new(1));
+        let return_data = Arc::new(Mutex::new(Vec::new()));
+        let posted = Arc::new(Mutex::new(Vec::new()));
+        let cpi_lamports = Arc::new(Mutex::new(HashMap::new()));
+        program_stubs::set_syscall_stubs(Box::new(Stubs {
+            clock: clock.clone(),
+            logs: logs.clone(),
+            stack_height: stack_height.clone(),
+            return_data: return_data.clone(),
+            posted: posted.clone(),
+            cpi_lamports: cpi_lamports.clone(),
+        }));
+
+        let mut harness = Harness {
+            accounts: HashMap::new(),
+            clock,
+            logs,
+            stack_height,
+            return_data,
+            posted,
+            cpi_lamports,
+            _guard: guard,
+            owner: Pubkey::new_unique(),
+            founder: Pubkey::new_unique(),
+            solbox: Pubkey::new_unique(),
+        };
+        for program in [
+            system_program::ID,
+            spl_token::ID,
+            spl_token_2022::ID,
+            address_lookup_table::program::ID,
+            WORMHOLE_CORE_BRIDGE_ID,
+            solbox_contract_devnet::ID,
+        ] {
+            harness.accounts.insert(program, StoredAccount {
+                lamports: 1,
+                data: Vec::new(),
+                owner: Pubkey::default(),
+                executable: true,
+            });
+        }
+        harness.airdrop(harness.owner, 100 * LAMPORTS_PER_SOL);
+        harness.airdrop(harness.founder, LAMPORTS_PER_SOL);
+        harness
+    }
+
+    pub fn default_config() -> ContractConfig {
+        ContractConfig {
+            matrix_width: 2,
+            matrix_depth: 3,
+            commission_percentage: 10,
+            commission_levels: 2,
+            bonus_percentage: 5,
+            valid_amounts: vec![PACKAGE],
+            refund_window: 0,
+            recycling: false,
+            cycle_bonus: 0,
+            holding_tank_period: 0,
+            stake_pool: Pubkey::default(),
+            max_stake_bps: 0,
+            yield_sharing: false,
+            support_tier_thresholds: [0; SUPPORT_TIERS],
+            tier_period: 0,
+            tier_grace_periods: 0,
+            residual_bps: 0,
+            membership_period: 0,
+            reactivation_window: 0,
+            reactivation_bps: 0,
+            min_claim: 0,
+            payout_weekday: 0,
+            claims_during_pause: false,
+            humanity_verifier: Pubkey::default(),
+            humanity_threshold: 0,
+            drip_threshold: 0,
+            drip_days: 0,
+            require_current_terms: false,
+            tier_window_days: 0,
+            voucher_chain: 0,
+            storefront_chain: 0,
+            storefront_emitter: [0; 32],
+            hot_daily_limit: 0,
+            hot_sweep_threshold: 0,
+            flag_funder_threshold: 0,
+            flag_burst_threshold: 0,
+            block_flagged: false,
+            card_bonus_bps: 0,
+            redemption_fee_bps: 0,
+        }
+    }
+
+    pub fn airdrop(&mut self, key: Pubkey, lamports: u64) {
+        let account = self.accounts.entry(key).or_insert_with(|| StoredAccount {
+            lamports: 0,
+            data: Vec::new(),
+            owner: system_program::ID,
+            executable: false,
+        });
+        account.lamports += lamports;
+    }
+
+    // Create a token mint account with no supply
+    pub fn create_mint(&mut self) -> Pubkey {
+        let mint = spl_token::state::Mint {
+            mint_authority: COption::Some(self.owner),
+            is_initialized: true,
+            ..Default::default()
+        };
+        let key = Pubkey::new_unique();
+        let mut data = vec![0; spl_token::state::Mint::LEN];
+        spl_token::state::Mint::pack(mint, &mut data).unwrap();
+        self.store_token_account(key, data);
+        key
+    }
+
+    // Create a token account holding `amount` freshly minted tokens
+    pub fn mint_to(&mut self, mint: Pubkey, owner: Pubkey, amount: u64) -> Pubkey {
+        let mut data = self.accounts[&mint].data.clone();
+        let mut supply = spl_token::state::Mint::unpack(&data).unwrap();
+        supply.supply += amount;
+        spl_token::state::Mint::pack(supply, &mut data).unwrap();
+        self.store_token_account(mint, data);
+
+        let holder = spl_token::state::Account {
+            mint,
+            owner,
+            amount,
+            state: spl_token::state::AccountState::Initialized,
+            ..Default::default()
+        };
+        let key = Pubkey::new_unique();
+        let mut data = vec![0; spl_token::state::Account::LEN];
+        spl_token::state::Account::pack(holder, &mut data).unwrap();
+        self.store_token_account(key, data);
+        key
+    }
+
+    // Create an empty Token-2022 account of `mint`, with the extensions
+    // the mint requires of its accounts
+    pub fn token_2022_account(&mut self, mint: Pubkey, owner: Pubkey) -> Pubkey {
+        let mint_data = &self.accounts[&mint].data;
+        let mint_extensions = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(mint_data)
+            .unwrap()
+            .get_extension_types()
+            .unwrap();
+        let extensions = ExtensionType::get_required_init_account_extensions(&mint_extensions);
+        let len = ExtensionType::try_calculate_account_len::<spl_token_2022::state::Account>(&extensions).unwrap();
+        let mut data = vec![0; len];
+        let mut holder = StateWithExtensionsMut::<spl_token_2022::state::Account>::unpack_uninitialized(&mut data).unwrap();
+        for extension in &extensions {
+            holder.init_account_extension_from_type(*extension).unwrap();
+        }
+        holder.base = spl_token_2022::state::Account {
+            mint,
+            owner,
+            state: spl_token_2022::state::AccountState::Initialized,
+            ..Default::default()
+        };
+        holder.pack_base();
+        if !extensions.is_empty() {
+            holder.init_account_type().unwrap();
+        }
+        let key = Pubkey::new_unique();
+        self.store_token_account(key, data);
+        self.accounts.get_mut(&key).unwrap().owner = spl_token_2022::ID;
+        key
+    }
+
+    pub fn token_balance(&self, key: &Pubkey) -> u64 {
+        StateWithExtensions::<spl_token_2022::state::Account>::unpack(&self.accounts[key].data)
+            .unwrap()
+            .base
+            .amount
+    }
+
+    // A holder's own Token-2022 transfer between two accounts of a mint
+    pub fn transfer_tokens(&mut self, from: &Pubkey, to: &Pubkey, amount: u64) {
+        for (key, delta) in [(from, amount.wrapping_neg()), (to, amount)] {
+            let data = &mut self.accounts.get_mut(key).unwrap().data;
+            let mut holder = StateWithExtensionsMut::<spl_token_2022::state::Account>::unpack(data).unwrap();
+            holder.base.amount = holder.base.amount.wrapping_add(delta);
+            holder.pack_base();
+        }
+    }
+
+    fn store_token_account(&mut self, key: Pubkey, data: Vec<u8>) {
+        self.accounts.insert(key, StoredAccount {
+            lamports: LAMPORTS_PER_SOL,
+            data,
+            owner: spl_token::ID,
+            executable: false,
+        });
+    }
+
+    pub fn lamports(&self, key: &Pubkey) -> u64 {
+        self.accounts.get(key).map_or(0, |account| account.lamports)
+    }
+
+    pub fn warp(&mut self, seconds: i64) {
+        let mut clock = self.clock.lock().unwrap();
+        clock.unix_timestamp += seconds;
+        clock.slot += 1;
+    }
+
+    pub fn next_epoch(&mut self) {
+        let mut clock = self.clock.lock().unwrap();
+        clock.epoch += 1;
+        clock.slot += 1;
+    }
+
+    // Run the next instructions as if invoked through CPI at this depth
+    pub fn set_stack_height(&mut self, height: u64) {
+        *self.stack_height.lock().unwrap() = height;
+    }
+
+    pub fn logs(&self) -> Vec<String> {
+        self.logs.lock().unwrap().clone()
+    }
+
+    // What the last view instruction returned, Borsh-encoded
+    pub fn return_data(&self) -> Vec<u8> {
+        self.return_data.lock().unwrap().clone()
+    }
+
+    // Payloads of the Wormhole messages posted so far
+    pub fn posted_messages(&self) -> Vec<Vec<u8>> {
+        self.posted.lock().unwrap().clone()
+    }
+
+    // Store a PostedVAA account for a message the guardians signed
+    pub fn post_vaa(&mut self, vaa: &PostedVaa) -> Pubkey {
+        let mut data = b"vaa".to_vec();
+        vaa.serialize(&mut data).unwrap();
+        let key = Pubkey::new_unique();
+        self.accounts.insert(key, StoredAccount {
+            lamports: LAMPORTS_PER_SOL,
+            data,
+            owner: WORMHOLE_CORE_BRIDGE_ID,
+            executable: false,
+        });
+        key
+    }
+
+    // Create the core bridge config, charging `fee` per message, and its
+    // fee collector
+    pub fn create_wormhole_bridge(&mut self, fee: u64) {
+        let mut data = vec![0; WORMHOLE_FEE_OFFSET + 8];
+        data[WORMHOLE_FEE_OFFSET..].copy_from_slice(&fee.to_le_bytes());
+        let key = Pubkey::find_program_address(&[b"Bridge"], &WORMHOLE_CORE_BRIDGE_ID).0;
+        self.accounts.insert(key, StoredAccount {
+            lamports: LAMPORTS_PER_SOL,
+            data,
+            owner: WORMHOLE_CORE_BRIDGE_ID,
+            executable: false,
+        });
+        let fee_collector = Pubkey::find_program_address(&[b"fee_collector"], &WORMHOLE_CORE_BRIDGE_ID).0;
+        self.airdrop(fee_collector, Rent::default().minimum_balance(0));
+    }
+
+    pub fn fetch<T: AccountDeserialize>(&self, key: &Pubkey) -> T {
+        client::fetch(self, key).expect("account decodes")
+    }
+
+    // Rewrite an account's state, for fixtures the runtime can't produce
+    pub fn modify<T: AccountDeserialize + AccountSerialize>(&mut self, key: &Pubkey, edit: impl FnOnce(&mut T)) {
+        let mut state: T = self.fetch(key);
+        edit(&mut state);
+        let account = self.accounts.get_mut(key).expect("account exists");
+        let mut data = Vec::new();
+        state.try_serialize(&mut data).expect("account encodes");
+        account.data[..data.len()].copy_from_slice(&data);
+    }
+
+    // Run a single instruction; state is only committed if it succeeds
+    // and leaves the accounts in a state the runtime would accept
+    pub fn process(&mut self, instruction: Instruction) -> ProgramResult {
+        self.logs.lock().unwrap().clear();
+        self.cpi_lamports.lock().unwrap().clear();
+        let mut infos: Vec<AccountInfo<'static>> = Vec::new();
+        let mut loaded: HashMap<Pubkey, AccountInfo<'static>> = HashMap::new();
+        let mut before: HashMap<Pubkey, StoredAccount> = HashMap::new();
+
+        // An account listed twice gets the privileges of both entries
+        let mut privileges: HashMap<Pubkey, (bool, bool)> = HashMap::new();
+        for meta in &instruction.accounts {
+            let (is_signer, is_writable) = privileges.entry(meta.pubkey).or_default();
+            *is_signer |= meta.is_signer;
+            *is_writable |= meta.is_writable;
+        }
+
+        for meta in &instruction.accounts {
+            let (is_signer, is_writable) = privileges[&meta.pubkey];
+            let info = match loaded.get(&meta.pubkey) {
+                Some(info) => info.clone(),
+                None => {
+                    let stored = self.accounts.get(&meta.pubkey).cloned().unwrap_or_else(|| StoredAccount {
+                        lamports: 0,
+                        data: self.sysvar_data(&meta.pubkey),
+                        owner: system_program::ID,
+                        executable: false,
+                    });
+                    let info = AccountInfo::new(
+                        Box::leak(Box::new(meta.pubkey)),
+                        is_signer,
+                        is_writable,
+                        Box::leak(Box::new(stored.lamports)),
+                        leak_data(&stored.data),
+                        Box::leak(Box::new(stored.owner)),
+                        stored.executable,
+                        0,
+                    );
+                    loaded.insert(meta.pubkey, info.clone());
+                    before.insert(meta.pubkey, stored);
+                    info
+                }
+            };
+            infos.push(info);
+        }
+
+        let infos: &'static [AccountInfo<'static>] = Box::leak(infos.into_boxed_slice());
+        solbox_contract_devnet::entry(&instruction.program_id, infos, &instruction.data)?;
+        self.check_accounts(&before, &loaded)?;
+
+        for (key, info) in loaded {
+            let executable = info.executable;
+            self.accounts.insert(key, StoredAccount {
+                lamports: info.lamports(),
+                data: info.data.borrow().to_vec(),
+                owner: *info.owner,
+                executable,
+            });
+        }
+        Ok(())
+    }
+
+    // The runtime's checks on an instruction's accounts once it returns
+    fn check_accounts(
+        &self,
+        before: &HashMap<Pubkey, StoredAccount>,
+        loaded: &HashMap<Pubkey, AccountInfo<'static>>,
+    ) -> ProgramResult {
+        let moved = self.cpi_lamports.lock().unwrap().clone();
+        let mut logs = self.logs.lock().unwrap();
+        let (mut lamports_before, mut lamports_after) = (0u128, 0u128);
+        for (key, info) in loaded {
+            let pre = &before[key];
+            let lamports = info.lamports();
+            let data = info.data.borrow();
+            lamports_before += pre.lamports as u128;
+            lamports_after += lamports as u128;
+
+            let changed = lamports != pre.lamports || *info.owner != pre.owner || data[..] != pre.data[..];
+            if changed && !info.is_writable {
+                logs.push(format!("{key} was modified without being writable"));
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            // Only the owner may take lamports out of an account; what the
+            // system program moved in a CPI was signed for
+            let debit = pre.lamports as i128 - lamports as i128 + moved.get(key).copied().unwrap_or(0);
+            if debit > 0 && pre.owner != solbox_contract_devnet::ID {
+                logs.push(format!("{key} was debited by a program that doesn't own it"));
+                return Err(ProgramError::InvalidAccountOwner);
+            }
+
+            // A writable account is left empty or rent-exempt, unless it
+            // was already short of rent and neither grew nor gained lamports
+            let rent = Rent::default();
+            let still_short = !rent.is_exempt(pre.lamports, pre.data.len())
+                && data.len() == pre.data.len()
+                && lamports <= pre.lamports;
+            if info.is_writable && lamports > 0 && !rent.is_exempt(lamports, data.len()) && !still_short {
+                logs.push(format!("{key} is left with {lamports} lamports, short of rent"));
+                return Err(ProgramError::AccountNotRentExempt);
+            }
+        }
+        if lamports_before != lamports_after {
+            logs.push(format!("lamports went from {lamports_before} to {lamports_after}"));
+            return Err(ProgramError::InvalidArgument);
+        }
+        Ok(())
+    }
+
+    // Accounts passed as the clock sysvar hold the stubbed clock
+    fn sysvar_data(&self, key: &Pubkey) -> Vec<u8> {
+        if *key != sysvar::clock::ID {
+            return Vec::new();
+        }
+        let mut data = vec![0; Clock::size_of()];
+        let mut lamports = 0;
+        let mut info = AccountInfo::new(key, false, false, &mut lamports, &mut data, key, false, 0);
+        sysvar::Sysvar::to_account_info(&*self.clock.lock().unwrap(), &mut info);
+        data
+    }
+
+    pub fn initialize(&mut self, config: ContractConfig) -> ProgramResult {
+        self.process(client::initialize(self.solbox, self.owner, self.founder, config, false, DEFAULT_CAPACITY))
+    }
+
+    pub fn initialize_sandbox(&mut self, config: ContractConfig) -> ProgramResult {
+        self.process(client::initialize(self.solbox, self.owner, self.founder, config, true, DEFAULT_CAPACITY))
+    }
+
+    // Fund a new wallet and create its User PDA
+    pub fn register(&mut self) -> Pubkey {
+        let wallet = Pubkey::new_unique();
+        self.register_wallet(wallet);
+        wallet
+    }
+
+    pub fn register_wallet(&mut self, wallet: Pubkey) {
+        self.airdrop(wallet, 10 * LAMPORTS_PER_SOL);
+        let solbox: solbox_contract_devnet::SolBox = self.fetch(&self.solbox);
+        self.process(client::register_user(self.solbox, wallet, solbox.terms_hash))
+            .expect("register_user succeeds");
+    }
+
+    // Replay a generated scenario; signatures aren't checked here, so each
+    // seed is used as the wallet address directly
+    pub fn run_scenario(&mut self, scenario: &Scenario) -> Vec<Pubkey> {
+        let wallets: Vec<Pubkey> = scenario
+            .wallet_seeds
+            .iter()
+            .map(|seed| Pubkey::new_from_array(*seed))
+            .collect();
+        for step in &scenario.steps {
+            match step {
+                Step::Register { user } => self.register_wallet(wallets[*user]),
+                Step::Buy { buyer, referrer, amount, uplines } => {
+                    let mut purchase = self.purchase();
+                    purchase.amount = *amount;
+                    purchase.uplines = uplines.iter().map(|upline| wallets[*upline]).collect();
+                    self.airdrop(wallets[*buyer], *amount);
+                    self.process(client::buy_gift_card(&purchase, wallets[*buyer], wallets[*referrer]))
+                        .expect("scenario purchase succeeds");
+                }
+            }
+        }
+        wallets
+    }
+
+    pub fn user_pda(&self, wallet: &Pubkey) -> Pubkey {
+        client::find_user_address(&self.solbox, wallet).0
+    }
+
+    pub fn receipt_pda(&self, purchase_id: u64) -> Pubkey {
+        client::find_receipt_address(&self.solbox, purchase_id).0
+    }
+
+    // Buy with the upline accounts resolved from the stored tree
+    pub fn buy(&mut self, buyer: Pubkey, referrer: Pubkey) -> ProgramResult {
+        let purchase = self.purchase_under(referrer);
+        self.process(client::buy_gift_card(&purchase, buyer, referrer))
+    }
+
+    pub fn buy_with_uplines(&mut self, buyer: Pubkey, referrer: Pubkey, uplines: &[Pubkey]) -> ProgramResult {
+        let mut purchase = self.purchase();
+        purchase.uplines = uplines.to_vec();
+        self.process(client::buy_gift_card(&purchase, buyer, referrer))
+    }
+
+    // Cancel a purchase, with the upline accounts resolved from the stored tree
+    pub fn cancel(&mut self, purchase_id: u64) -> ProgramResult {
+        let state: solbox_contract_devnet::SolBox = self.fetch(&self.solbox);
+        let receipt = self.fetch(&self.receipt_pda(purchase_id));
+        self.process(client::cancel_purchase(self.solbox, &state, &receipt))
+    }
+
+    // A plain package purchase at the next purchase id
+    pub fn purchase(&self) -> client::Purchase {
+        let solbox: solbox_contract_devnet::SolBox = self.fetch(&self.solbox);
+        client::Purchase {
+            solbox: self.solbox,
+            founder: self.founder,
+            purchase_id: solbox.purchase_count,
+            day: (self.clock.lock().unwrap().unix_timestamp + solbox.time_offset).div_euclid(86_400) as u64,
+            amount: PACKAGE,
+            integrator: None,
+            attribution: None,
+            session_key: None,
+            sponsor_paid: false,
+            cpi_authority: None,
+            referrer_boost: false,
+            humanity_verifier: None,
+            tranche: false,
+            package: false,
+            drop_commit: None,
+            uplines: Vec::new(),
+        }
+    }
+
+    // A purchase placed under `referrer`, carrying the upline wallets a
+    // first purchase needs
+    pub fn purchase_under(&self, referrer: Pubkey) -> client::Purchase {
+        let solbox: solbox_contract_devnet::SolBox = self.fetch(&self.solbox);
+        client::Purchase {
+            uplines: client::purchase_uplines(&solbox, referrer).unwrap_or_default(),
+            ..self.purchase()
+        }
+    }
+
+    // Admin instructions share the AdminAction accounts, which carry a User
+    pub fn admin_action(&mut self, user: Pubkey, data: impl InstructionData) -> ProgramResult {
+        let mut ix = client::toggle_pause(self.solbox, self.owner, user);
+        ix.data = data.data();
+        self.process(ix)
+    }
+
+    pub fn call(&mut self, accounts: &impl ToAccountMetas, data: impl InstructionData) -> ProgramResult {
+        let ix = Instruction {
+            program_id: solbox_contract_devnet::ID,
+            accounts: accounts.to_account_metas(None),
+            data: data.data(),
+        };
+        self.process(ix)
+    }
+}
+
+impl client::AccountSource for Harness {
+    fn account_data(&self, address: &Pubkey) -> Option<Vec<u8>> {
+        self.accounts.get(address).map(|account| account.data.clone())
+    }
+}
+
+pub fn pda(seeds: &[&[u8]]) -> Pubkey {
+    Pubkey::find_program_address(seeds, &solbox_contract_devnet::ID).0
+}
+
+// Assert an instruction failed with the given program error
+pub fn assert_error(result: ProgramResult, error: solbox_contract_devnet::CustomError) {
+    assert_eq!(result, Err(ProgramError::Custom(error.into())));
+}
@@ -0,0 +1,166 @@
+mod common;
+
+use anchor_lang::{AnchorSerialize, Discriminator, Event as _};
+use common::{assert_error, Harness, PACKAGE};
+use solbox_contract_devnet::{self as program, client, CustomError};
+use solbox_events::{Address, BlacklistAction, Event};
+
+// Every program event must keep the discriminator the events crate expects
+macro_rules! assert_discriminators {
+    ($($name:ident),* $(,)?) => {
+        $(assert_eq!(
+            <program::$name as Discriminator>::DISCRIMINATOR,
+            <solbox_events::$name as solbox_events::Discriminator>::DISCRIMINATOR,
+            stringify!($name)
+        );)*
+    };
+}
+
+#[test]
+fn discriminators_match_the_program() {
+    assert_discriminators!(
+        InitializeEvent, UserRegisteredEvent, ConfigUpdateEvent, PauseEvent,
+        PackageUpgradeEvent, GiftCardPurchaseEvent, TenantLinkedEvent,
+        UserProofExportedEvent, UserImportedEvent, SessionKeyCreatedEvent,
+        SessionKeyRevokedEvent, AttributionRecordedEvent, PurchaseSettledEvent,
+        IntegratorUpdatedEvent, PurchaseCancelledEvent, AdminRefundEvent,
+        TreasuryWithdrawnEvent, InvariantViolationEvent, StateVerifiedEvent,
+        AccountExpandedEvent, PlacementEvent, CycleEvent, StakeDelegatedEvent,
+        StakeUndelegatedEvent, YieldDistributionEvent, YieldClaimedEvent,
+        PackageGrantedEvent, CommissionConfigEvent, FeatureFlagsProposedEvent,
+        FeatureFlagsAppliedEvent, FounderWalletProposedEvent, FounderWalletChangedEvent,
+        BoostsConfiguredEvent, BoostActivatedEvent, ContestCreatedEvent, ContestFinalizedEvent,
+        ContestPrizeClaimedEvent, TierDecayWarningEvent, TierDecayedEvent, NoticePostedEvent,
+        RecoveryApprovedEvent, WalletRecoveredEvent, BeneficiarySetEvent,
+        InheritanceClaimedEvent, StatementsAnchoredEvent, PauseGuardiansSetEvent,
+        UnpauseApprovedEvent, ResidualClaimedEvent, TimeWarpedEvent, LookupTableEvent,
+        PackageMetadataEvent, FiatPurchaseAttestedEvent, HandleEvent, MigrationRootCommittedEvent,
+        PositionMigratedEvent, SponsorshipEvent, MembershipEvent, HouseCommissionEvent,
+        QueuedPayoutEvent, CoverageEvent, EmergencyWithdrawInitiatedEvent,
+        EmergencyWithdrawCancelledEvent, EmergencyWithdrawExecutedEvent, BrandCodeEvent,
+        EarningsEvent, DeferredFounderShareEvent, CardEvent, CardClawbackEvent, TierTokenEvent,
+        PositionTokenEvent, TermsPublishedEvent, TermsAcceptedEvent, TrancheReleasedEvent,
+        ConfigExportedEvent, RestoreApprovedEvent, InstanceRestoredEvent, LowStockEvent,
+        ClaimVoucherEvent, WormholePurchaseEvent, DeletionRequestedEvent, UserDeletedEvent,
+        TreeCheckpointEvent, TreasurySweptEvent, ColdWithdrawalEvent, FraudFlagEvent,
+        IntegratorLimitsEvent, InvitationEvent, CardBalanceEvent, BlacklistEvent,
+        MerchantRedemptionEvent,
+    );
+}
+
+#[test]
+fn layouts_round_trip_through_the_events_crate() {
+    let config = Harness::default_config();
+    let emitted = program::ConfigUpdateEvent {
+        admin: anchor_lang::prelude::Pubkey::new_unique(),
+        new_config: config.clone(),
+        version: 3,
+        timestamp: 1_700_000_000,
+        brand_code: *b"acme\0\0\0\0",
+    };
+    let Ok(Event::ConfigUpdateEvent(decoded)) = Event::decode(&emitted.data()) else {
+        panic!("ConfigUpdateEvent decodes");
+    };
+    assert_eq!(decoded.admin, Address(emitted.admin.to_bytes()));
+    assert_eq!(decoded.new_config.valid_amounts, config.valid_amounts);
+    assert_eq!(decoded.brand_code, emitted.brand_code);
+    assert_eq!(decoded.try_to_vec().unwrap(), emitted.try_to_vec().unwrap());
+
+    let emitted = program::BlacklistEvent {
+        admin: anchor_lang::prelude::Pubkey::new_unique(),
+        user: anchor_lang::prelude::Pubkey::new_unique(),
+        action: program::BlacklistAction::Remove,
+        timestamp: 1_700_000_000,
+        brand_code: [0; 8],
+    };
+    let Ok(Event::BlacklistEvent(decoded)) = Event::decode(&emitted.data()) else {
+        panic!("BlacklistEvent decodes");
+    };
+    assert_eq!(decoded.action, BlacklistAction::Remove);
+}
+
+#[test]
+fn purchase_events_decode_from_logs() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let referrer = harness.register();
+    let buyer = harness.register();
+    harness.buy(buyer, referrer).unwrap();
+
+    let purchase = harness
+        .logs()
+        .iter()
+        .filter_map(|line| Event::from_log(line))
+        .map(|event| event.expect("program events decode"))
+        .find_map(|event| match event {
+            Event::GiftCardPurchaseEvent(purchase) => Some(purchase),
+            _ => None,
+        })
+        .expect("purchase event logged");
+    assert_eq!(purchase.user, Address(buyer.to_bytes()));
+    assert_eq!(purchase.referrer, Address(referrer.to_bytes()));
+    assert_eq!(purchase.amount, PACKAGE);
+    assert_eq!(purchase.integrator, None);
+
+    // Consumers get a tagged JSON shape
+    let json = serde_json::to_value(Event::GiftCardPurchaseEvent(purchase)).unwrap();
+    assert_eq!(json["event"], "GiftCardPurchaseEvent");
+    assert_eq!(json["data"]["user"], buyer.to_string());
+}
+
+#[test]
+fn events_carry_the_tenant_brand_code() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let (solbox, owner) = (harness.solbox, harness.owner);
+    let brand = *b"acme\0\0\0\0";
+    harness.process(client::set_brand_code(solbox, owner, brand)).unwrap();
+    let referrer = harness.register();
+    let buyer = harness.register();
+    harness.buy(buyer, referrer).unwrap();
+
+    let brands: Vec<_> = harness
+        .logs()
+        .iter()
+        .filter_map(|line| Event::from_log(line))
+        .filter_map(|event| match event.expect("program events decode") {
+            Event::GiftCardPurchaseEvent(event) => Some(event.brand_code),
+            Event::CoverageEvent(event) => Some(event.brand_code),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(brands, vec![brand, brand]);
+
+    let other = harness.register();
+    assert_error(
+        harness.process(client::set_brand_code(solbox, other, [1; 8])),
+        CustomError::Unauthorized,
+    );
+}
+
+#[test]
+fn purchases_report_treasury_coverage() {
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.refund_window = 3_600;
+    harness.initialize(config).unwrap();
+    let referrer = harness.register();
+    let buyer = harness.register();
+    harness.buy(buyer, referrer).unwrap();
+
+    let coverage = harness
+        .logs()
+        .iter()
+        .filter_map(|line| Event::from_log(line))
+        .find_map(|event| match event {
+            Ok(Event::CoverageEvent(coverage)) => Some(coverage),
+            _ => None,
+        })
+        .expect("coverage event logged");
+
+    // The escrowed purchase, less the 5% bonus, is owed and held in full
+    let escrowed = PACKAGE * 95 / 100;
+    assert_eq!(coverage.liabilities, escrowed);
+    assert!(coverage.vault_balance >= escrowed);
+    assert_eq!(coverage.coverage_bps, coverage.vault_balance * 10_000 / escrowed);
+}
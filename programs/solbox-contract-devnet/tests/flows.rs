@@ -0,0 +1,4154 @@
+mod common;
+
+use anchor_lang::error::ErrorCode;
+use anchor_lang::prelude::{AnchorDeserialize, ProgramError, Pubkey};
+use common::{assert_error, pda, Harness, LAMPORTS_PER_SOL, PACKAGE};
+use solbox_contract_devnet::scenario::{self, ScenarioParams};
+use solbox_contract_devnet::statements::{self, MigratedPosition, Statement};
+use solbox_contract_devnet::{
+    accounts, client, instruction, BlacklistFilter, Boost, ClaimReceipt, ClaimVoucher, ConfigHistory, ConfigSnapshot, Contest,
+    ContestMetric, CustomError, DailyStats, DropCommit, FeatureFlags, FiatAttestation, InstanceSnapshot, Migration, Notice, Package, PostedVaa, Receipt,
+    ReceiptStatus, SolBox, StorefrontPurchase, Tranche, User, WormholePurchase, CONFIG_HISTORY_LEN, DEFAULT_CAPACITY, EMERGENCY_WITHDRAW_TIMELOCK,
+    FEATURE_BINARY_MODE, FEATURE_FLAGS_TIMELOCK, FOUNDER_CHANGE_TIMELOCK, INTEGRATOR_ALLOW_BUY_GIFT_CARD,
+    INTEGRATOR_ALLOW_FIAT_ATTESTATION, MIN_INACTIVITY_PERIOD, NOTICE_CAMPAIGN, PACKAGE_GENERAL,
+    PACKAGE_SEASONAL, PURCHASE_PROBLEM_SELF_REFERRAL, PURCHASE_PROBLEM_STALE_DAY,
+    PURCHASE_PROBLEM_STALE_RECEIPT, PURCHASE_PROBLEM_UPLINE_MISSING, RECOVERY_TIMELOCK, RESTORE_TIMELOCK,
+    drop_commitment, tombstone_key, TreeCheckpoint, COLD_WITHDRAW_TIMELOCK, MAX_CHECKPOINT_BATCH,
+    FRAUD_BURST_WINDOW, FRAUD_FLAG_BURST, FRAUD_FLAG_SHARED_FUNDER, Integrator, Invitation, invitation_hash,
+    PURCHASE_PROBLEM_INVITATION_MISMATCH, PurchaseFlows, estimate_unique_buyers, PlacementPreview, Attribution, SessionKey, SupportTierView, AdminDashboard, MAX_RELEASE_BATCH,
+    Merchant, MerchantMonth, month_of, MAX_REDEMPTION_FEE_BPS, Capacity,
+};
+use solbox_events::{EarningSource, Event};
+
+#[test]
+fn initialize_creates_state_accounts() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+
+    let solbox: SolBox = harness.fetch(&harness.solbox);
+    assert_eq!(solbox.owner, harness.owner);
+    assert_eq!(solbox.founder_wallet, harness.founder);
+    assert!(!solbox.paused);
+
+    let history: ConfigHistory = harness.fetch(&pda(&[b"config_history", harness.solbox.as_ref()]));
+    assert_eq!(history.version, 1);
+
+    let filter: BlacklistFilter = harness.fetch(&pda(&[b"blacklist_filter", harness.solbox.as_ref()]));
+    assert_eq!(filter.solbox, harness.solbox);
+}
+
+#[test]
+fn initialize_rejects_invalid_matrix() {
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.matrix_width = 0;
+    assert_error(harness.initialize(config), CustomError::InvalidMatrixConfig);
+}
+
+#[test]
+fn matrix_shape_comes_from_the_config() {
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.commission_levels = 2;
+    config.matrix_depth = 1;
+    assert_error(harness.initialize(config.clone()), CustomError::InvalidMatrixConfig);
+    config.commission_levels = 1;
+    config.matrix_width = 3;
+    harness.initialize(config.clone()).unwrap();
+    let (solbox, owner) = (harness.solbox, harness.owner);
+    let referrer = harness.register();
+
+    // Three legs one level deep, and then the matrix is full
+    for _ in 0..3 {
+        let buyer = harness.register();
+        harness.buy(buyer, referrer).unwrap();
+    }
+    let state: SolBox = harness.fetch(&solbox);
+    assert!(state.referral_relationships.iter().all(|r| r.referrer == referrer));
+    let late = harness.register();
+    assert_error(harness.buy(late, referrer), CustomError::NoSpilloverAvailable);
+
+    // The matrix can widen, but not narrow below the legs already there
+    config.matrix_width = 2;
+    assert_error(
+        harness.process(client::update_config(solbox, owner, config.clone())),
+        CustomError::MatrixWidthViolated
+    );
+    config.matrix_width = 4;
+    harness.process(client::update_config(solbox, owner, config)).unwrap();
+    harness.buy(late, referrer).unwrap();
+    assert_eq!(harness.fetch::<SolBox>(&solbox).referral_relationships.len(), 4);
+}
+
+#[test]
+fn initialize_sizes_the_config_history_for_the_package_menu() {
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    let max_amounts = DEFAULT_CAPACITY.max_amounts as u64;
+    config.valid_amounts = (1..=max_amounts + 1).map(|n| n * PACKAGE).collect();
+    assert_error(harness.initialize(config.clone()), CustomError::TooManyAmounts);
+    harness.initialize(Harness::default_config()).unwrap();
+    let (solbox, owner) = (harness.solbox, harness.owner);
+
+    assert_error(
+        harness.process(client::update_config(solbox, owner, config.clone())),
+        CustomError::TooManyAmounts
+    );
+
+    // A full history of full menus still fits
+    config.valid_amounts.pop();
+    for _ in 0..CONFIG_HISTORY_LEN {
+        harness.process(client::update_config(solbox, owner, config.clone())).unwrap();
+    }
+    let history: ConfigHistory = harness.fetch(&pda(&[b"config_history", solbox.as_ref()]));
+    assert_eq!(history.entries.len(), CONFIG_HISTORY_LEN);
+    assert!(history.entries.iter().all(|entry| entry.config.valid_amounts.len() == max_amounts as usize));
+}
+
+#[test]
+fn initialize_sizes_user_accounts_for_the_commission_levels() {
+    use solbox_contract_devnet::client::AccountSource;
+    let capacity = Capacity { max_levels: 2, ..DEFAULT_CAPACITY };
+    let mut config = Harness::default_config();
+    assert_eq!(config.commission_levels, 2);
+    let mut harness = Harness::new();
+    let (owner, founder) = (harness.owner, harness.founder);
+    let too_small = Capacity { max_levels: 1, ..capacity };
+    assert_error(
+        harness.process(client::initialize(harness.solbox, owner, founder, config.clone(), false, too_small)),
+        CustomError::TooManyLevels
+    );
+    harness.process(client::initialize(harness.solbox, owner, founder, config.clone(), false, capacity)).unwrap();
+    let solbox = harness.solbox;
+
+    let member = harness.register();
+    let data = harness.account_data(&harness.user_pda(&member)).unwrap();
+    assert_eq!(data.len(), 8 + User::space(2));
+    assert_eq!(harness.fetch::<User>(&harness.user_pda(&member)).downline_counts, [0; 2]);
+
+    // Levels can be lowered, but not raised past what users have room for
+    config.commission_levels = 1;
+    harness.process(client::update_config(solbox, owner, config.clone())).unwrap();
+    config.commission_levels = 3;
+    assert_error(
+        harness.process(client::update_config(solbox, owner, config)),
+        CustomError::TooManyLevels
+    );
+}
+
+#[test]
+fn config_history_keeps_the_latest_versions() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let (solbox, owner) = (harness.solbox, harness.owner);
+    let history_pda = pda(&[b"config_history", solbox.as_ref()]);
+    let history: ConfigHistory = harness.fetch(&history_pda);
+    assert_eq!((history.version, history.entries.len(), history.entries[0].admin), (1, 1, owner));
+
+    // Only the admin records versions
+    let stranger = harness.register();
+    assert_error(
+        harness.process(client::update_config(solbox, stranger, Harness::default_config())),
+        CustomError::Unauthorized
+    );
+
+    // Once the ring is full each version overwrites the oldest
+    let mut config = Harness::default_config();
+    for n in 1..=CONFIG_HISTORY_LEN as u64 {
+        harness.warp(1);
+        config.commission_percentage = 10 + n;
+        harness.process(client::update_config(solbox, owner, config.clone())).unwrap();
+    }
+    let history: ConfigHistory = harness.fetch(&history_pda);
+    let latest = CONFIG_HISTORY_LEN as u64 + 1;
+    assert_eq!((history.version, history.entries.len()), (latest, CONFIG_HISTORY_LEN));
+    let newest = &history.entries[(latest as usize - 1) % CONFIG_HISTORY_LEN];
+    assert_eq!((newest.version, newest.config.commission_percentage), (latest, 10 + CONFIG_HISTORY_LEN as u64));
+    assert!(history.entries.iter().all(|entry| entry.version > 1));
+    let oldest = &history.entries[1 % CONFIG_HISTORY_LEN];
+    assert!(oldest.activation_slot < newest.activation_slot);
+}
+
+#[test]
+fn accounts_lead_with_their_type_tag_and_layout_version() {
+    use solbox_contract_devnet::client::AccountSource;
+    use solbox_contract_devnet::{
+        ACCOUNT_TYPE_BLACKLIST_FILTER, ACCOUNT_TYPE_CONFIG_HISTORY, ACCOUNT_TYPE_DAILY_STATS, ACCOUNT_TYPE_RECEIPT,
+        ACCOUNT_TYPE_SOLBOX, ACCOUNT_TYPE_USER, BLACKLIST_FILTER_VERSION, CONFIG_HISTORY_VERSION, DAILY_STATS_VERSION,
+        RECEIPT_VERSION, SOLBOX_VERSION, USER_VERSION,
+    };
+
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let solbox = harness.solbox;
+    let referrer = harness.register();
+    let buyer = harness.register();
+    harness.buy(buyer, referrer).unwrap();
+
+    // Indexers tell accounts apart by the two bytes after the discriminator
+    let day = harness.purchase().day;
+    let expected = [
+        (solbox, ACCOUNT_TYPE_SOLBOX, SOLBOX_VERSION),
+        (harness.user_pda(&buyer), ACCOUNT_TYPE_USER, USER_VERSION),
+        (pda(&[b"config_history", solbox.as_ref()]), ACCOUNT_TYPE_CONFIG_HISTORY, CONFIG_HISTORY_VERSION),
+        (harness.receipt_pda(0), ACCOUNT_TYPE_RECEIPT, RECEIPT_VERSION),
+        (client::find_daily_stats_address(&solbox, day).0, ACCOUNT_TYPE_DAILY_STATS, DAILY_STATS_VERSION),
+        (pda(&[b"blacklist_filter", solbox.as_ref()]), ACCOUNT_TYPE_BLACKLIST_FILTER, BLACKLIST_FILTER_VERSION),
+    ];
+    for (address, tag, version) in expected {
+        let data = harness.account_data(&address).unwrap();
+        assert_eq!((data[8], data[9]), (tag, version));
+    }
+}
+
+#[test]
+fn admins_grow_core_accounts_without_touching_their_state() {
+    use anchor_lang::prelude::Rent;
+    use solbox_contract_devnet::client::AccountSource;
+
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let (solbox, owner) = (harness.solbox, harness.owner);
+    let referrer = harness.register();
+    let buyer = harness.register();
+    harness.buy(buyer, referrer).unwrap();
+    let user = harness.user_pda(&buyer);
+    let system_program = anchor_lang::system_program::ID;
+    let expand_solbox = |admin| accounts::ExpandSolBox { solbox, admin, system_program };
+    let expand_user = |admin| accounts::ExpandUser { solbox, user, admin, system_program };
+
+    assert_error(
+        harness.call(&expand_solbox(referrer), instruction::ExpandSolbox { additional_bytes: 512 }),
+        CustomError::Unauthorized
+    );
+    assert_error(
+        harness.call(&expand_user(owner), instruction::ExpandUser { additional_bytes: 0 }),
+        CustomError::InvalidAccountSize
+    );
+
+    // The admin pays the rent for the zeroed tail, and the state reads back unchanged
+    let state_before: SolBox = harness.fetch(&solbox);
+    let account_before: User = harness.fetch(&user);
+    let (solbox_len, user_len) = (harness.account_data(&solbox).unwrap().len(), harness.account_data(&user).unwrap().len());
+    harness.call(&expand_solbox(owner), instruction::ExpandSolbox { additional_bytes: 512 }).unwrap();
+    harness.call(&expand_user(owner), instruction::ExpandUser { additional_bytes: 256 }).unwrap();
+    for (address, len) in [(solbox, solbox_len + 512), (user, user_len + 256)] {
+        let data = harness.account_data(&address).unwrap();
+        assert_eq!(data.len(), len);
+        assert!(data[len - 256..].iter().all(|&byte| byte == 0));
+        assert!(harness.lamports(&address) >= Rent::default().minimum_balance(len));
+    }
+    let state: SolBox = harness.fetch(&solbox);
+    assert_eq!((state.user_count, state.total_sold), (state_before.user_count, state_before.total_sold));
+    let account: User = harness.fetch(&user);
+    assert_eq!((account.key, account.total_earnings), (account_before.key, account_before.total_earnings));
+    harness.buy(buyer, referrer).unwrap();
+}
+
+#[test]
+fn register_counts_users() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let wallet = harness.register();
+
+    let user: User = harness.fetch(&harness.user_pda(&wallet));
+    assert_eq!(user.key, wallet);
+    assert_eq!(user.current_package, 0);
+
+    let solbox: SolBox = harness.fetch(&harness.solbox);
+    assert_eq!(solbox.user_count, 1);
+}
+
+#[test]
+fn buy_pays_referrer_and_founder() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let referrer = harness.register();
+    let buyer = harness.register();
+    let referrer_before = harness.lamports(&referrer);
+    let founder_before = harness.lamports(&harness.founder);
+
+    harness.buy(buyer, referrer).unwrap();
+
+    // 10% commission, 5% bonus withheld, the rest to the founder
+    assert_eq!(harness.lamports(&referrer) - referrer_before, PACKAGE / 10);
+    assert_eq!(harness.lamports(&harness.founder) - founder_before, PACKAGE * 85 / 100);
+
+    let receipt: Receipt = harness.fetch(&harness.receipt_pda(0));
+    assert_eq!(receipt.buyer, buyer);
+    assert_eq!(receipt.status, ReceiptStatus::Settled);
+
+    let referrer_account: User = harness.fetch(&harness.user_pda(&referrer));
+    assert_eq!(referrer_account.total_earnings, PACKAGE / 10);
+    let buyer_account: User = harness.fetch(&harness.user_pda(&buyer));
+    assert_eq!(buyer_account.lifetime_volume, PACKAGE);
+}
+
+#[test]
+fn referrer_earnings_are_split_by_source() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let referrer = harness.register();
+    let commission = PACKAGE / 10;
+
+    // Two signups fill the referrer's own legs, the third spills below them
+    let mut sources = Vec::new();
+    for _ in 0..3 {
+        let buyer = harness.register();
+        harness.buy(buyer, referrer).unwrap();
+        sources.extend(harness.logs().iter().filter_map(|line| match Event::from_log(line) {
+            Some(Ok(Event::EarningsEvent(earned))) => Some((earned.source, earned.amount)),
+            _ => None,
+        }));
+    }
+    assert_eq!(
+        sources,
+        vec![
+            (EarningSource::Direct, commission),
+            (EarningSource::Direct, commission),
+            (EarningSource::Spillover, commission),
+        ]
+    );
+
+    let account: User = harness.fetch(&harness.user_pda(&referrer));
+    assert_eq!((account.earnings_from_direct, account.earnings_from_spillover), (2 * commission, commission));
+    assert_eq!(account.earnings_from_direct + account.earnings_from_spillover, account.total_earnings);
+    assert!(harness.fetch::<Receipt>(&harness.receipt_pda(2)).spillover);
+}
+
+#[test]
+fn preview_placement_matches_where_signups_land() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let solbox = harness.solbox;
+    let referrer = harness.register();
+    let preview = |harness: &mut Harness| {
+        harness.call(&accounts::PreviewPlacement { solbox }, instruction::PreviewPlacement { referrer }).unwrap();
+        PlacementPreview::deserialize(&mut harness.return_data().as_slice()).unwrap()
+    };
+
+    // Two signups fill the referrer's legs, then the preview points below them
+    let mut previews = Vec::new();
+    for _ in 0..3 {
+        let expected = preview(&mut harness);
+        let buyer = harness.register();
+        harness.buy(buyer, referrer).unwrap();
+        let state: SolBox = harness.fetch(&solbox);
+        let placed = state.referral_relationships.iter().find(|r| r.user == buyer).unwrap();
+        assert_eq!((placed.referrer, placed.referrer_cycle), (expected.placed_under, expected.placed_under_cycle));
+        previews.push(expected);
+    }
+    let summary: Vec<_> = previews.iter().map(|p| (p.placed_under == referrer, p.is_spillover, p.direct_referrals)).collect();
+    assert_eq!(summary, [(true, false, 0), (true, false, 1), (false, true, 0)]);
+    assert_eq!(preview(&mut harness).direct_referrals, 1);
+}
+
+#[test]
+fn buy_across_levels_updates_downline_counts() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let top = harness.register();
+    let middle = harness.register();
+    let bottom = harness.register();
+
+    harness.buy(middle, top).unwrap();
+
+    // Every paid level comes in from remaining accounts, nearest first,
+    // and has to match the stored referral chain
+    assert_error(harness.buy_with_uplines(bottom, middle, &[middle]), CustomError::UplineAccountMissing);
+    assert_error(harness.buy_with_uplines(bottom, middle, &[top, middle]), CustomError::UplineAccountMismatch);
+    let state: SolBox = harness.fetch(&harness.solbox);
+    let uplines = client::purchase_uplines(&state, middle).unwrap();
+    assert_eq!(uplines, [middle, top]);
+    harness.buy_with_uplines(bottom, middle, &uplines).unwrap();
+
+    let top_account: User = harness.fetch(&harness.user_pda(&top));
+    assert_eq!(top_account.downline_counts[..2], [1, 1]);
+    let middle_account: User = harness.fetch(&harness.user_pda(&middle));
+    assert_eq!(middle_account.downline_counts[..2], [1, 0]);
+
+    let solbox: SolBox = harness.fetch(&harness.solbox);
+    assert_eq!(solbox.referral_relationships.len(), 2);
+    assert_eq!(solbox.purchase_count, 2);
+}
+
+#[test]
+fn full_positions_recycle_under_the_sponsor_with_a_bonus() {
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.matrix_width = 2;
+    config.matrix_depth = 1;
+    config.commission_levels = 1;
+    config.recycling = true;
+    config.cycle_bonus = PACKAGE / 20;
+    harness.initialize(config).unwrap();
+    let solbox = harness.solbox;
+    let sponsor = harness.register();
+    let member = harness.register();
+    harness.buy(member, sponsor).unwrap();
+    harness.airdrop(solbox, PACKAGE);
+
+    // The second leg fills the member's position, which re-enters under
+    // the sponsor and is paid the cycle bonus on top of the commission
+    let (left, right) = (harness.register(), harness.register());
+    harness.buy(left, member).unwrap();
+    let before = harness.lamports(&member);
+    harness.buy(right, member).unwrap();
+    assert_eq!(harness.lamports(&member) - before, PACKAGE / 10 + PACKAGE / 20);
+    let state: SolBox = harness.fetch(&solbox);
+    let reentry = state.referral_relationships.iter().find(|r| r.user == member && r.cycle == 1).unwrap();
+    assert_eq!((reentry.referrer, reentry.referrer_cycle), (sponsor, 0));
+    let cycles: Vec<_> = harness.logs().iter().filter_map(|line| match Event::from_log(line) {
+        Some(Ok(Event::CycleEvent(cycle))) => Some((cycle.cycle, cycle.bonus)),
+        _ => None,
+    }).collect();
+    assert_eq!(cycles, [(1, PACKAGE / 20)]);
+
+    // The placement that set the recycle off is marked on the tree, and
+    // can't be cancelled out from under the re-entered position
+    let marked = |wallet| state.referral_relationships.iter().find(|r| r.user == wallet).unwrap().set_off_recycle;
+    assert_eq!((marked(left), marked(right)), (false, true));
+    harness.modify::<Receipt>(&harness.receipt_pda(2), |receipt| {
+        receipt.status = ReceiptStatus::Pending;
+        receipt.refundable_until = i64::MAX;
+    });
+    harness.modify::<SolBox>(&solbox, |state| state.escrow_balance = PACKAGE);
+    assert_error(harness.cancel(2), CustomError::PlacementInUse);
+    harness.modify::<SolBox>(&solbox, |state| state.escrow_balance = 0);
+
+    // New signups fill the fresh position
+    let next = harness.register();
+    harness.buy(next, member).unwrap();
+    let state: SolBox = harness.fetch(&solbox);
+    let placed = state.referral_relationships.iter().find(|r| r.user == next).unwrap();
+    assert_eq!((placed.referrer, placed.referrer_cycle), (member, 1));
+}
+
+#[test]
+fn holding_tank_signups_are_placed_by_the_sponsor_or_the_crank() {
+    use anchor_lang::{InstructionData, ToAccountMetas};
+    use anchor_lang::solana_program::instruction::Instruction;
+    use solbox_contract_devnet::Position;
+
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.holding_tank_period = 3_600;
+    harness.initialize(config).unwrap();
+    let solbox = harness.solbox;
+    let sponsor = harness.register();
+    let (alice, bob, carol) = (harness.register(), harness.register(), harness.register());
+    // Placements take the upline accounts of the spot, as purchases do
+    let placement = |accounts: &dyn ToAccountMetas, data: Vec<u8>, uplines: Vec<Pubkey>| {
+        let mut accounts = accounts.to_account_metas(None);
+        accounts.extend(client::upline_accounts(&solbox, &uplines));
+        Instruction { program_id: solbox_contract_devnet::ID, accounts, data }
+    };
+    let place = |harness: &Harness, signer, user, target| {
+        let state: SolBox = harness.fetch(&solbox);
+        let uplines = client::upline_wallets(&state, Position { user: target, cycle: 0 });
+        let data = instruction::PlaceUser { user, target, target_cycle: 0 }.data();
+        placement(&accounts::PlaceUser { solbox, sponsor: signer }, data, uplines)
+    };
+
+    // Signups wait in the tank while the sponsor is paid as usual
+    let before = harness.lamports(&sponsor);
+    for buyer in [alice, bob, carol] {
+        harness.buy(buyer, sponsor).unwrap();
+    }
+    assert_eq!(harness.lamports(&sponsor) - before, 3 * PACKAGE / 10);
+    let state: SolBox = harness.fetch(&solbox);
+    assert_eq!((state.holding_tank.len(), state.referral_relationships.len()), (3, 0));
+
+    // Only the sponsor places, inside its own matrix
+    assert_error(harness.process(place(&harness, alice, alice, sponsor)), CustomError::Unauthorized);
+    harness.process(place(&harness, sponsor, alice, sponsor)).unwrap();
+    assert_error(harness.process(place(&harness, sponsor, bob, carol)), CustomError::InvalidPlacement);
+    harness.process(place(&harness, sponsor, bob, alice)).unwrap();
+    let state: SolBox = harness.fetch(&solbox);
+    let parent = |user| state.referral_relationships.iter().find(|r| r.user == user).map(|r| r.referrer);
+    assert_eq!((parent(alice), parent(bob)), (Some(sponsor), Some(alice)));
+    assert_eq!(harness.fetch::<User>(&harness.user_pda(&sponsor)).downline_counts[..2], [1, 1]);
+
+    // Once the hold runs out the crank places what is left
+    harness.warp(3_600);
+    assert_error(harness.process(place(&harness, sponsor, carol, sponsor)), CustomError::HoldingTankExpired);
+    let data = instruction::AutoPlaceExpired { max_entries: 5 }.data();
+    let uplines = client::purchase_uplines(&harness.fetch(&solbox), sponsor).unwrap();
+    let crank = placement(&accounts::AutoPlace { solbox, cranker: bob }, data, uplines);
+    harness.process(crank).unwrap();
+    let state: SolBox = harness.fetch(&solbox);
+    assert!(state.holding_tank.is_empty());
+    assert!(state.referral_relationships.iter().any(|r| r.user == carol && r.referrer == sponsor));
+}
+
+#[test]
+fn downline_counters_stop_at_the_paid_levels() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let chain: Vec<Pubkey> = (0..4).map(|_| harness.register()).collect();
+    for pair in chain.windows(2) {
+        harness.buy(pair[1], pair[0]).unwrap();
+    }
+
+    // commission_levels is 2, so the root only sees two levels down
+    let counts = |harness: &Harness, wallet| harness.fetch::<User>(&harness.user_pda(wallet)).downline_counts;
+    assert_eq!(counts(&harness, &chain[0])[..3], [1, 1, 0]);
+    assert_eq!(counts(&harness, &chain[1])[..3], [1, 1, 0]);
+    assert_eq!(counts(&harness, &chain[2])[..3], [1, 0, 0]);
+
+    // Repeat purchases hold the same position and count nothing new
+    harness.buy(chain[3], chain[2]).unwrap();
+    assert_eq!(counts(&harness, &chain[1])[..2], [1, 1]);
+    assert_eq!(harness.fetch::<SolBox>(&harness.solbox).user_count, 4);
+}
+
+#[test]
+fn buy_rejects_self_referral() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let buyer = harness.register();
+    assert_error(harness.buy(buyer, buyer), CustomError::SelfReferralNotAllowed);
+}
+
+#[test]
+fn daily_stats_bucket_purchases_by_day() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let solbox = harness.solbox;
+    let referrer = harness.register();
+    let (alice, bob) = (harness.register(), harness.register());
+    let day = harness.purchase().day;
+    let stats_pda = client::find_daily_stats_address(&solbox, day).0;
+
+    // Repeat buyers count towards volume but not registrations or the
+    // unique buyer estimate
+    harness.buy(alice, referrer).unwrap();
+    let once = estimate_unique_buyers(&harness.fetch::<DailyStats>(&stats_pda).buyer_sketch);
+    harness.buy(alice, referrer).unwrap();
+    let stats: DailyStats = harness.fetch(&stats_pda);
+    assert_eq!(once, 1);
+    assert_eq!(estimate_unique_buyers(&stats.buyer_sketch), once);
+    harness.buy(bob, referrer).unwrap();
+    let stats: DailyStats = harness.fetch(&stats_pda);
+    assert_eq!((stats.solbox, stats.day), (solbox, day));
+    assert_eq!((stats.purchase_count, stats.volume, stats.new_registrations), (3, 3 * PACKAGE, 2));
+    assert_eq!(stats.commission, 3 * PACKAGE / 10);
+    assert!((1..=2).contains(&estimate_unique_buyers(&stats.buyer_sketch)));
+
+    // The next day opens a fresh bucket
+    harness.warp(86_400);
+    harness.buy(bob, referrer).unwrap();
+    let next: DailyStats = harness.fetch(&client::find_daily_stats_address(&solbox, day + 1).0);
+    assert_eq!((next.day, next.purchase_count, next.new_registrations), (day + 1, 1, 0));
+    assert_eq!(harness.fetch::<DailyStats>(&stats_pda).purchase_count, 3);
+}
+
+#[test]
+fn pause_blocks_purchases() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let referrer = harness.register();
+    let buyer = harness.register();
+
+    harness.admin_action(referrer, instruction::TogglePause {}).unwrap();
+    assert_error(harness.buy(buyer, referrer), CustomError::ContractPaused);
+
+    harness.admin_action(referrer, instruction::TogglePause {}).unwrap();
+    harness.buy(buyer, referrer).unwrap();
+}
+
+#[test]
+fn claims_can_stay_open_through_a_purchase_pause() {
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.residual_bps = 3_000;
+    config.claims_during_pause = true;
+    harness.initialize(config).unwrap();
+    let referrer = harness.register();
+    let buyer = harness.register();
+    let solbox = harness.solbox;
+    harness.buy(buyer, referrer).unwrap();
+    harness.next_epoch();
+
+    // With the exemption, earned residual is still paid out
+    harness.admin_action(referrer, instruction::TogglePause {}).unwrap();
+    assert_error(harness.buy(buyer, referrer), CustomError::ContractPaused);
+    let referrer_before = harness.lamports(&referrer);
+    harness.process(client::claim_residual(solbox, referrer)).unwrap();
+    assert_eq!(harness.lamports(&referrer) - referrer_before, PACKAGE * 3 / 100);
+
+    // Without it, a pause freezes claims as well
+    harness.admin_action(referrer, instruction::TogglePause {}).unwrap();
+    harness.buy(buyer, referrer).unwrap();
+    harness.next_epoch();
+    harness.admin_action(referrer, instruction::TogglePause {}).unwrap();
+    harness.modify::<SolBox>(&solbox, |state| state.config.claims_during_pause = false);
+    assert_error(
+        harness.process(client::claim_residual(solbox, referrer)),
+        CustomError::ContractPaused
+    );
+}
+
+#[test]
+fn blacklist_blocks_purchases() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let referrer = harness.register();
+    let buyer = harness.register();
+    let solbox = harness.solbox;
+    let owner = harness.owner;
+    let blacklist = accounts::UpdateBlacklist {
+        solbox,
+        blacklist_filter: pda(&[b"blacklist_filter", solbox.as_ref()]),
+        admin: owner,
+    };
+
+    harness.call(&blacklist, instruction::AddToBlacklist { user: buyer }).unwrap();
+    assert_error(harness.buy(buyer, referrer), CustomError::UserBlacklisted);
+
+    harness.call(&blacklist, instruction::RemoveFromBlacklist { user: buyer }).unwrap();
+    harness.buy(buyer, referrer).unwrap();
+}
+
+#[test]
+fn blacklist_filter_tracks_the_list() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let referrer = harness.register();
+    let (alice, bob) = (harness.register(), harness.register());
+    let (solbox, owner) = (harness.solbox, harness.owner);
+    let filter_pda = pda(&[b"blacklist_filter", solbox.as_ref()]);
+    let blacklist = accounts::UpdateBlacklist { solbox, blacklist_filter: filter_pda, admin: owner };
+    let bits = |harness: &Harness| harness.fetch::<BlacklistFilter>(&filter_pda).bits;
+    assert!(bits(&harness).iter().all(|&byte| byte == 0));
+
+    // Removing one wallet rebuilds the filter from the wallets left
+    harness.call(&blacklist, instruction::AddToBlacklist { user: bob }).unwrap();
+    let bob_only = bits(&harness);
+    harness.call(&blacklist, instruction::AddToBlacklist { user: alice }).unwrap();
+    assert_ne!(bits(&harness), bob_only);
+    harness.call(&blacklist, instruction::RemoveFromBlacklist { user: alice }).unwrap();
+    assert_eq!(bits(&harness), bob_only);
+    harness.buy(alice, referrer).unwrap();
+    assert_error(harness.buy(bob, referrer), CustomError::UserBlacklisted);
+
+    // A filter hit is only a maybe; the list has the final say
+    harness.modify::<BlacklistFilter>(&filter_pda, |filter| filter.bits.fill(0xff));
+    harness.buy(alice, referrer).unwrap();
+    assert_error(harness.buy(bob, referrer), CustomError::UserBlacklisted);
+    let blacklist = accounts::UpdateBlacklist { admin: referrer, ..blacklist };
+    assert_error(
+        harness.call(&blacklist, instruction::AddToBlacklist { user: alice }),
+        CustomError::Unauthorized
+    );
+}
+
+#[test]
+fn escrowed_purchase_can_be_cancelled_then_settled() {
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.refund_window = 3_600;
+    harness.initialize(config).unwrap();
+    let referrer = harness.register();
+    let buyer = harness.register();
+    let solbox = harness.solbox;
+
+    // Cancelling inside the window returns the escrow
+    harness.buy(buyer, referrer).unwrap();
+    let escrowed: SolBox = harness.fetch(&solbox);
+    assert_eq!(escrowed.escrow_balance, PACKAGE * 95 / 100);
+    let buyer_before = harness.lamports(&buyer);
+    harness.cancel(0).unwrap();
+    assert_eq!(harness.lamports(&buyer) - buyer_before, PACKAGE * 95 / 100);
+    let cancelled: SolBox = harness.fetch(&solbox);
+    assert_eq!(cancelled.escrow_balance, 0);
+    assert_eq!(cancelled.pending_receipts, 0);
+    assert_eq!(harness.fetch::<User>(&harness.user_pda(&buyer)).pending_receipts, 0);
+
+    // Settling waits for the window to pass
+    harness.buy(buyer, referrer).unwrap();
+    let settle = accounts::SettlePurchase {
+        solbox,
+        receipt: harness.receipt_pda(1),
+        referrer,
+        referrer_user_account: harness.user_pda(&referrer),
+        buyer_user_account: harness.user_pda(&buyer),
+        founder: harness.founder,
+        integrator: None,
+        integrator_wallet: None,
+        tranche: None,
+    };
+    assert_error(
+        harness.call(&settle, instruction::SettlePurchase {}),
+        CustomError::RefundWindowOpen
+    );
+    harness.warp(3_601);
+    let referrer_before = harness.lamports(&referrer);
+    harness.call(&settle, instruction::SettlePurchase {}).unwrap();
+    assert_eq!(harness.lamports(&referrer) - referrer_before, PACKAGE / 10);
+
+    let receipt: Receipt = harness.fetch(&harness.receipt_pda(1));
+    assert_eq!(receipt.status, ReceiptStatus::Settled);
+    assert_eq!(harness.fetch::<User>(&harness.user_pda(&referrer)).pending_receipts, 0);
+}
+
+#[test]
+fn cancelled_signups_come_off_the_tree_and_the_counters() {
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.refund_window = 3_600;
+    harness.initialize(config).unwrap();
+    let (solbox, owner) = (harness.solbox, harness.owner);
+    let name_hash = [6; 32];
+    let integrator = client::find_integrator_address(&solbox, &name_hash).0;
+    harness.call(
+        &accounts::RegisterIntegrator {
+            solbox,
+            integrator,
+            admin: owner,
+            system_program: anchor_lang::system_program::ID,
+        },
+        instruction::RegisterIntegrator {
+            name_hash,
+            wallet: Pubkey::new_unique(),
+            fee_share_bps: 0,
+            allowed_instructions: INTEGRATOR_ALLOW_BUY_GIFT_CARD,
+            cpi_authority: Pubkey::new_unique(),
+        }
+    ).unwrap();
+    let referrer = harness.register();
+    let buyer = harness.register();
+    let child = harness.register();
+    harness.buy(buyer, referrer).unwrap();
+
+    // A child signs up under the buyer inside the buyer's refund window
+    let purchase = client::Purchase {
+        integrator: Some((integrator, Pubkey::new_unique())),
+        ..harness.purchase_under(buyer)
+    };
+    harness.process(client::buy_gift_card(&purchase, child, buyer)).unwrap();
+    let counts = |harness: &Harness, wallet| {
+        let user: User = harness.fetch(&harness.user_pda(&wallet));
+        (user.downline_counts[..2].to_vec(), user.unseen_downline_events)
+    };
+    assert_eq!(counts(&harness, referrer), (vec![1, 1], 2));
+    assert_eq!(counts(&harness, buyer), (vec![1, 0], 1));
+
+    // The buyer's position now carries the child, so it stays
+    assert_error(harness.cancel(0), CustomError::PlacementInUse);
+
+    // The child's cancel takes it off every upline, the day and the
+    // integrator's usage
+    harness.cancel(1).unwrap();
+    assert_eq!(counts(&harness, referrer), (vec![1, 0], 1));
+    assert_eq!(counts(&harness, buyer), (vec![0, 0], 0));
+    let usage: Integrator = harness.fetch(&integrator);
+    assert_eq!((usage.epoch_volume, usage.epoch_purchases), (0, 0));
+    let day = client::find_daily_stats_address(&solbox, 1_700_000_000 / 86_400).0;
+    let stats: DailyStats = harness.fetch(&day);
+    assert_eq!((stats.purchase_count, stats.volume, stats.new_registrations), (1, PACKAGE, 1));
+
+    // With nothing below it, the buyer's position can go too
+    harness.cancel(0).unwrap();
+    assert_eq!(counts(&harness, referrer), (vec![0, 0], 0));
+    let state: SolBox = harness.fetch(&solbox);
+    assert!(state.referral_relationships.is_empty());
+    assert_eq!((state.referral_count, state.total_sold), (0, 0));
+    let stats: DailyStats = harness.fetch(&day);
+    assert_eq!((stats.purchase_count, stats.volume, stats.commission, stats.new_registrations), (0, 0, 0, 0));
+}
+
+#[test]
+fn admins_refund_settled_purchases_from_the_treasury() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let (solbox, owner) = (harness.solbox, harness.owner);
+    let referrer = harness.register();
+    let buyer = harness.register();
+    harness.buy(buyer, referrer).unwrap();
+    let receipt: Receipt = harness.fetch(&harness.receipt_pda(0));
+    let paid = receipt.commission + receipt.founder_share;
+    let refund = |harness: &mut Harness, admin, amount, reason: &str| {
+        harness.process(client::admin_refund(solbox, admin, &receipt, amount, reason))
+    };
+
+    // Refunds come out of the treasury, never what is owed to others
+    assert_error(refund(&mut harness, owner, paid / 2, "duplicate"), CustomError::InsufficientCoverage);
+    harness.airdrop(solbox, PACKAGE);
+    assert_error(refund(&mut harness, buyer, paid / 2, "duplicate"), CustomError::Unauthorized);
+    assert_error(refund(&mut harness, owner, paid / 2, &"x".repeat(65)), CustomError::RefundReasonTooLong);
+    assert_error(refund(&mut harness, owner, 0, "duplicate"), CustomError::InvalidRefundAmount);
+
+    // Partial refunds add up to at most what the buyer paid, reversing
+    // the same share of the sale
+    let buyer_before = harness.lamports(&buyer);
+    refund(&mut harness, owner, paid / 2, "duplicate").unwrap();
+    assert_eq!(harness.lamports(&buyer) - buyer_before, paid / 2);
+    let state: SolBox = harness.fetch(&solbox);
+    assert_eq!(state.total_sold, PACKAGE - PACKAGE / 2);
+    assert_eq!(state.total_commission_distributed, receipt.commission - receipt.commission / 2);
+    let payee: User = harness.fetch(&harness.user_pda(&referrer));
+    let kept = receipt.commission - receipt.commission / 2;
+    assert_eq!((payee.total_earnings, payee.earnings_from_direct), (kept, kept));
+    assert_error(refund(&mut harness, owner, paid - paid / 2 + 1, "rest"), CustomError::InvalidRefundAmount);
+    
+    // Refunds too small to reverse a whole lamport on their own still
+    // leave nothing behind once the refunds add up to the whole
+    for _ in 0..3 {
+        refund(&mut harness, owner, 3, "dust").unwrap();
+    }
+    refund(&mut harness, owner, paid - paid / 2 - 9, "rest").unwrap();
+    assert_eq!(harness.fetch::<Receipt>(&harness.receipt_pda(0)).refunded_amount, paid);
+    let state: SolBox = harness.fetch(&solbox);
+    assert_eq!((state.total_sold, state.total_commission_distributed), (0, 0));
+    let payee: User = harness.fetch(&harness.user_pda(&referrer));
+    assert_eq!((payee.total_earnings, payee.earnings_from_direct), (0, 0));
+    assert_error(refund(&mut harness, owner, 1, "rest"), CustomError::InvalidRefundAmount);
+
+    // Escrowed purchases are cancelled by the buyer instead
+    harness.modify::<SolBox>(&solbox, |state| state.config.refund_window = 3_600);
+    harness.buy(buyer, referrer).unwrap();
+    let receipt: Receipt = harness.fetch(&harness.receipt_pda(1));
+    assert_error(
+        harness.process(client::admin_refund(solbox, owner, &receipt, 1, "early")),
+        CustomError::ReceiptNotRefundable
+    );
+}
+
+#[test]
+fn idle_treasury_is_delegated_to_the_stake_pool_and_back() {
+    use anchor_lang::solana_program::{stake, system_program, sysvar};
+
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.stake_pool = Pubkey::new_unique();
+    config.max_stake_bps = 5_000;
+    config.yield_sharing = true;
+    harness.initialize(config.clone()).unwrap();
+    let solbox = harness.solbox;
+    harness.airdrop(solbox, 10 * LAMPORTS_PER_SOL);
+
+    let stake_authority = client::find_stake_authority_address(&solbox).0;
+    let pool_mint = harness.create_mint();
+    let pool_token_account = harness.mint_to(pool_mint, stake_authority, 0);
+    let reserve_stake = Pubkey::new_unique();
+    let action = |admin| accounts::StakePoolAction {
+        solbox,
+        stake_authority,
+        pool_token_account,
+        stake_pool: config.stake_pool,
+        stake_pool_withdraw_authority: Pubkey::new_unique(),
+        reserve_stake,
+        manager_fee_account: Pubkey::new_unique(),
+        pool_mint,
+        admin,
+        clock: sysvar::clock::ID,
+        stake_history: sysvar::stake_history::ID,
+        stake_program: stake::program::ID,
+        stake_pool_program: solbox_contract_devnet::SPL_STAKE_POOL_PROGRAM_ID,
+        token_program: anchor_spl::token::ID,
+        system_program: system_program::ID,
+    };
+    let owner = harness.owner;
+
+    let outsider = harness.register();
+    assert_error(
+        harness.call(&action(outsider), instruction::DelegateToStakePool { lamports: LAMPORTS_PER_SOL }),
+        CustomError::Unauthorized
+    );
+    // Only half of the treasury may be delegated
+    assert_error(
+        harness.call(&action(owner), instruction::DelegateToStakePool { lamports: 6 * LAMPORTS_PER_SOL }),
+        CustomError::StakeLimitExceeded
+    );
+
+    let vault_before = harness.lamports(&solbox);
+    harness.call(&action(owner), instruction::DelegateToStakePool { lamports: 4 * LAMPORTS_PER_SOL }).unwrap();
+    assert_eq!(vault_before - harness.lamports(&solbox), 4 * LAMPORTS_PER_SOL);
+    assert_eq!(harness.lamports(&reserve_stake), 4 * LAMPORTS_PER_SOL);
+    assert_eq!(harness.fetch::<SolBox>(&solbox).staked_lamports, 4 * LAMPORTS_PER_SOL);
+
+    // SOL owed to users is never delegated, whatever the stake limit
+    harness.modify::<SolBox>(&solbox, |state| state.escrow_balance = 5 * LAMPORTS_PER_SOL + LAMPORTS_PER_SOL / 2);
+    assert_error(
+        harness.call(&action(owner), instruction::DelegateToStakePool { lamports: LAMPORTS_PER_SOL }),
+        CustomError::StakeLimitExceeded
+    );
+    harness.modify::<SolBox>(&solbox, |state| state.escrow_balance = 0);
+
+    // Rewards accrue to the reserve; withdrawing every pool token returns
+    // the principal plus the rewards, which are reserved as yield
+    let rewards = 4 * LAMPORTS_PER_SOL / 10;
+    harness.airdrop(reserve_stake, rewards);
+    let vault_before = harness.lamports(&solbox);
+    harness.call(&action(owner), instruction::UndelegateFromStakePool { pool_tokens: 4 * LAMPORTS_PER_SOL }).unwrap();
+    assert_eq!(harness.lamports(&solbox) - vault_before, 4 * LAMPORTS_PER_SOL + rewards);
+    let state: SolBox = harness.fetch(&solbox);
+    assert_eq!(state.staked_lamports, 0);
+    assert_eq!(state.undistributed_yield, rewards);
+    assert_eq!(state.yield_pool_balance, rewards);
+}
+
+#[test]
+fn yield_is_shared_pro_rata_by_package_size() {
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.valid_amounts = vec![PACKAGE, 3 * PACKAGE];
+    config.yield_sharing = true;
+    harness.initialize(config).unwrap();
+    let solbox = harness.solbox;
+    let cranker = harness.owner;
+    let distribute = |harness: &mut Harness| {
+        harness.call(&accounts::DistributeYield { solbox, cranker }, instruction::DistributeYield {})
+    };
+
+    assert_error(distribute(&mut harness), CustomError::NoActivePackages);
+    let small = harness.register();
+    let large = harness.register();
+    harness.admin_action(small, instruction::GrantPackage { user: small, package: PACKAGE }).unwrap();
+    harness.admin_action(large, instruction::GrantPackage { user: large, package: 3 * PACKAGE }).unwrap();
+
+    let realized = 4 * LAMPORTS_PER_SOL / 100;
+    harness.airdrop(solbox, realized);
+    harness.modify::<SolBox>(&solbox, |state| {
+        state.undistributed_yield = realized;
+        state.yield_pool_balance = realized;
+        state.config.yield_sharing = false;
+    });
+    assert_error(distribute(&mut harness), CustomError::YieldSharingDisabled);
+    harness.modify::<SolBox>(&solbox, |state| state.config.yield_sharing = true);
+    distribute(&mut harness).unwrap();
+
+    // A package taken up after the distribution earns none of it
+    let late = harness.register();
+    harness.admin_action(late, instruction::GrantPackage { user: late, package: PACKAGE }).unwrap();
+
+    for (wallet, share) in [(small, realized / 4), (large, 3 * realized / 4)] {
+        let before = harness.lamports(&wallet);
+        harness.process(client::claim_yield(solbox, wallet, 0)).unwrap();
+        let rent = harness.lamports(&client::find_claim_receipt_address(&solbox, &wallet, 0).0);
+        assert_eq!(harness.lamports(&wallet) + rent - before, share);
+    }
+    assert_error(harness.process(client::claim_yield(solbox, late, 0)), CustomError::NothingToClaim);
+    assert_eq!(harness.fetch::<SolBox>(&solbox).yield_pool_balance, 0);
+}
+
+#[test]
+fn package_holders_claim_distributed_yield() {
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.yield_sharing = true;
+    harness.initialize(config).unwrap();
+    let holder = harness.register();
+    let solbox = harness.solbox;
+
+    harness.admin_action(holder, instruction::GrantPackage { user: holder, package: PACKAGE }).unwrap();
+
+    // Stand in for yield realized from the stake pool
+    let realized = LAMPORTS_PER_SOL / 100;
+    harness.airdrop(solbox, realized);
+    harness.modify::<SolBox>(&solbox, |state| {
+        state.undistributed_yield = realized;
+        state.yield_pool_balance = realized;
+    });
+
+    let cranker = harness.owner;
+    harness.call(
+        &accounts::DistributeYield { solbox, cranker },
+        instruction::DistributeYield {}
+    ).unwrap();
+    assert_error(
+        harness.call(&accounts::DistributeYield { solbox, cranker }, instruction::DistributeYield {}),
+        CustomError::YieldAlreadyDistributed
+    );
+
+    assert_error(
+        harness.process(client::claim_yield(solbox, holder, 1)),
+        CustomError::InvalidClaimEpoch
+    );
+    let receipt_key = client::find_claim_receipt_address(&solbox, &holder, 0).0;
+    let holder_before = harness.lamports(&holder);
+    harness.process(client::claim_yield(solbox, holder, 0)).unwrap();
+    let rent = harness.lamports(&receipt_key);
+    assert_eq!(harness.lamports(&holder) + rent - holder_before, realized);
+    let receipted = harness.logs().iter().any(|line| match Event::from_log(line) {
+        Some(Ok(Event::YieldClaimedEvent(claim))) => claim.receipt.0 == receipt_key.to_bytes(),
+        _ => false,
+    });
+    assert!(receipted);
+
+    // A retried submission finds the receipt and pays nothing
+    let holder_before = harness.lamports(&holder);
+    harness.process(client::claim_yield(solbox, holder, 0)).unwrap();
+    assert_eq!(harness.lamports(&holder), holder_before);
+    let receipt: ClaimReceipt = harness.fetch(&receipt_key);
+    assert_eq!((receipt.wallet, receipt.epoch, receipt.amount), (holder, 0, realized));
+    assert_eq!(harness.fetch::<User>(&harness.user_pda(&holder)).earnings_from_pools, realized);
+    harness.next_epoch();
+    assert_error(
+        harness.process(client::claim_yield(solbox, holder, 1)),
+        CustomError::NothingToClaim
+    );
+
+    let state: SolBox = harness.fetch(&solbox);
+    assert_eq!(state.yield_pool_balance, 0);
+    assert_eq!(state.active_users, 1);
+}
+
+#[test]
+fn verify_state_reports_broken_invariants() {
+    use anchor_lang::{InstructionData, ToAccountMetas};
+    use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+    use solbox_contract_devnet::{INVARIANT_ACTIVE_USERS, INVARIANT_ESCROW, MAX_VERIFY_BATCH};
+
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.refund_window = 3_600;
+    harness.initialize(config).unwrap();
+    let solbox = harness.solbox;
+    let referrer = harness.register();
+    for _ in 0..2 {
+        let buyer = harness.register();
+        harness.buy(buyer, referrer).unwrap();
+    }
+    let receipts = [harness.receipt_pda(0), harness.receipt_pda(1)];
+    let verify = |receipts: &[Pubkey]| {
+        let mut accounts = accounts::VerifyState { solbox }.to_account_metas(None);
+        accounts.extend(receipts.iter().map(|receipt| AccountMeta::new_readonly(*receipt, false)));
+        Instruction { program_id: solbox_contract_devnet::ID, accounts, data: instruction::VerifyState {}.data() }
+    };
+    let report = |harness: &Harness| {
+        let mut violations = Vec::new();
+        let mut verified = None;
+        for line in harness.logs() {
+            match Event::from_log(&line) {
+                Some(Ok(Event::InvariantViolationEvent(event))) => violations.push((event.invariant, event.expected, event.actual)),
+                Some(Ok(Event::StateVerifiedEvent(event))) => verified = Some((event.pending_count, event.violations)),
+                _ => {}
+            }
+        }
+        (violations, verified.unwrap())
+    };
+
+    harness.process(verify(&receipts)).unwrap();
+    assert_eq!(report(&harness), (vec![], (2, 0)));
+
+    // Ledger totals that fall behind the receipts are reported, and the
+    // crank itself still succeeds
+    let escrow = harness.fetch::<SolBox>(&solbox).escrow_balance;
+    harness.modify::<SolBox>(&solbox, |state| {
+        state.escrow_balance = escrow / 2;
+        state.active_users = state.user_count + 1;
+    });
+    harness.process(verify(&receipts)).unwrap();
+    let (violations, verified) = report(&harness);
+    assert_eq!(verified, (2, 2));
+    assert!(violations.contains(&(INVARIANT_ESCROW, escrow / 2, escrow)));
+    assert!(violations.iter().any(|violation| violation.0 == INVARIANT_ACTIVE_USERS));
+
+    assert_error(
+        harness.process(verify(&vec![receipts[0]; MAX_VERIFY_BATCH + 1])),
+        CustomError::VerifyBatchTooLarge
+    );
+}
+
+#[test]
+fn treasury_withdrawal_keeps_every_liability_covered() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let solbox = harness.solbox;
+    let owner = harness.owner;
+    harness.airdrop(solbox, LAMPORTS_PER_SOL);
+    let withdraw = accounts::WithdrawTreasury { solbox, admin: owner, recipient: owner };
+
+    // Each kind of balance owed to users holds back the same lamports
+    let owed: [fn(&mut SolBox, u64); 6] = [
+        |state, owed| state.card_balance_total = owed,
+        |state, owed| state.residual_balance = owed,
+        |state, owed| state.queued_payouts = owed,
+        |state, owed| state.voucher_balance = owed,
+        |state, owed| state.deferred_founder_share = owed,
+        |state, owed| state.tranche_balance = owed,
+    ];
+    for set in owed {
+        harness.modify::<SolBox>(&solbox, |state| set(state, LAMPORTS_PER_SOL / 2));
+        assert_error(
+            harness.call(&withdraw, instruction::WithdrawTreasury { amount: LAMPORTS_PER_SOL / 2 + 1 }),
+            CustomError::InsufficientCoverage
+        );
+        harness.modify::<SolBox>(&solbox, |state| set(state, 0));
+    }
+
+    let owner_before = harness.lamports(&owner);
+    harness.call(&withdraw, instruction::WithdrawTreasury { amount: LAMPORTS_PER_SOL }).unwrap();
+    assert_eq!(harness.lamports(&owner) - owner_before, LAMPORTS_PER_SOL);
+}
+
+#[test]
+fn treasury_withdrawal_keeps_escrow_covered() {
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.refund_window = 3_600;
+    harness.initialize(config).unwrap();
+    let referrer = harness.register();
+    let buyer = harness.register();
+    harness.buy(buyer, referrer).unwrap();
+    let solbox = harness.solbox;
+    let owner = harness.owner;
+
+    // Everything above rent is escrow, so nothing can leave
+    let withdraw = accounts::WithdrawTreasury {
+        solbox,
+        admin: owner,
+        recipient: owner,
+    };
+    assert_error(
+        harness.call(&withdraw, instruction::WithdrawTreasury { amount: 1 }),
+        CustomError::InsufficientCoverage
+    );
+
+    harness.airdrop(solbox, LAMPORTS_PER_SOL);
+    let owner_before = harness.lamports(&owner);
+    harness.call(&withdraw, instruction::WithdrawTreasury { amount: LAMPORTS_PER_SOL }).unwrap();
+    assert_eq!(harness.lamports(&owner) - owner_before, LAMPORTS_PER_SOL);
+
+    let state: SolBox = harness.fetch(&solbox);
+    assert_eq!(state.escrow_balance, PACKAGE * 95 / 100);
+}
+
+#[test]
+fn integrators_take_their_revshare_from_the_founder_share() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let solbox = harness.solbox;
+    let owner = harness.owner;
+    let founder = harness.founder;
+    let register = |harness: &mut Harness, name_hash: [u8; 32], allowed_instructions| {
+        let integrator = client::find_integrator_address(&solbox, &name_hash).0;
+        let wallet = Pubkey::new_unique();
+        harness.call(
+            &accounts::RegisterIntegrator {
+                solbox,
+                integrator,
+                admin: owner,
+                system_program: anchor_lang::system_program::ID,
+            },
+            instruction::RegisterIntegrator {
+                name_hash,
+                wallet,
+                fee_share_bps: 300,
+                allowed_instructions,
+                cpi_authority: Pubkey::new_unique(),
+            }
+        ).unwrap();
+        (integrator, wallet)
+    };
+    let on_ramp = register(&mut harness, [1; 32], INTEGRATOR_ALLOW_FIAT_ATTESTATION);
+    let wallet_partner = register(&mut harness, [2; 32], INTEGRATOR_ALLOW_BUY_GIFT_CARD);
+    let referrer = harness.register();
+    let direct = harness.register();
+    let routed = harness.register();
+
+    let founder_before = harness.lamports(&founder);
+    harness.buy(direct, referrer).unwrap();
+    let founder_share = harness.lamports(&founder) - founder_before;
+
+    // An integrator only routes the instructions it was allowed
+    let purchase = client::Purchase { integrator: Some(on_ramp), ..harness.purchase_under(referrer) };
+    assert_error(
+        harness.process(client::buy_gift_card(&purchase, routed, referrer)),
+        CustomError::InvalidIntegrator
+    );
+
+    let (integrator, partner_wallet) = wallet_partner;
+    let purchase = client::Purchase { integrator: Some(wallet_partner), ..harness.purchase_under(referrer) };
+    let founder_before = harness.lamports(&founder);
+    harness.process(client::buy_gift_card(&purchase, routed, referrer)).unwrap();
+    let fee = PACKAGE * 3 / 100;
+    assert_eq!(harness.lamports(&partner_wallet), fee);
+    assert_eq!(harness.lamports(&founder) - founder_before, founder_share - fee);
+
+    let tagged = harness.logs().iter().any(|line| match Event::from_log(line) {
+        Some(Ok(Event::GiftCardPurchaseEvent(event))) => {
+            event.integrator.map(|key| key.0) == Some(integrator.to_bytes()) && event.integrator_fee == fee
+        }
+        _ => false,
+    });
+    assert!(tagged);
+    assert_eq!(harness.fetch::<Receipt>(&harness.receipt_pda(1)).integrator, integrator);
+}
+
+#[test]
+fn purchases_consume_the_click_they_came_through() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let solbox = harness.solbox;
+    let referrer = harness.register();
+    let other_referrer = harness.register();
+    let buyer = harness.register();
+    let second_buyer = harness.register();
+
+    let nonce_hash = [9; 32];
+    let attribution = client::find_attribution_address(&solbox, &nonce_hash).0;
+    let recorder = Pubkey::new_unique();
+    harness.airdrop(recorder, LAMPORTS_PER_SOL);
+    harness.call(
+        &accounts::RecordAttribution {
+            solbox,
+            attribution,
+            recorder,
+            system_program: anchor_lang::system_program::ID,
+        },
+        instruction::RecordAttribution { nonce_hash, referrer }
+    ).unwrap();
+
+    // The click names its referrer; a purchase under anyone else can't use it
+    let purchase = client::Purchase { attribution: Some(attribution), ..harness.purchase_under(other_referrer) };
+    assert_error(
+        harness.process(client::buy_gift_card(&purchase, buyer, other_referrer)),
+        CustomError::InvalidAttribution
+    );
+
+    let purchase = client::Purchase { attribution: Some(attribution), ..harness.purchase_under(referrer) };
+    harness.process(client::buy_gift_card(&purchase, buyer, referrer)).unwrap();
+    let click: Attribution = harness.fetch(&attribution);
+    assert_eq!((click.recorded_by, click.consumed_by, click.purchase_id), (recorder, buyer, 0));
+    let anchored = harness.logs().iter().any(|line| match Event::from_log(line) {
+        Some(Ok(Event::GiftCardPurchaseEvent(event))) => event.attribution == Some(nonce_hash),
+        _ => false,
+    });
+    assert!(anchored);
+
+    // Each click attributes one purchase
+    let purchase = client::Purchase { attribution: Some(attribution), ..harness.purchase_under(referrer) };
+    assert_error(
+        harness.process(client::buy_gift_card(&purchase, second_buyer, referrer)),
+        CustomError::AttributionConsumed
+    );
+}
+
+#[test]
+fn users_carry_an_attested_history_into_linked_tenants() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let source = harness.solbox;
+    let owner = harness.owner;
+    let holder = harness.register();
+    harness.admin_action(holder, instruction::GrantPackage { user: holder, package: PACKAGE }).unwrap();
+    harness.modify::<User>(&harness.user_pda(&holder), |user| user.lifetime_volume = 5 * PACKAGE);
+
+    let user_proof = client::find_user_proof_address(&source, &holder).0;
+    let export = |admin| accounts::ExportUserProof {
+        solbox: source,
+        user_account: client::find_user_address(&source, &holder).0,
+        user_proof,
+        admin,
+        system_program: anchor_lang::system_program::ID,
+    };
+    assert_error(harness.call(&export(holder), instruction::ExportUserProof {}), CustomError::Unauthorized);
+    harness.call(&export(owner), instruction::ExportUserProof {}).unwrap();
+
+    // A second tenant, run by the same authority here
+    harness.solbox = Pubkey::new_unique();
+    harness.initialize(Harness::default_config()).unwrap();
+    let target = harness.solbox;
+    harness.register_wallet(holder);
+    let tenant_link = pda(&[b"tenant_link", target.as_ref(), source.as_ref()]);
+    let import = accounts::ImportUser {
+        solbox: target,
+        tenant_link,
+        user_proof,
+        user_account: harness.user_pda(&holder),
+        wallet: holder,
+    };
+
+    // Only proofs from linked tenants are accepted
+    assert!(harness.call(&import, instruction::ImportUser {}).is_err());
+    harness.call(
+        &accounts::LinkTenant { solbox: target, tenant_link, admin: owner, system_program: anchor_lang::system_program::ID },
+        instruction::LinkTenant { source }
+    ).unwrap();
+    harness.call(&import, instruction::ImportUser {}).unwrap();
+    let user: User = harness.fetch(&harness.user_pda(&holder));
+    assert_eq!((user.current_package, user.lifetime_volume, user.imported_from), (PACKAGE, 5 * PACKAGE, source));
+    assert_eq!(harness.fetch::<SolBox>(&target).total_package_weight, PACKAGE);
+
+    assert_error(harness.call(&import, instruction::ImportUser {}), CustomError::UserAlreadyImported);
+}
+
+#[test]
+fn session_keys_buy_from_their_budget_until_they_expire() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let solbox = harness.solbox;
+    let referrer = harness.register();
+    let buyer = harness.register();
+    let stranger = harness.register();
+    let signer = Pubkey::new_unique();
+    harness.airdrop(signer, LAMPORTS_PER_SOL);
+
+    let session = client::find_session_key_address(&solbox, &buyer, &signer).0;
+    let create = accounts::CreateSessionKey { solbox, session, owner: buyer, system_program: anchor_lang::system_program::ID };
+    assert_error(
+        harness.call(&create, instruction::CreateSessionKey { session_key: signer, max_lamports: PACKAGE, expires_at: 0 }),
+        CustomError::InvalidSessionKey
+    );
+    // The harness clock starts at 1_700_000_000
+    let expires_at = 1_700_000_000 + 3_600;
+    harness.call(
+        &create,
+        instruction::CreateSessionKey { session_key: signer, max_lamports: PACKAGE + PACKAGE / 2, expires_at }
+    ).unwrap();
+
+    // The session budget pays; the owner's wallet doesn't move
+    let session_purchase = |harness: &Harness| client::Purchase { session_key: Some(signer), ..harness.purchase_under(referrer) };
+    let buyer_before = harness.lamports(&buyer);
+    let budget_before = harness.lamports(&session);
+    harness.process(client::buy_gift_card(&session_purchase(&harness), buyer, referrer)).unwrap();
+    let spent = harness.fetch::<SessionKey>(&session).spent;
+    assert!(spent > 0);
+    assert_eq!(harness.lamports(&buyer), buyer_before);
+    assert_eq!(budget_before - harness.lamports(&session), spent);
+
+    // A session only buys for its owner, up to its budget, until it expires
+    let mut ix = client::buy_gift_card(&session_purchase(&harness), stranger, referrer);
+    let stranger_session = client::find_session_key_address(&solbox, &stranger, &signer).0;
+    ix.accounts.iter_mut().filter(|meta| meta.pubkey == stranger_session).for_each(|meta| meta.pubkey = session);
+    assert_error(harness.process(ix), CustomError::InvalidSessionKey);
+    assert_error(
+        harness.process(client::buy_gift_card(&session_purchase(&harness), buyer, referrer)),
+        CustomError::SessionKeyLimitExceeded
+    );
+    harness.warp(3_600);
+    assert_error(
+        harness.process(client::buy_gift_card(&session_purchase(&harness), buyer, referrer)),
+        CustomError::SessionKeyExpired
+    );
+
+    // Revoking returns what's left of the budget to the owner
+    let remaining = harness.lamports(&session);
+    let buyer_before = harness.lamports(&buyer);
+    harness.call(&accounts::RevokeSessionKey { solbox, session, owner: buyer }, instruction::RevokeSessionKey {}).unwrap();
+    assert_eq!(harness.lamports(&buyer) - buyer_before, remaining);
+}
+
+#[test]
+fn cpi_purchases_require_an_allowlisted_caller() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let referrer = harness.register();
+    let buyer = harness.register();
+    let solbox = harness.solbox;
+    let owner = harness.owner;
+
+    let name_hash = [7; 32];
+    let integrator = client::find_integrator_address(&solbox, &name_hash).0;
+    let partner_wallet = Pubkey::new_unique();
+    let partner_authority = Pubkey::new_unique();
+    harness.call(
+        &accounts::RegisterIntegrator {
+            solbox,
+            integrator,
+            admin: owner,
+            system_program: anchor_lang::system_program::ID,
+        },
+        instruction::RegisterIntegrator {
+            name_hash,
+            wallet: partner_wallet,
+            fee_share_bps: 0,
+            allowed_instructions: INTEGRATOR_ALLOW_BUY_GIFT_CARD,
+            cpi_authority: partner_authority,
+        }
+    ).unwrap();
+    harness.airdrop(partner_authority, LAMPORTS_PER_SOL);
+
+    // Through CPI, a purchase without an allowlisted integrator fails
+    harness.set_stack_height(2);
+    assert_error(harness.buy(buyer, referrer), CustomError::CpiCallerNotAllowed);
+
+    // An integrator without its authority signature is not enough
+    let mut purchase = harness.purchase_under(referrer);
+    purchase.integrator = Some((integrator, partner_wallet));
+    assert_error(
+        harness.process(client::buy_gift_card(&purchase, buyer, referrer)),
+        CustomError::CpiCallerNotAllowed
+    );
+
+    purchase.cpi_authority = Some(partner_authority);
+    harness.process(client::buy_gift_card(&purchase, buyer, referrer)).unwrap();
+    let receipt: Receipt = harness.fetch(&harness.receipt_pda(0));
+    assert_eq!(receipt.integrator, integrator);
+}
+
+#[test]
+fn scenarios_replay_to_identical_state() {
+    let params = ScenarioParams {
+        seed: 42,
+        users: 20,
+        depth: 4,
+        repeat_purchases: 30,
+        amounts: vec![PACKAGE],
+        matrix_width: 2,
+        commission_levels: 2,
+    };
+    let scenario = scenario::generate(&params).unwrap();
+    assert_eq!(scenario::generate(&params).unwrap(), scenario);
+
+    let replay = || {
+        let mut harness = Harness::new();
+        harness.initialize(Harness::default_config()).unwrap();
+        let wallets = harness.run_scenario(&scenario);
+        let state: SolBox = harness.fetch(&harness.solbox);
+        let index = |key: &Pubkey| wallets.iter().position(|wallet| wallet == key).unwrap();
+        let tree: Vec<(usize, usize)> = state
+            .referral_relationships
+            .iter()
+            .map(|relationship| (index(&relationship.user), index(&relationship.referrer)))
+            .collect();
+        (tree, state.purchase_count, state.total_sold)
+    };
+
+    let (tree, purchases, sold) = replay();
+    assert_eq!(replay(), (tree.clone(), purchases, sold));
+
+    // Every signup lands under the referrer the scenario chose
+    for (user, referrer) in tree {
+        assert_eq!(scenario.referrers[user], Some(referrer));
+    }
+    assert_eq!(purchases, 19 + 30);
+    assert_eq!(sold, 49 * PACKAGE);
+}
+
+#[test]
+fn scenarios_must_fit_the_tree() {
+    let params = ScenarioParams {
+        seed: 1,
+        users: 8,
+        depth: 2,
+        repeat_purchases: 0,
+        amounts: vec![PACKAGE],
+        matrix_width: 2,
+        commission_levels: 2,
+    };
+    // A width 2 tree holds 1 + 2 + 4 users within two levels
+    assert!(scenario::generate(&params).is_none());
+    assert!(scenario::generate(&ScenarioParams { users: 7, ..params }).is_some());
+}
+
+#[test]
+fn feature_flag_changes_wait_out_the_timelock() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let solbox = harness.solbox;
+    let owner = harness.owner;
+    let address = client::find_feature_flags_address(&solbox).0;
+
+    harness.process(client::initialize_feature_flags(solbox, owner)).unwrap();
+    assert_error(
+        harness.process(client::apply_feature_flags(solbox)),
+        CustomError::NoPendingFeatureFlags
+    );
+
+    harness.process(client::propose_feature_flags(solbox, owner, FEATURE_BINARY_MODE)).unwrap();
+    assert_error(
+        harness.process(client::apply_feature_flags(solbox)),
+        CustomError::TimelockNotElapsed
+    );
+    let flags: FeatureFlags = harness.fetch(&address);
+    assert!(!flags.is_enabled(FEATURE_BINARY_MODE));
+
+    harness.warp(FEATURE_FLAGS_TIMELOCK);
+    harness.process(client::apply_feature_flags(solbox)).unwrap();
+    let flags: FeatureFlags = harness.fetch(&address);
+    assert!(flags.is_enabled(FEATURE_BINARY_MODE));
+    assert_eq!(flags.pending_eta, 0);
+
+    // Only the owner proposes, and cancelling drops the pending change
+    let stranger = harness.register();
+    assert_error(
+        harness.process(client::propose_feature_flags(solbox, stranger, 0)),
+        CustomError::Unauthorized
+    );
+    harness.process(client::propose_feature_flags(solbox, owner, 0)).unwrap();
+    harness.process(client::cancel_feature_flags(solbox, owner)).unwrap();
+    harness.warp(FEATURE_FLAGS_TIMELOCK);
+    assert_error(
+        harness.process(client::apply_feature_flags(solbox)),
+        CustomError::NoPendingFeatureFlags
+    );
+}
+
+#[test]
+fn stale_clients_fail_the_version_check() {
+    let mut harness = Harness::new();
+    harness.process(client::assert_version()).unwrap();
+
+    let mut stale = client::assert_version();
+    stale.data = anchor_lang::InstructionData::data(&instruction::AssertVersion {
+        expected_version: solbox_contract_devnet::INTERFACE_VERSION - 1,
+    });
+    assert_error(harness.process(stale), CustomError::StaleClientVersion);
+}
+
+#[test]
+fn founder_wallet_rotates_after_the_timelock() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let referrer = harness.register();
+    let buyer = harness.register();
+    let solbox = harness.solbox;
+    let owner = harness.owner;
+    let old_founder = harness.founder;
+    let new_founder = Pubkey::new_unique();
+
+    // The founder account must match state
+    harness.founder = Pubkey::new_unique();
+    assert_error(harness.buy(buyer, referrer), CustomError::InvalidFounderWallet);
+    harness.founder = old_founder;
+
+    harness.process(client::propose_founder_wallet(solbox, owner, new_founder)).unwrap();
+    assert_error(
+        harness.process(client::accept_founder_wallet(solbox, new_founder)),
+        CustomError::TimelockNotElapsed
+    );
+    harness.warp(FOUNDER_CHANGE_TIMELOCK);
+    assert_error(
+        harness.process(client::accept_founder_wallet(solbox, referrer)),
+        CustomError::Unauthorized
+    );
+    harness.process(client::accept_founder_wallet(solbox, new_founder)).unwrap();
+
+    // Payouts follow the rotated wallet immediately
+    assert_error(harness.buy(buyer, referrer), CustomError::InvalidFounderWallet);
+    harness.founder = new_founder;
+    harness.buy(buyer, referrer).unwrap();
+    assert_eq!(harness.lamports(&new_founder), PACKAGE * 85 / 100);
+
+    let state: SolBox = harness.fetch(&solbox);
+    assert_eq!(state.founder_wallet, new_founder);
+    assert_eq!(state.pending_founder_wallet, Pubkey::default());
+}
+
+#[test]
+fn loyalty_burns_boost_referrer_commission() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let referrer = harness.register();
+    let buyer = harness.register();
+    let solbox = harness.solbox;
+    let owner = harness.owner;
+    let mint = harness.create_mint();
+    let tokens = harness.mint_to(mint, referrer, 250);
+    const WEEK: i64 = 7 * 86_400;
+
+    let activate = client::activate_boost(solbox, referrer, mint, tokens, 100, WEEK);
+    assert_error(
+        harness.process(client::configure_boosts(solbox, owner, mint, 100, 5_000, WEEK)),
+        CustomError::InvalidBoostConfig
+    );
+    harness.process(client::configure_boosts(solbox, owner, mint, 100, 200, WEEK)).unwrap();
+
+    // A second activation extends the running boost
+    harness.process(activate.clone()).unwrap();
+    harness.process(activate.clone()).unwrap();
+    assert_eq!(harness.token_balance(&tokens), 50);
+    let boost: Boost = harness.fetch(&client::find_boost_address(&solbox, &referrer).0);
+    assert_eq!(boost.bonus_bps, 200);
+    assert!(harness.process(activate.clone()).is_err());
+
+    // The extra 2% comes out of the founder share
+    let referrer_before = harness.lamports(&referrer);
+    let purchase = client::Purchase { referrer_boost: true, ..harness.purchase_under(referrer) };
+    harness.process(client::buy_gift_card(&purchase, buyer, referrer)).unwrap();
+    assert_eq!(harness.lamports(&referrer) - referrer_before, PACKAGE * 12 / 100);
+    assert_eq!(harness.lamports(&harness.founder), LAMPORTS_PER_SOL + PACKAGE * 83 / 100);
+
+    // Expired boosts pay nothing extra
+    harness.warp(2 * WEEK);
+    let referrer_before = harness.lamports(&referrer);
+    let purchase = client::Purchase { referrer_boost: true, ..harness.purchase_under(referrer) };
+    harness.process(client::buy_gift_card(&purchase, buyer, referrer)).unwrap();
+    assert_eq!(harness.lamports(&referrer) - referrer_before, PACKAGE / 10);
+
+    // Only the referrer's own boost applies
+    let other = harness.register();
+    let purchase = client::Purchase { referrer_boost: true, ..harness.purchase_under(other) };
+    let mut ix = client::buy_gift_card(&purchase, buyer, other);
+    let other_boost = client::find_boost_address(&solbox, &other).0;
+    let meta = ix.accounts.iter_mut().find(|meta| meta.pubkey == other_boost).unwrap();
+    meta.pubkey = client::find_boost_address(&solbox, &referrer).0;
+    assert_error(harness.process(ix), CustomError::InvalidBoost);
+
+    // Repricing or shortening the boost does not apply to signed activations
+    harness.process(client::configure_boosts(solbox, owner, mint, 150, 200, WEEK)).unwrap();
+    assert_error(harness.process(activate.clone()), CustomError::SlippageExceeded);
+    harness.process(client::configure_boosts(solbox, owner, mint, 100, 200, WEEK / 2)).unwrap();
+    assert_error(harness.process(activate.clone()), CustomError::SlippageExceeded);
+
+    harness.process(client::configure_boosts(solbox, owner, mint, 0, 0, 0)).unwrap();
+    assert_error(harness.process(activate), CustomError::BoostsDisabled);
+}
+
+#[test]
+fn contests_rank_entrants_and_escrow_prizes() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let referrer = harness.register();
+    let first = harness.register();
+    let second = harness.register();
+    let idle = harness.register();
+    let solbox = harness.solbox;
+    let owner = harness.owner;
+    let prizes = vec![PACKAGE / 2, PACKAGE / 4, PACKAGE / 8];
+    let contest = client::find_contest_address(&solbox, 7).0;
+
+    let owner_before = harness.lamports(&owner);
+    let create = client::create_contest(solbox, owner, 7, 1_700_000_100, 1_700_086_400, ContestMetric::Volume, prizes);
+    harness.process(create).unwrap();
+    assert!(owner_before - harness.lamports(&owner) > PACKAGE * 7 / 8);
+    for wallet in [first, second, idle] {
+        harness.process(client::enter_contest(solbox, contest, wallet)).unwrap();
+    }
+
+    // Entries close when the contest starts
+    harness.warp(100);
+    let late = harness.register();
+    assert_error(
+        harness.process(client::enter_contest(solbox, contest, late)),
+        CustomError::ContestEntriesClosed
+    );
+
+    harness.buy(first, referrer).unwrap();
+    harness.buy(first, referrer).unwrap();
+    harness.buy(second, referrer).unwrap();
+    let finalize = client::finalize_contest(solbox, contest, owner, &[first, second, idle]);
+    assert_error(harness.process(finalize.clone()), CustomError::ContestNotEnded);
+
+    // Every entry has to be ranked
+    harness.warp(86_400);
+    assert_error(
+        harness.process(client::finalize_contest(solbox, contest, owner, &[first, second])),
+        CustomError::ContestEntriesMismatch
+    );
+    assert_error(
+        harness.process(client::finalize_contest(solbox, contest, owner, &[first, second, second])),
+        CustomError::ContestEntriesMismatch
+    );
+
+    // Entrants without growth win nothing; their prize returns to the creator
+    let owner_before = harness.lamports(&owner);
+    harness.process(finalize.clone()).unwrap();
+    assert_eq!(harness.lamports(&owner) - owner_before, PACKAGE / 8);
+    assert_error(harness.process(finalize), CustomError::ContestFinalized);
+
+    let state: Contest = harness.fetch(&contest);
+    let ranking: Vec<_> = state.winners.iter().map(|winner| (winner.wallet, winner.score, winner.prize)).collect();
+    assert_eq!(ranking, vec![(first, 2 * PACKAGE, PACKAGE / 2), (second, PACKAGE, PACKAGE / 4)]);
+
+    let first_before = harness.lamports(&first);
+    harness.process(client::claim_contest_prize(solbox, contest, first)).unwrap();
+    assert_eq!(harness.lamports(&first) - first_before, PACKAGE / 2);
+    assert_error(harness.process(client::claim_contest_prize(solbox, contest, first)), CustomError::NothingToClaim);
+    assert_error(harness.process(client::claim_contest_prize(solbox, contest, idle)), CustomError::NothingToClaim);
+}
+
+#[test]
+fn support_tiers_follow_the_package_and_the_live_thresholds() {
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.valid_amounts = vec![PACKAGE, 3 * PACKAGE];
+    config.support_tier_thresholds = [PACKAGE, 3 * PACKAGE, 0];
+    harness.initialize(config).unwrap();
+    let solbox = harness.solbox;
+    let owner = harness.owner;
+    let holder = harness.register();
+    let verify = |harness: &mut Harness, min_tier| {
+        let accounts = accounts::VerifyTier { solbox, user_account: harness.user_pda(&holder) };
+        harness.call(&accounts, instruction::VerifyTier { min_tier })?;
+        Ok(SupportTierView::deserialize(&mut harness.return_data().as_slice()).unwrap().support_tier)
+    };
+
+    assert_eq!(verify(&mut harness, 0), Ok(0));
+    assert_error(verify(&mut harness, 1).map(|_| ()), CustomError::SupportTierTooLow);
+
+    harness.process(client::grant_package(solbox, owner, holder, PACKAGE)).unwrap();
+    assert_eq!(verify(&mut harness, 1), Ok(1));
+    assert_error(verify(&mut harness, 2).map(|_| ()), CustomError::SupportTierTooLow);
+    harness.process(client::grant_package(solbox, owner, holder, 3 * PACKAGE)).unwrap();
+    assert_eq!(verify(&mut harness, 2), Ok(2));
+
+    // Raising a threshold applies at once, with nothing stored to migrate
+    harness.modify::<SolBox>(&solbox, |state| state.config.support_tier_thresholds[1] = 5 * PACKAGE);
+    assert_eq!(verify(&mut harness, 1), Ok(1));
+}
+
+#[test]
+fn admin_dashboard_rolls_up_the_instance() {
+    use anchor_lang::prelude::Rent;
+    use solbox_contract_devnet::client::AccountSource;
+
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.refund_window = 86_400;
+    harness.initialize(config).unwrap();
+    let solbox = harness.solbox;
+    let referrer = harness.register();
+    let buyer = harness.register();
+    harness.buy(buyer, referrer).unwrap();
+    harness.airdrop(solbox, LAMPORTS_PER_SOL);
+
+    harness.call(&accounts::ViewAdminDashboard { solbox }, instruction::ViewAdminDashboard {}).unwrap();
+    let dashboard = AdminDashboard::deserialize(&mut harness.return_data().as_slice()).unwrap();
+    let state: SolBox = harness.fetch(&solbox);
+    assert!(!dashboard.paused);
+    assert_eq!((dashboard.user_count, dashboard.purchase_count, dashboard.total_sold), (2, 1, PACKAGE));
+    assert_eq!((dashboard.pending_receipts, dashboard.escrow_balance), (1, state.escrow_balance));
+    assert!(dashboard.escrow_balance > 0);
+
+    // Escrow is owed to buyers, so the treasury is what's above it and rent
+    let rent = Rent::default().minimum_balance(harness.account_data(&solbox).unwrap().len());
+    assert_eq!(dashboard.vault_balance, harness.lamports(&solbox));
+    assert_eq!(dashboard.liabilities, dashboard.escrow_balance);
+    assert_eq!(dashboard.treasury, dashboard.vault_balance - rent - dashboard.liabilities);
+}
+
+#[test]
+fn tiers_decay_without_requalifying_volume() {
+    let mut harness = Harness::new();
+    const WEEK: i64 = 7 * 86_400;
+    let mut config = Harness::default_config();
+    config.support_tier_thresholds = [PACKAGE, 0, 0];
+    config.tier_period = WEEK;
+    config.tier_grace_periods = 1;
+    harness.initialize(config).unwrap();
+    let referrer = harness.register();
+    let holder = harness.register();
+    let solbox = harness.solbox;
+    harness.process(client::grant_package(solbox, harness.owner, holder, PACKAGE)).unwrap();
+    let verify = |harness: &mut Harness| {
+        let accounts = accounts::VerifyTier { solbox, user_account: harness.user_pda(&holder) };
+        harness.call(&accounts, instruction::VerifyTier { min_tier: 1 })
+    };
+
+    // A purchase starts tracking and qualifies its period
+    harness.buy(holder, referrer).unwrap();
+    harness.warp(WEEK);
+    harness.process(client::refresh_tier(solbox, holder)).unwrap();
+    let user: User = harness.fetch(&harness.user_pda(&holder));
+    assert_eq!((user.support_tier, user.missed_periods), (1, 0));
+
+    // The grace period warns before the tier drops
+    harness.warp(WEEK);
+    harness.process(client::refresh_tier(solbox, holder)).unwrap();
+    let warned = harness.logs().iter().any(|line| match Event::from_log(line) {
+        Some(Ok(Event::TierDecayWarningEvent(warning))) => warning.grace_periods_left == 0,
+        _ => false,
+    });
+    assert!(warned);
+    let user: User = harness.fetch(&harness.user_pda(&holder));
+    assert_eq!((user.support_tier, user.missed_periods), (1, 1));
+    verify(&mut harness).unwrap();
+
+    harness.warp(WEEK);
+    harness.process(client::refresh_tier(solbox, holder)).unwrap();
+    let user: User = harness.fetch(&harness.user_pda(&holder));
+    assert_eq!((user.support_tier, user.tier_decay), (0, 1));
+    assert_error(verify(&mut harness), CustomError::SupportTierTooLow);
+
+    // Qualifying volume restores the full tier
+    harness.buy(holder, referrer).unwrap();
+    harness.warp(WEEK);
+    harness.process(client::refresh_tier(solbox, holder)).unwrap();
+    let user: User = harness.fetch(&harness.user_pda(&holder));
+    assert_eq!((user.support_tier, user.tier_decay, user.missed_periods), (1, 0, 0));
+    verify(&mut harness).unwrap();
+}
+
+#[test]
+fn notices_are_read_and_closed_for_rent() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let user = harness.register();
+    let other = harness.register();
+    let solbox = harness.solbox;
+    let owner = harness.owner;
+
+    let post = |id: u64, recipient: Pubkey| {
+        client::post_notice(solbox, owner, id, recipient, NOTICE_CAMPAIGN, "Spring campaign starts".to_string())
+    };
+    harness.process(post(1, user)).unwrap();
+    harness.process(post(2, Pubkey::default())).unwrap();
+    assert_error(
+        harness.process(client::post_notice(solbox, owner, 3, user, NOTICE_CAMPAIGN, String::new())),
+        CustomError::InvalidNotice
+    );
+
+    // Recipients close their notices once read; rent returns to the poster
+    assert_error(harness.process(client::close_notice(solbox, 1, owner, user)), CustomError::Unauthorized);
+    assert_error(harness.process(client::mark_notice_read(solbox, 1, other)), CustomError::Unauthorized);
+    harness.process(client::mark_notice_read(solbox, 1, user)).unwrap();
+    let notice: Notice = harness.fetch(&client::find_notice_address(&solbox, 1).0);
+    assert!(notice.read_at > 0);
+    let owner_before = harness.lamports(&owner);
+    harness.process(client::close_notice(solbox, 1, owner, user)).unwrap();
+    assert!(harness.lamports(&owner) > owner_before);
+    assert_eq!(harness.lamports(&client::find_notice_address(&solbox, 1).0), 0);
+
+    // Global notices have no reader to close them
+    assert_error(harness.process(client::mark_notice_read(solbox, 2, user)), CustomError::Unauthorized);
+    assert_error(harness.process(client::close_notice(solbox, 2, owner, user)), CustomError::Unauthorized);
+    harness.process(client::close_notice(solbox, 2, owner, owner)).unwrap();
+}
+
+#[test]
+fn guardians_move_a_user_to_a_new_wallet() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let referrer = harness.register();
+    let lost = harness.register();
+    let solbox = harness.solbox;
+    harness.buy(lost, referrer).unwrap();
+    let guardians: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+    let new_wallet = Pubkey::new_unique();
+    harness.airdrop(new_wallet, LAMPORTS_PER_SOL);
+
+    assert_error(
+        harness.process(client::set_guardians(solbox, lost, guardians.clone(), 4)),
+        CustomError::InvalidGuardians
+    );
+    harness.process(client::set_guardians(solbox, lost, guardians.clone(), 2)).unwrap();
+
+    // One approval is not enough, and the timelock runs from the second
+    harness.process(client::approve_recovery(solbox, lost, guardians[0], new_wallet)).unwrap();
+    assert_error(
+        harness.process(client::execute_recovery(solbox, lost, new_wallet)),
+        CustomError::NoPendingRecovery
+    );
+    assert_error(
+        harness.process(client::approve_recovery(solbox, lost, referrer, new_wallet)),
+        CustomError::Unauthorized
+    );
+    harness.process(client::approve_recovery(solbox, lost, guardians[2], new_wallet)).unwrap();
+    assert_error(
+        harness.process(client::execute_recovery(solbox, lost, new_wallet)),
+        CustomError::TimelockNotElapsed
+    );
+
+    // The current wallet can still veto
+    harness.process(client::cancel_recovery(solbox, lost)).unwrap();
+    harness.process(client::approve_recovery(solbox, lost, guardians[0], new_wallet)).unwrap();
+    harness.process(client::approve_recovery(solbox, lost, guardians[1], new_wallet)).unwrap();
+    harness.warp(RECOVERY_TIMELOCK);
+    harness.process(client::execute_recovery(solbox, lost, new_wallet)).unwrap();
+
+    let moved: User = harness.fetch(&harness.user_pda(&new_wallet));
+    assert_eq!((moved.key, moved.lifetime_volume), (new_wallet, PACKAGE));
+    assert_eq!(harness.lamports(&harness.user_pda(&lost)), 0);
+    let state: SolBox = harness.fetch(&solbox);
+    assert!(state.referral_relationships.iter().any(|r| r.user == new_wallet && r.referrer == referrer));
+    assert!(!state.referral_relationships.iter().any(|r| r.user == lost));
+
+    // The recovered wallet buys from its existing position
+    harness.buy(new_wallet, referrer).unwrap();
+    let state: SolBox = harness.fetch(&solbox);
+    assert_eq!(state.referral_relationships.iter().filter(|r| r.user == new_wallet).count(), 1);
+}
+
+#[test]
+fn beneficiaries_inherit_inactive_positions() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let referrer = harness.register();
+    let holder = harness.register();
+    let solbox = harness.solbox;
+    harness.buy(holder, referrer).unwrap();
+    let heir = Pubkey::new_unique();
+    harness.airdrop(heir, LAMPORTS_PER_SOL);
+
+    assert_error(
+        harness.process(client::set_beneficiary(solbox, holder, heir, MIN_INACTIVITY_PERIOD - 1)),
+        CustomError::InvalidBeneficiary
+    );
+    harness.process(client::set_beneficiary(solbox, holder, heir, MIN_INACTIVITY_PERIOD)).unwrap();
+    let impostor = Pubkey::new_unique();
+    harness.airdrop(impostor, LAMPORTS_PER_SOL);
+    assert_error(
+        harness.process(client::claim_inheritance(solbox, holder, impostor)),
+        CustomError::Unauthorized
+    );
+
+    // A purchase restarts the inactivity clock
+    harness.warp(MIN_INACTIVITY_PERIOD - 10);
+    harness.buy(holder, referrer).unwrap();
+    harness.warp(20);
+    assert_error(
+        harness.process(client::claim_inheritance(solbox, holder, heir)),
+        CustomError::UserStillActive
+    );
+
+    harness.warp(MIN_INACTIVITY_PERIOD);
+    harness.process(client::claim_inheritance(solbox, holder, heir)).unwrap();
+    let inherited: User = harness.fetch(&harness.user_pda(&heir));
+    assert_eq!((inherited.key, inherited.lifetime_volume), (heir, 2 * PACKAGE));
+    assert_eq!(inherited.beneficiary, Pubkey::default());
+    assert_eq!(harness.lamports(&harness.user_pda(&holder)), 0);
+    let state: SolBox = harness.fetch(&solbox);
+    assert!(state.referral_relationships.iter().any(|r| r.user == heir && r.referrer == referrer));
+}
+
+#[test]
+fn statements_verify_against_the_anchored_root() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let referrer = harness.register();
+    let buyer = harness.register();
+    let solbox = harness.solbox;
+    harness.buy(buyer, referrer).unwrap();
+
+    // Statements built off-chain from the users' accounts at epoch close
+    let statements: Vec<Statement> = [referrer, buyer]
+        .iter()
+        .map(|wallet| {
+            let user: User = harness.fetch(&harness.user_pda(wallet));
+            Statement {
+                wallet: *wallet,
+                epoch: 0,
+                total_earnings: user.total_earnings,
+                unclaimed_yield: user.unclaimed_yield,
+                lifetime_volume: user.lifetime_volume,
+            }
+        })
+        .collect();
+    let leaves: Vec<[u8; 32]> = statements.iter().map(Statement::leaf).collect();
+    let root = statements::merkle_root(&leaves);
+    assert!(statements[0].total_earnings > 0);
+
+    assert_error(
+        harness.process(client::anchor_statements(solbox, harness.owner, 0, root, 2)),
+        CustomError::EpochNotClosed
+    );
+    harness.next_epoch();
+    assert_error(
+        harness.process(client::anchor_statements(solbox, referrer, 0, root, 2)),
+        CustomError::Unauthorized
+    );
+    harness.process(client::anchor_statements(solbox, harness.owner, 0, root, 2)).unwrap();
+    // Anchored roots cannot be replaced
+
+    let proof = statements::merkle_proof(&leaves, 0);
+    harness.process(client::verify_statement(solbox, statements[0].clone(), proof.clone())).unwrap();
+    let mut inflated = statements[0].clone();
+    inflated.total_earnings += 1;
+    assert_error(
+        harness.process(client::verify_statement(solbox, inflated, proof)),
+        CustomError::StatementMismatch
+    );
+}
+
+#[test]
+fn unpausing_needs_a_guardian_quorum() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let member = harness.register();
+    let solbox = harness.solbox;
+    let owner = harness.owner;
+    let guardians: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+    for guardian in &guardians {
+        harness.airdrop(*guardian, LAMPORTS_PER_SOL);
+    }
+
+    assert_error(
+        harness.process(client::set_pause_guardians(solbox, owner, guardians.clone(), 0)),
+        CustomError::InvalidGuardians
+    );
+    harness.process(client::set_pause_guardians(solbox, owner, guardians.clone(), 2)).unwrap();
+    harness.process(client::toggle_pause(solbox, owner, member)).unwrap();
+
+    // The admin alone can neither reopen nor replace the guardians
+    assert_error(
+        harness.process(client::toggle_pause(solbox, owner, member)),
+        CustomError::UnpauseQuorumRequired
+    );
+    assert_error(
+        harness.process(client::set_pause_guardians(solbox, owner, vec![owner], 1)),
+        CustomError::ContractPaused
+    );
+    assert_error(
+        harness.process(client::approve_unpause(solbox, owner)),
+        CustomError::Unauthorized
+    );
+
+    harness.process(client::approve_unpause(solbox, guardians[0])).unwrap();
+    assert_error(
+        harness.process(client::execute_unpause(solbox, owner, &guardians[..1])),
+        CustomError::UnpauseQuorumRequired
+    );
+    harness.process(client::approve_unpause(solbox, guardians[2])).unwrap();
+    harness.process(client::execute_unpause(solbox, owner, &[guardians[0], guardians[2]])).unwrap();
+    assert!(!harness.fetch::<SolBox>(&solbox).paused);
+
+    // Approvals from the last incident do not carry over to the next
+    harness.process(client::toggle_pause(solbox, owner, member)).unwrap();
+    harness.process(client::approve_unpause(solbox, guardians[1])).unwrap();
+    assert_error(
+        harness.process(client::execute_unpause(solbox, owner, &guardians)),
+        CustomError::UnpauseQuorumRequired
+    );
+}
+
+#[test]
+fn residual_commission_pays_after_the_epoch_closes() {
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.residual_bps = 10_001;
+    assert_error(harness.initialize(config.clone()), CustomError::InvalidResidualConfig);
+    config.residual_bps = 3_000;
+    harness.initialize(config).unwrap();
+    let referrer = harness.register();
+    let buyer = harness.register();
+    let solbox = harness.solbox;
+
+    // 70% of the 10% commission is paid at once, the rest is held
+    let referrer_before = harness.lamports(&referrer);
+    harness.buy(buyer, referrer).unwrap();
+    assert_eq!(harness.lamports(&referrer) - referrer_before, PACKAGE * 7 / 100);
+    let state: SolBox = harness.fetch(&solbox);
+    assert_eq!(state.residual_balance, PACKAGE * 3 / 100);
+    assert_error(
+        harness.process(client::claim_residual(solbox, referrer)),
+        CustomError::NothingToClaim
+    );
+
+    harness.next_epoch();
+    let referrer_before = harness.lamports(&referrer);
+    harness.process(client::claim_residual(solbox, referrer)).unwrap();
+    assert_eq!(harness.lamports(&referrer) - referrer_before, PACKAGE * 3 / 100);
+    let user: User = harness.fetch(&harness.user_pda(&referrer));
+    assert_eq!((user.residual_pending, user.residual_claimable), (0, 0));
+    assert_eq!(harness.fetch::<SolBox>(&solbox).residual_balance, 0);
+}
+
+#[test]
+fn upgrades_are_paid_from_the_wallet() {
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.valid_amounts = vec![PACKAGE, 2 * PACKAGE];
+    harness.initialize(config).unwrap();
+    let solbox = harness.solbox;
+    let member = harness.register();
+    let member_pda = harness.user_pda(&member);
+
+    // The wallet pays the difference into the vault; the User PDA keeps its rent
+    let before = [member, member_pda, solbox].map(|key| harness.lamports(&key));
+    harness.process(client::upgrade_package(solbox, member, PACKAGE, PACKAGE)).unwrap();
+    let after = [member, member_pda, solbox].map(|key| harness.lamports(&key));
+    assert_eq!((before[0] - after[0], after[1], after[2] - before[2]), (PACKAGE, before[1], PACKAGE));
+    assert_eq!(harness.fetch::<User>(&member_pda).current_package, PACKAGE);
+    assert_error(
+        harness.process(client::upgrade_package(solbox, member, PACKAGE, PACKAGE)),
+        CustomError::InvalidUpgrade
+    );
+
+    // Another wallet can't upgrade someone else's position
+    let other = harness.register();
+    let mut ix = client::upgrade_package(solbox, other, 2 * PACKAGE, 2 * PACKAGE);
+    let other_pda = harness.user_pda(&other);
+    ix.accounts.iter_mut().find(|meta| meta.pubkey == other_pda).unwrap().pubkey = member_pda;
+    assert!(harness.process(ix).is_err());
+    assert_eq!(harness.fetch::<User>(&member_pda).current_package, PACKAGE);
+
+    // Paying only the step up from the current package, within the bound
+    assert_error(
+        harness.process(client::upgrade_package(solbox, member, 2 * PACKAGE, PACKAGE - 1)),
+        CustomError::SlippageExceeded
+    );
+    harness.process(client::upgrade_package(solbox, member, 2 * PACKAGE, PACKAGE)).unwrap();
+    assert_eq!(after[0] - harness.lamports(&member), PACKAGE);
+    assert_eq!(harness.fetch::<User>(&member_pda).current_package, 2 * PACKAGE);
+}
+
+#[test]
+fn only_sandbox_instances_warp_time_and_grant_freely() {
+    {
+        let mut production = Harness::new();
+        production.initialize(Harness::default_config()).unwrap();
+        let member = production.register();
+        let (solbox, owner) = (production.solbox, production.owner);
+        assert_error(
+            production.process(client::warp_time_offset(solbox, owner, 60, 1)),
+            CustomError::NotSandbox
+        );
+        assert_error(
+            production.process(client::grant_package(solbox, owner, member, PACKAGE + 1)),
+            CustomError::InvalidAmount
+        );
+    }
+
+    let mut config = Harness::default_config();
+    config.residual_bps = 3_000;
+    let mut harness = Harness::new();
+    harness.initialize_sandbox(config).unwrap();
+    let referrer = harness.register();
+    let buyer = harness.register();
+    let (solbox, owner) = (harness.solbox, harness.owner);
+    harness.process(client::grant_package(solbox, owner, referrer, PACKAGE + 1)).unwrap();
+    assert_eq!(harness.fetch::<User>(&harness.user_pda(&referrer)).current_package, PACKAGE + 1);
+
+    // Skipping an epoch on the program clock matures the residual
+    harness.buy(buyer, referrer).unwrap();
+    assert_error(
+        harness.process(client::warp_time_offset(solbox, buyer, 0, 1)),
+        CustomError::Unauthorized
+    );
+    assert_error(
+        harness.process(client::warp_time_offset(solbox, owner, -1, 0)),
+        CustomError::InvalidTimeWarp
+    );
+    harness.process(client::warp_time_offset(solbox, owner, 3_600, 1)).unwrap();
+    let state: SolBox = harness.fetch(&solbox);
+    assert_eq!((state.time_offset, state.epoch_offset), (3_600, 1));
+    harness.process(client::claim_residual(solbox, referrer)).unwrap();
+    assert_eq!(harness.fetch::<SolBox>(&solbox).residual_balance, 0);
+
+    // Daily stats are bucketed by the program clock's day as well
+    let yesterday = harness.purchase().day;
+    harness.process(client::warp_time_offset(solbox, owner, 86_400, 0)).unwrap();
+    let purchase = harness.purchase_under(referrer);
+    harness.process(client::validate_purchase_accounts(&purchase, buyer, referrer)).unwrap();
+    assert_eq!(u32::from_le_bytes(harness.return_data().try_into().unwrap()), 0);
+    harness.process(client::buy_gift_card(&purchase, buyer, referrer)).unwrap();
+    let today = yesterday + 1;
+    assert_eq!(purchase.day, today);
+    let stats: DailyStats = harness.fetch(&client::find_daily_stats_address(&solbox, today).0);
+    assert_eq!((stats.day, stats.purchase_count), (today, 1));
+}
+
+#[test]
+fn lookup_table_holds_hot_accounts_for_v0_purchases() {
+    use anchor_lang::solana_program::hash::Hash;
+    use anchor_lang::solana_program::message::v0;
+
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let root = harness.register();
+    let member = harness.register();
+    harness.buy(member, root).unwrap();
+    let (solbox, owner) = (harness.solbox, harness.owner);
+
+    let (create, table) = client::create_lookup_table(solbox, owner, 1);
+    harness.process(create).unwrap();
+    assert_eq!(harness.fetch::<SolBox>(&solbox).lookup_table, table);
+    assert_error(
+        harness.process(client::create_lookup_table(solbox, owner, 2).0),
+        CustomError::LookupTableExists
+    );
+
+    let hot = client::hot_accounts(&solbox, &harness.fetch(&solbox), 2);
+    assert!(hot.contains(&harness.user_pda(&root)) && hot.contains(&member));
+    assert_error(
+        harness.process(client::extend_lookup_table(solbox, member, table, hot.clone())),
+        CustomError::Unauthorized
+    );
+    harness.process(client::extend_lookup_table(solbox, owner, table, hot.clone())).unwrap();
+    let lookup_table = client::fetch_lookup_table(&harness, &table).unwrap();
+    assert_eq!(lookup_table.addresses, hot);
+
+    // The next purchase under the member resolves its uplines through the table
+    let buyer = harness.register();
+    let purchase = harness.purchase_under(member);
+    let ix = client::buy_gift_card(&purchase, buyer, member);
+    let message = v0::Message::try_compile(&buyer, &[ix], &[lookup_table], Hash::default()).unwrap();
+    assert_eq!(message.address_table_lookups.len(), 1);
+    let lookups = &message.address_table_lookups[0];
+    assert!(lookups.writable_indexes.len() + lookups.readonly_indexes.len() >= 4);
+}
+
+#[test]
+fn packages_carry_a_category_and_metadata_uri() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let member = harness.register();
+    let (solbox, owner) = (harness.solbox, harness.owner);
+    let uri = "https://cards.solbox.example/basic.json".to_string();
+
+    assert_error(
+        harness.process(client::create_package(solbox, member, PACKAGE, PACKAGE_GENERAL, uri.clone())),
+        CustomError::Unauthorized
+    );
+    assert_error(
+        harness.process(client::create_package(solbox, owner, PACKAGE + 1, PACKAGE_GENERAL, uri.clone())),
+        CustomError::InvalidAmount
+    );
+    assert_error(
+        harness.process(client::create_package(solbox, owner, PACKAGE, PACKAGE_GENERAL, "x".repeat(65))),
+        CustomError::InvalidMetadataUri
+    );
+    harness.process(client::create_package(solbox, owner, PACKAGE, PACKAGE_GENERAL, uri.clone())).unwrap();
+    let address = client::find_package_address(&solbox, PACKAGE).0;
+    let package: Package = harness.fetch(&address);
+    assert_eq!((package.amount, package.category, package.metadata_uri), (PACKAGE, PACKAGE_GENERAL, uri));
+
+    let seasonal = "ar://winter-card".to_string();
+    harness.process(client::update_package(solbox, owner, PACKAGE, PACKAGE_SEASONAL, seasonal.clone())).unwrap();
+    let package: Package = harness.fetch(&address);
+    assert_eq!((package.category, package.metadata_uri), (PACKAGE_SEASONAL, seasonal));
+    let announced = harness.logs().iter().any(|line| match Event::from_log(line) {
+        Some(Ok(Event::PackageMetadataEvent(event))) => event.category == PACKAGE_SEASONAL,
+        _ => false,
+    });
+    assert!(announced);
+}
+
+#[test]
+fn on_ramp_partners_attest_fiat_purchases() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let referrer = harness.register();
+    let buyer = harness.register();
+    let (solbox, owner) = (harness.solbox, harness.owner);
+
+    let name_hash = [9; 32];
+    let integrator = client::find_integrator_address(&solbox, &name_hash).0;
+    let partner = Pubkey::new_unique();
+    harness.call(
+        &accounts::RegisterIntegrator {
+            solbox,
+            integrator,
+            admin: owner,
+            system_program: anchor_lang::system_program::ID,
+        },
+        instruction::RegisterIntegrator {
+            name_hash,
+            wallet: partner,
+            fee_share_bps: 0,
+            allowed_instructions: INTEGRATOR_ALLOW_FIAT_ATTESTATION,
+            cpi_authority: Pubkey::default(),
+        }
+    ).unwrap();
+    harness.airdrop(partner, LAMPORTS_PER_SOL);
+    harness.buy(buyer, referrer).unwrap();
+    let external_ref = [3; 32];
+
+    // Only the partner's wallet attests, and only what the receipt shows
+    assert_error(
+        harness.process(client::attest_fiat_purchase(solbox, integrator, buyer, 0, buyer, PACKAGE, external_ref)),
+        CustomError::InvalidIntegrator
+    );
+    assert_error(
+        harness.process(client::attest_fiat_purchase(solbox, integrator, partner, 0, buyer, PACKAGE / 2, external_ref)),
+        CustomError::AttestationMismatch
+    );
+    harness.process(client::attest_fiat_purchase(solbox, integrator, partner, 0, buyer, PACKAGE, external_ref)).unwrap();
+    let attestation: FiatAttestation = harness.fetch(&client::find_fiat_attestation_address(&solbox, 0).0);
+    assert_eq!(attestation.receipt, harness.receipt_pda(0));
+    assert_eq!((attestation.buyer, attestation.external_ref, attestation.attested_by), (buyer, external_ref, partner));
+}
+
+#[test]
+fn handles_are_unique_and_moderated() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let alice = harness.register();
+    let mallory = harness.register();
+    let (solbox, owner) = (harness.solbox, harness.owner);
+
+    assert_error(harness.process(client::register_handle(solbox, alice, "al ice")), CustomError::InvalidHandle);
+    harness.process(client::register_handle(solbox, alice, "Alice")).unwrap();
+    assert_eq!(client::display_name(&harness, &solbox, &alice), "Alice");
+    assert!(client::display_name(&harness, &solbox, &mallory).contains('…'));
+    assert_error(harness.process(client::register_handle(solbox, alice, "Alicia")), CustomError::HandleAlreadySet);
+
+    // Case variants collide on the same PDA; other scripts are fine
+    assert_eq!(client::find_handle_address(&solbox, "ALICE"), client::find_handle_address(&solbox, "Alice"));
+    harness.process(client::register_handle(solbox, mallory, "アリス")).unwrap();
+    assert_eq!(client::display_name(&harness, &solbox, &mallory), "アリス");
+
+    // Only the admin revokes; the name is free again afterwards
+    assert_error(
+        harness.process(client::revoke_handle(solbox, mallory, alice, "Alice")),
+        CustomError::Unauthorized
+    );
+    let wallet_before = harness.lamports(&alice);
+    harness.process(client::revoke_handle(solbox, owner, alice, "Alice")).unwrap();
+    assert!(harness.lamports(&alice) > wallet_before);
+    assert_eq!(harness.fetch::<User>(&harness.user_pda(&alice)).handle, Pubkey::default());
+    harness.process(client::release_handle(solbox, mallory, "アリス")).unwrap();
+    harness.process(client::register_handle(solbox, mallory, "alice")).unwrap();
+}
+
+#[test]
+fn legacy_users_claim_migrated_positions() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let (solbox, owner) = (harness.solbox, harness.owner);
+    let leader = harness.register();
+    let alice = harness.register();
+    let bob = harness.register();
+    let positions = [
+        MigratedPosition { wallet: leader, package: PACKAGE, sponsor: Pubkey::default(), lifetime_volume: 9 * PACKAGE },
+        MigratedPosition { wallet: alice, package: PACKAGE, sponsor: leader, lifetime_volume: 3 * PACKAGE },
+        MigratedPosition { wallet: bob, package: 0, sponsor: alice, lifetime_volume: 0 },
+    ];
+    let leaves: Vec<[u8; 32]> = positions.iter().map(MigratedPosition::leaf).collect();
+    harness.process(client::commit_migration_root(solbox, owner, [1; 32], 3)).unwrap();
+    harness.process(client::commit_migration_root(solbox, owner, statements::merkle_root(&leaves), 3)).unwrap();
+
+    let claim = |harness: &mut Harness, index: usize| {
+        let state: SolBox = harness.fetch(&solbox);
+        let position = &positions[index];
+        let uplines = match position.sponsor == Pubkey::default() {
+            true => Vec::new(),
+            false => client::purchase_uplines(&state, position.sponsor).unwrap(),
+        };
+        let proof = statements::merkle_proof(&leaves, index);
+        harness.process(client::claim_migrated_position(solbox, position, proof, &uplines))
+    };
+
+    // A leaf only proves with its own figures
+    let mut inflated = positions[1].clone();
+    inflated.lifetime_volume *= 10;
+    assert_error(
+        harness.process(client::claim_migrated_position(solbox, &inflated, statements::merkle_proof(&leaves, 1), &[])),
+        CustomError::InvalidMigrationProof
+    );
+
+    for index in 0..positions.len() {
+        claim(&mut harness, index).unwrap();
+    }
+    assert_error(claim(&mut harness, 1), CustomError::UserAlreadyImported);
+
+    let migrated: User = harness.fetch(&harness.user_pda(&alice));
+    assert_eq!((migrated.current_package, migrated.lifetime_volume), (PACKAGE, 3 * PACKAGE));
+    assert_eq!(migrated.imported_from, client::find_migration_address(&solbox).0);
+    assert_eq!(migrated.downline_counts[0], 1);
+    let state: SolBox = harness.fetch(&solbox);
+    assert!(state.referral_relationships.iter().any(|r| r.user == alice && r.referrer == leader));
+    assert!(state.referral_relationships.iter().any(|r| r.user == bob && r.referrer == alice));
+
+    // The root is locked once anyone has claimed against it
+    assert_error(
+        harness.process(client::commit_migration_root(solbox, owner, [2; 32], 3)),
+        CustomError::MigrationStarted
+    );
+}
+
+#[test]
+fn sponsors_pay_for_newcomers_who_accept_later() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let sponsor = harness.register();
+    let other = harness.register();
+    let newcomer = Pubkey::new_unique();
+    let solbox = harness.solbox;
+
+    harness.process(client::sponsor_registration(solbox, sponsor, newcomer)).unwrap();
+    let registered: User = harness.fetch(&harness.user_pda(&newcomer));
+    assert_eq!((registered.sponsored_by, registered.pending_acceptance), (sponsor, true));
+    assert_eq!(harness.lamports(&newcomer), 0);
+
+    // Only the sponsor pays, and only for a first package under itself
+    let paid_by = |referrer: Pubkey, harness: &Harness| client::Purchase {
+        sponsor_paid: true,
+        ..harness.purchase_under(referrer)
+    };
+    assert_error(
+        harness.process(client::buy_gift_card(&paid_by(other, &harness), newcomer, other)),
+        CustomError::Unauthorized
+    );
+    let sponsor_before = harness.lamports(&sponsor);
+    harness.process(client::buy_gift_card(&paid_by(sponsor, &harness), newcomer, sponsor)).unwrap();
+    assert_eq!(harness.fetch::<User>(&harness.user_pda(&newcomer)).lifetime_volume, PACKAGE);
+    assert!(sponsor_before - harness.lamports(&sponsor) > PACKAGE / 2);
+    assert_error(
+        harness.process(client::buy_gift_card(&paid_by(sponsor, &harness), newcomer, sponsor)),
+        CustomError::Unauthorized
+    );
+
+    // Referring others waits for the newcomer's acceptance
+    let friend = harness.register();
+    assert_error(harness.buy(friend, newcomer), CustomError::SponsorshipPending);
+    harness.process(client::accept_sponsorship(solbox, newcomer)).unwrap();
+    assert_error(
+        harness.process(client::accept_sponsorship(solbox, newcomer)),
+        CustomError::NoPendingSponsorship
+    );
+    harness.buy(friend, newcomer).unwrap();
+}
+
+#[test]
+fn lapsed_members_reactivate_at_a_discount_within_the_window() {
+    const WEEK: i64 = 7 * 86_400;
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.membership_period = 4 * WEEK;
+    config.reactivation_window = WEEK;
+    config.reactivation_bps = 2_500;
+    harness.initialize(config).unwrap();
+    let (solbox, owner) = (harness.solbox, harness.owner);
+    let member = harness.register();
+    harness.process(client::grant_package(solbox, owner, member, PACKAGE)).unwrap();
+    let paid_until = |harness: &Harness| harness.fetch::<User>(&harness.user_pda(&member)).paid_until;
+    let lapses_at = paid_until(&harness);
+
+    // Renewing ahead of the lapse costs the full package, which the
+    // signed bounds have to allow
+    for (max_lamports_in, min_paid_until) in [(PACKAGE - 1, 0), (PACKAGE, lapses_at + 4 * WEEK + 1)] {
+        assert_error(
+            harness.process(client::renew_membership(solbox, member, max_lamports_in, min_paid_until)),
+            CustomError::SlippageExceeded
+        );
+    }
+    let before = harness.lamports(&member);
+    harness.process(client::renew_membership(solbox, member, PACKAGE, lapses_at + 4 * WEEK)).unwrap();
+    assert_eq!(before - harness.lamports(&member), PACKAGE);
+    assert_eq!(paid_until(&harness), lapses_at + 4 * WEEK);
+
+    // Just after the lapse the package is kept and reactivates at a quarter
+    harness.warp(8 * WEEK + 1);
+    assert_error(
+        harness.process(client::lapse_membership(solbox, member)),
+        CustomError::MembershipActive
+    );
+    let before = harness.lamports(&member);
+    harness.process(client::renew_membership(solbox, member, PACKAGE / 4, 0)).unwrap();
+    assert_eq!(before - harness.lamports(&member), PACKAGE / 4);
+    assert_eq!(harness.fetch::<User>(&harness.user_pda(&member)).current_package, PACKAGE);
+
+    // Past the window the package is dropped and can't be renewed
+    harness.warp(5 * WEEK);
+    assert_error(
+        harness.process(client::renew_membership(solbox, member, PACKAGE, 0)),
+        CustomError::MembershipNotRenewable
+    );
+    harness.process(client::lapse_membership(solbox, member)).unwrap();
+    let lapsed: User = harness.fetch(&harness.user_pda(&member));
+    assert_eq!((lapsed.current_package, lapsed.paid_until), (0, 0));
+    assert_eq!(harness.fetch::<SolBox>(&solbox).active_users, 0);
+}
+
+#[test]
+fn house_positions_absorb_spillover_and_fund_the_bonus_pool() {
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.yield_sharing = true;
+    harness.initialize(config).unwrap();
+    let (solbox, owner) = (harness.solbox, harness.owner);
+    let root = harness.register();
+    let first = harness.register();
+    harness.buy(first, root).unwrap();
+
+    // The house takes the second leg under the root, without counting as a user
+    let state: SolBox = harness.fetch(&solbox);
+    let uplines = client::purchase_uplines(&state, root).unwrap();
+    harness.process(client::create_house_position(solbox, owner, 0, root, &uplines)).unwrap();
+    let house = client::find_house_address(&solbox, 0).0;
+    let state: SolBox = harness.fetch(&solbox);
+    assert_eq!(state.house_positions, vec![house]);
+    assert_eq!((state.user_count, state.active_users), (2, 0));
+    assert!(state.referral_relationships.iter().any(|r| r.user == house && r.referrer == root));
+
+    // Signups under the root now spill below the house
+    let second = harness.register();
+    harness.buy(second, root).unwrap();
+    let state: SolBox = harness.fetch(&solbox);
+    assert!(state.referral_relationships.iter().any(|r| r.user == second && r.referrer == first));
+
+    // Commission earned by the house lands in the bonus pool
+    let buyer = harness.register();
+    harness.buy(buyer, house).unwrap();
+    let commission = PACKAGE / 10;
+    let state: SolBox = harness.fetch(&solbox);
+    assert_eq!((state.house_commission, state.undistributed_yield), (commission, commission));
+    assert_eq!(harness.lamports(&house), 0);
+    assert_eq!(harness.fetch::<User>(&harness.user_pda(&house)).total_earnings, 0);
+}
+
+#[test]
+fn small_claims_wait_and_off_day_claims_queue_for_the_payout_day() {
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.residual_bps = 3_000;
+    config.min_claim = PACKAGE / 20;
+    config.payout_weekday = 8;
+    assert_error(harness.initialize(config.clone()), CustomError::InvalidPayoutDay);
+    config.payout_weekday = 5; // the harness clock starts on a Tuesday
+    harness.initialize(config).unwrap();
+    let referrer = harness.register();
+    let solbox = harness.solbox;
+
+    // One purchase leaves a residual below the minimum payout
+    let buyer = harness.register();
+    harness.buy(buyer, referrer).unwrap();
+    harness.next_epoch();
+    assert_error(
+        harness.process(client::claim_residual(solbox, referrer)),
+        CustomError::ClaimBelowMinimum
+    );
+
+    // With two, the claim goes through but is queued until Friday
+    let buyer = harness.register();
+    harness.buy(buyer, referrer).unwrap();
+    harness.next_epoch();
+    let before = harness.lamports(&referrer);
+    harness.process(client::claim_residual(solbox, referrer)).unwrap();
+    assert_eq!(harness.lamports(&referrer), before);
+    let queued = PACKAGE * 6 / 100;
+    assert_eq!(harness.fetch::<User>(&harness.user_pda(&referrer)).queued_payout, queued);
+    assert_eq!(harness.fetch::<SolBox>(&solbox).queued_payouts, queued);
+    assert_error(
+        harness.process(client::pay_queued_payout(solbox, referrer)),
+        CustomError::OutsidePayoutWindow
+    );
+
+    harness.warp(3 * 86_400);
+    harness.process(client::pay_queued_payout(solbox, referrer)).unwrap();
+    assert_eq!(harness.lamports(&referrer) - before, queued);
+    assert_eq!(harness.fetch::<SolBox>(&solbox).queued_payouts, 0);
+    assert_error(
+        harness.process(client::pay_queued_payout(solbox, referrer)),
+        CustomError::NothingToClaim
+    );
+}
+
+#[test]
+fn emergency_withdrawals_are_announced_and_timelocked() {
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.refund_window = 3_600;
+    harness.initialize(config).unwrap();
+    let referrer = harness.register();
+    let buyer = harness.register();
+    let (solbox, owner) = (harness.solbox, harness.owner);
+    let recipient = Pubkey::new_unique();
+    harness.buy(buyer, referrer).unwrap();
+    let other_buyer = harness.register();
+    harness.buy(other_buyer, referrer).unwrap();
+    let escrowed = PACKAGE * 95 / 100;
+
+    assert_error(
+        harness.process(client::initiate_emergency_withdraw(solbox, referrer, recipient)),
+        CustomError::Unauthorized
+    );
+    harness.process(client::initiate_emergency_withdraw(solbox, owner, recipient)).unwrap();
+    let announced = harness
+        .logs()
+        .iter()
+        .filter_map(|line| Event::from_log(line))
+        .find_map(|event| match event {
+            Ok(Event::EmergencyWithdrawInitiatedEvent(announced)) => Some(announced),
+            _ => None,
+        })
+        .expect("announcement logged");
+    assert_eq!(announced.liabilities, 2 * escrowed);
+
+    // Money stops coming in while users get theirs out
+    let late_buyer = harness.register();
+    assert_error(harness.buy(late_buyer, referrer), CustomError::EmergencyWithdrawPending);
+    harness.cancel(0).unwrap();
+    assert_error(
+        harness.process(client::execute_emergency_withdraw(solbox, owner, recipient)),
+        CustomError::TimelockNotElapsed
+    );
+
+    harness.warp(EMERGENCY_WITHDRAW_TIMELOCK);
+    assert_error(
+        harness.process(client::execute_emergency_withdraw(solbox, owner, referrer)),
+        CustomError::InvalidEmergencyRecipient
+    );
+    let vault_before = harness.lamports(&solbox);
+    harness.process(client::execute_emergency_withdraw(solbox, owner, recipient)).unwrap();
+    assert_eq!(harness.lamports(&recipient), escrowed);
+    assert_eq!(vault_before - harness.lamports(&solbox), escrowed);
+    let state: SolBox = harness.fetch(&solbox);
+    assert!(state.paused);
+    assert_eq!(state.emergency_eta, 0);
+    assert_error(
+        harness.process(client::cancel_emergency_withdraw(solbox, owner)),
+        CustomError::NoPendingEmergencyWithdraw
+    );
+}
+
+#[test]
+fn founder_shares_the_founder_wallet_cannot_take_wait_in_the_vault() {
+    let mut harness = Harness::new();
+    let small = LAMPORTS_PER_SOL / 1_000;
+    let mut config = Harness::default_config();
+    config.valid_amounts = vec![PACKAGE, small];
+    harness.initialize(config).unwrap();
+    let solbox = harness.solbox;
+
+    // An unfunded founder wallet can't take a share below the rent minimum
+    let founder = Pubkey::new_unique();
+    harness.modify::<SolBox>(&solbox, |state| state.founder_wallet = founder);
+    harness.founder = founder;
+    let referrer = harness.register();
+    let buyer = harness.register();
+    let purchase = client::Purchase { amount: small, ..harness.purchase_under(referrer) };
+    harness.process(client::buy_gift_card(&purchase, buyer, referrer)).unwrap();
+    let deferred = small * 85 / 100;
+    assert_eq!(harness.lamports(&founder), 0);
+    assert_eq!(harness.fetch::<SolBox>(&solbox).deferred_founder_share, deferred);
+    assert_error(
+        harness.process(client::flush_founder_share(solbox, founder)),
+        CustomError::FounderCannotReceive
+    );
+
+    // A full package funds the wallet, after which the held share flushes
+    harness.buy(buyer, referrer).unwrap();
+    assert_eq!(harness.lamports(&founder), PACKAGE * 85 / 100);
+    harness.process(client::flush_founder_share(solbox, founder)).unwrap();
+    assert_eq!(harness.lamports(&founder), PACKAGE * 85 / 100 + deferred);
+    assert_eq!(harness.fetch::<SolBox>(&solbox).deferred_founder_share, 0);
+    assert_error(
+        harness.process(client::flush_founder_share(solbox, founder)),
+        CustomError::NothingToClaim
+    );
+}
+
+#[test]
+fn cards_are_issued_as_token_2022_units_and_redeemed_for_their_package() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let (solbox, owner) = (harness.solbox, harness.owner);
+    let uri = "https://cards.example/general.json".to_string();
+    harness.process(client::create_package(solbox, owner, PACKAGE, PACKAGE_GENERAL, uri)).unwrap();
+    harness.process(client::create_card_mint(solbox, owner, PACKAGE)).unwrap();
+    let mint = client::find_card_mint_address(&solbox, PACKAGE).0;
+
+    let referrer = harness.register();
+    let buyer = harness.register();
+    harness.buy(buyer, referrer).unwrap();
+
+    // The buyer sends the card to a friend's wallet
+    let friend = harness.register();
+    let card = harness.token_2022_account(mint, friend);
+    assert_error(
+        harness.process(client::issue_card(solbox, friend, 0, PACKAGE, card)),
+        CustomError::Unauthorized
+    );
+    harness.process(client::issue_card(solbox, buyer, 0, PACKAGE, card)).unwrap();
+    assert_eq!(harness.token_balance(&card), 1);
+    assert_error(
+        harness.process(client::issue_card(solbox, buyer, 0, PACKAGE, card)),
+        CustomError::CardIssued
+    );
+    assert!(harness.fetch::<Receipt>(&harness.receipt_pda(0)).card_issued);
+
+    // Redeeming burns the unit and takes up the package
+    harness.process(client::redeem_card(solbox, friend, PACKAGE, card)).unwrap();
+    assert_eq!(harness.token_balance(&card), 0);
+    assert_eq!(harness.fetch::<User>(&harness.user_pda(&friend)).current_package, PACKAGE);
+    assert!(harness.process(client::redeem_card(solbox, friend, PACKAGE, card)).is_err());
+}
+
+#[test]
+fn admins_claw_back_stolen_cards_without_the_holder_signing() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let (solbox, owner) = (harness.solbox, harness.owner);
+    let uri = "https://cards.example/general.json".to_string();
+    harness.process(client::create_package(solbox, owner, PACKAGE, PACKAGE_GENERAL, uri)).unwrap();
+    harness.process(client::create_card_mint(solbox, owner, PACKAGE)).unwrap();
+    let mint = client::find_card_mint_address(&solbox, PACKAGE).0;
+
+    let referrer = harness.register();
+    let buyer = harness.register();
+    harness.buy(buyer, referrer).unwrap();
+    let card = harness.token_2022_account(mint, buyer);
+    harness.process(client::issue_card(solbox, buyer, 0, PACKAGE, card)).unwrap();
+
+    // A phisher moved the card out; the appeal sends it back
+    let thief = harness.register();
+    let stolen = harness.token_2022_account(mint, thief);
+    harness.transfer_tokens(&card, &stolen, 1);
+    let reason = "appeal 17: phished".to_string();
+    assert_error(
+        harness.process(client::claw_back_card(solbox, thief, PACKAGE, stolen, card, reason.clone())),
+        CustomError::Unauthorized
+    );
+    assert_error(
+        harness.process(client::claw_back_card(solbox, owner, PACKAGE, stolen, card, "x".repeat(65))),
+        CustomError::ClawbackReasonTooLong
+    );
+    harness.process(client::claw_back_card(solbox, owner, PACKAGE, stolen, card, reason)).unwrap();
+    assert_eq!(harness.token_balance(&stolen), 0);
+    assert_eq!(harness.token_balance(&card), 1);
+    assert_error(
+        harness.process(client::claw_back_card(solbox, owner, PACKAGE, stolen, card, String::new())),
+        CustomError::InvalidAmount
+    );
+}
+
+#[test]
+fn tier_position_tokens_follow_the_support_tier() {
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.support_tier_thresholds = [PACKAGE, 0, 0];
+    harness.initialize_sandbox(config).unwrap();
+    let (solbox, owner) = (harness.solbox, harness.owner);
+    assert_error(
+        harness.process(client::create_tier_mint(solbox, owner, 0)),
+        CustomError::InvalidSupportTiers
+    );
+    harness.process(client::create_tier_mint(solbox, owner, 1)).unwrap();
+    let mint = client::find_tier_mint_address(&solbox, 1).0;
+
+    let member = harness.register();
+    let token = harness.token_2022_account(mint, member);
+    let sync = client::sync_tier_token(solbox, member, 1, token);
+
+    // Nothing to hold below the tier; the token appears once it is reached
+    harness.process(sync.clone()).unwrap();
+    assert_eq!(harness.token_balance(&token), 0);
+    harness.admin_action(member, instruction::GrantPackage { user: member, package: PACKAGE }).unwrap();
+    harness.process(sync.clone()).unwrap();
+    harness.process(sync.clone()).unwrap();
+    assert_eq!(harness.token_balance(&token), 1);
+
+    // Dropping the package burns it without the holder signing
+    harness.admin_action(member, instruction::GrantPackage { user: member, package: 0 }).unwrap();
+    harness.process(sync).unwrap();
+    assert_eq!(harness.token_balance(&token), 0);
+}
+
+#[test]
+fn wrapped_positions_pay_commission_to_the_token_holder() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let solbox = harness.solbox;
+    let affiliate = harness.register();
+    let buyer = harness.register();
+    harness.process(client::create_position_mint(solbox, affiliate)).unwrap();
+    let mint = client::find_position_mint_address(&solbox, &affiliate).0;
+    let token = harness.token_2022_account(mint, affiliate);
+    harness.process(client::wrap_position(solbox, affiliate, token)).unwrap();
+    assert_eq!(harness.token_balance(&token), 1);
+    assert_error(
+        harness.process(client::wrap_position(solbox, affiliate, token)),
+        CustomError::PositionWrapped
+    );
+
+    // The affiliate sells the organization by handing over the token
+    let acquirer = harness.register();
+    let acquired = harness.token_2022_account(mint, acquirer);
+    harness.transfer_tokens(&token, &acquired, 1);
+
+    // Commission waits in the vault for the holder instead of the wallet
+    let affiliate_before = harness.lamports(&affiliate);
+    harness.buy(buyer, affiliate).unwrap();
+    assert_eq!(harness.lamports(&affiliate), affiliate_before);
+    harness.next_epoch();
+    assert_error(
+        harness.process(client::claim_residual(solbox, affiliate)),
+        CustomError::PositionWrapped
+    );
+    assert_error(
+        harness.process(client::claim_position_residual(solbox, affiliate, affiliate, token)),
+        CustomError::Unauthorized
+    );
+    let acquirer_before = harness.lamports(&acquirer);
+    harness.process(client::claim_position_residual(solbox, affiliate, acquirer, acquired)).unwrap();
+    assert_eq!(harness.lamports(&acquirer) - acquirer_before, PACKAGE / 10);
+
+    // Once the token is back with the wallet it can unwrap
+    assert!(harness.process(client::unwrap_position(solbox, affiliate, token)).is_err());
+    harness.transfer_tokens(&acquired, &token, 1);
+    harness.process(client::unwrap_position(solbox, affiliate, token)).unwrap();
+    harness.buy(buyer, affiliate).unwrap();
+    assert_eq!(harness.lamports(&affiliate) - affiliate_before, PACKAGE / 10);
+}
+
+#[test]
+fn large_first_purchases_need_the_humanity_verifier() {
+    let mut harness = Harness::new();
+    let small = LAMPORTS_PER_SOL / 10;
+    let verifier = Pubkey::new_unique();
+    let mut config = Harness::default_config();
+    config.valid_amounts = vec![PACKAGE, small];
+    config.humanity_verifier = verifier;
+    config.humanity_threshold = PACKAGE;
+    harness.initialize(config).unwrap();
+    let referrer = harness.register();
+    let (bot, human) = (harness.register(), harness.register());
+
+    assert_error(harness.buy(bot, referrer), CustomError::HumanityProofRequired);
+    let forged = client::Purchase {
+        humanity_verifier: Some(Pubkey::new_unique()),
+        ..harness.purchase_under(referrer)
+    };
+    assert_error(
+        harness.process(client::buy_gift_card(&forged, bot, referrer)),
+        CustomError::HumanityProofRequired
+    );
+
+    // Small first purchases and later purchases go through without it
+    let purchase = client::Purchase { amount: small, ..harness.purchase_under(referrer) };
+    harness.process(client::buy_gift_card(&purchase, bot, referrer)).unwrap();
+    harness.buy(bot, referrer).unwrap();
+
+    let verified = client::Purchase { humanity_verifier: Some(verifier), ..harness.purchase_under(referrer) };
+    harness.process(client::buy_gift_card(&verified, human, referrer)).unwrap();
+}
+
+#[test]
+fn one_claim_catches_up_residual_from_many_epochs() {
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.residual_bps = 3_000;
+    harness.initialize(config).unwrap();
+    let referrer = harness.register();
+    let buyer = harness.register();
+    let solbox = harness.solbox;
+    harness.airdrop(buyer, 20 * PACKAGE);
+    for _ in 0..20 {
+        harness.buy(buyer, referrer).unwrap();
+        harness.next_epoch();
+    }
+
+    let before = harness.lamports(&referrer);
+    harness.process(client::claim_residual(solbox, referrer)).unwrap();
+    assert_eq!(harness.lamports(&referrer) - before, 20 * (PACKAGE * 3 / 100));
+}
+
+#[test]
+fn uplines_count_unseen_downline_activity_until_acknowledged() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let solbox = harness.solbox;
+    let top = harness.register();
+    let middle = harness.register();
+    let bottom = harness.register();
+    let unseen = |harness: &Harness, wallet: &Pubkey| {
+        harness.fetch::<User>(&harness.user_pda(wallet)).unseen_downline_events
+    };
+
+    // Signups reach both paid levels, repeat purchases only the referrer
+    harness.buy(middle, top).unwrap();
+    harness.buy(bottom, middle).unwrap();
+    harness.buy(bottom, middle).unwrap();
+    assert_eq!((unseen(&harness, &top), unseen(&harness, &middle)), (2, 2));
+
+    harness.process(client::ack_events(solbox, top)).unwrap();
+    assert_eq!((unseen(&harness, &top), unseen(&harness, &middle)), (0, 2));
+}
+
+#[test]
+fn failures_log_the_field_level_and_source_line_behind_them() {
+    let mut harness = Harness::new();
+    let logged = |harness: &Harness, text: &str| harness.logs().iter().any(|line| line.contains(text));
+    let mut config = Harness::default_config();
+    config.reactivation_bps = 10_001;
+    assert_error(harness.initialize(config), CustomError::InvalidMembershipConfig);
+    assert!(logged(&harness, "Config field out of bounds: reactivation_bps"));
+
+    harness.initialize(Harness::default_config()).unwrap();
+    let (top, middle, bottom) = (harness.register(), harness.register(), harness.register());
+    harness.buy(middle, top).unwrap();
+    assert_error(harness.buy_with_uplines(bottom, middle, &[middle]), CustomError::UplineAccountMissing);
+    assert!(logged(&harness, &format!("Upline accounts missing from level 2, expected {top}")));
+    assert_error(harness.buy_with_uplines(bottom, middle, &[top, middle]), CustomError::UplineAccountMismatch);
+    assert!(logged(&harness, "Upline accounts at level 1 don't match the referral chain"));
+
+    // Overflows point at the line that overflowed
+    let solbox = harness.solbox;
+    harness.modify::<SolBox>(&solbox, |state| state.total_sold = u64::MAX);
+    assert_error(harness.buy(bottom, middle), CustomError::ArithmeticError);
+    assert!(logged(&harness, "AnchorError thrown in programs/solbox-contract-devnet/src/lib.rs:"));
+}
+
+#[test]
+fn validate_purchase_accounts_reports_every_problem_before_buying() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let (top, middle, bottom) = (harness.register(), harness.register(), harness.register());
+    let other = harness.register();
+    harness.buy(middle, top).unwrap();
+    let validate = |harness: &mut Harness, purchase: &client::Purchase, referrer: Pubkey| {
+        harness.process(client::validate_purchase_accounts(purchase, bottom, referrer)).unwrap();
+        u32::from_le_bytes(harness.return_data().try_into().unwrap())
+    };
+
+    let purchase = harness.purchase_under(middle);
+    assert_eq!(validate(&mut harness, &purchase, middle), 0);
+
+    // A purchase built a day ago, before another buyer took its receipt,
+    // without the uplines of its placement
+    let stale = client::Purchase { day: purchase.day - 1, uplines: Vec::new(), ..purchase };
+    harness.buy(other, middle).unwrap();
+    let problems = validate(&mut harness, &stale, middle);
+    assert_eq!(
+        problems,
+        PURCHASE_PROBLEM_STALE_RECEIPT | PURCHASE_PROBLEM_STALE_DAY | PURCHASE_PROBLEM_UPLINE_MISSING
+    );
+    assert_eq!(
+        client::purchase_problems(problems),
+        ["receipt is not the next purchase id", "daily stats are not for today", "upline accounts are missing"]
+    );
+    let own = harness.purchase_under(bottom);
+    assert_eq!(validate(&mut harness, &own, bottom), PURCHASE_PROBLEM_SELF_REFERRAL);
+}
+
+#[test]
+fn corrupted_instances_restore_from_an_exported_snapshot() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let (source, owner) = (harness.solbox, harness.owner);
+    let (top, middle) = (harness.register(), harness.register());
+    harness.buy(middle, top).unwrap();
+    let positions = [
+        MigratedPosition { wallet: top, package: 0, sponsor: Pubkey::default(), lifetime_volume: 0 },
+        MigratedPosition { wallet: middle, package: PACKAGE, sponsor: top, lifetime_volume: PACKAGE },
+    ];
+    let leaves: Vec<[u8; 32]> = positions.iter().map(MigratedPosition::leaf).collect();
+    let tree_root = statements::merkle_root(&leaves);
+
+    // Exports need guardians to approve the restore
+    assert_error(
+        harness.process(client::export_config_hash(source, owner, tree_root, 2)),
+        CustomError::InvalidGuardians
+    );
+    let guardians: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+    harness.process(client::set_pause_guardians(source, owner, guardians.clone(), 2)).unwrap();
+    harness.process(client::export_config_hash(source, owner, tree_root, 2)).unwrap();
+    let snapshot = client::instance_snapshot(&harness.fetch(&source), source, tree_root, 2);
+
+    let target = Pubkey::new_unique();
+    harness.solbox = target;
+    harness.initialize(Harness::default_config()).unwrap();
+    let restore = |harness: &mut Harness, snapshot: &InstanceSnapshot| {
+        harness.process(client::restore_instance(source, target, owner, snapshot.clone()))
+    };
+    assert_error(restore(&mut harness, &snapshot), CustomError::NoApprovedRestore);
+
+    // The quorum starts the timelock
+    harness.process(client::approve_restore(source, target, guardians[0])).unwrap();
+    let config_snapshot: ConfigSnapshot = harness.fetch(&client::find_config_snapshot_address(&source).0);
+    assert_eq!(config_snapshot.restore_eta, 0);
+    harness.process(client::approve_restore(source, target, guardians[2])).unwrap();
+    assert_error(restore(&mut harness, &snapshot), CustomError::TimelockNotElapsed);
+    harness.warp(RESTORE_TIMELOCK);
+
+    // Only the exported figures load
+    let inflated = InstanceSnapshot { total_sold: snapshot.total_sold * 10, ..snapshot.clone() };
+    assert_error(restore(&mut harness, &inflated), CustomError::InvalidSnapshot);
+    restore(&mut harness, &snapshot).unwrap();
+
+    let restored: SolBox = harness.fetch(&target);
+    assert_eq!((restored.total_sold, restored.purchase_count), (PACKAGE, 1));
+    let migration: Migration = harness.fetch(&client::find_migration_address(&target).0);
+    assert_eq!((migration.root, migration.positions), (tree_root, 2));
+    assert_error(restore(&mut harness, &snapshot), CustomError::InstanceNotFresh);
+}
+
+#[test]
+fn large_commissions_drip_to_the_referrer_over_days() {
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.drip_threshold = PACKAGE / 20;
+    config.drip_days = 4;
+    harness.initialize(config).unwrap();
+    let solbox = harness.solbox;
+    let referrer = harness.register();
+    let buyer = harness.register();
+
+    // The tranche comes along exactly when the commission drips
+    assert_error(harness.buy(buyer, referrer), CustomError::TrancheMismatch);
+    let purchase = client::Purchase { tranche: true, ..harness.purchase_under(referrer) };
+    let before = harness.lamports(&referrer);
+    harness.process(client::buy_gift_card(&purchase, buyer, referrer)).unwrap();
+    assert_eq!(harness.lamports(&referrer), before);
+    let tranche: Tranche = harness.fetch(&client::find_tranche_address(&solbox, 0).0);
+    assert_eq!((tranche.wallet, tranche.total), (referrer, PACKAGE / 10));
+    assert_eq!(harness.fetch::<SolBox>(&solbox).tranche_balance, tranche.total);
+
+    // A day's share per full day, never more than the total
+    let release = |harness: &mut Harness| harness.process(client::release_tranche(solbox, 0, referrer));
+    assert_error(release(&mut harness), CustomError::NothingToClaim);
+    harness.warp(86_400);
+    release(&mut harness).unwrap();
+    assert_eq!(harness.lamports(&referrer) - before, tranche.total / 4);
+    harness.warp(10 * 86_400);
+    release(&mut harness).unwrap();
+    assert_eq!(harness.lamports(&referrer) - before, tranche.total);
+    assert_error(release(&mut harness), CustomError::NothingToClaim);
+    assert_eq!(harness.fetch::<SolBox>(&solbox).tranche_balance, 0);
+}
+
+#[test]
+fn one_release_pays_every_tranche_of_a_wallet() {
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.drip_threshold = PACKAGE / 20;
+    config.drip_days = 4;
+    harness.initialize(config).unwrap();
+    let solbox = harness.solbox;
+    let referrer = harness.register();
+    let buyer = harness.register();
+    for _ in 0..3 {
+        let purchase = client::Purchase { tranche: true, ..harness.purchase_under(referrer) };
+        harness.process(client::buy_gift_card(&purchase, buyer, referrer)).unwrap();
+    }
+    let other_referrer = harness.register();
+    let other_buyer = harness.register();
+    let purchase = client::Purchase { tranche: true, ..harness.purchase_under(other_referrer) };
+    harness.process(client::buy_gift_card(&purchase, other_buyer, other_referrer)).unwrap();
+    let total = PACKAGE / 10;
+
+    // Only the wallet's own tranches, and only so many per call
+    harness.warp(86_400);
+    assert_error(
+        harness.process(client::release_tranches(solbox, &[0, 3], referrer)),
+        CustomError::Unauthorized
+    );
+    assert_error(
+        harness.process(client::release_tranches(solbox, &[0; MAX_RELEASE_BATCH + 1], referrer)),
+        CustomError::ReleaseBatchTooLarge
+    );
+
+    // A tranche listed twice still pays once
+    let before = harness.lamports(&referrer);
+    harness.process(client::release_tranches(solbox, &[0, 1, 2, 0], referrer)).unwrap();
+    assert_eq!(harness.lamports(&referrer) - before, 3 * (total / 4));
+    assert_error(
+        harness.process(client::release_tranches(solbox, &[0, 1, 2], referrer)),
+        CustomError::NothingToClaim
+    );
+
+    harness.warp(10 * 86_400);
+    harness.process(client::release_tranches(solbox, &[0, 1, 2], referrer)).unwrap();
+    assert_eq!(harness.lamports(&referrer) - before, 3 * total);
+    assert_eq!(harness.fetch::<SolBox>(&solbox).tranche_balance, total);
+}
+
+#[test]
+fn buyers_accept_the_published_terms() {
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.require_current_terms = true;
+    harness.initialize(config).unwrap();
+    let (solbox, owner) = (harness.solbox, harness.owner);
+    harness.process(client::publish_terms(solbox, owner, [1; 32])).unwrap();
+
+    // Registration proves acceptance of the terms in force
+    let stranger = Pubkey::new_unique();
+    harness.airdrop(stranger, LAMPORTS_PER_SOL);
+    assert_error(
+        harness.process(client::register_user(solbox, stranger, [2; 32])),
+        CustomError::TermsMismatch
+    );
+    let referrer = harness.register();
+    let buyer = harness.register();
+    let accepted: User = harness.fetch(&harness.user_pda(&buyer));
+    assert_eq!((accepted.terms_hash, accepted.terms_version), ([1; 32], 1));
+    harness.buy(buyer, referrer).unwrap();
+
+    // New terms block purchases until the buyer accepts them
+    harness.process(client::publish_terms(solbox, owner, [2; 32])).unwrap();
+    assert_error(harness.buy(buyer, referrer), CustomError::TermsOutdated);
+    assert_error(
+        harness.process(client::accept_terms(solbox, buyer, [1; 32])),
+        CustomError::TermsMismatch
+    );
+    harness.process(client::accept_terms(solbox, buyer, [2; 32])).unwrap();
+    harness.buy(buyer, referrer).unwrap();
+    assert_eq!(harness.fetch::<User>(&harness.user_pda(&buyer)).terms_version, 2);
+}
+
+#[test]
+fn limited_packages_sell_out() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let (solbox, owner) = (harness.solbox, harness.owner);
+    let uri = "https://cards.example/limited.json".to_string();
+    harness.process(client::create_package(solbox, owner, PACKAGE, PACKAGE_SEASONAL, uri)).unwrap();
+    harness.process(client::set_package_supply(solbox, owner, PACKAGE, Some(3), [2, 0, 0])).unwrap();
+    let referrer = harness.register();
+    let buyer = harness.register();
+
+    // Buys pass the Package PDA while a package is limited
+    assert_error(harness.buy(buyer, referrer), CustomError::PackageRequired);
+    let buy = |harness: &mut Harness| {
+        let purchase = client::Purchase { package: true, ..harness.purchase_under(referrer) };
+        harness.process(client::buy_gift_card(&purchase, buyer, referrer))
+    };
+    buy(&mut harness).unwrap();
+    let low = harness.logs().iter().any(|line| match Event::from_log(line) {
+        Some(Ok(Event::LowStockEvent(event))) => event.remaining == 2,
+        _ => false,
+    });
+    assert!(low);
+    buy(&mut harness).unwrap();
+    buy(&mut harness).unwrap();
+    let address = client::find_package_address(&solbox, PACKAGE).0;
+    assert_eq!(harness.fetch::<Package>(&address).remaining, 0);
+    assert_error(buy(&mut harness), CustomError::SoldOut);
+    
+    // A sale refunded in full gives its unit back, a partial refund doesn't
+    let receipt: Receipt = harness.fetch(&harness.receipt_pda(2));
+    assert_eq!(receipt.package, address);
+    let paid = receipt.commission + receipt.founder_share;
+    harness.airdrop(solbox, PACKAGE);
+    harness.process(client::admin_refund(solbox, owner, &receipt, 1, "damaged")).unwrap();
+    assert_eq!(harness.fetch::<Package>(&address).remaining, 0);
+    harness.process(client::admin_refund(solbox, owner, &receipt, paid - 1, "damaged")).unwrap();
+    assert_eq!(harness.fetch::<Package>(&address).remaining, 1);
+    
+    // So does a cancelled one
+    harness.modify::<SolBox>(&solbox, |state| state.config.refund_window = 3_600);
+    buy(&mut harness).unwrap();
+    assert_eq!(harness.fetch::<Package>(&address).remaining, 0);
+    harness.cancel(3).unwrap();
+    assert_eq!(harness.fetch::<Package>(&address).remaining, 1);
+    buy(&mut harness).unwrap();
+    harness.modify::<SolBox>(&solbox, |state| state.config.refund_window = 0);
+
+    // Restocking resumes sales; lifting the limit drops the extra account
+    harness.process(client::set_package_supply(solbox, owner, PACKAGE, Some(1), [0; 3])).unwrap();
+    buy(&mut harness).unwrap();
+    harness.process(client::set_package_supply(solbox, owner, PACKAGE, None, [0; 3])).unwrap();
+    assert_eq!(harness.fetch::<SolBox>(&solbox).limited_packages, 0);
+    harness.buy(buyer, referrer).unwrap();
+}
+
+#[test]
+fn rolling_windows_requalify_tiers() {
+    let mut harness = Harness::new();
+    const WEEK: i64 = 7 * 86_400;
+    let mut config = Harness::default_config();
+    config.support_tier_thresholds = [PACKAGE, 0, 0];
+    config.tier_period = WEEK;
+    config.tier_grace_periods = 2;
+    config.tier_window_days = 30;
+    harness.initialize(config).unwrap();
+    let referrer = harness.register();
+    let holder = harness.register();
+    let solbox = harness.solbox;
+    harness.process(client::grant_package(solbox, harness.owner, holder, PACKAGE)).unwrap();
+    harness.buy(holder, referrer).unwrap();
+
+    // One purchase keeps qualifying every weekly close for 30 days
+    for _ in 0..4 {
+        harness.warp(WEEK);
+        harness.process(client::refresh_tier(solbox, holder)).unwrap();
+        let user: User = harness.fetch(&harness.user_pda(&holder));
+        assert_eq!((user.support_tier, user.missed_periods), (1, 0));
+    }
+    harness.warp(2 * WEEK);
+    harness.process(client::refresh_tier(solbox, holder)).unwrap();
+    let user: User = harness.fetch(&harness.user_pda(&holder));
+    assert_eq!(user.support_tier, 1);
+    assert!(user.missed_periods > 0);
+}
+
+#[test]
+fn residual_exports_as_a_claim_voucher() {
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.residual_bps = 3_000;
+    harness.initialize(config).unwrap();
+    let referrer = harness.register();
+    let buyer = harness.register();
+    let solbox = harness.solbox;
+    harness.buy(buyer, referrer).unwrap();
+    harness.next_epoch();
+    harness.create_wormhole_bridge(100);
+    let recipient = [7; 32];
+    assert_error(
+        harness.process(client::export_claim_voucher(solbox, referrer, 0, recipient, 1)),
+        CustomError::VouchersDisabled
+    );
+    harness.modify::<SolBox>(&solbox, |state| state.config.voucher_chain = 2);
+
+    // The residual is posted for the sister contract instead of paid out
+    let referrer_before = harness.lamports(&referrer);
+    harness.process(client::export_claim_voucher(solbox, referrer, 0, recipient, 1)).unwrap();
+    assert_eq!(referrer_before - harness.lamports(&referrer), 100);
+    let voucher = ClaimVoucher {
+        voucher_id: 0,
+        solbox,
+        wallet: referrer,
+        amount: PACKAGE * 3 / 100,
+        recipient_chain: 2,
+        recipient,
+    };
+    assert_eq!(harness.posted_messages(), vec![voucher.payload()]);
+    let state: SolBox = harness.fetch(&solbox);
+    assert_eq!((state.residual_balance, state.voucher_balance, state.voucher_count), (0, PACKAGE * 3 / 100, 1));
+    assert_error(
+        harness.process(client::export_claim_voucher(solbox, referrer, 1, recipient, 2)),
+        CustomError::NothingToClaim
+    );
+}
+
+#[test]
+fn storefront_purchases_join_the_tree() {
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.storefront_chain = 2;
+    config.storefront_emitter = [5; 32];
+    harness.initialize(config).unwrap();
+    let referrer = harness.register();
+    let buyer = harness.register();
+    let (solbox, owner, relayer) = (harness.solbox, harness.owner, harness.owner);
+    let sale = StorefrontPurchase { buyer, referrer, amount: PACKAGE, integrator: None };
+    let vaa = PostedVaa {
+        vaa_version: 1,
+        consistency_level: 1,
+        vaa_time: 0,
+        vaa_signature_account: Pubkey::new_unique(),
+        submission_time: 0,
+        nonce: 0,
+        sequence: 9,
+        emitter_chain: 2,
+        emitter_address: [5; 32],
+        payload: sale.payload(),
+    };
+    let redeem = |harness: &mut Harness, vaa: &PostedVaa, sale: &StorefrontPurchase, package: bool| {
+        let posted = harness.post_vaa(vaa);
+        let purchase = client::Purchase { package, ..harness.purchase_under(sale.referrer) };
+        harness.process(client::redeem_wormhole_purchase(&purchase, relayer, posted, vaa, sale))
+    };
+
+    // Messages from any other emitter are refused
+    let forged = harness.post_vaa(&PostedVaa { emitter_address: [6; 32], ..vaa.clone() });
+    let purchase = harness.purchase_under(referrer);
+    assert_error(
+        harness.process(client::redeem_wormhole_purchase(&purchase, relayer, forged, &vaa, &sale)),
+        CustomError::InvalidVaa
+    );
+
+    // The buyer passes the same checks as a purchase on Solana
+    let buyer_pda = harness.user_pda(&buyer);
+    harness.modify::<User>(&buyer_pda, |account| account.deletion_requested_at = 1);
+    assert_error(redeem(&mut harness, &vaa, &sale, false), CustomError::DeletionPending);
+    harness.modify::<User>(&buyer_pda, |account| account.deletion_requested_at = 0);
+    harness.modify::<User>(&buyer_pda, |account| account.invited_by = Pubkey::new_unique());
+    assert_error(redeem(&mut harness, &vaa, &sale, false), CustomError::InvitationMismatch);
+    harness.modify::<User>(&buyer_pda, |account| account.invited_by = Pubkey::default());
+
+    // and takes a unit of a limited package
+    let uri = "https://cards.example/storefront.json".to_string();
+    harness.process(client::create_package(solbox, owner, PACKAGE, PACKAGE_SEASONAL, uri)).unwrap();
+    harness.process(client::set_package_supply(solbox, owner, PACKAGE, Some(1), [0; 3])).unwrap();
+    assert_error(redeem(&mut harness, &vaa, &sale, false), CustomError::PackageRequired);
+
+    // The buyer is placed and the referrer credited, without lamports moving
+    let vault_before = harness.lamports(&solbox);
+    redeem(&mut harness, &vaa, &sale, true).unwrap();
+    assert_eq!(harness.lamports(&solbox), vault_before);
+    let state: SolBox = harness.fetch(&solbox);
+    assert!(state.referral_relationships.iter().any(|relationship| relationship.user == buyer));
+    assert_eq!(harness.fetch::<User>(&buyer_pda).lifetime_volume, PACKAGE);
+    assert_eq!(harness.fetch::<User>(&harness.user_pda(&referrer)).total_earnings, PACKAGE / 10);
+    assert_eq!(harness.fetch::<Package>(&client::find_package_address(&solbox, PACKAGE).0).remaining, 0);
+    let stats: DailyStats = harness.fetch(&client::find_daily_stats_address(&solbox, harness.purchase().day).0);
+    assert_eq!((stats.purchase_count, stats.volume), (1, PACKAGE));
+
+    // The message's WormholePurchase PDA, keyed by emitter and sequence,
+    // keeps it from being credited twice
+    let redemption_pda = client::find_wormhole_purchase_address(&solbox, 2, &[5; 32], 9).0;
+    let redemption: WormholePurchase = harness.fetch(&redemption_pda);
+    assert_eq!((redemption.sequence, redemption.buyer, redemption.amount), (9, buyer, PACKAGE));
+    assert_eq!((redemption.emitter_chain, redemption.emitter_address), (2, [5; 32]));
+    let next = StorefrontPurchase { buyer: harness.register(), ..sale.clone() };
+    let message = PostedVaa { sequence: 10, payload: next.payload(), ..vaa.clone() };
+    assert_error(redeem(&mut harness, &message, &next, true), CustomError::SoldOut);
+    harness.process(client::set_package_supply(solbox, owner, PACKAGE, None, [0; 3])).unwrap();
+    assert_eq!(redeem(&mut harness, &vaa, &sale, false), Err(ProgramError::AccountAlreadyInitialized));
+
+    // A sale routed through an integrator counts against its limits
+    let name_hash = [4; 32];
+    let integrator = client::find_integrator_address(&solbox, &name_hash).0;
+    harness.call(
+        &accounts::RegisterIntegrator {
+            solbox,
+            integrator,
+            admin: owner,
+            system_program: anchor_lang::system_program::ID,
+        },
+        instruction::RegisterIntegrator {
+            name_hash,
+            wallet: Pubkey::new_unique(),
+            fee_share_bps: 0,
+            allowed_instructions: INTEGRATOR_ALLOW_BUY_GIFT_CARD,
+            cpi_authority: Pubkey::new_unique(),
+        }
+    ).unwrap();
+    harness.call(
+        &accounts::UpdateIntegrator { solbox, integrator, admin: owner },
+        instruction::SetIntegratorLimits { epoch_volume_limit: 0, epoch_purchase_limit: 1 }
+    ).unwrap();
+    for (sequence, expected) in [(11, None), (12, Some(CustomError::IntegratorLimitExceeded))] {
+        let routed = StorefrontPurchase { buyer: harness.register(), integrator: Some(integrator), ..sale.clone() };
+        let message = PostedVaa { sequence, payload: routed.payload(), ..vaa.clone() };
+        match expected {
+            None => redeem(&mut harness, &message, &routed, false).unwrap(),
+            Some(error) => assert_error(redeem(&mut harness, &message, &routed, false), error),
+        }
+    }
+    assert_eq!(harness.fetch::<Integrator>(&integrator).epoch_purchases, 1);
+}
+
+#[test]
+fn drops_sell_to_revealed_commits() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let (solbox, owner) = (harness.solbox, harness.owner);
+    let uri = "https://cards.example/drop.json".to_string();
+    harness.process(client::create_package(solbox, owner, PACKAGE, PACKAGE_SEASONAL, uri)).unwrap();
+    assert_error(
+        harness.process(client::schedule_drop(solbox, owner, PACKAGE, 2, 4)),
+        CustomError::PackageNotLimited,
+    );
+    harness.process(client::set_package_supply(solbox, owner, PACKAGE, Some(3), [0; 3])).unwrap();
+    assert_error(
+        harness.process(client::schedule_drop(solbox, owner, PACKAGE, 2, 2)),
+        CustomError::InvalidDropWindow,
+    );
+    harness.process(client::schedule_drop(solbox, owner, PACKAGE, 2, 4)).unwrap();
+    let referrer = harness.register();
+    let package = client::find_package_address(&solbox, PACKAGE).0;
+    let [first, second, silent, copier] = [(); 4].map(|_| harness.register());
+    let salt = [7; 32];
+    let commitment = |wallet: &Pubkey, quantity| drop_commitment(&package, wallet, 1, quantity, &salt);
+    for (wallet, quantity) in [(first, 2), (second, 2), (silent, 1)] {
+        harness.process(client::commit_purchase(solbox, wallet, PACKAGE, 1, commitment(&wallet, quantity))).unwrap();
+    }
+    // Copying another wallet's commit doesn't let the copy reveal it
+    harness.process(client::commit_purchase(solbox, copier, PACKAGE, 1, commitment(&first, 2))).unwrap();
+    let commit_of = |wallet: &Pubkey| client::find_drop_commit_address(&solbox, PACKAGE, 1, wallet).0;
+    let commit: DropCommit = harness.fetch(&commit_of(&second));
+    assert_eq!((commit.package, commit.wallet, commit.drop_id, commit.order), (package, second, 1, 1));
+
+    // Nothing is revealed or sold while commits are open
+    assert_error(
+        harness.process(client::reveal_purchase(solbox, first, PACKAGE, 1, 2, salt)),
+        CustomError::CommitWindowOpen,
+    );
+    let buy = |harness: &mut Harness, buyer: Pubkey, drop_commit: Option<u64>| {
+        let purchase = client::Purchase { package: true, drop_commit, ..harness.purchase_under(referrer) };
+        harness.process(client::buy_gift_card(&purchase, buyer, referrer))
+    };
+    assert_error(buy(&mut harness, first, None), CustomError::CommitRequired);
+
+    // After the commit window only the committed quantity and salt reveal
+    harness.warp(1);
+    harness.warp(1);
+    let late = harness.register();
+    assert_error(
+        harness.process(client::commit_purchase(solbox, late, PACKAGE, 1, commitment(&late, 1))),
+        CustomError::CommitWindowClosed,
+    );
+    assert_error(
+        harness.process(client::reveal_purchase(solbox, first, PACKAGE, 1, 1, salt)),
+        CustomError::CommitMismatch,
+    );
+    assert_error(
+        harness.process(client::reveal_purchase(solbox, copier, PACKAGE, 1, 2, salt)),
+        CustomError::CommitMismatch,
+    );
+    harness.process(client::reveal_purchase(solbox, first, PACKAGE, 1, 2, salt)).unwrap();
+    harness.process(client::reveal_purchase(solbox, second, PACKAGE, 1, 2, salt)).unwrap();
+    let commits = [first, second, silent, copier].map(|wallet| commit_of(&wallet));
+    assert_error(
+        harness.process(client::allocate_drop(solbox, PACKAGE, &commits)),
+        CustomError::RevealWindowOpen,
+    );
+
+    // The reveal window ends, and the supply goes out in commit order
+    harness.warp(1);
+    harness.warp(1);
+    assert_error(
+        harness.process(client::reveal_purchase(solbox, silent, PACKAGE, 1, 1, salt)),
+        CustomError::RevealWindowClosed,
+    );
+    assert_error(
+        harness.process(client::allocate_drop(solbox, PACKAGE, &commits[1..])),
+        CustomError::InvalidDropCommit,
+    );
+    harness.process(client::allocate_drop(solbox, PACKAGE, &commits[..1])).unwrap();
+    harness.process(client::allocate_drop(solbox, PACKAGE, &commits[1..])).unwrap();
+    let allocated = commits.map(|commit| harness.fetch::<DropCommit>(&commit).allocated);
+    assert_eq!(allocated, [2, 1, 0, 0]);
+
+    // Allocated commits buy their cards and close once spent
+    assert_error(buy(&mut harness, silent, Some(1)), CustomError::CommitRequired);
+    buy(&mut harness, first, Some(1)).unwrap();
+    assert!(harness.lamports(&commits[0]) > 0);
+    buy(&mut harness, first, Some(1)).unwrap();
+    assert_eq!(harness.lamports(&commits[0]), 0);
+    assert_error(buy(&mut harness, late, None), CustomError::CommitRequired);
+    buy(&mut harness, second, Some(1)).unwrap();
+    assert_eq!(harness.lamports(&commits[1]), 0);
+    assert_eq!(harness.fetch::<Package>(&package).remaining, 0);
+
+    // Commits the allocation passed over close back to their wallets
+    let before = harness.lamports(&silent);
+    harness.process(client::close_drop_commit(solbox, PACKAGE, 1, silent)).unwrap();
+    assert_eq!(harness.lamports(&commits[2]), 0);
+    assert!(harness.lamports(&silent) > before);
+
+    // A new drop leaves the old commits stale
+    harness.process(client::set_package_supply(solbox, owner, PACKAGE, Some(2), [0; 3])).unwrap();
+    harness.process(client::schedule_drop(solbox, owner, PACKAGE, 10, 12)).unwrap();
+    let fresh = drop_commitment(&package, &late, 2, 1, &salt);
+    harness.process(client::commit_purchase(solbox, late, PACKAGE, 2, fresh)).unwrap();
+    assert_error(
+        harness.process(client::close_drop_commit(solbox, PACKAGE, 2, late)),
+        CustomError::CommitInUse,
+    );
+    assert_error(buy(&mut harness, copier, Some(1)), CustomError::StaleCommit);
+    harness.process(client::close_drop_commit(solbox, PACKAGE, 1, copier)).unwrap();
+
+    // Ending the drop sells to anyone again
+    harness.process(client::schedule_drop(solbox, owner, PACKAGE, 0, 0)).unwrap();
+    buy(&mut harness, copier, None).unwrap();
+    harness.process(client::close_drop_commit(solbox, PACKAGE, 2, late)).unwrap();
+}
+
+#[test]
+fn purchases_only_close_the_buyers_own_commit() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let (solbox, owner) = (harness.solbox, harness.owner);
+    let uri = "https://cards.example/drop.json".to_string();
+    harness.process(client::create_package(solbox, owner, PACKAGE, PACKAGE_SEASONAL, uri)).unwrap();
+    harness.process(client::set_package_supply(solbox, owner, PACKAGE, Some(5), [0; 3])).unwrap();
+    harness.process(client::schedule_drop(solbox, owner, PACKAGE, 2, 4)).unwrap();
+    let referrer = harness.register();
+    let victim = harness.register();
+    let buyer = harness.register();
+    let package = client::find_package_address(&solbox, PACKAGE).0;
+    let salt = [7; 32];
+    let commitment = drop_commitment(&package, &victim, 1, 1, &salt);
+    harness.process(client::commit_purchase(solbox, victim, PACKAGE, 1, commitment)).unwrap();
+    harness.warp(1);
+    harness.warp(1);
+    harness.process(client::reveal_purchase(solbox, victim, PACKAGE, 1, 1, salt)).unwrap();
+    harness.process(client::schedule_drop(solbox, owner, PACKAGE, 0, 0)).unwrap();
+
+    // Once the drop is open, another wallet's commit still can't be closed
+    let victim_commit = client::find_drop_commit_address(&solbox, PACKAGE, 1, &victim).0;
+    let own_commit = client::find_drop_commit_address(&solbox, PACKAGE, 1, &buyer).0;
+    let purchase = client::Purchase { package: true, drop_commit: Some(1), ..harness.purchase_under(referrer) };
+    let mut instruction = client::buy_gift_card(&purchase, buyer, referrer);
+    for meta in instruction.accounts.iter_mut().filter(|meta| meta.pubkey == own_commit) {
+        meta.pubkey = victim_commit;
+    }
+    assert_error(harness.process(instruction), CustomError::CommitRequired);
+    assert!(harness.lamports(&victim_commit) > 0);
+    assert!(harness.fetch::<DropCommit>(&victim_commit).revealed);
+
+    // Its owner can still spend it
+    let purchase = client::Purchase { package: true, drop_commit: Some(1), ..harness.purchase_under(referrer) };
+    harness.process(client::buy_gift_card(&purchase, victim, referrer)).unwrap();
+    assert_eq!(harness.lamports(&victim_commit), 0);
+}
+
+#[test]
+fn deleted_users_leave_tombstones() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let (solbox, owner) = (harness.solbox, harness.owner);
+    let referrer = harness.register();
+    let alice = harness.register();
+    harness.buy(alice, referrer).unwrap();
+    let downline = harness.register();
+    harness.buy(downline, alice).unwrap();
+    harness.process(client::register_handle(solbox, alice, "alice")).unwrap();
+    assert_error(
+        harness.process(client::process_deletion(solbox, owner, alice, 0)),
+        CustomError::DeletionNotRequested,
+    );
+
+    // A pending deletion stops purchases on both sides
+    harness.process(client::request_deletion(solbox, alice)).unwrap();
+    assert_error(harness.buy(alice, referrer), CustomError::DeletionPending);
+    let late = harness.register();
+    assert_error(harness.buy(late, alice), CustomError::DeletionPending);
+    assert_error(
+        harness.process(client::process_deletion(solbox, owner, alice, 0)),
+        CustomError::ObligationsOutstanding,
+    );
+    harness.process(client::release_handle(solbox, alice, "alice")).unwrap();
+    let alice_pda = harness.user_pda(&alice);
+
+    // A card balance is owed to the wallet too
+    harness.modify::<User>(&alice_pda, |account| account.card_balance = PACKAGE);
+    assert_error(
+        harness.process(client::process_deletion(solbox, owner, alice, 0)),
+        CustomError::ObligationsOutstanding,
+    );
+    harness.modify::<User>(&alice_pda, |account| account.card_balance = 0);
+
+    // Only the wallet's own purchases in escrow hold it back
+    harness.modify::<SolBox>(&solbox, |state| state.config.refund_window = 3_600);
+    let other = harness.register();
+    harness.buy(other, referrer).unwrap();
+    assert_eq!(harness.fetch::<User>(&harness.user_pda(&other)).pending_receipts, 1);
+    assert_eq!(harness.fetch::<User>(&harness.user_pda(&referrer)).pending_receipts, 1);
+    assert_eq!(harness.fetch::<SolBox>(&solbox).pending_receipts, 1);
+    harness.modify::<User>(&alice_pda, |account| account.pending_receipts = 1);
+    assert_error(
+        harness.process(client::process_deletion(solbox, owner, alice, 0)),
+        CustomError::ObligationsOutstanding,
+    );
+    harness.modify::<User>(&alice_pda, |account| account.pending_receipts = 0);
+    harness.modify::<SolBox>(&solbox, |state| state.config.refund_window = 0);
+    let downline_counts = harness.fetch::<User>(&alice_pda).downline_counts;
+    harness.process(client::process_deletion(solbox, owner, alice, 0)).unwrap();
+
+    // The tombstone takes the position without anything of the wallet's
+    let tombstone = tombstone_key(&solbox, 0);
+    assert_eq!(harness.lamports(&alice_pda), 0);
+    let relationships = harness.fetch::<SolBox>(&solbox).referral_relationships;
+    assert!(relationships.iter().all(|r| r.user != alice && r.referrer != alice));
+    assert!(relationships.iter().any(|r| r.user == tombstone && r.referrer == referrer));
+    assert!(relationships.iter().any(|r| r.user == downline && r.referrer == tombstone));
+    let record: User = harness.fetch(&harness.user_pda(&tombstone));
+    assert_eq!((record.key, record.downline_counts, record.current_package), (tombstone, downline_counts, 0));
+    assert_eq!(harness.fetch::<SolBox>(&solbox).tombstone_count, 1);
+
+    // Commission credited to the tombstone goes to the bonus pool
+    harness.buy(late, tombstone).unwrap();
+    assert_eq!(harness.fetch::<SolBox>(&solbox).house_commission, PACKAGE / 10);
+}
+
+#[test]
+fn tree_checkpoints_build_over_batches() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let (solbox, owner) = (harness.solbox, harness.owner);
+    let referrer = harness.register();
+    let mut buyers = vec![referrer];
+    for _ in 0..MAX_CHECKPOINT_BATCH + 8 {
+        let buyer = harness.register();
+        harness.buy(buyer, *buyers.last().unwrap()).unwrap();
+        buyers.push(buyer);
+    }
+    let cranker = harness.register();
+    let address = client::find_tree_checkpoint_address(&solbox).0;
+    let batch = MAX_CHECKPOINT_BATCH as u64;
+    harness.process(client::checkpoint_tree(solbox, cranker, 0)).unwrap();
+    assert_eq!(harness.fetch::<TreeCheckpoint>(&address).cursor, batch);
+    assert_error(
+        harness.process(client::checkpoint_tree(solbox, cranker, 1)),
+        CustomError::CheckpointCursorMismatch,
+    );
+    
+    // A run with progress can't be reset from cursor 0 while it's current
+    assert_error(
+        harness.process(client::checkpoint_tree(solbox, cranker, 0)),
+        CustomError::CheckpointInProgress,
+    );
+
+    // Rewriting the tree mid-run voids it; appends don't
+    harness.process(client::request_deletion(solbox, buyers[1])).unwrap();
+    harness.process(client::process_deletion(solbox, owner, buyers[1], 0)).unwrap();
+    assert_error(
+        harness.process(client::checkpoint_tree(solbox, cranker, batch)),
+        CustomError::CheckpointStale,
+    );
+    harness.process(client::checkpoint_tree(solbox, cranker, 0)).unwrap();
+    let late = harness.register();
+    harness.buy(late, *buyers.last().unwrap()).unwrap();
+    harness.process(client::checkpoint_tree(solbox, cranker, batch)).unwrap();
+
+    let tree = harness.fetch::<SolBox>(&solbox);
+    let checkpoint: TreeCheckpoint = harness.fetch(&address);
+    assert_eq!(checkpoint.relationships, tree.referral_relationships.len() as u64);
+    assert_eq!(checkpoint.root, client::tree_root(&tree));
+    assert_ne!(checkpoint.checkpointed_at, 0);
+
+    // One checkpoint per epoch
+    assert_error(
+        harness.process(client::checkpoint_tree(solbox, cranker, 0)),
+        CustomError::TreeCheckpointed,
+    );
+    harness.next_epoch();
+    harness.process(client::checkpoint_tree(solbox, cranker, 0)).unwrap();
+    let next: TreeCheckpoint = harness.fetch(&address);
+    assert_eq!((next.cursor, next.root), (batch, checkpoint.root));
+    
+    // A run left unfinished goes stale with the epoch
+    harness.next_epoch();
+    harness.process(client::checkpoint_tree(solbox, cranker, 0)).unwrap();
+    assert_eq!(harness.fetch::<TreeCheckpoint>(&address).epoch, next.epoch + 1);
+}
+
+#[test]
+fn treasury_splits_into_hot_and_cold() {
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.hot_daily_limit = LAMPORTS_PER_SOL;
+    config.hot_sweep_threshold = 2 * LAMPORTS_PER_SOL;
+    harness.initialize(config).unwrap();
+    let (solbox, owner) = (harness.solbox, harness.owner);
+    harness.airdrop(solbox, 10 * LAMPORTS_PER_SOL);
+    let recipient = Pubkey::new_unique();
+
+    // The hot treasury pays out up to the daily limit
+    let withdraw = |harness: &mut Harness, amount| {
+        harness.process(client::withdraw_treasury(solbox, owner, recipient, amount))
+    };
+    withdraw(&mut harness, LAMPORTS_PER_SOL / 2).unwrap();
+    assert_error(withdraw(&mut harness, LAMPORTS_PER_SOL), CustomError::HotLimitExceeded);
+    withdraw(&mut harness, LAMPORTS_PER_SOL / 2).unwrap();
+    harness.warp(86_400);
+    withdraw(&mut harness, LAMPORTS_PER_SOL).unwrap();
+
+    // Sweeps leave the threshold hot and move the rest to cold
+    harness.process(client::initialize_cold_treasury(solbox, owner)).unwrap();
+    let cold = client::find_cold_treasury_address(&solbox).0;
+    let cold_rent = harness.lamports(&cold);
+    harness.process(client::sweep_treasury(solbox)).unwrap();
+    assert_eq!(harness.lamports(&cold) - cold_rent, 6 * LAMPORTS_PER_SOL);
+    assert_error(harness.process(client::sweep_treasury(solbox)), CustomError::NothingToSweep);
+
+    // Cold withdrawals need the guardians and the timelock
+    assert_error(
+        harness.process(client::propose_cold_withdrawal(solbox, owner, recipient, LAMPORTS_PER_SOL)),
+        CustomError::InvalidGuardians,
+    );
+    let guardians = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+    harness.process(client::set_pause_guardians(solbox, owner, guardians.to_vec(), 2)).unwrap();
+    assert_error(
+        harness.process(client::propose_cold_withdrawal(solbox, owner, recipient, 7 * LAMPORTS_PER_SOL)),
+        CustomError::InvalidAmount,
+    );
+    harness.process(client::propose_cold_withdrawal(solbox, owner, recipient, 4 * LAMPORTS_PER_SOL)).unwrap();
+    assert_error(
+        harness.process(client::approve_cold_withdrawal(solbox, owner)),
+        CustomError::Unauthorized,
+    );
+    harness.process(client::approve_cold_withdrawal(solbox, guardians[0])).unwrap();
+    assert_error(
+        harness.process(client::execute_cold_withdrawal(solbox, owner, recipient)),
+        CustomError::NoPendingColdWithdrawal,
+    );
+    harness.process(client::approve_cold_withdrawal(solbox, guardians[2])).unwrap();
+    assert_error(
+        harness.process(client::execute_cold_withdrawal(solbox, owner, recipient)),
+        CustomError::TimelockNotElapsed,
+    );
+    harness.warp(COLD_WITHDRAW_TIMELOCK);
+    let before = harness.lamports(&recipient);
+    harness.process(client::execute_cold_withdrawal(solbox, owner, recipient)).unwrap();
+    assert_eq!(harness.lamports(&recipient) - before, 4 * LAMPORTS_PER_SOL);
+    assert_eq!(harness.lamports(&cold) - cold_rent, 2 * LAMPORTS_PER_SOL);
+}
+
+#[test]
+fn suspicious_purchases_get_flagged() {
+    use anchor_lang::solana_program::instruction::AccountMeta;
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.flag_funder_threshold = 1;
+    config.flag_burst_threshold = 2;
+    harness.initialize(config).unwrap();
+    let (solbox, owner) = (harness.solbox, harness.owner);
+
+    // A sponsor paying for a second newcomer the same day trips the funder check
+    let sponsor = harness.register();
+    let newcomers = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let flagged = |harness: &Harness, wallet: &Pubkey| harness.fetch::<User>(&harness.user_pda(wallet)).flagged;
+    for (i, newcomer) in newcomers.into_iter().enumerate() {
+        harness.process(client::sponsor_registration(solbox, sponsor, newcomer)).unwrap();
+        let purchase = client::Purchase { sponsor_paid: true, ..harness.purchase_under(sponsor) };
+        harness.process(client::buy_gift_card(&purchase, newcomer, sponsor)).unwrap();
+        if i == 0 {
+            // Paying for the same wallet again doesn't count as another one
+            harness.airdrop(newcomer, 10 * PACKAGE);
+            let mut ix = client::buy_gift_card(&harness.purchase_under(sponsor), newcomer, sponsor);
+            ix.accounts[3] = AccountMeta::new(sponsor, true);
+            harness.process(ix).unwrap();
+            assert_eq!(flagged(&harness, &newcomer), 0);
+        }
+    }
+    assert_eq!(flagged(&harness, &newcomers[0]), 0);
+    assert_eq!(flagged(&harness, &newcomers[1]), FRAUD_FLAG_SHARED_FUNDER);
+    let event = harness.logs().iter().find_map(|line| match Event::from_log(line) {
+        Some(Ok(Event::FraudFlagEvent(event))) => Some(event),
+        _ => None,
+    });
+    assert_eq!(event.unwrap().funder.map(|funder| funder.0), Some(sponsor.to_bytes()));
+
+    // The third purchase through one integrator within the window is a burst
+    let name_hash = [9; 32];
+    let integrator = client::find_integrator_address(&solbox, &name_hash).0;
+    let partner_wallet = Pubkey::new_unique();
+    harness.call(
+        &accounts::RegisterIntegrator {
+            solbox,
+            integrator,
+            admin: owner,
+            system_program: anchor_lang::system_program::ID,
+        },
+        instruction::RegisterIntegrator {
+            name_hash,
+            wallet: partner_wallet,
+            fee_share_bps: 0,
+            allowed_instructions: INTEGRATOR_ALLOW_BUY_GIFT_CARD,
+            cpi_authority: Pubkey::new_unique(),
+        }
+    ).unwrap();
+    let referrer = harness.register();
+    let mut cohort = Vec::new();
+    for _ in 0..3 {
+        let buyer = harness.register();
+        let purchase = client::Purchase { integrator: Some((integrator, partner_wallet)), ..harness.purchase_under(referrer) };
+        harness.process(client::buy_gift_card(&purchase, buyer, referrer)).unwrap();
+        cohort.push(buyer);
+    }
+    assert_eq!(cohort.iter().map(|buyer| flagged(&harness, buyer)).collect::<Vec<_>>(), [0, 0, FRAUD_FLAG_BURST]);
+    harness.warp(FRAUD_BURST_WINDOW);
+    let calm = harness.register();
+    let purchase = client::Purchase { integrator: Some((integrator, partner_wallet)), ..harness.purchase_under(referrer) };
+    harness.process(client::buy_gift_card(&purchase, calm, referrer)).unwrap();
+    assert_eq!(flagged(&harness, &calm), 0);
+
+    // Flags don't block unless configured to, and review clears them
+    harness.buy(cohort[2], referrer).unwrap();
+    let mut config = harness.fetch::<SolBox>(&solbox).config;
+    config.block_flagged = true;
+    harness.process(client::update_config(solbox, owner, config)).unwrap();
+    assert_error(harness.buy(cohort[2], referrer), CustomError::UserFlagged);
+    harness.process(client::clear_fraud_flags(solbox, owner, cohort[2])).unwrap();
+    harness.buy(cohort[2], referrer).unwrap();
+}
+
+#[test]
+fn integrators_are_capped_per_epoch() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let (solbox, owner) = (harness.solbox, harness.owner);
+
+    let name_hash = [5; 32];
+    let integrator = client::find_integrator_address(&solbox, &name_hash).0;
+    let partner_wallet = Pubkey::new_unique();
+    harness.call(
+        &accounts::RegisterIntegrator {
+            solbox,
+            integrator,
+            admin: owner,
+            system_program: anchor_lang::system_program::ID,
+        },
+        instruction::RegisterIntegrator {
+            name_hash,
+            wallet: partner_wallet,
+            fee_share_bps: 0,
+            allowed_instructions: INTEGRATOR_ALLOW_BUY_GIFT_CARD,
+            cpi_authority: Pubkey::new_unique(),
+        }
+    ).unwrap();
+    let set_limits = |harness: &mut Harness, epoch_volume_limit, epoch_purchase_limit| {
+        harness.call(
+            &accounts::UpdateIntegrator { solbox, integrator, admin: owner },
+            instruction::SetIntegratorLimits { epoch_volume_limit, epoch_purchase_limit }
+        )
+    };
+    let referrer = harness.register();
+    let buy_through = |harness: &mut Harness| {
+        let buyer = harness.register();
+        let purchase = client::Purchase { integrator: Some((integrator, partner_wallet)), ..harness.purchase_under(referrer) };
+        harness.process(client::buy_gift_card(&purchase, buyer, referrer))
+    };
+
+    // Only the admin sets the ceilings
+    let intruder = harness.register();
+    assert_error(
+        harness.call(
+            &accounts::UpdateIntegrator { solbox, integrator, admin: intruder },
+            instruction::SetIntegratorLimits { epoch_volume_limit: 0, epoch_purchase_limit: 0 }
+        ),
+        CustomError::Unauthorized
+    );
+
+    // The purchase count ceiling holds for the epoch, then resets
+    set_limits(&mut harness, 0, 2).unwrap();
+    buy_through(&mut harness).unwrap();
+    buy_through(&mut harness).unwrap();
+    assert_error(buy_through(&mut harness), CustomError::IntegratorLimitExceeded);
+    let direct = harness.register();
+    harness.buy(direct, referrer).unwrap();
+    harness.next_epoch();
+    buy_through(&mut harness).unwrap();
+
+    // So does the volume ceiling, counted against what was already routed
+    set_limits(&mut harness, 2 * PACKAGE, 0).unwrap();
+    buy_through(&mut harness).unwrap();
+    assert_error(buy_through(&mut harness), CustomError::IntegratorLimitExceeded);
+    let state: Integrator = harness.fetch(&integrator);
+    assert_eq!((state.epoch_volume, state.epoch_purchases), (2 * PACKAGE, 2));
+
+    set_limits(&mut harness, 0, 0).unwrap();
+    buy_through(&mut harness).unwrap();
+}
+
+#[test]
+fn invitations_bind_the_first_purchase() {
+    let mut harness = Harness::new();
+    harness.initialize(Harness::default_config()).unwrap();
+    let solbox = harness.solbox;
+    let referrer = harness.register();
+    let stranger = harness.register();
+    let identifier = b"alice@example.com";
+    harness.process(client::create_invitation(solbox, referrer, identifier)).unwrap();
+
+    // The invitee claims with the identifier it was invited by, once
+    let invitee = harness.register();
+    harness.process(client::claim_invitation(solbox, invitee, identifier)).unwrap();
+    assert_eq!(harness.fetch::<User>(&harness.user_pda(&invitee)).invited_by, referrer);
+    let latecomer = harness.register();
+    assert_error(
+        harness.process(client::claim_invitation(solbox, latecomer, identifier)),
+        CustomError::InvitationClaimed
+    );
+
+    // Arriving through another link doesn't move the attribution
+    let elsewhere = harness.purchase_under(stranger);
+    harness.process(client::validate_purchase_accounts(&elsewhere, invitee, stranger)).unwrap();
+    assert_eq!(
+        u32::from_le_bytes(harness.return_data().try_into().unwrap()),
+        PURCHASE_PROBLEM_INVITATION_MISMATCH
+    );
+    assert_error(harness.buy(invitee, stranger), CustomError::InvitationMismatch);
+    harness.buy(invitee, referrer).unwrap();
+    harness.buy(invitee, referrer).unwrap();
+
+    // Wallets already placed can't be claimed by an invitation
+    let identifier = b"+15550100";
+    harness.process(client::create_invitation(solbox, referrer, identifier)).unwrap();
+    harness.buy(latecomer, stranger).unwrap();
+    assert_error(
+        harness.process(client::claim_invitation(solbox, latecomer, identifier)),
+        CustomError::InvitationClosed
+    );
+    let invitation: Invitation = harness.fetch(&client::find_invitation_address(&solbox, &invitation_hash(&solbox, identifier)).0);
+    assert_eq!((invitation.referrer, invitation.claimed_by), (referrer, Pubkey::default()));
+}
+
+#[test]
+fn residual_claims_to_card_balance_with_a_bonus() {
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.residual_bps = 10_000;
+    config.card_bonus_bps = 500;
+    harness.initialize(config).unwrap();
+    let solbox = harness.solbox;
+    let upline = harness.register();
+    let referrer = harness.register();
+    let buyer = harness.register();
+    for _ in 0..10 {
+        harness.buy(buyer, referrer).unwrap();
+    }
+    harness.next_epoch();
+
+    // The bonus has to come out of the treasury, not what users are owed
+    let bonus = PACKAGE / 20;
+    assert_error(harness.process(client::claim_to_card(solbox, referrer)), CustomError::InsufficientCoverage);
+    harness.airdrop(solbox, bonus);
+    let vault_before = harness.lamports(&solbox);
+    harness.process(client::claim_to_card(solbox, referrer)).unwrap();
+    assert_eq!(harness.lamports(&solbox), vault_before);
+    let account: User = harness.fetch(&harness.user_pda(&referrer));
+    assert_eq!((account.residual_claimable, account.card_balance), (0, PACKAGE + bonus));
+    let state: SolBox = harness.fetch(&solbox);
+    assert_eq!((state.residual_balance, state.card_balance_total), (0, PACKAGE + bonus));
+
+    // A balance covering a purchase pays for it, and a cancelled one
+    // goes back to the balance. The wallet only pays receipt rent
+    harness.modify::<SolBox>(&solbox, |state| state.config.refund_window = 3_600);
+    let wallet_before = harness.lamports(&referrer);
+    harness.buy(referrer, upline).unwrap();
+    let rent = harness.lamports(&harness.receipt_pda(10));
+    let wallet_before = wallet_before - rent;
+    assert_eq!(harness.lamports(&referrer), wallet_before);
+    let receipt: Receipt = harness.fetch(&harness.receipt_pda(10));
+    assert!(receipt.card_funded);
+    let price = receipt.commission + receipt.founder_share;
+    let left = PACKAGE + bonus - price;
+    assert_eq!(harness.fetch::<User>(&harness.user_pda(&referrer)).card_balance, left);
+    harness.cancel(10).unwrap();
+    assert_eq!(harness.lamports(&referrer), wallet_before);
+    assert_eq!(harness.fetch::<User>(&harness.user_pda(&referrer)).card_balance, PACKAGE + bonus);
+
+    // Settled, the commission is held for the upline as usual
+    harness.modify::<SolBox>(&solbox, |state| state.config.refund_window = 0);
+    harness.buy(referrer, upline).unwrap();
+    assert_eq!(harness.lamports(&referrer), wallet_before - rent);
+    assert_eq!(harness.fetch::<User>(&harness.user_pda(&upline)).residual_pending, PACKAGE / 10);
+    assert_eq!(harness.fetch::<SolBox>(&solbox).card_balance_total, left);
+
+    // A balance short of the price leaves the wallet paying
+    harness.buy(referrer, upline).unwrap();
+    assert_eq!(wallet_before - harness.lamports(&referrer), 2 * rent + price);
+    assert_eq!(harness.fetch::<User>(&harness.user_pda(&referrer)).card_balance, left);
+    
+    // Admin refunds of a card-funded purchase go back to the balance too
+    let owner = harness.owner;
+    harness.airdrop(solbox, price);
+    let vault_before = harness.lamports(&solbox);
+    let receipt: Receipt = harness.fetch(&harness.receipt_pda(11));
+    harness.process(client::admin_refund(solbox, owner, &receipt, price, "duplicate")).unwrap();
+    assert_eq!(harness.lamports(&solbox), vault_before);
+    assert_eq!(wallet_before - harness.lamports(&referrer), 2 * rent + price);
+    assert_eq!(harness.fetch::<User>(&harness.user_pda(&referrer)).card_balance, PACKAGE + bonus);
+    assert_eq!(harness.fetch::<SolBox>(&solbox).card_balance_total, PACKAGE + bonus);
+}
+
+#[test]
+fn card_balance_redeems_to_a_merchant_net_of_the_fee() {
+    let mut config = Harness::default_config();
+    config.redemption_fee_bps = MAX_REDEMPTION_FEE_BPS + 1;
+    assert_error(Harness::new().initialize(config.clone()), CustomError::InvalidRedemptionFee);
+    let mut harness = Harness::new();
+    config.redemption_fee_bps = 250;
+    harness.initialize(config).unwrap();
+    let solbox = harness.solbox;
+    let holder = harness.register();
+    let holder_pda = harness.user_pda(&holder);
+    let shop = Pubkey::new_unique();
+    harness.airdrop(shop, LAMPORTS_PER_SOL);
+    harness.process(client::register_merchant(solbox, harness.owner, shop)).unwrap();
+    let merchant = client::find_merchant_address(&solbox, &shop).0;
+
+    // The vault already holds what card balances are owed
+    let balance = 3 * PACKAGE;
+    harness.airdrop(solbox, balance);
+    harness.modify::<User>(&holder_pda, |user| user.card_balance = balance);
+    harness.modify::<SolBox>(&solbox, |state| state.card_balance_total = balance);
+
+    // The merchant is paid at once, less the fee the treasury keeps
+    let fee = PACKAGE / 40;
+    let november = month_of(1_700_000_000);
+    assert_eq!(november, (2023 - 1970) * 12 + 10);
+    let vault_before = harness.lamports(&solbox);
+    let shop_before = harness.lamports(&shop);
+    harness.process(client::redeem_to_merchant(solbox, holder, shop, november, PACKAGE)).unwrap();
+    let bucket = client::find_merchant_month_address(&merchant, november).0;
+    let rent = harness.lamports(&bucket);
+    assert_eq!(harness.lamports(&shop), shop_before - rent + PACKAGE - fee);
+    assert_eq!(harness.lamports(&solbox), vault_before - PACKAGE + fee);
+    assert_eq!(harness.fetch::<User>(&holder_pda).card_balance, balance - PACKAGE);
+    assert_eq!(harness.fetch::<SolBox>(&solbox).card_balance_total, balance - PACKAGE);
+    let redeemed = harness.logs().iter().find_map(|line| match Event::from_log(line) {
+        Some(Ok(Event::MerchantRedemptionEvent(event))) => Some((event.amount, event.fee, event.month)),
+        _ => None,
+    });
+    assert_eq!(redeemed, Some((PACKAGE, fee, november)));
+
+    // Redemptions roll up per merchant and per calendar month
+    harness.warp(30 * 86_400);
+    let december = month_of(1_700_000_000 + 30 * 86_400);
+    assert_eq!(december, november + 1);
+    harness.process(client::redeem_to_merchant(solbox, holder, shop, december, PACKAGE)).unwrap();
+    let totals: Merchant = harness.fetch(&merchant);
+    assert_eq!((totals.redeemed, totals.fees, totals.redemptions), (2 * PACKAGE, 2 * fee, 2));
+    for month in [november, december] {
+        let bucket: MerchantMonth = harness.fetch(&client::find_merchant_month_address(&merchant, month).0);
+        assert_eq!((bucket.month, bucket.redeemed, bucket.fees, bucket.redemptions), (month, PACKAGE, fee, 1));
+    }
+
+    // Nothing beyond the balance can be redeemed
+    assert_error(
+        harness.process(client::redeem_to_merchant(solbox, holder, shop, december, PACKAGE + 1)),
+        CustomError::InsufficientCardBalance
+    );
+}
+
+#[test]
+fn spillover_receipts_settle_and_cancel_against_the_payee() {
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.refund_window = 3_600;
+    harness.initialize(config).unwrap();
+    let solbox = harness.solbox;
+    let referrer = harness.register();
+    let commission = PACKAGE / 10;
+    for _ in 0..2 {
+        let buyer = harness.register();
+        harness.buy(buyer, referrer).unwrap();
+    }
+
+    // The third signup is placed below a leg but earns for the referrer
+    let spilled = harness.register();
+    harness.buy(spilled, referrer).unwrap();
+    let receipt: Receipt = harness.fetch(&harness.receipt_pda(2));
+    assert!(receipt.spillover);
+    assert_ne!(receipt.referrer, referrer);
+    assert_eq!(receipt.payee, referrer);
+    let parent = receipt.referrer;
+    let parent_before: User = harness.fetch(&harness.user_pda(&parent));
+
+    // Cancelling rolls back the payee, not the placement parent, and only
+    // the payee's User PDA in this tenant
+    let home = harness.solbox;
+    harness.solbox = Pubkey::new_unique();
+    harness.initialize(Harness::default_config()).unwrap();
+    harness.register_wallet(referrer);
+    let elsewhere = harness.user_pda(&referrer);
+    harness.solbox = home;
+    let mut cancel = client::cancel_purchase(solbox, &harness.fetch(&solbox), &receipt);
+    for account in [harness.user_pda(&parent), elsewhere] {
+        cancel.accounts[4].pubkey = account;
+        assert_eq!(
+            harness.process(cancel.clone()),
+            Err(ProgramError::Custom(ErrorCode::ConstraintSeeds.into()))
+        );
+    }
+    harness.cancel(2).unwrap();
+    let account: User = harness.fetch(&harness.user_pda(&referrer));
+    assert_eq!((account.earnings_from_direct, account.earnings_from_spillover), (2 * commission, 0));
+    let parent_after: User = harness.fetch(&harness.user_pda(&parent));
+    assert_eq!(parent_after.total_earnings, parent_before.total_earnings);
+
+    // Settling pays the payee
+    let spilled = harness.register();
+    harness.buy(spilled, referrer).unwrap();
+    assert_eq!(harness.fetch::<Receipt>(&harness.receipt_pda(3)).payee, referrer);
+    harness.warp(3_601);
+    let (referrer_before, parent_before) = (harness.lamports(&referrer), harness.lamports(&parent));
+    let receipt: Receipt = harness.fetch(&harness.receipt_pda(3));
+    harness.process(client::settle_purchase(solbox, harness.founder, &receipt, None)).unwrap();
+    assert_eq!(harness.lamports(&referrer) - referrer_before, commission);
+    assert_eq!(harness.lamports(&parent), parent_before);
+    let account: User = harness.fetch(&harness.user_pda(&referrer));
+    assert_eq!(account.earnings_from_spillover, commission);
+}
+
+#[test]
+fn spillover_commission_reaches_one_payee_in_every_path() {
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.drip_threshold = PACKAGE / 20;
+    config.drip_days = 1;
+    harness.initialize(config).unwrap();
+    let solbox = harness.solbox;
+    let referrer = harness.register();
+    let commission = PACKAGE / 10;
+    let buy_dripped = |harness: &mut Harness| {
+        let buyer = harness.register();
+        let purchase = client::Purchase { tranche: true, ..harness.purchase_under(referrer) };
+        harness.process(client::buy_gift_card(&purchase, buyer, referrer)).unwrap();
+    };
+    for _ in 0..3 {
+        buy_dripped(&mut harness);
+    }
+
+    // Paid at once, the spilled purchase's tranche is the referrer's
+    let receipt: Receipt = harness.fetch(&harness.receipt_pda(2));
+    assert!(receipt.spillover);
+    let tranche: Tranche = harness.fetch(&client::find_tranche_address(&solbox, 2).0);
+    assert_eq!((tranche.wallet, receipt.payee), (referrer, referrer));
+
+    // Escrowed, it is the same wallet that the settlement starts dripping to
+    harness.modify::<SolBox>(&solbox, |state| state.config.refund_window = 3_600);
+    buy_dripped(&mut harness);
+    let receipt: Receipt = harness.fetch(&harness.receipt_pda(3));
+    assert!(receipt.spillover);
+    let tranche: Tranche = harness.fetch(&client::find_tranche_address(&solbox, 3).0);
+    assert_eq!((tranche.wallet, receipt.payee), (referrer, referrer));
+    harness.warp(3_601);
+    harness.process(client::settle_purchase(solbox, harness.founder, &receipt, None)).unwrap();
+    harness.warp(86_400);
+    let before = harness.lamports(&referrer);
+    harness.process(client::release_tranche(solbox, 3, referrer)).unwrap();
+    assert_eq!(harness.lamports(&referrer) - before, commission);
+}
+
+#[test]
+fn simulate_purchase_returns_what_the_purchase_pays() {
+    let mut harness = Harness::new();
+    let mut config = Harness::default_config();
+    config.residual_bps = 2_500;
+    harness.initialize(config).unwrap();
+    let (solbox, owner, founder) = (harness.solbox, harness.owner, harness.founder);
+    let name_hash = [3; 32];
+    let integrator = client::find_integrator_address(&solbox, &name_hash).0;
+    let partner_wallet = Pubkey::new_unique();
+    harness.call(
+        &accounts::RegisterIntegrator {
+            solbox,
+            integrator,
+            admin: owner,
+            system_program: anchor_lang::system_program::ID,
+        },
+        instruction::RegisterIntegrator {
+            name_hash,
+            wallet: partner_wallet,
+            fee_share_bps: 300,
+            allowed_instructions: INTEGRATOR_ALLOW_BUY_GIFT_CARD,
+            cpi_authority: Pubkey::new_unique(),
+        }
+    ).unwrap();
+    let referrer = harness.register();
+    let buyer = harness.register();
+    let simulate = |harness: &mut Harness, purchase: &client::Purchase| {
+        harness.process(client::simulate_purchase(purchase, buyer, referrer)).unwrap();
+        PurchaseFlows::deserialize(&mut harness.return_data().as_slice()).unwrap()
+    };
+
+    // Every wallet moves by what the dry run said, the buyer by the price
+    // on top of the receipt and daily stats rent
+    let purchase = client::Purchase { integrator: Some((integrator, partner_wallet)), ..harness.purchase_under(referrer) };
+    let flows = simulate(&mut harness, &purchase);
+    assert_eq!((flows.payee, flows.referrer, flows.house, flows.dripped), (referrer, referrer, false, false));
+    let wallets = [buyer, referrer, founder, partner_wallet, solbox];
+    let before = wallets.map(|wallet| harness.lamports(&wallet));
+    harness.process(client::buy_gift_card(&purchase, buyer, referrer)).unwrap();
+    let after = wallets.map(|wallet| harness.lamports(&wallet));
+    let rent = harness.lamports(&harness.receipt_pda(0))
+        + harness.lamports(&client::find_daily_stats_address(&solbox, purchase.day).0);
+    assert_eq!(before[0] - after[0] - rent, flows.paid);
+    assert_eq!(after[1] - before[1], flows.fast_start);
+    assert_eq!(after[2] - before[2], flows.founder_share);
+    assert_eq!(after[3] - before[3], flows.integrator_fee);
+    assert_eq!(after[4] - before[4], flows.residual);
+    let receipt: Receipt = harness.fetch(&harness.receipt_pda(0));
+    assert_eq!(
+        (receipt.commission, receipt.founder_share, receipt.integrator_fee, receipt.residual),
+        (flows.commission, flows.founder_share, flows.integrator_fee, flows.residual)
+    );
+    assert_eq!(flows.paid, flows.commission + flows.founder_share + flows.integrator_fee);
+
+    // A dripped commission opens a tranche of the fast-start part
+    harness.modify::<SolBox>(&solbox, |state| state.config.drip_threshold = PACKAGE / 20);
+    let purchase = client::Purchase { tranche: true, ..harness.purchase_under(referrer) };
+    let flows = simulate(&mut harness, &purchase);
+    assert!(flows.dripped);
+    harness.process(client::buy_gift_card(&purchase, buyer, referrer)).unwrap();
+    let tranche: Tranche = harness.fetch(&client::find_tranche_address(&solbox, 1).0);
+    assert_eq!(tranche.total, flows.fast_start);
+    harness.modify::<SolBox>(&solbox, |state| state.config.drip_threshold = 0);
+
+    // A covering card balance pays, leaving the wallet the rent
+    harness.airdrop(solbox, 2 * PACKAGE);
+    harness.modify::<User>(&harness.user_pda(&buyer), |account| account.card_balance = 2 * PACKAGE);
+    harness.modify::<SolBox>(&solbox, |state| state.card_balance_total += 2 * PACKAGE);
+    let purchase = harness.purchase_under(referrer);
+    let flows = simulate(&mut harness, &purchase);
+    assert!(flows.card_funded);
+    harness.process(client::buy_gift_card(&purchase, buyer, referrer)).unwrap();
+    assert!(harness.fetch::<Receipt>(&harness.receipt_pda(2)).card_funded);
+    assert_eq!(harness.fetch::<User>(&harness.user_pda(&buyer)).card_balance, 2 * PACKAGE - flows.paid);
+
+    // And it refuses what the purchase would
+    let dry_run = client::simulate_purchase(&harness.purchase_under(referrer), buyer, referrer);
+    harness.modify::<SolBox>(&solbox, |state| state.emergency_eta = 1);
+    assert_error(harness.process(dry_run.clone()), CustomError::EmergencyWithdrawPending);
+    harness.modify::<SolBox>(&solbox, |state| state.emergency_eta = 0);
+    harness.modify::<SolBox>(&solbox, |state| {
+        state.config.require_current_terms = true;
+        state.terms_version += 1;
+    });
+    assert_error(harness.process(dry_run.clone()), CustomError::TermsOutdated);
+    harness.modify::<SolBox>(&solbox, |state| state.config.require_current_terms = false);
+    let blacklist = accounts::UpdateBlacklist {
+        solbox,
+        blacklist_filter: pda(&[b"blacklist_filter", solbox.as_ref()]),
+        admin: owner,
+    };
+    harness.call(&blacklist, instruction::AddToBlacklist { user: buyer }).unwrap();
+    assert_error(harness.process(dry_run.clone()), CustomError::UserBlacklisted);
+}
@@ -0,0 +1,218 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { SolboxContractDevnet } from "../target/types/solbox_contract_devnet";
+import { PublicKey, Keypair, LAMPORTS_PER_SOL, SystemProgram } from "@solana/web3.js";
+import { expect } from "chai";
+import { BN } from "bn.js";
+import * as fs from "fs";
+import * as path from "path";
+
+// Compute-unit regression benchmarks. Each instruction is simulated against
+// small, medium and large states and compared with the recorded baseline;
+// a run fails when usage grows more than CU_REGRESSION_PCT percent (default
+// 10) over it, or when buy_gift_card gets close to the default 200k budget.
+//
+// Record a new baseline with CU_BASELINE=update after an intended change.
+
+const BASELINE_FILE = path.join(__dirname, "compute-units.baseline.json");
+const REGRESSION_PCT = Number(process.env.CU_REGRESSION_PCT ?? "10");
+const UPDATE_BASELINE = process.env.CU_BASELINE === "update";
+const BUY_CEILING = 180_000;
+
+// Signups per state size; the SolBox holds about 20 relationships before
+// it needs expand_solbox
+const STATE_SIZES: Record<string, number> = { small: 1, medium: 8, large: 18 };
+
+describe("compute units", () => {
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+  const program = anchor.workspace.SolboxContractDevnet as Program<SolboxContractDevnet>;
+
+  const PACKAGE = new BN(LAMPORTS_PER_SOL);
+  const baseline: Record<string, number> = fs.existsSync(BASELINE_FILE)
+    ? JSON.parse(fs.readFileSync(BASELINE_FILE, "utf8"))
+    : {};
+  const measured: Record<string, number> = {};
+
+  const config = {
+    matrixWidth: 2,
+    matrixDepth: 3,
+    commissionPercentage: new BN(10),
+    commissionLevels: 2,
+    bonusPercentage: new BN(5),
+    validAmounts: [PACKAGE],
+    refundWindow: new BN(0),
+    recycling: false,
+    cycleBonus: new BN(0),
+    holdingTankPeriod: new BN(0),
+    stakePool: PublicKey.default,
+    maxStakeBps: 0,
+    yieldSharing: false,
+    supportTierThresholds: [new BN(0), new BN(0), new BN(0)],
+    tierPeriod: new BN(0),
+    tierGracePeriods: 0,
+    residualBps: 0,
+  };
+  const capacity = { maxAmounts: 8, maxLevels: 15, maxRelationships: 64, maxBlacklisted: 32 };
+
+  function pda(...seeds: Buffer[]): PublicKey {
+    return PublicKey.findProgramAddressSync(seeds, program.programId)[0];
+  }
+
+  function u64(value: number): Buffer {
+    return new BN(value).toArrayLike(Buffer, "le", 8);
+  }
+
+  async function airdrop(recipient: PublicKey, sol: number) {
+    const signature = await provider.connection.requestAirdrop(recipient, sol * LAMPORTS_PER_SOL);
+    await provider.connection.confirmTransaction(signature);
+  }
+
+  // Units the program itself consumed in a simulated transaction
+  async function computeUnits(builder: any, signers: Keypair[]): Promise<number> {
+    const tx = await builder.transaction();
+    tx.feePayer = signers[0].publicKey;
+    tx.recentBlockhash = (await provider.connection.getLatestBlockhash()).blockhash;
+    tx.sign(...signers);
+    const { value } = await provider.connection.simulateTransaction(tx);
+    expect(value.err, JSON.stringify(value.logs)).to.be.null;
+    const consumed = (value.logs ?? [])
+      .map((line) => line.match(/^Program (\w+) consumed (\d+) of \d+ compute units$/))
+      .find((match) => match && match[1] === program.programId.toBase58());
+    expect(consumed, "program compute units logged").to.exist;
+    return Number(consumed![2]);
+  }
+
+  function record(name: string, units: number) {
+    measured[name] = units;
+    const previous = baseline[name];
+    if (UPDATE_BASELINE || previous === undefined) {
+      return;
+    }
+    const limit = Math.floor(previous * (1 + REGRESSION_PCT / 100));
+    expect(units, `${name} used ${units} CU, baseline ${previous}`).to.be.at.most(limit);
+  }
+
+  after(() => {
+    if (UPDATE_BASELINE) {
+      const sorted = Object.fromEntries(Object.entries(measured).sort());
+      fs.writeFileSync(BASELINE_FILE, JSON.stringify(sorted, null, 2) + "\n");
+    }
+  });
+
+  for (const [size, signups] of Object.entries(STATE_SIZES)) {
+    it(`stays within budget with a ${size} state`, async () => {
+      const owner = Keypair.generate();
+      const solbox = Keypair.generate();
+      await airdrop(owner.publicKey, 10);
+      await program.methods
+        .initialize(Keypair.generate().publicKey, config, false, capacity)
+        .accountsPartial({
+          solbox: solbox.publicKey,
+          configHistory: pda(Buffer.from("config_history"), solbox.publicKey.toBuffer()),
+          blacklistFilter: pda(Buffer.from("blacklist_filter"), solbox.publicKey.toBuffer()),
+          owner: owner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner, solbox])
+        .rpc();
+      const state = await program.account.solBox.fetch(solbox.publicKey);
+      const founder = state.founderWallet;
+
+      const userPda = (wallet: PublicKey) =>
+        pda(Buffer.from("user"), solbox.publicKey.toBuffer(), wallet.toBuffer());
+
+      const register = (wallet: Keypair) =>
+        program.methods.registerUser().accountsPartial({
+          solbox: solbox.publicKey,
+          userAccount: userPda(wallet.publicKey),
+          wallet: wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        });
+
+      // Users join in heap order, so user i sits under (i - 1) / 2 and
+      // a width 2 matrix never spills over
+      const users: Keypair[] = [];
+      const buy = async (buyer: number, referrer: number) => {
+        const current = await program.account.solBox.fetch(solbox.publicKey);
+        const day = Math.floor(Date.now() / 1000 / 86_400);
+        // Paid levels, nearest first: the referrer, then its own referrer
+        const uplines = [users[referrer]];
+        if (referrer > 0) {
+          uplines.push(users[Math.floor((referrer - 1) / 2)]);
+        }
+        return program.methods
+          .buyGiftCard(PACKAGE)
+          .accountsPartial({
+            solbox: solbox.publicKey,
+            blacklistFilter: pda(Buffer.from("blacklist_filter"), solbox.publicKey.toBuffer()),
+            user: users[buyer].publicKey,
+            payer: users[buyer].publicKey,
+            userAccount: userPda(users[buyer].publicKey),
+            referrer: users[referrer].publicKey,
+            referrerUserAccount: userPda(users[referrer].publicKey),
+            founder,
+            receipt: pda(
+              Buffer.from("receipt"),
+              solbox.publicKey.toBuffer(),
+              u64(current.purchaseCount.toNumber())
+            ),
+            dailyStats: pda(Buffer.from("daily_stats"), solbox.publicKey.toBuffer(), u64(day)),
+            systemProgram: SystemProgram.programId,
+          })
+          .remainingAccounts(
+            uplines.flatMap((upline) => [
+              { pubkey: userPda(upline.publicKey), isSigner: false, isWritable: true },
+              { pubkey: upline.publicKey, isSigner: false, isWritable: true },
+            ])
+          );
+      };
+
+      for (let i = 0; i <= signups; i++) {
+        const wallet = Keypair.generate();
+        await airdrop(wallet.publicKey, 5);
+        users.push(wallet);
+        const builder = register(wallet);
+        if (i === signups) {
+          record(`register_user/${size}`, await computeUnits(builder, [wallet]));
+        }
+        await builder.signers([wallet]).rpc();
+        if (i === 0) {
+          continue;
+        }
+
+        // The last signup of the state is the one measured
+        const signup = await buy(i, Math.floor((i - 1) / 2));
+        if (i === signups) {
+          const units = await computeUnits(signup, [users[i]]);
+          record(`buy_gift_card/signup/${size}`, units);
+          expect(units, "buy_gift_card close to the 200k budget").to.be.below(BUY_CEILING);
+        }
+        await signup.signers([users[i]]).rpc();
+      }
+
+      const repeat = await buy(signups, Math.floor((signups - 1) / 2));
+      const units = await computeUnits(repeat, [users[signups]]);
+      record(`buy_gift_card/repeat/${size}`, units);
+      expect(units, "buy_gift_card close to the 200k budget").to.be.below(BUY_CEILING);
+
+      const admin = (method: any) =>
+        method.accountsPartial({
+          solbox: solbox.publicKey,
+          blacklistFilter: pda(Buffer.from("blacklist_filter"), solbox.publicKey.toBuffer()),
+          admin: owner.publicKey,
+        });
+      record(
+        `add_to_blacklist/${size}`,
+        await computeUnits(admin(program.methods.addToBlacklist(Keypair.generate().publicKey)), [owner])
+      );
+      record(
+        `verify_state/${size}`,
+        await computeUnits(
+          program.methods.verifyState().accountsPartial({ solbox: solbox.publicKey }),
+          [owner]
+        )
+      );
+    });
+  }
+});